@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// The value of an attribute for a path: `attr`, `-attr`, `attr=value`, or not given at all
+// which `!attr` also brings back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeState {
+    Set,
+    Unset,
+    Value(String),
+    Unspecified,
+}
+
+type Assignments = Vec<(String, AttributeState)>;
+
+// a line of an attributes file: the files matching the pattern get the assignments.
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: String,
+    assignments: Assignments,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AttributesFile {
+    rules: Vec<Rule>,
+    // `[attr]name` lines, only honored at the top of the working tree and in `info/attributes`
+    macros: Vec<(String, Assignments)>,
+}
+
+// The attributes of the files of a working tree, from the `.gitattributes` files of the
+// directories holding them, the deepest one taking precedence, then `info/attributes` of the
+// git directory. The files are read when a path of their directory is first looked up.
+pub struct Attributes {
+    work_tree: PathBuf,
+    info: AttributesFile,
+    files: Mutex<HashMap<PathBuf, Arc<AttributesFile>>>,
+}
+
+impl Attributes {
+    pub fn new(work_tree: &Path, git_dir: &Path) -> Self {
+        let info = fs::read_to_string(git_dir.join("info").join("attributes"))
+            .map(|content| parse(&content))
+            .unwrap_or_default();
+        Attributes {
+            work_tree: work_tree.to_path_buf(),
+            info,
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // forgets the files read so far, after a checkout changed them.
+    pub fn clear_cache(&self) {
+        self.files.lock().unwrap().clear();
+    }
+
+    fn file(&self, dir: &Path) -> Arc<AttributesFile> {
+        let mut files = self.files.lock().unwrap();
+        files
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| {
+                let path = self.work_tree.join(dir).join(".gitattributes");
+                Arc::new(fs::read_to_string(path).map(|content| parse(&content)).unwrap_or_default())
+            })
+            .clone()
+    }
+
+    // The attributes of `path`, relative to the top of the working tree. The lines of the
+    // attributes files are applied in order of precedence, a macro set on a path sets its
+    // own attributes with it, like `binary` which is `-diff -merge -text`.
+    pub fn get(&self, path: &Path) -> HashMap<String, AttributeState> {
+        let dirs: Vec<&Path> = match path.parent() {
+            Some(parent) => {
+                let mut dirs: Vec<&Path> = parent.ancestors().collect();
+                dirs.reverse();
+                dirs
+            }
+            None => vec![Path::new("")],
+        };
+        let files: Vec<(PathBuf, Arc<AttributesFile>)> =
+            dirs.iter().map(|dir| (dir.to_path_buf(), self.file(dir))).collect();
+
+        let mut macros: HashMap<String, Assignments> =
+            HashMap::from([("binary".to_string(), parse_assignments("-diff -merge -text"))]);
+        let top = files.first().map(|(_, file)| file.macros.iter()).into_iter().flatten();
+        for (name, assignments) in top.chain(self.info.macros.iter()) {
+            macros.insert(name.to_string(), assignments.clone());
+        }
+
+        let mut result = HashMap::new();
+        let rules = files
+            .iter()
+            .flat_map(|(dir, file)| file.rules.iter().map(move |rule| (dir.as_path(), rule)))
+            .chain(self.info.rules.iter().map(|rule| (Path::new(""), rule)));
+        for (dir, rule) in rules {
+            if let Ok(relative) = path.strip_prefix(dir) {
+                if pattern_matches(&rule.pattern, relative) {
+                    apply(&rule.assignments, &macros, &mut result, 0);
+                }
+            }
+        }
+        result
+    }
+}
+
+// how deep macros may refer to other macros, which also stops cycles.
+const MAX_MACRO_DEPTH: usize = 8;
+
+fn apply(
+    assignments: &Assignments,
+    macros: &HashMap<String, Assignments>,
+    result: &mut HashMap<String, AttributeState>,
+    depth: usize,
+) {
+    for (name, state) in assignments {
+        if *state == AttributeState::Set && depth < MAX_MACRO_DEPTH {
+            if let Some(expansion) = macros.get(name) {
+                apply(expansion, macros, result, depth + 1);
+            }
+        }
+        result.insert(name.to_string(), state.clone());
+    }
+}
+
+fn parse(content: &str) -> AttributesFile {
+    let mut file = AttributesFile::default();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (pattern, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let assignments = parse_assignments(rest);
+        match pattern.strip_prefix("[attr]") {
+            Some(name) => file.macros.push((name.to_string(), assignments)),
+            // negative patterns are not allowed in attributes files
+            None if pattern.starts_with('!') => {}
+            None => file.rules.push(Rule {
+                pattern: pattern.to_string(),
+                assignments,
+            }),
+        }
+    }
+    file
+}
+
+fn parse_assignments(text: &str) -> Assignments {
+    text.split_whitespace()
+        .map(|token| {
+            if let Some(name) = token.strip_prefix('-') {
+                (name.to_string(), AttributeState::Unset)
+            } else if let Some(name) = token.strip_prefix('!') {
+                (name.to_string(), AttributeState::Unspecified)
+            } else if let Some((name, value)) = token.split_once('=') {
+                (name.to_string(), AttributeState::Value(value.to_string()))
+            } else {
+                (token.to_string(), AttributeState::Set)
+            }
+        })
+        .collect()
+}
+
+// A pattern without a slash matches the name of a file at any depth, the others match the
+// path relative to the directory of the attributes file.
+fn pattern_matches(pattern: &str, path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    if pattern.contains('/') {
+        wildmatch(pattern.trim_start_matches('/').as_bytes(), path.as_bytes())
+    } else {
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        wildmatch(pattern.as_bytes(), name.as_bytes())
+    }
+}
+
+// Glob matching as in git: `*` and `?` do not match a slash, `**` matches across
+// directories, `[...]` matches a class of characters and `\` escapes the next one.
+pub fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            // `**/` also matches no directory at all
+            if let Some(after) = rest.strip_prefix(b"/") {
+                if wildmatch(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| wildmatch(rest, &text[i..]))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if wildmatch(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => matches!(text.first(), Some(c) if *c != b'/') && wildmatch(&pattern[1..], &text[1..]),
+        Some(b'[') => match (text.first(), match_class(&pattern[1..], text.first().copied())) {
+            (Some(_), Some((true, rest))) => wildmatch(rest, &text[1..]),
+            (_, None) => text.first() == Some(&b'[') && wildmatch(&pattern[1..], &text[1..]),
+            _ => false,
+        },
+        Some(b'\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && wildmatch(&pattern[2..], &text[1..]),
+        Some(c) => text.first() == Some(c) && wildmatch(&pattern[1..], &text[1..]),
+    }
+}
+
+// Matches `c` against the class starting after a `[`, returning whether it matched and the
+// rest of the pattern, or `None` when the class is not closed.
+fn match_class(pattern: &[u8], c: Option<u8>) -> Option<(bool, &[u8])> {
+    let (negated, mut i) = match pattern.first() {
+        Some(b'!') | Some(b'^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let start = pattern[i];
+        if start == b']' && !first {
+            let matched = c.is_some_and(|c| c != b'/') && matched != negated;
+            return Some((matched, &pattern[i + 1..]));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).is_some_and(|end| *end != b']') {
+            let end = pattern[i + 2];
+            matched |= c.is_some_and(|c| start <= c && c <= end);
+            i += 3;
+        } else {
+            matched |= c == Some(start);
+            i += 1;
+        }
+    }
+    None
+}
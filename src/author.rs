@@ -1,7 +1,8 @@
+use anyhow::anyhow;
 use anyhow::Result;
 use chrono::{DateTime, Local};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Author {
     name: String,
     email: String,
@@ -17,19 +18,37 @@ impl Author {
         }
     }
 
+    // `Name Surname <email> <timestamp> <timezone>`, the name may contain spaces.
     pub fn parse(line: &str) -> Result<Self> {
-        let mut iter = line.split_whitespace();
-        let name = iter.next().unwrap();
-        let email = iter.next().unwrap();
-        let email = email.strip_prefix('<').unwrap().strip_suffix('>').unwrap();
-        let time = iter.next().unwrap();
-        let time_zone = iter.next().unwrap();
+        let invalid = || anyhow!("invalid author line: {}", line);
+        let (name, rest) = line.split_once('<').ok_or_else(invalid)?;
+        let (email, rest) = rest.split_once('>').ok_or_else(invalid)?;
+        let mut iter = rest.split_whitespace();
+        let time = iter.next().ok_or_else(invalid)?;
+        let time_zone = iter.next().ok_or_else(invalid)?;
         let time = DateTime::parse_from_str(&format!("{} {}", time, time_zone), "%s %z")?;
-        Ok(Author::new(name, email, time.into()))
+        Ok(Author::new(name.trim(), email, time.into()))
     }
 
     pub fn to_s(&self) -> String {
         let timestampt = &self.time.format("%s %z").to_string();
         format!("{} <{}> {}", &self.name, &self.email, timestampt)
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn time(&self) -> DateTime<Local> {
+        self.time
+    }
+
+    // the date as printed by `git log`
+    pub fn readable_time(&self) -> String {
+        self.time.format("%a %b %-d %H:%M:%S %Y %z").to_string()
+    }
 }
@@ -1,99 +1,677 @@
 use anyhow::anyhow;
 use anyhow::Result;
-use gitclone::Command;
-use std::path::Path;
+use gitclone::tree_diff::{self, RenameOptions};
+use gitclone::{CatFileMode, Cleanup, Command, CommitOptions, DiffFormat, HashAlgorithm, Repository, RestoreOptions, SequencerAction, StatusFormat};
+use std::env;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use std::env::current_dir;
 
-use clap::{App, Arg, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 
 extern crate clap;
+
+// the directory the command runs from, after applying every `-C <path>` in order.
+fn start_dir(matches: &ArgMatches) -> Result<PathBuf> {
+    let mut dir = current_dir()?;
+    if let Some(values) = matches.values_of("C") {
+        for value in values {
+            dir = dir.join(value);
+        }
+    }
+    Ok(dir)
+}
+
+// like git, commands run from the top of the working tree once the repository is found, so
+// paths relative to it can be used directly. Bare repositories stay in the start directory.
+fn open_repository(matches: &ArgMatches) -> Result<Command> {
+    let repository = Repository::discover(&start_dir(matches)?)?;
+    if let Some(work_tree) = &repository.work_tree {
+        env::set_current_dir(work_tree)?;
+    }
+    Command::from_repository(repository)
+}
+
+// `cherry-pick` and `revert` take the same arguments, both run the sequencer.
+fn sequencer_subcommand<'a, 'b>(name: &'static str, help: &'static str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .help(help)
+        .arg(Arg::from_usage("--continue 'Continue the operation in progress after resolving conflicts'"))
+        .arg(Arg::from_usage("--skip 'Skip the current commit and continue with the rest of the sequence'"))
+        .arg(Arg::from_usage("--abort 'Cancel the operation and return to the pre-sequence state'"))
+        .arg(Arg::with_name("COMMITS").multiple(true).required_unless_one(&["continue", "skip", "abort"]))
+}
+
+// the options of `diff` and `log` that choose the output and how renames are detected.
+fn diff_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    let similarity = |name: &'a str, long: &'a str, help: &'b str| {
+        Arg::with_name(name)
+            .short(name)
+            .long(long)
+            .help(help)
+            .value_name("n")
+            .takes_value(true)
+            .min_values(0)
+            .require_equals(true)
+            .empty_values(true)
+    };
+    app.arg(Arg::from_usage("--name-status 'Show only the names and the status of the changed files'"))
+        .arg(Arg::from_usage("--name-only 'Show only the names of the changed files'"))
+        .arg(similarity("M", "find-renames", "Detect renames, of files at least <n> similar"))
+        .arg(similarity("C", "find-copies", "Detect copies as well as renames"))
+        .arg(Arg::from_usage("--no-renames 'Turn off rename detection'"))
+        .arg(Arg::from_usage("-l [n] 'Skip inexact rename detection beyond <n> files'"))
+}
+
+fn diff_format(matches: &ArgMatches) -> Option<DiffFormat> {
+    if matches.is_present("name-status") {
+        Some(DiffFormat::NameStatus)
+    } else if matches.is_present("name-only") {
+        Some(DiffFormat::NameOnly)
+    } else {
+        None
+    }
+}
+
+// the rename detection of the configuration, overridden by the options.
+fn rename_options(command: &Command, matches: &ArgMatches) -> Result<Option<RenameOptions>> {
+    if matches.is_present("no-renames") {
+        return Ok(None);
+    }
+    let find = ["M", "C"].iter().any(|name| matches.is_present(name));
+    let mut options = match command.rename_options() {
+        Some(options) => options,
+        None if find => RenameOptions::default(),
+        None => return Ok(None),
+    };
+    for name in ["M", "C"].iter() {
+        if let Some(value) = matches.value_of(name) {
+            options.threshold = tree_diff::parse_threshold(value)?;
+        }
+    }
+    if matches.is_present("C") {
+        options.copies = true;
+    }
+    if let Some(limit) = matches.value_of("l") {
+        options.limit = limit.parse().map_err(|_| anyhow!("invalid rename limit '{}'", limit))?;
+    }
+    Ok(Some(options))
+}
+
+fn run_sequencer(mut command: Command, action: SequencerAction, matches: &ArgMatches) -> Result<()> {
+    let (author, email) = command.identity(None, None)?;
+    if matches.is_present("continue") {
+        command.sequencer_continue(&author, &email)
+    } else if matches.is_present("skip") {
+        command.sequencer_skip(&author, &email)
+    } else if matches.is_present("abort") {
+        command.sequencer_abort()
+    } else {
+        let commits: Vec<_> = matches.values_of("COMMITS").unwrap().collect();
+        command.sequencer_start(action, &commits, &author, &email)
+    }
+}
+
 fn main() -> Result<()> {
     let matches = App::new("My git clone")
         .version("0.1")
+        .arg(
+            Arg::with_name("C")
+                .short("C")
+                .help("Run as if git was started in <path> instead of the current working directory")
+                .takes_value(true)
+                .value_name("path")
+                .multiple(true)
+                .number_of_values(1),
+        )
         .subcommand(
             SubCommand::with_name("init").arg(
                 Arg::with_name("PATH")
                     .help("the path where git should be initialized")
                     .required(false),
-            ),
+            )
+            .arg(Arg::from_usage("--object-format=[FORMAT] 'The hash algorithm used to name objects: sha1 or sha256'").required(false))
+            .arg(Arg::from_usage("--bare 'Create a bare repository, without a working tree'")),
+        )
+        .subcommand(
+            SubCommand::with_name("status")
+                .help("show the working tree status")
+                .arg(Arg::from_usage("-s --short 'Give the output in the short format'"))
+                .arg(
+                    Arg::with_name("porcelain")
+                        .long("porcelain")
+                        .help("Give the output in an easy-to-parse format for scripts, v1 or v2")
+                        .value_name("version")
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true),
+                )
+                .arg(Arg::from_usage("-b --branch 'Show the branch in the short and porcelain formats'"))
+                .arg(Arg::from_usage("-z 'Terminate entries with NUL, implies --porcelain=v1 by default'")),
         )
         .subcommand(
-            SubCommand::with_name("status").help("list untracked files")
+            SubCommand::with_name("fsck").help("verify the connectivity and validity of the objects in the database")
             )
+        .subcommand(diff_args(
+            SubCommand::with_name("log")
+                .help("show the commits reachable from a revision")
+                .arg(Arg::from_usage("-p --patch 'Show the changes of each commit as a patch'"))
+                .arg(Arg::from_usage("--show-signature 'Check the signature of each signed commit'"))
+                .arg(Arg::with_name("REVISION").help("the commit to start from, HEAD by default")),
+        ))
+        .subcommand(
+            SubCommand::with_name("tag")
+                .help("create, list or delete tags")
+                .arg(Arg::from_usage("-a --annotate 'Make an annotated tag object'"))
+                .arg(Arg::from_usage("-s --sign 'Make a signed annotated tag'"))
+                .arg(Arg::from_usage("-m --message=[MESSAGE] 'The message of the annotated tag'"))
+                .arg(Arg::from_usage("-f --force 'Replace an existing tag'"))
+                .arg(Arg::from_usage("-d --delete 'Delete the tag'"))
+                .arg(Arg::from_usage("-l --list 'List the tags'"))
+                .arg(Arg::with_name("NAME"))
+                .arg(Arg::with_name("COMMIT").help("the object to tag, HEAD by default")),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-commit")
+                .help("check the signatures of commits")
+                .arg(Arg::with_name("COMMITS").required(true).multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("verify-tag")
+                .help("check the signatures of tags")
+                .arg(Arg::with_name("TAGS").required(true).multiple(true)),
+        )
+        .subcommand(diff_args(
+            SubCommand::with_name("diff")
+                .help("show changes between the index, the working tree and commits")
+                .arg(Arg::from_usage("--cached 'Compare the index to HEAD or to the commit given'").alias("staged"))
+                .arg(Arg::with_name("COMMITS").multiple(true).max_values(2)),
+        ))
+        .subcommand(
+            SubCommand::with_name("cat-file")
+                .help("show the type, size or content of an object")
+                .arg(Arg::from_usage("-t 'Show the type of the object'"))
+                .arg(Arg::from_usage("-s 'Show the size of the object'"))
+                .arg(Arg::from_usage("-p 'Pretty-print the content of the object'"))
+                .arg(Arg::with_name("ARGS").required(true).multiple(true).max_values(2)),
+        )
+        .subcommand(
+            SubCommand::with_name("blame")
+                .help("show what revision and author last modified each line of a file")
+                .arg(
+                    Arg::from_usage("-L [range] 'Annotate only the lines in <start>,<end> or <start>,+<count>'")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(Arg::from_usage("--porcelain 'Show in a format designed for machine consumption'"))
+                .arg(Arg::with_name("ARGS").required(true).multiple(true).max_values(2)),
+        )
+        .subcommand(
+            SubCommand::with_name("update-ref")
+                .help("update the object name stored in a ref safely")
+                .arg(Arg::from_usage("-d 'Delete the ref'"))
+                .arg(Arg::with_name("REF").required(true))
+                .arg(Arg::with_name("VALUES").multiple(true).max_values(2)),
+        )
+        .subcommand(
+            SubCommand::with_name("push")
+                .help("update a ref of another local repository along with its objects")
+                .arg(Arg::from_usage("-f --force 'Update the ref even if it is not a fast-forward'"))
+                .arg(Arg::from_usage("--no-verify 'Skip the pre-push hook'"))
+                .arg(Arg::with_name("REPOSITORY").required(true))
+                .arg(Arg::with_name("REFSPEC")),
+        )
+        .subcommand(
+            SubCommand::with_name("worktree")
+                .help("manage multiple working trees")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .arg(Arg::from_usage("-b [new-branch] 'Create a new branch and check it out in the new worktree'"))
+                        .arg(Arg::from_usage("--detach 'Detach HEAD in the new worktree'"))
+                        .arg(Arg::from_usage("-f --force 'Check out a branch even if it is checked out in another worktree'"))
+                        .arg(Arg::with_name("PATH").required(true))
+                        .arg(Arg::with_name("COMMIT-ISH")),
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .arg(Arg::from_usage("--porcelain 'Output in an easy-to-parse format for scripts'")),
+                )
+                .subcommand(
+                    SubCommand::with_name("remove")
+                        .arg(Arg::from_usage("-f --force 'Remove the worktree even if it is dirty or locked'"))
+                        .arg(Arg::with_name("WORKTREE").required(true)),
+                )
+                .subcommand(SubCommand::with_name("prune")),
+        )
+        .subcommand(
+            SubCommand::with_name("sparse-checkout")
+                .help("reduce the working tree to a subset of its directories")
+                .subcommand(SubCommand::with_name("init").arg(Arg::from_usage("--cone 'Use the patterns of cone mode, the only ones supported'")))
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .arg(Arg::from_usage("--cone 'Use the patterns of cone mode, the only ones supported'"))
+                        .arg(Arg::with_name("DIRS").multiple(true)),
+                )
+                .subcommand(SubCommand::with_name("add").arg(Arg::with_name("DIRS").multiple(true).required(true)))
+                .subcommand(SubCommand::with_name("list"))
+                .subcommand(SubCommand::with_name("reapply"))
+                .subcommand(SubCommand::with_name("disable")),
+        )
+        .subcommand(
+            SubCommand::with_name("submodule")
+                .help("initialize, update or inspect submodules")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .arg(Arg::with_name("REPOSITORY").required(true))
+                        .arg(Arg::with_name("PATH")),
+                )
+                .subcommand(SubCommand::with_name("init").arg(Arg::with_name("PATHS").multiple(true)))
+                .subcommand(
+                    SubCommand::with_name("update")
+                        .arg(Arg::from_usage("--init 'Initialize the submodules that are not yet'"))
+                        .arg(Arg::with_name("PATHS").multiple(true)),
+                )
+                .subcommand(SubCommand::with_name("status").arg(Arg::with_name("PATHS").multiple(true))),
+        )
+        .subcommand(
+            SubCommand::with_name("stash")
+                .help("stash the changes in a dirty working directory away")
+                .arg(Arg::from_usage("-m --message=[MESSAGE] 'The description of the stash entry'"))
+                .arg(Arg::from_usage("-u --include-untracked 'Stash the untracked files too'"))
+                .subcommand(
+                    SubCommand::with_name("push")
+                        .arg(Arg::from_usage("-m --message=[MESSAGE] 'The description of the stash entry'"))
+                        .arg(Arg::from_usage("-u --include-untracked 'Stash the untracked files too'")),
+                )
+                .subcommand(
+                    SubCommand::with_name("apply")
+                        .arg(Arg::from_usage("--index 'Restore the changes of the index too'"))
+                        .arg(Arg::with_name("STASH")),
+                )
+                .subcommand(
+                    SubCommand::with_name("pop")
+                        .arg(Arg::from_usage("--index 'Restore the changes of the index too'"))
+                        .arg(Arg::with_name("STASH")),
+                )
+                .subcommand(SubCommand::with_name("list"))
+                .subcommand(SubCommand::with_name("drop").arg(Arg::with_name("STASH"))),
+        )
+        .subcommand(sequencer_subcommand("cherry-pick", "apply the changes introduced by some existing commits"))
+        .subcommand(sequencer_subcommand("revert", "revert some existing commits"))
+        .subcommand(
+            SubCommand::with_name("rebase")
+                .help("reapply commits on top of another base")
+                .arg(Arg::from_usage("-i --interactive 'Edit the list of commits to rebase before it runs'"))
+                .arg(Arg::from_usage("--autosquash 'Move the fixup! and squash! commits after the commit they amend'"))
+                .arg(Arg::from_usage("--continue 'Continue the rebase after resolving conflicts or editing a commit'"))
+                .arg(Arg::from_usage("--skip 'Skip the current commit and continue the rebase'"))
+                .arg(Arg::from_usage("--abort 'Cancel the rebase and check out the original branch'"))
+                .arg(Arg::with_name("UPSTREAM").required_unless_one(&["continue", "skip", "abort"])),
+        )
         .subcommand(
             App::new("commit")
+                .arg(Arg::from_usage("-m --message=[MESSAGE] 'Add the commit message'"))
+                .arg(
+                    Arg::from_usage("-F --file=[FILE] 'Take the commit message from the file, - for the standard input'")
+                        .conflicts_with("message"),
+                )
+                .arg(Arg::from_usage("--amend 'Replace the tip of the current branch by a new commit'"))
+                .arg(Arg::from_usage("--reset-author 'Make the amended commit with the current author'"))
+                .arg(Arg::from_usage("--no-edit 'Keep the message of the amended commit'"))
+                .arg(Arg::from_usage("--allow-empty 'Allow a commit with the same tree as its parent'"))
                 .arg(
-                    Arg::from_usage("-m --message=[MESSAGE] 'Add the commit message'")
-                        .required(true),
+                    Arg::from_usage("--cleanup=[MODE] 'How to clean up the message'")
+                        .possible_values(&["strip", "whitespace", "verbatim", "scissors", "default"]),
                 )
                 .arg(Arg::from_usage("--author=[author] 'The name of the author'").required(false))
-                .arg(Arg::from_usage("--email=[email] 'The email of the author'").required(false)),
+                .arg(Arg::from_usage("--email=[email] 'The email of the author'").required(false))
+                .arg(Arg::from_usage("-S --gpg-sign 'Sign the commit with the key of user.signingKey'"))
+                .arg(Arg::from_usage("--no-gpg-sign 'Do not sign the commit, even with commit.gpgSign set'"))
+                .arg(Arg::from_usage("-n --no-verify 'Skip the pre-commit and commit-msg hooks'")),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .arg(Arg::from_usage("-p --patch 'Pick the hunks of the changes to add'"))
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("the FILE to add into the index")
+                        .required_unless("patch")
+                        .multiple(true),
+                ),
         )
         .subcommand(
-            SubCommand::with_name("add").arg(
-                Arg::with_name("FILE")
-                    .help("the FILE to add into the index")
-                    .required(false)
-                    .multiple(true),
-            ),
+            SubCommand::with_name("reset")
+                .help("reset the index to a tree")
+                .arg(Arg::from_usage("-p --patch 'Pick the hunks of the index to reset'").required(true))
+                .arg(Arg::with_name("ARGS").help("[<tree-ish>] [<paths>...]").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .help("restore files of the working tree or of the index")
+                .arg(Arg::from_usage("-p --patch 'Pick the hunks to restore'"))
+                .arg(Arg::from_usage("-S --staged 'Restore the index'"))
+                .arg(Arg::from_usage("-W --worktree 'Restore the working tree, the default'"))
+                .arg(Arg::from_usage("-s --source=[TREE-ISH] 'Restore from this tree'"))
+                .arg(Arg::from_usage("--ours 'Restore unmerged files from our side'").conflicts_with("theirs"))
+                .arg(Arg::from_usage("--theirs 'Restore unmerged files from their side'"))
+                .arg(Arg::with_name("PATHS").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("checkout")
+                .help("restore files of the working tree from the index or a tree")
+                .arg(Arg::from_usage("--ours 'Check out unmerged files from our side'").conflicts_with("theirs"))
+                .arg(Arg::from_usage("--theirs 'Check out unmerged files from their side'"))
+                .arg(Arg::with_name("TREE-ISH").help("the tree to check the paths out from, the index by default"))
+                .arg(Arg::with_name("PATHS").multiple(true).last(true)),
         )
         .get_matches();
     match matches.subcommand() {
-        ("status", Some(_)) => {
-            let root_path = current_dir();
-            match root_path {
-                Ok(root_path) => {
-                    let command = Command::new(root_path)?;
-                    command.status()
-                }
-                Err(e) => Err(anyhow!(e)),
-            }
+        ("status", Some(_matches)) => {
+            let format = if _matches.is_present("porcelain") {
+                StatusFormat::porcelain(_matches.value_of("porcelain").unwrap_or("v1"))?
+            } else if _matches.is_present("short") {
+                StatusFormat::Short
+            } else if _matches.is_present("z") {
+                StatusFormat::PorcelainV1
+            } else {
+                StatusFormat::Long
+            };
+            let command = open_repository(&matches)?;
+            command.status(format, _matches.is_present("branch"), _matches.is_present("z"))
+        }
+        ("fsck", Some(_)) => {
+            let command = open_repository(&matches)?;
+            command.fsck()
         }
         ("init", Some(_matches)) => {
+            let root_path = start_dir(&matches)?;
             let root_path = _matches
                 .value_of("PATH")
-                .map_or(current_dir(), |v| Path::new(v).canonicalize());
+                .map_or(Ok(root_path.to_path_buf()), |v| {
+                    let path = root_path.join(v);
+                    std::fs::create_dir_all(&path).and_then(|_| path.canonicalize())
+                });
+            let object_format = match _matches.value_of("object-format") {
+                Some(name) => HashAlgorithm::from_name(name)?,
+                None => HashAlgorithm::default(),
+            };
             match root_path {
+                Ok(root_path) if _matches.is_present("bare") => {
+                    let mut command = Command::from_repository(Repository::bare(&root_path))?;
+                    command.init_with_object_format(object_format)
+                }
                 Ok(root_path) => {
-                    let command = Command::new(root_path)?;
-                    command.init()
+                    let mut command = Command::new(root_path)?;
+                    command.init_with_object_format(object_format)
                 }
                 Err(e) => Err(anyhow!(e)),
             }
         }
+        ("log", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            let format = match diff_format(_matches) {
+                None if _matches.is_present("patch") => Some(DiffFormat::Patch),
+                format => format,
+            };
+            let renames = rename_options(&command, _matches)?;
+            command.log(_matches.value_of("REVISION"), format, renames, _matches.is_present("show-signature"))
+        }
+        ("tag", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            match _matches.value_of("NAME") {
+                Some(name) if _matches.is_present("delete") => command.tag_delete(name),
+                Some(name) if !_matches.is_present("list") => {
+                    let message = _matches.value_of("message");
+                    if _matches.is_present("annotate") && message.is_none() {
+                        return Err(anyhow!("a message is needed to annotate the tag, use -m"));
+                    }
+                    let (author, email) = command.identity(None, None)?;
+                    command.tag_create(
+                        name,
+                        _matches.value_of("COMMIT"),
+                        message,
+                        _matches.is_present("sign"),
+                        (&author, &email),
+                        _matches.is_present("force"),
+                    )
+                }
+                _ => command.tag_list(),
+            }
+        }
+        ("verify-commit", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            command.verify_commit(&_matches.values_of("COMMITS").unwrap().collect::<Vec<_>>())
+        }
+        ("verify-tag", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            command.verify_tag(&_matches.values_of("TAGS").unwrap().collect::<Vec<_>>())
+        }
+        ("diff", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            let commits: Vec<_> = _matches.values_of("COMMITS").map(|v| v.collect()).unwrap_or_default();
+            let format = diff_format(_matches).unwrap_or(DiffFormat::Patch);
+            let renames = rename_options(&command, _matches)?;
+            command.diff(&commits, _matches.is_present("cached"), format, renames)
+        }
+        ("cat-file", Some(_matches)) => {
+            let args: Vec<_> = _matches.values_of("ARGS").unwrap().collect();
+            let (mode, object) = match args.as_slice() {
+                [object] if _matches.is_present("t") => (CatFileMode::Type, object),
+                [object] if _matches.is_present("s") => (CatFileMode::Size, object),
+                [object] if _matches.is_present("p") => (CatFileMode::Pretty, object),
+                [type_, object] => (CatFileMode::Content(type_.to_string()), object),
+                _ => return Err(anyhow!("usage: cat-file (-t | -s | -p | <type>) <object>")),
+            };
+            let mut command = open_repository(&matches)?;
+            command.cat_file(mode, object)
+        }
+        ("blame", Some(_matches)) => {
+            let args: Vec<_> = _matches.values_of("ARGS").unwrap().collect();
+            let (revision, file) = match args.as_slice() {
+                [file] => (None, file),
+                [revision, file] => (Some(*revision), file),
+                _ => return Err(anyhow!("usage: blame [-L <range>] [--porcelain] [<rev>] <file>")),
+            };
+            let ranges: Vec<_> = _matches.values_of("L").map(|v| v.collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            command.blame(revision, Path::new(file), &ranges, _matches.is_present("porcelain"))
+        }
+        ("update-ref", Some(_matches)) => {
+            let name = _matches.value_of("REF").unwrap();
+            let values: Vec<_> = _matches.values_of("VALUES").map(|v| v.collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            match (_matches.is_present("d"), values.as_slice()) {
+                (true, [old_value]) => command.delete_ref(name, Some(old_value)),
+                (true, []) => command.delete_ref(name, None),
+                (false, [new_value]) => command.update_ref(name, new_value, None),
+                (false, [new_value, old_value]) => command.update_ref(name, new_value, Some(old_value)),
+                _ => Err(anyhow!("usage: update-ref [-d] <ref> [<new-value>] [<old-value>]")),
+            }
+        }
+        ("push", Some(_matches)) => {
+            let path = start_dir(&matches)?.join(_matches.value_of("REPOSITORY").unwrap());
+            let mut command = open_repository(&matches)?;
+            command.push(
+                &path,
+                _matches.value_of("REFSPEC"),
+                _matches.is_present("force"),
+                _matches.is_present("no-verify"),
+            )
+        }
         ("add", Some(_matches)) => {
-            let inputs = _matches.values_of("FILE").unwrap();
-            let paths = inputs.map(|v| Path::new(v).to_path_buf()).collect();
-            let root_path = current_dir();
-            match root_path {
-                Ok(root_path) => {
-                    let mut command = Command::new(root_path)?;
-                    command.add(paths)
+            let paths: Vec<PathBuf> =
+                _matches.values_of("FILE").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            if _matches.is_present("patch") {
+                return command.add_patch(&paths);
+            }
+            command.add(paths)
+        }
+        ("reset", Some(_matches)) => {
+            let args: Vec<&str> = _matches.values_of("ARGS").map(|v| v.collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            command.reset_patch(&args)
+        }
+        ("restore", Some(_matches)) => {
+            let paths: Vec<PathBuf> =
+                _matches.values_of("PATHS").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            if _matches.is_present("patch") {
+                return command.restore_patch(
+                    _matches.value_of("source"),
+                    _matches.is_present("staged"),
+                    _matches.is_present("worktree"),
+                    &paths,
+                );
+            }
+            let options = RestoreOptions {
+                source: _matches.value_of("source").map(String::from),
+                staged: _matches.is_present("staged"),
+                worktree: _matches.is_present("worktree"),
+                ours: _matches.is_present("ours"),
+                theirs: _matches.is_present("theirs"),
+                overlay: false,
+            };
+            command.restore(&paths, &options)
+        }
+        ("checkout", Some(_matches)) => {
+            let paths: Vec<PathBuf> =
+                _matches.values_of("PATHS").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default();
+            let mut command = open_repository(&matches)?;
+            command.checkout_paths(
+                _matches.value_of("TREE-ISH"),
+                &paths,
+                _matches.is_present("ours"),
+                _matches.is_present("theirs"),
+            )
+        }
+        ("worktree", Some(_matches)) => {
+            let start = start_dir(&matches)?;
+            let mut command = open_repository(&matches)?;
+            match _matches.subcommand() {
+                ("add", Some(args)) => command.worktree_add(
+                    &start.join(args.value_of("PATH").unwrap()),
+                    args.value_of("COMMIT-ISH"),
+                    args.value_of("b"),
+                    args.is_present("detach"),
+                    args.is_present("force"),
+                ),
+                ("list", Some(args)) => command.worktree_list(args.is_present("porcelain")),
+                ("remove", Some(args)) => command.worktree_remove(
+                    &start.join(args.value_of("WORKTREE").unwrap()),
+                    args.is_present("force"),
+                ),
+                ("prune", Some(_)) => command.worktree_prune(),
+                _ => Err(anyhow!("usage: worktree (add | list | remove | prune)")),
+            }
+        }
+        ("sparse-checkout", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            let dirs = |args: &ArgMatches| -> Vec<PathBuf> {
+                args.values_of("DIRS").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default()
+            };
+            match _matches.subcommand() {
+                ("init", Some(_)) => command.sparse_checkout_init(),
+                ("set", Some(args)) => command.sparse_checkout_set(&dirs(args)),
+                ("add", Some(args)) => command.sparse_checkout_add(&dirs(args)),
+                ("list", Some(_)) => command.sparse_checkout_list(),
+                ("reapply", Some(_)) => command.sparse_checkout_reapply(),
+                ("disable", Some(_)) => command.sparse_checkout_disable(),
+                _ => Err(anyhow!("usage: sparse-checkout (init | set | add | list | reapply | disable)")),
+            }
+        }
+        ("submodule", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            let paths = |args: &ArgMatches| -> Vec<PathBuf> {
+                args.values_of("PATHS").map(|v| v.map(PathBuf::from).collect()).unwrap_or_default()
+            };
+            match _matches.subcommand() {
+                ("add", Some(args)) => {
+                    command.submodule_add(args.value_of("REPOSITORY").unwrap(), args.value_of("PATH").map(Path::new))
                 }
-                Err(e) => Err(anyhow!(e)),
+                ("init", Some(args)) => command.submodule_init(&paths(args)),
+                ("update", Some(args)) => command.submodule_update(&paths(args), args.is_present("init")),
+                ("status", Some(args)) => command.submodule_status(&paths(args)),
+                _ => command.submodule_status(&[]),
             }
         }
-        ("commit", Some(_matches)) => {
-            let message = _matches
-                .value_of("message")
-                .expect("unable to get the message for the commit");
-            let author = _matches
-                .value_of("author")
-                .or(option_env!("GIT_AUTHOR_NAME"))
-                .expect("unable to get the author of the commit");
-            let email = _matches
-                .value_of("email")
-                .or(option_env!("GIT_AUTHOR_EMAIL"))
-                .expect("unable to get the email of author of the commit");
-            let root_path = current_dir();
-            match root_path {
-                Ok(root_path) => {
-                    let mut command = Command::new(root_path)?;
-                    command.commit(author, email, message)
+        ("stash", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            match _matches.subcommand() {
+                ("push", Some(args)) => {
+                    let (author, email) = command.identity(None, None)?;
+                    command.stash_push(&author, &email, args.value_of("message"), args.is_present("include-untracked"))
+                }
+                ("apply", Some(args)) => command
+                    .stash_apply(args.value_of("STASH"), args.is_present("index"))
+                    .and_then(|clean| if clean { Ok(()) } else { Err(anyhow!("conflicts while applying the stash")) }),
+                ("pop", Some(args)) => command.stash_pop(args.value_of("STASH"), args.is_present("index")),
+                ("list", Some(_)) => command.stash_list(),
+                ("drop", Some(args)) => command.stash_drop(args.value_of("STASH")),
+                _ => {
+                    let (author, email) = command.identity(None, None)?;
+                    command.stash_push(&author, &email, _matches.value_of("message"), _matches.is_present("include-untracked"))
                 }
-                Err(e) => Err(anyhow!(e)),
             }
         }
+        ("cherry-pick", Some(_matches)) => {
+            let command = open_repository(&matches)?;
+            run_sequencer(command, SequencerAction::Pick, _matches)
+        }
+        ("revert", Some(_matches)) => {
+            let command = open_repository(&matches)?;
+            run_sequencer(command, SequencerAction::Revert, _matches)
+        }
+        ("rebase", Some(_matches)) => {
+            let mut command = open_repository(&matches)?;
+            if _matches.is_present("continue") {
+                command.rebase_continue()
+            } else if _matches.is_present("skip") {
+                command.rebase_skip()
+            } else if _matches.is_present("abort") {
+                command.rebase_abort()
+            } else {
+                command.rebase(
+                    _matches.value_of("UPSTREAM").unwrap(),
+                    _matches.is_present("interactive"),
+                    _matches.is_present("autosquash"),
+                )
+            }
+        }
+        ("commit", Some(_matches)) => {
+            // the file is read before the current directory changes to the working tree
+            let message = match (_matches.value_of("message"), _matches.value_of("file")) {
+                (Some(message), _) => Some(message.to_string()),
+                (None, Some("-")) => {
+                    let mut message = String::new();
+                    std::io::stdin().read_to_string(&mut message)?;
+                    Some(message)
+                }
+                (None, Some(file)) => Some(
+                    std::fs::read_to_string(file).map_err(|e| anyhow!("could not read log file '{}': {}", file, e))?,
+                ),
+                (None, None) => None,
+            };
+            let sign = if _matches.is_present("no-gpg-sign") {
+                Some(false)
+            } else if _matches.is_present("gpg-sign") {
+                Some(true)
+            } else {
+                None
+            };
+            let options = CommitOptions {
+                sign,
+                no_verify: _matches.is_present("no-verify"),
+                amend: _matches.is_present("amend"),
+                reset_author: _matches.is_present("reset-author") || _matches.is_present("author"),
+                no_edit: _matches.is_present("no-edit"),
+                allow_empty: _matches.is_present("allow-empty"),
+                cleanup: Cleanup::parse(_matches.value_of("cleanup").unwrap_or("default"))?,
+            };
+            let mut command = open_repository(&matches)?;
+            let (author, email) = command.identity(_matches.value_of("author"), _matches.value_of("email"))?;
+            command.commit(&author, &email, message.as_deref(), &options)
+        }
         _ => unreachable!(),
     }
 }
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::diff::{self, EditKind};
+use crate::{util, Commit, Database};
+
+// A line of the blamed file with the commit that introduced it and the number it had there.
+// Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlameLine {
+    pub commit: String,
+    pub orig_line: usize,
+    pub final_line: usize,
+    pub content: Vec<u8>,
+}
+
+pub struct Blame {
+    pub path: PathBuf,
+    pub lines: Vec<BlameLine>,
+    // the commits the lines are attributed to
+    pub commits: HashMap<String, Commit>,
+    // for those commits, the first parent that has the file
+    pub previous: HashMap<String, String>,
+}
+
+impl Blame {
+    // whether `commit` has no parent, the lines it is blamed for may be older.
+    pub fn is_boundary(&self, commit: &str) -> bool {
+        self.commits.get(commit).is_some_and(|c| c.parents().is_empty())
+    }
+}
+
+// The lines of one version of the file that are still looking for their origin: for each,
+// its index in the final file and in this version.
+struct Suspect {
+    blob: String,
+    lines: Vec<(usize, usize)>,
+}
+
+fn load_commit(db: &mut Database, oid: &str) -> Result<Commit> {
+    match db.load(oid)? {
+        ObjectType::CommitType { commit } => Ok(commit.clone()),
+        _ => Err(anyhow!("object {} is not a commit", oid)),
+    }
+}
+
+fn load_blob(db: &mut Database, oid: &str) -> Result<Vec<u8>> {
+    match db.load(oid)? {
+        ObjectType::BlobType { blob } => Ok(blob.get_content().to_vec()),
+        _ => Err(anyhow!("object {} is not a blob", oid)),
+    }
+}
+
+// the blob `path` names in the tree of `commit`, if any.
+fn path_blob(db: &mut Database, commit: &Commit, path: &Path) -> Result<Option<String>> {
+    let list = db.load_tree_list(&commit.tree_ref)?;
+    Ok(list.get(path).map(|entry| util::encode_vec(&entry.sha1_hash)))
+}
+
+// Attributes every line of `path` as it is in `commit` to the commit that introduced it. The
+// history is walked newest commit first: the lines a commit kept from a parent, according to
+// a line diff between both versions, are passed on to that parent, the others were written by
+// the commit itself.
+pub fn blame(db: &mut Database, commit: &str, path: &Path) -> Result<Blame> {
+    let start = load_commit(db, commit)?;
+    let blob = path_blob(db, &start, path)?
+        .ok_or(anyhow!("no such path {} in {}", path.display(), commit))?;
+    let content = load_blob(db, &blob)?;
+    let final_lines: Vec<Vec<u8>> = diff::lines(&content).iter().map(|l| l.to_vec()).collect();
+
+    let mut commits = HashMap::new();
+    commits.insert(commit.to_string(), start);
+    let mut pending: HashMap<String, Suspect> = HashMap::new();
+    pending.insert(
+        commit.to_string(),
+        Suspect {
+            blob,
+            lines: (0..final_lines.len()).map(|n| (n, n)).collect(),
+        },
+    );
+    let mut origins = vec![None; final_lines.len()];
+    let mut previous = HashMap::new();
+
+    while let Some(oid) = pending
+        .keys()
+        .max_by_key(|oid| (commits[*oid].author().time(), oid.to_string()))
+        .cloned()
+    {
+        let mut suspect = match pending.remove(&oid) {
+            Some(suspect) => suspect,
+            None => break,
+        };
+        let parents = commits[&oid].parents().to_vec();
+        for parent in parents {
+            if suspect.lines.is_empty() {
+                break;
+            }
+            if !commits.contains_key(&parent) {
+                commits.insert(parent.to_string(), load_commit(db, &parent)?);
+            }
+            let parent_blob = match path_blob(db, &commits[&parent], path)? {
+                Some(blob) => blob,
+                None => continue,
+            };
+            previous.entry(oid.to_string()).or_insert(parent.to_string());
+            // the lines of this version kept from the parent, with their number there
+            let kept: HashMap<usize, usize> = if parent_blob == suspect.blob {
+                suspect.lines.iter().map(|(_, n)| (*n, *n)).collect()
+            } else {
+                let ours = load_blob(db, &suspect.blob)?;
+                let theirs = load_blob(db, &parent_blob)?;
+                diff::diff(&diff::lines(&theirs), &diff::lines(&ours))
+                    .into_iter()
+                    .filter(|e| e.kind == EditKind::Eql)
+                    .filter_map(|e| Some((e.b_index?, e.a_index?)))
+                    .collect()
+            };
+            let (passed, remaining): (Vec<_>, Vec<_>) =
+                suspect.lines.into_iter().partition(|(_, n)| kept.contains_key(n));
+            suspect.lines = remaining;
+            if passed.is_empty() {
+                continue;
+            }
+            let target = pending.entry(parent.to_string()).or_insert(Suspect {
+                blob: parent_blob,
+                lines: vec![],
+            });
+            target.lines.extend(passed.into_iter().map(|(f, n)| (f, kept[&n])));
+        }
+        for (final_line, line) in suspect.lines {
+            origins[final_line] = Some((oid.to_string(), line));
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (n, (origin, content)) in origins.into_iter().zip(final_lines).enumerate() {
+        let (commit, orig_line) = origin.ok_or(anyhow!("line {} of {} has no origin", n + 1, path.display()))?;
+        lines.push(BlameLine {
+            commit,
+            orig_line: orig_line + 1,
+            final_line: n + 1,
+            content,
+        });
+    }
+    let used: HashSet<&String> = lines.iter().map(|l| &l.commit).collect();
+    let commits = commits
+        .iter()
+        .filter(|(oid, _)| used.contains(*oid))
+        .map(|(oid, commit)| (oid.to_string(), commit.clone()))
+        .collect();
+    previous.retain(|oid, _| used.contains(oid));
+    Ok(Blame {
+        path: path.to_path_buf(),
+        lines,
+        commits,
+        previous,
+    })
+}
@@ -2,7 +2,7 @@ use std::{io::{Cursor, Read}, path::PathBuf};
 
 use anyhow::Result;
 
-use crate::{util, Object};
+use crate::{util, HashAlgorithm, Object};
 
 #[derive(Eq, Clone, PartialEq, PartialOrd, Debug)]
 pub struct Blob2 {
@@ -12,27 +12,38 @@ pub struct Blob2 {
 }
 
 pub struct Blob {
-    content: Vec<u8>
+    content: Vec<u8>,
+    hash: HashAlgorithm,
 }
 
 impl Blob {
-    pub fn new(content: Vec<u8>) -> Result<Self> {
+    pub fn new(content: Vec<u8>, hash: HashAlgorithm) -> Result<Self> {
         Ok(Blob{
-            content
+            content,
+            hash
         })
     }
 
-    pub fn parse(cursor: &mut Cursor<Vec<u8>>) -> Result<Self> {
+    pub fn parse(cursor: &mut Cursor<Vec<u8>>, hash: HashAlgorithm) -> Result<Self> {
         let mut message = vec![];
         cursor.read_to_end(&mut message)?;
-        Blob::new(message)
+        Blob::new(message, hash)
     }
 
+    pub fn get_content(&self) -> &[u8] {
+        &self.content
+    }
 }
 
 impl Object for Blob {
     fn get_data(&self) -> Result<Vec<u8>> {
-        Ok(self.content.to_vec())
+        let mut data = Vec::new();
+        data.extend_from_slice("blob".as_bytes());
+        data.push(0x20u8);
+        data.extend_from_slice(self.content.len().to_string().as_bytes());
+        data.push(0x00u8);
+        data.extend_from_slice(&self.content);
+        Ok(data)
     }
 
     fn type_(&self) -> &str {
@@ -40,7 +51,7 @@ impl Object for Blob {
     }
 
     fn get_oid(&self) -> Result<Vec<u8>> {
-        let digest = util::hexdigest_vec(&self.content);
+        let digest = util::hexdigest_vec(&self.get_data()?, self.hash);
         Ok(digest)
     }
 }
@@ -5,20 +5,22 @@ use std::{
 
 use anyhow::anyhow;
 use anyhow::Result;
-use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY};
+use ring::digest::Context;
+
+use crate::HashAlgorithm;
 
 pub struct Checksum {
     reader: BufReader<File>,
     context: Context,
+    checksum_size: usize,
 }
 
-const CHECKSUM_SIZE: usize = 20;
-
 impl Checksum {
-    pub fn new(file: File) -> Self {
+    pub fn new(file: File, hash: HashAlgorithm) -> Self {
         Checksum {
             reader: BufReader::new(file),
-            context: Context::new(&SHA1_FOR_LEGACY_USE_ONLY),
+            context: hash.context(),
+            checksum_size: hash.size(),
         }
     }
 
@@ -32,7 +34,7 @@ impl Checksum {
     }
 
     pub fn verify_checksum(mut self) -> Result<()> {
-        let sum = self.read(CHECKSUM_SIZE, false)?;
+        let sum = self.read(self.checksum_size, false)?;
         let digest = self.context.finish().as_ref().to_vec();
         if sum != digest {
             return Err(anyhow!("Checksum does not match value stored on disk"));
@@ -3,26 +3,80 @@ use std::collections::{BTreeSet, HashMap};
 use std::fs::{DirEntry, Metadata};
 use std::path::Path;
 use std::rc::Rc;
+use std::env;
+use std::sync::Arc;
 use std::{fs, path::PathBuf};
 
 use anyhow::anyhow;
 use anyhow::Result;
 use chrono::Local;
 
-use crate::database::ObjectType;
-use crate::tree::{self, Tree};
-use crate::{Blob, Entry, EntryAdd, util};
+use crate::database::{self, ObjectType};
+use crate::tree::{self, Tree, TreeList};
+use crate::convert::Converter;
+use crate::tree_diff::ChangeKind;
+use crate::{Entry, EntryAdd, util};
 use crate::Author;
 use crate::Commit;
 use crate::Object;
 use crate::Refs;
-use crate::{Database, Index, Workspace};
+use crate::hooks::Hooks;
+use crate::refs::HEAD;
+use crate::editor::{self, Cleanup};
+use crate::signing;
+use crate::{Config, Database, HashAlgorithm, Index, Repository, StatChange, StatOptions, Workspace};
+
+mod blame;
+mod cat_file;
+mod diff;
+mod fsck;
+mod log;
+mod migration;
+mod patch;
+mod push;
+mod rebase;
+mod restore;
+mod sequencer;
+mod signature;
+mod sparse_checkout;
+mod stash;
+mod status;
+mod submodule;
+mod tag;
+mod update_ref;
+mod upstream;
+mod worktree;
+pub use cat_file::CatFileMode;
+pub use diff::DiffFormat;
+pub use fsck::Fsck;
+pub use restore::RestoreOptions;
+pub use sequencer::SequencerAction;
+pub use status::StatusFormat;
+pub use worktree::Worktree;
+use submodule::SubmoduleChange;
+
+// How `commit` makes the commit: `sign` overrides `commit.gpgSign`, `no_verify` skips the
+// `pre-commit` and `commit-msg` hooks and `amend` replaces HEAD, keeping its author unless
+// `reset_author` and its message with `no_edit`.
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    pub sign: Option<bool>,
+    pub no_verify: bool,
+    pub amend: bool,
+    pub reset_author: bool,
+    pub no_edit: bool,
+    pub allow_empty: bool,
+    pub cleanup: Cleanup,
+}
 
 pub struct Command {
-    workspace: Workspace,
+    repository: Repository,
+    // bare repositories have no working tree to read files from
+    workspace: Option<Workspace>,
     db: Database,
     pub index: Index,
-    refs: Refs
+    refs: Refs,
+    config: Config
 }
 
 
@@ -41,6 +95,7 @@ pub enum IndexStatus{
     Added,
     Modified,
     Deleted,
+    Renamed,
 }
 
 pub struct Status {
@@ -50,11 +105,23 @@ pub struct Status {
     index_changes: HashMap<String, IndexStatus>,
     workspace_changes: HashMap<String, WorkspaceStatus>,
     cmd: Rc<RefCell<Command>>,
-    head_tree: HashMap<String, Entry>
+    head_tree: HashMap<String, Entry>,
+    // the files of HEAD renamed in the index and the similarity of the rename, by new path
+    renamed: HashMap<String, (String, u32)>,
+    // how the modified submodules differ from the commit recorded for them, by path
+    submodules: HashMap<String, SubmoduleChange>,
+    // the paths a merge left unmerged in the index
+    unmerged: BTreeSet<String>,
+    hash: HashAlgorithm,
+    converter: Option<Arc<Converter>>,
+    work_tree: PathBuf,
 }
 
 impl Status {
     pub fn new(cmd: Command) -> Self {
+        let hash = cmd.db.hash_algorithm();
+        let converter = cmd.db.converter();
+        let work_tree = cmd.repository.work_tree.clone().unwrap_or_default();
         Status {
             stat: HashMap::new(),
             untracked: BTreeSet::new(),
@@ -62,45 +129,41 @@ impl Status {
             cmd: Rc::new(RefCell::new(cmd)),
             index_changes: HashMap::new(),
             workspace_changes: HashMap::new(),
-            head_tree: HashMap::new()
+            head_tree: HashMap::new(),
+            renamed: HashMap::new(),
+            submodules: HashMap::new(),
+            unmerged: BTreeSet::new(),
+            hash,
+            converter,
+            work_tree,
         }
     }
-    pub fn run(&mut self) -> Result<()> {
+    pub fn run(&mut self, format: StatusFormat, branch: bool, nul: bool) -> Result<()> {
+        self.collect()?;
+        self.print(format, branch, nul)
+    }
+
+    // finds the changes of the index and the working tree, refreshing the stat data of the
+    // index on the way.
+    fn collect(&mut self) -> Result<()> {
         let cmd = self.cmd.clone();
-        if !cmd.borrow().workspace.get_git_path().exists() {
+        if !cmd.borrow().repository.git_dir.exists() {
             return Err(anyhow!("not a git repository (or any parent up to mount point /)"))
         }
-        if cmd.borrow().workspace.get_git_path().join("index").exists() {
+        cmd.borrow().workspace()?;
+        if cmd.borrow().repository.git_dir.join("index").exists() {
             cmd.borrow_mut().index.load()?;
         }
         self.scan_workspace(None)?;
-        if cmd.borrow().refs.head_path().exists() {
+        if cmd.borrow().refs.read_head().is_some() {
             self.load_head_tree()?;
         }
         self.check_index_entries()?;
+        self.unmerged = cmd.borrow().index.unmerged_paths().cloned().collect();
         self.collect_deleted_head_entries()?;
-        cmd.borrow().index.write_updates()?;
-        self.changed.iter().for_each(|path| {
-            let left = 
-                match self.index_changes.get(path){
-                    Some(IndexStatus::Added) => "A",
-                    Some(IndexStatus::Deleted) => "D",
-                    Some(IndexStatus::Modified) => "M",
-                    None => ""
-            };
-            let right =
-                match self.workspace_changes.get(path) {
-                    Some(WorkspaceStatus::Deleted) => "D",
-                    Some(WorkspaceStatus::Modified) => "M",
-                    None => ""
-                };
-            println!(" {}{} {}", left, right, path)
-        });
-        self.untracked.iter().for_each(|e| {
-            println!("?? {}", e)
-        });
-        Ok(())
-
+        self.detect_renames()?;
+        let result = cmd.borrow().index.write_updates();
+        result
     }
 
     pub fn scan_workspace(&mut self, prefix: Option<PathBuf>) -> Result<()> {
@@ -112,12 +175,13 @@ impl Status {
             };
         let mut work = vec![prefix];
         while let Some(dir) = work.pop() {
-            for (key, value) in cmd.workspace.list_dir(dir, e)?.iter() {
+            for (key, value) in cmd.workspace()?.list_dir(dir, e)?.iter() {
                 if cmd.index.is_tracked(key.to_path_buf()) {
-                    if value.is_dir() {
+                    let is_gitlink = cmd.index.entry_mode(&key.display().to_string()) == Some(util::GITLINK_MODE);
+                    if value.is_dir() && !is_gitlink {
                         work.push(key.to_path_buf())
                     }
-                    if value.is_file() {
+                    if util::is_blob(value) || is_gitlink {
                         self.stat.insert(key.display().to_string(), value.clone());
                     }
                 } else {
@@ -148,7 +212,7 @@ impl Status {
         let mut work = vec![(path.to_path_buf(), stat.to_owned())];
         let mut res: bool = false;
         while let Some((dir, stat)) = work.pop() {
-            if stat.is_file() {
+            if util::is_blob(&stat) {
                 res = !cmd.index.is_tracked(path.to_path_buf());
                 break;
             }
@@ -157,9 +221,9 @@ impl Status {
                 res = false;
                 break;
             } else {
-                let items = cmd.workspace.list_dir(dir.to_path_buf(), e)?;
+                let items = cmd.workspace()?.list_dir(dir.to_path_buf(), e)?;
                 let iter = items.into_iter();
-                let (files, dirs): (Vec<_>, Vec<_>) = iter.partition(|(_, item_stat)| item_stat.is_file());
+                let (files, dirs): (Vec<_>, Vec<_>) = iter.partition(|(_, item_stat)| util::is_blob(item_stat));
                 work.extend(dirs);
                 work.extend(files);
             }
@@ -194,31 +258,49 @@ impl Status {
         Ok(())
     }
 
-    pub fn check_index_against_workspace(&mut self, entry: &mut RefMut<EntryAdd>) -> Result<bool> {
+    // A submodule is modified when another commit than the recorded one is checked out or
+    // when it has changes of its own. One that is not checked out is left alone.
+    fn check_submodule(&mut self, entry: &EntryAdd) -> Result<()> {
+        let path = entry.get_path();
+        if !self.stat.contains_key(&path) {
+            return self.record_change(path, SetStatus::WorkspaceSet(WorkspaceStatus::Deleted));
+        }
+        let oid = util::encode_vec(&entry.oid);
+        if let Some(change) = submodule::submodule_change(&self.work_tree.join(&path), &oid)? {
+            if change.is_changed() {
+                self.submodules.insert(path.to_string(), change);
+                self.record_change(path, SetStatus::WorkspaceSet(WorkspaceStatus::Modified))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_index_against_workspace(&mut self, index: &Index, entry: &mut RefMut<EntryAdd>) -> Result<bool> {
+        if entry.get_mode()? == util::GITLINK_MODE {
+            self.check_submodule(entry)?;
+            return Ok(false);
+        }
         let stat = self.stat.get(&entry.get_path());
         let mut changed_index: bool = false;
         match stat {
             Some(stat) => {
-                if !entry.is_stat_match(stat) {
-                    let workspace_status = WorkspaceStatus::Modified;
-                    self.record_change(entry.get_path(), SetStatus::WorkspaceSet(workspace_status))?;
-                    return Ok(changed_index);
-                } 
-
                 // we want to avoid whatever is possible to read a file's content
-                if entry.times_match(stat) {
-                    return Ok(changed_index);
+                match index.stat_change(entry, stat) {
+                    StatChange::Unchanged => return Ok(changed_index),
+                    StatChange::Changed => {
+                        let workspace_status = WorkspaceStatus::Modified;
+                        self.record_change(entry.get_path(), SetStatus::WorkspaceSet(workspace_status))?;
+                        return Ok(changed_index);
+                    }
+                    StatChange::Unknown => {}
                 }
 
-                let data = util::read_file(entry.path.to_path_buf())?;
-
-                let blob = Blob::new(data)?;
+                let oid = database::hash_worktree_file(&self.work_tree.join(&entry.path), self.hash, self.converter.as_deref())?;
 
                 // if the file has not changed despite the previous checks, it is necessary to
                 // update index info for the next time.
-                if entry.oid == blob.get_oid()? {
-                    // println!("touched file: {:?}", entry);
-                    entry.update_entry_stat(stat);
+                if entry.oid == oid {
+                    entry.update_entry_stat(stat, index.stat_options());
                     changed_index = true;
                 } else {
                     let workspace_status = WorkspaceStatus::Modified;
@@ -241,7 +323,10 @@ impl Status {
             let mut entries = index.each_mut_entry()?;
             while let Some(mut entry) = entries.pop() {
                 self.check_index_against_head_tree(&entry)?;
-                changed_index = self.check_index_against_workspace(&mut entry)?;
+                // the files outside of a sparse checkout are not expected in the working tree
+                if !entry.is_skip_worktree() {
+                    changed_index |= self.check_index_against_workspace(index, &mut entry)?;
+                }
             };
         }
         if changed_index {
@@ -271,7 +356,7 @@ impl Status {
             ObjectType::BlobType{blob: _} => {
                 Err(anyhow!("this is not a valid commit object"))
             },
-            ObjectType::TreeType{tree: _} => {
+            ObjectType::TreeType{tree: _} | ObjectType::TagType{tag: _} => {
                 Err(anyhow!("this is not a valid commit object"))
             },
         }
@@ -286,7 +371,7 @@ impl Status {
             match tree {
                 ObjectType::CommitType{commit: _} => {
                 },
-                ObjectType::BlobType{blob: _} => {
+                ObjectType::BlobType{blob: _} | ObjectType::TagType{tag: _} => {
                 },
                 ObjectType::TreeType{tree} => {
                     // println!("to process: {:?}", tree);
@@ -322,31 +407,160 @@ impl Status {
         }
         Ok(())
     }
+
+    // pairs the files deleted from HEAD with the files added to the index that have the same
+    // or a similar content, following `status.renames` or else `diff.renames`.
+    fn detect_renames(&mut self) -> Result<()> {
+        let cmd = self.cmd.clone();
+        let options = match cmd.borrow().config.get_bool("status.renames") {
+            Some(false) => None,
+            _ => cmd.borrow().rename_options(),
+        };
+        let options = match options {
+            Some(options) => options,
+            None => return Ok(()),
+        };
+        let mut deleted = TreeList::new();
+        let mut added = TreeList::new();
+        for (path, status) in self.index_changes.iter() {
+            if *status == IndexStatus::Deleted {
+                let entry = &self.head_tree[path];
+                let path = Path::new(path);
+                deleted.insert(path.to_path_buf(), migration::tree_entry(path, entry.sha1_hash.clone(), entry.mode.clone()));
+            }
+        }
+        for entry in cmd.borrow().index.each_entry()?.iter() {
+            if self.index_changes.get(&entry.get_path()) == Some(&IndexStatus::Added) {
+                let mode = util::get_mode_u(entry.get_mode()?);
+                added.insert(entry.path.to_path_buf(), migration::tree_entry(&entry.path, entry.oid.to_vec(), mode));
+            }
+        }
+        if deleted.is_empty() || added.is_empty() {
+            return Ok(());
+        }
+        let changes = cmd.borrow_mut().diff_tree_lists(&deleted, &added, Some(&options))?;
+        for change in changes.iter().filter(|c| c.kind == ChangeKind::Renamed) {
+            let old = change.old_path().unwrap_or(change.path()).display().to_string();
+            let new = change.path().display().to_string();
+            self.changed.remove(&old);
+            self.index_changes.remove(&old);
+            self.index_changes.insert(new.to_string(), IndexStatus::Renamed);
+            self.renamed.insert(new, (old, change.score));
+        }
+        Ok(())
+    }
 }
 
 impl Command {
     pub fn new(path_buf: PathBuf) -> Result<Self> {
-        let ws = Workspace::new(&path_buf);
-        let db = Database::new(&path_buf.join(".git/objects"));
-        let index = Index::new(&path_buf.join(".git/index"));
-        let refs = Refs::new(&ws.get_git_path());
+        Command::from_repository(Repository::at(&path_buf))
+    }
+
+    // opens the repository containing `cwd`, see `Repository::discover`.
+    pub fn discover(cwd: PathBuf) -> Result<Self> {
+        Command::from_repository(Repository::discover(&cwd)?)
+    }
+
+    pub fn from_repository(repository: Repository) -> Result<Self> {
+        let git_path = &repository.git_dir;
+        let common_path = &repository.common_dir;
+        let mut config = Config::new(&common_path.join("config"));
+        config.load()?;
+        let stat_options = StatOptions::from_config(&config);
+        let converter = repository
+            .work_tree
+            .as_ref()
+            .map(|path| Arc::new(Converter::new(path, common_path, &config)));
+        let ws = repository.work_tree.as_ref().map(|path| Workspace {
+            symlinks: stat_options.symlinks,
+            converter: converter.clone(),
+            ..Workspace::new(path)
+        });
+        let hash = match config.get("extensions.objectformat") {
+            Some(name) => HashAlgorithm::from_name(&name)?,
+            None => HashAlgorithm::default(),
+        };
+        let mut db = Database::new(&common_path.join("objects"), hash);
+        if let Some(converter) = converter {
+            db.set_converter(converter);
+        }
+        let mut index = Index::new_with_hash(&git_path.join("index"), hash);
+        index.set_stat_options(stat_options);
+        let mut refs = Refs::with_common_dir(git_path, common_path);
+        refs.set_hooks(Hooks::new(&repository, &config));
         Ok(Command {
+            repository,
             workspace: ws,
             db,
             index,
-            refs
+            refs,
+            config
         })
     }
 
-    pub fn status(self) -> Result<()> {
+    fn workspace(&self) -> Result<&Workspace> {
+        self.workspace
+            .as_ref()
+            .ok_or(anyhow!("this operation must be run in a work tree"))
+    }
+
+    // The name and email of the commits and tags made: the given ones, then the ones of
+    // GIT_AUTHOR_NAME and GIT_AUTHOR_EMAIL, then `user.name` and `user.email`.
+    pub fn identity(&self, name: Option<&str>, email: Option<&str>) -> Result<(String, String)> {
+        let lookup = |given: Option<&str>, var: &str, key: &str| {
+            given
+                .map(|value| value.to_string())
+                .or_else(|| env::var(var).ok().filter(|value| !value.is_empty()))
+                .or_else(|| self.config.get(key))
+        };
+        match (lookup(name, "GIT_AUTHOR_NAME", "user.name"), lookup(email, "GIT_AUTHOR_EMAIL", "user.email")) {
+            (Some(name), Some(email)) => Ok((name, email)),
+            _ => Err(anyhow!(
+                "Author identity unknown\n\n*** Please tell me who you are.\n\nRun\n\n  \
+                 git config user.email \"you@example.com\"\n  git config user.name \"Your Name\"\n\n\
+                 to set your account's default identity."
+            )),
+        }
+    }
+
+    // shows the changes of the index and of the working tree in `format`, with the branch in
+    // the short and porcelain formats when `branch` is set and NUL terminated entries with `nul`.
+    pub fn status(self, format: StatusFormat, branch: bool, nul: bool) -> Result<()> {
         let mut status = Status::new(self);
-        status.run()
+        status.run(format, branch, nul)
     }
-    pub fn init(&self) -> Result<()> {
-        let git_path = &self.workspace.get_git_path();
-        for dir in ["objects", "refs"] {
+    pub fn fsck(self) -> Result<()> {
+        let mut fsck = Fsck::new(self);
+        fsck.run()
+    }
+
+    pub fn init(&mut self) -> Result<()> {
+        self.init_with_object_format(HashAlgorithm::default())
+    }
+
+    pub fn init_with_object_format(&mut self, object_format: HashAlgorithm) -> Result<()> {
+        let git_path = &self.repository.git_dir;
+        self.db = Database::new(&git_path.join("objects"), object_format);
+        self.index = Index::new_with_hash(&git_path.join("index"), object_format);
+        self.index.set_stat_options(StatOptions::from_config(&self.config));
+        for dir in ["objects", "refs/heads", "refs/tags"] {
             fs::create_dir_all(git_path.join(dir))?
         }
+        if !self.refs.head_path().exists() {
+            self.refs.set_symbolic_ref(HEAD, "refs/heads/master")?;
+        }
+        // repositories that use an object format other than sha1 must declare the extension,
+        // which requires the version 1 of the repository format.
+        if object_format == HashAlgorithm::Sha1 {
+            self.config.set("core.repositoryformatversion", "0")?;
+        } else {
+            self.config.set("core.repositoryformatversion", "1")?;
+            self.config.set("extensions.objectformat", object_format.name())?;
+        }
+        self.config.set("core.filemode", "true")?;
+        let bare = if self.repository.is_bare() { "true" } else { "false" };
+        self.config.set("core.bare", bare)?;
+        self.config.save()?;
 
         println!(
             "Initialized empty Jit repository in: {:?}",
@@ -356,44 +570,188 @@ impl Command {
     }
 
     pub fn add(&mut self, paths: Vec<PathBuf>) -> Result<()> {
-        if !self.workspace.get_git_path().exists() {
+        if !self.repository.git_dir.exists() {
             return Err(anyhow!("not a git repository (or any parent up to mount point /)"))
         }
-        if self.workspace.get_git_path().join("index").exists() {
+        if self.repository.git_dir.join("index").exists() {
             self.index.load()?;
         }
+        let paths = paths
+            .iter()
+            .map(|path| self.repository.resolve_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        // the tracked files at `paths` deleted from the working tree are removed from the index,
+        // the unmerged ones are resolved as deleted
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let within = |key: &str| paths.iter().any(|path| path == Path::new(".") || Path::new(key).starts_with(path));
+        let deleted: Vec<String> = self
+            .index
+            .each_entry()?
+            .iter()
+            .filter(|entry| !entry.is_skip_worktree())
+            .map(|entry| entry.key())
+            .chain(self.index.unmerged_paths().cloned())
+            .filter(|key| within(key) && fs::symlink_metadata(work_tree.join(key)).is_err())
+            .collect();
+        if !deleted.is_empty() {
+            deleted.iter().for_each(|key| self.index.remove(key));
+            self.index.write_updates()?;
+        }
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| {
+                fs::symlink_metadata(work_tree.join(path)).is_ok() || !deleted.iter().any(|key| Path::new(key).starts_with(path))
+            })
+            .collect();
+        // the paths outside of a sparse checkout are not updated, the others still are
+        let sparse = self.sparse_checkout()?;
+        let (paths, outside): (Vec<PathBuf>, Vec<PathBuf>) = paths.into_iter().partition(|path| {
+            let file = if path.is_dir() { path.join("_") } else { path.to_path_buf() };
+            sparse.as_ref().is_none_or(|sparse| sparse.includes(&file))
+        });
+        if !paths.is_empty() {
+            self.stage(paths)?;
+        }
+        if !outside.is_empty() {
+            let paths: Vec<String> = outside.iter().map(|path| path.display().to_string()).collect();
+            return Err(anyhow!(
+                "The following paths and/or pathspecs matched paths that exist\n\
+                 outside of your sparse-checkout definition, so will not be\n\
+                 updated in the index:\n{}\n\
+                 hint: If you intend to update such entries, disable or modify the sparsity rules.",
+                paths.join("\n")
+            ));
+        }
+        Ok(())
+    }
+
+    // Stores the files of `paths`, relative to the top of the working tree, and records them
+    // in the loaded index. The files outside of a sparse checkout are left out.
+    pub(crate) fn stage(&mut self, paths: Vec<PathBuf>) -> Result<()> {
         let tree = Tree::new_from_files(paths, &self.db)?;
-        self.workspace
-            .create_index_entry(&tree, &mut self.index)?;
+        let sparse = self.sparse_checkout()?;
+        let workspace = self.workspace.as_ref().ok_or(anyhow!("this operation must be run in a work tree"))?;
+        workspace.create_index_entry(&tree, &mut self.index, |entry| {
+            migration::is_checked_out(sparse.as_ref(), &entry.path, entry)
+        })?;
         self.index.write_updates()?;
         Ok(())
     }
 
-    pub fn commit(&mut self, author: &str, email: &str, message: &str) -> Result<()> {
-        if self.workspace.get_git_path().join("index").exists() {
+    // Runs the hook `name`, with `GIT_INDEX_FILE` naming the index of this worktree. Whether
+    // it succeeded.
+    pub(crate) fn run_hook(&self, name: &str, args: &[&str], input: Option<&[u8]>) -> Result<bool> {
+        let index_path = self.repository.git_dir.join("index");
+        Hooks::new(&self.repository, &self.config)
+            .with_env("GIT_INDEX_FILE", &index_path.display().to_string())
+            .with_env("GIT_EDITOR", ":")
+            .run(name, args, input)
+    }
+
+    // Commits the index, with `message` or else the message written in the editor. The
+    // `pre-commit` hook may still change the index, then the message goes through
+    // `prepare-commit-msg`, the editor and `commit-msg` in `COMMIT_EDITMSG`, and `post-commit`
+    // runs once the commit is made. An amended commit replaces HEAD on top of its parents.
+    pub fn commit(&mut self, author: &str, email: &str, message: Option<&str>, options: &CommitOptions) -> Result<()> {
+        self.workspace()?;
+        if !options.no_verify && !self.run_hook("pre-commit", &[], None)? {
+            return Err(anyhow!("the pre-commit hook declined the commit"));
+        }
+        if self.repository.git_dir.join("index").exists() {
             self.index.load()?;
         } else {
             return Err(anyhow!("Unable to commit if there is not a index file"));
         }
+        self.check_unmerged("Committing")?;
         let entries = self.index.each_entry()?;
-        let root = self.workspace.create_tree_from_index(entries)?;
-        let mut tree = tree::build_add_tree(root)?;
+        let is_empty_index = entries.is_empty();
+        let root = self.workspace()?.create_tree_from_index(entries)?;
+        let mut tree = tree::build_add_tree(root, self.db.hash_algorithm())?;
         tree.save_tree(&self.db)?;
-        let refs = Refs::new(&self.workspace.get_git_path());
-        let parent = refs.read_head();
-        let current_time = Local::now();
-        let author = Author::new(author, email, current_time);
-        let oid  = util::encode_vec(&tree.sha1_hash);
-        let mut commit = Commit::new(
-            oid,
-            author,
-            message.to_string(),
-            parent.clone(),
-            None
-        )?;
+        let tree_oid = util::encode_vec(&tree.sha1_hash);
+
+        let head = self.refs.read_head();
+        let amended = match (&head, options.amend) {
+            (Some(head), true) => Some(self.load_commit(head)?),
+            (None, true) => return Err(anyhow!("You have nothing to amend.")),
+            (_, false) => None,
+        };
+        let parents = match &amended {
+            Some(amended) => amended.parents().to_vec(),
+            None => head.clone().into_iter().collect(),
+        };
+        if !options.allow_empty {
+            let unchanged = match parents.first() {
+                Some(parent) => self.load_commit(parent)?.tree_ref == tree_oid,
+                None => is_empty_index,
+            };
+            if unchanged && amended.is_some() {
+                return Err(anyhow!(
+                    "You asked to amend the most recent commit, but doing so would make\n\
+                     it empty. You can repeat your command with --allow-empty, or you can\n\
+                     remove the commit entirely with \"git reset HEAD^\"."
+                ));
+            }
+            if unchanged {
+                Command::from_repository(self.repository.clone())?.status(StatusFormat::Long, false, false)?;
+                return Err(anyhow!("nothing to commit"));
+            }
+        }
+
+        // the message given, kept by `--no-edit`, or else written in the editor after the
+        // message of the amended commit
+        let previous_message = amended.as_ref().map(|commit| commit.message().trim_end().to_string());
+        let (text, edit) = match (message, previous_message) {
+            (Some(message), _) => (message.to_string(), false),
+            (None, Some(previous)) if options.no_edit => (previous, false),
+            (None, previous) => (previous.unwrap_or_default(), true),
+        };
+        let message_path = self.repository.git_dir.join("COMMIT_EDITMSG");
+        let mut content = text.clone();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        if edit {
+            let template = Status::new(Command::from_repository(self.repository.clone())?).commit_template()?;
+            content.push_str(&format!("\n{}#\n{}", options.cleanup.help(), template));
+        }
+        fs::write(&message_path, content)?;
+        let message_file = message_path.display().to_string();
+        let source: &[&str] = match (message, &amended) {
+            (Some(_), _) => &["message"],
+            (None, Some(_)) => &["commit", "HEAD"],
+            (None, None) => &[],
+        };
+        let args: Vec<&str> = std::iter::once(message_file.as_str()).chain(source.iter().copied()).collect();
+        if !self.run_hook("prepare-commit-msg", &args, None)? {
+            return Err(anyhow!("the prepare-commit-msg hook declined the commit"));
+        }
+        if edit {
+            editor::edit_file(&editor::editor(&self.config, false), &message_path)?;
+        }
+        if !options.no_verify && !self.run_hook("commit-msg", &[&message_file], None)? {
+            return Err(anyhow!("the commit-msg hook declined the commit"));
+        }
+        let message = options.cleanup.apply(&fs::read_to_string(&message_path)?, edit);
+        if message.trim().is_empty() {
+            return Err(anyhow!("Aborting commit due to empty commit message."));
+        }
+
+        let hash = self.db.hash_algorithm();
+        let committer = Author::new(author, email, Local::now());
+        let author = match &amended {
+            Some(amended) if !options.reset_author => amended.author().clone(),
+            _ => committer.clone(),
+        };
+        let mut commit = Commit::new(tree_oid, author, message.clone(), parents.clone(), None, hash)?;
+        commit.set_committer(committer, hash)?;
+        if options.sign.unwrap_or(self.config.get_bool("commit.gpgsign") == Some(true)) {
+            let signature = signing::sign(&self.config, &commit.payload()?)?;
+            commit.set_signature(signature, hash)?;
+        }
         self.db.store(&mut commit)?;
-        refs.update_head(util::encode_vec(&commit.get_oid()?))?;
-        let is_root = if parent.is_none() {
+        self.refs.update_head(util::encode_vec(&commit.get_oid()?))?;
+        let is_root = if parents.is_empty() {
             "(root-commit)"
         } else {
             ""
@@ -404,6 +762,7 @@ impl Command {
             util::encode_vec(&commit.get_oid()?),
             message.lines().next().unwrap()
         );
+        self.run_hook("post-commit", &[], None)?;
         Ok(())
     }
 }
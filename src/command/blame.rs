@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::blame::{self, Blame, BlameLine};
+use crate::refs::HEAD;
+use crate::Revision;
+
+use super::Command;
+
+// Parses a `-L` range, `<start>,<end>`, `<start>,+<count>` or an open range like `<start>,`,
+// into the first and last line numbers it covers in a file of `total` lines.
+fn parse_range(range: &str, total: usize) -> Result<(usize, usize)> {
+    let invalid = || anyhow!("invalid -L range: '{}'", range);
+    let (start, end) = range.split_once(',').unwrap_or((range, ""));
+    let start = match start {
+        "" => 1,
+        n => n.parse::<usize>().map_err(|_| invalid())?,
+    };
+    let end = match end {
+        "" => total,
+        n => match n.strip_prefix('+') {
+            Some(count) => start + count.parse::<usize>().map_err(|_| invalid())?.max(1) - 1,
+            None => n.parse::<usize>().map_err(|_| invalid())?,
+        },
+    };
+    if start == 0 || end < start {
+        return Err(invalid());
+    }
+    if start > total {
+        return Err(anyhow!("file has only {} lines", total));
+    }
+    Ok((start, end.min(total)))
+}
+
+fn content(line: &BlameLine) -> String {
+    let content = String::from_utf8_lossy(&line.content).to_string();
+    content.strip_suffix('\n').unwrap_or(&content).to_string()
+}
+
+impl Command {
+    // Shows the commit that last changed each line of `path` as it is in `revision`, HEAD by
+    // default, restricted to the `-L` ranges if any.
+    pub fn blame(&mut self, revision: Option<&str>, path: &Path, ranges: &[&str], porcelain: bool) -> Result<()> {
+        let path = self.repository.resolve_path(path)?;
+        let commit = Revision::new(&self.refs, &mut self.db).resolve_commit(revision.unwrap_or(HEAD))?;
+        let blame = blame::blame(&mut self.db, &commit, &path)?;
+        let total = blame.lines.len();
+        let mut shown = Vec::new();
+        for range in ranges {
+            let (start, end) = parse_range(range, total)?;
+            shown.extend(blame.lines[start - 1..end].iter());
+        }
+        if ranges.is_empty() {
+            shown.extend(blame.lines.iter());
+        }
+        if porcelain {
+            print_porcelain(&blame, &shown);
+        } else {
+            print_blame(&blame, &shown);
+        }
+        Ok(())
+    }
+}
+
+// `<oid> (<author> <date> <line number>) <line>`, the oid of commits without parents
+// marked with a `^`.
+fn print_blame(blame: &Blame, lines: &[&BlameLine]) {
+    let name_width = lines
+        .iter()
+        .map(|l| blame.commits[&l.commit].author().name().chars().count())
+        .max()
+        .unwrap_or(0);
+    let number_width = lines.iter().map(|l| l.final_line.to_string().len()).max().unwrap_or(0);
+    for line in lines {
+        let author = blame.commits[&line.commit].author();
+        let oid = if blame.is_boundary(&line.commit) {
+            format!("^{}", &line.commit[..7])
+        } else {
+            line.commit[..8].to_string()
+        };
+        println!(
+            "{} ({:<name_width$} {} {:>number_width$}) {}",
+            oid,
+            author.name(),
+            author.time().format("%Y-%m-%d %H:%M:%S %z"),
+            line.final_line,
+            content(line),
+            name_width = name_width,
+            number_width = number_width
+        );
+    }
+}
+
+// The format meant for tools: each line is introduced by `<oid> <orig line> <final line>`,
+// followed by the number of lines of the group it starts if any. The details of a commit are
+// given the first time it appears.
+fn print_porcelain(blame: &Blame, lines: &[&BlameLine]) {
+    let mut described = HashSet::new();
+    for (n, line) in lines.iter().enumerate() {
+        let continues = |prev: &BlameLine, next: &BlameLine| {
+            prev.commit == next.commit
+                && prev.orig_line + 1 == next.orig_line
+                && prev.final_line + 1 == next.final_line
+        };
+        let starts_group = n == 0 || !continues(lines[n - 1], line);
+        if starts_group {
+            let size = 1 + lines[n + 1..]
+                .iter()
+                .zip(lines[n..].iter())
+                .take_while(|(next, prev)| continues(prev, next))
+                .count();
+            println!("{} {} {} {}", line.commit, line.orig_line, line.final_line, size);
+        } else {
+            println!("{} {} {}", line.commit, line.orig_line, line.final_line);
+        }
+        if described.insert(line.commit.to_string()) {
+            let commit = &blame.commits[&line.commit];
+            for (role, person) in [("author", commit.author()), ("committer", commit.committer())] {
+                println!("{} {}", role, person.name());
+                println!("{}-mail <{}>", role, person.email());
+                println!("{}-time {}", role, person.time().timestamp());
+                println!("{}-tz {}", role, person.time().format("%z"));
+            }
+            println!("summary {}", commit.title_line());
+            if blame.is_boundary(&line.commit) {
+                println!("boundary");
+            }
+            if let Some(parent) = blame.previous.get(&line.commit) {
+                println!("previous {} {}", parent, blame.path.display());
+            }
+            println!("filename {}", blame.path.display());
+        }
+        println!("\t{}", content(line));
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::{self, Write};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::{util, Revision};
+
+use super::Command;
+
+// What `cat-file` prints about an object.
+pub enum CatFileMode {
+    Type,
+    Size,
+    Pretty,
+    // the raw content, only if the object has the given type
+    Content(String),
+}
+
+impl Command {
+    pub fn cat_file(&mut self, mode: CatFileMode, object: &str) -> Result<()> {
+        let oid = Revision::new(&self.refs, &mut self.db).resolve(object)?;
+        let mut reader = self.db.open_object(&oid)?;
+        match mode {
+            CatFileMode::Type => println!("{}", reader.type_),
+            CatFileMode::Size => println!("{}", reader.size),
+            CatFileMode::Pretty if reader.type_ == "tree" => self.print_tree(&oid)?,
+            CatFileMode::Content(ref type_) if *type_ != reader.type_ => {
+                return Err(anyhow!("{}: bad file", object))
+            }
+            CatFileMode::Pretty | CatFileMode::Content(_) => {
+                // blobs can be big, so they are streamed instead of being loaded in memory
+                let stdout = io::stdout();
+                let mut out = stdout.lock();
+                io::copy(&mut reader, &mut out)?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn print_tree(&mut self, oid: &str) -> Result<()> {
+        let entries = match self.db.load(oid)? {
+            ObjectType::TreeType { tree } => tree.entries.clone(),
+            _ => return Err(anyhow!("object {} is not a tree", oid)),
+        };
+        for entry in entries.iter() {
+            let type_ = if entry.is_tree() {
+                "tree"
+            } else if entry.is_gitlink() {
+                "commit"
+            } else {
+                "blob"
+            };
+            println!(
+                "{:0>6} {} {}\t{}",
+                entry.mode,
+                type_,
+                util::encode_vec(&entry.sha1_hash),
+                entry.name
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::convert;
+use crate::database::ObjectType;
+use crate::diff::{self, EditKind};
+use crate::tree::TreeList;
+use crate::tree_diff::{self, Change, ChangeKind, RenameOptions};
+use crate::{util, Entry, Revision, StatChange};
+
+use super::migration::tree_entry;
+use super::submodule;
+use super::Command;
+
+// How `diff` and `log` show the changes: as patches or as the list of the changed files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Patch,
+    NameStatus,
+    NameOnly,
+}
+
+const CONTEXT_LINES: usize = 3;
+
+impl Command {
+    // The rename detection configured with `diff.renames`, on by default as in git, and
+    // `diff.renameLimit`. `diff.renames=copies` looks for copies too.
+    pub fn rename_options(&self) -> Option<RenameOptions> {
+        let mut options = RenameOptions::default();
+        match self.config.get("diff.renames").map(|v| v.to_lowercase()).as_deref() {
+            Some("false") | Some("no") | Some("off") | Some("0") => return None,
+            Some("copies") | Some("copy") => options.copies = true,
+            _ => {}
+        }
+        if let Some(limit) = self.config.get("diff.renamelimit").and_then(|v| v.parse().ok()) {
+            options.limit = limit;
+        }
+        Some(options)
+    }
+
+    // Shows the changes between the index and the working tree, or with `cached` between
+    // HEAD, or the commit given, and the index. A commit alone is compared to the working
+    // tree and two commits to each other.
+    pub fn diff(
+        &mut self,
+        revisions: &[&str],
+        cached: bool,
+        format: DiffFormat,
+        renames: Option<RenameOptions>,
+    ) -> Result<()> {
+        let mut commits = Vec::new();
+        for revision in revisions {
+            commits.push(Revision::new(&self.refs, &mut self.db).resolve_commit(revision)?);
+        }
+        let workspace = commits.len() < 2 && !cached;
+        let (old, new) = match (commits.as_slice(), cached) {
+            ([a, b], _) => (self.commit_tree_list(a)?, self.commit_tree_list(b)?),
+            ([commit], true) => {
+                self.load_index()?;
+                (self.commit_tree_list(commit)?, self.index_tree_list()?)
+            }
+            ([], true) => {
+                self.load_index()?;
+                (self.head_tree_list()?, self.index_tree_list()?)
+            }
+            ([commit], false) => {
+                self.load_index()?;
+                let old = self.commit_tree_list(commit)?;
+                let paths: Vec<PathBuf> = self.index_tree_list()?.keys().chain(old.keys()).cloned().collect();
+                (old, self.hashed_workspace_list(&paths)?)
+            }
+            _ => {
+                self.load_index()?;
+                let index = self.index_tree_list()?;
+                let paths: Vec<PathBuf> = index.keys().cloned().collect();
+                (index, self.hashed_workspace_list(&paths)?)
+            }
+        };
+        let mut changes = self.diff_tree_lists(&old, &new, renames.as_ref())?;
+        let dirty = if workspace {
+            self.dirty_submodules(&new)?
+        } else {
+            BTreeSet::new()
+        };
+        // a submodule with changes of its own differs from the commit it has checked out
+        let unchanged: Vec<&PathBuf> =
+            dirty.iter().filter(|path| !changes.iter().any(|change| change.path() == *path)).collect();
+        if !unchanged.is_empty() {
+            for path in unchanged {
+                changes.push(Change {
+                    kind: ChangeKind::Modified,
+                    old: old.get(path).cloned(),
+                    new: new.get(path).cloned(),
+                    score: 0,
+                });
+            }
+            changes.sort_by(|a, b| a.path().cmp(b.path()));
+        }
+        self.print_changes(&changes, format, &dirty)
+    }
+
+    // the submodules of the working tree `list` with modified or untracked files.
+    fn dirty_submodules(&self, list: &TreeList) -> Result<BTreeSet<PathBuf>> {
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let mut dirty = BTreeSet::new();
+        for (path, entry) in list.iter().filter(|(_, entry)| entry.is_gitlink()) {
+            let oid = util::encode_vec(&entry.sha1_hash);
+            if let Some(change) = submodule::submodule_change(&work_tree.join(path), &oid)? {
+                if change.is_dirty() {
+                    dirty.insert(path.to_path_buf());
+                }
+            }
+        }
+        Ok(dirty)
+    }
+
+    pub(crate) fn diff_tree_lists(
+        &mut self,
+        old: &TreeList,
+        new: &TreeList,
+        renames: Option<&RenameOptions>,
+    ) -> Result<Vec<Change>> {
+        tree_diff::diff_lists(old, new, renames, |entry| self.entry_content(entry))
+    }
+
+    // The entries of the files of the working tree among `paths`, without storing them: their
+    // oid is computed from the content unless the index says the file did not change.
+    pub(super) fn hashed_workspace_list(&mut self, paths: &[PathBuf]) -> Result<TreeList> {
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let entries = self.index.each_entry()?;
+        let mut list = TreeList::new();
+        for path in paths {
+            let index_entry = entries.iter().find(|e| e.path == *path);
+            // the files outside of a sparse checkout are unchanged wherever they are
+            if let Some(entry) = index_entry.filter(|e| e.is_skip_worktree()) {
+                let mode = util::get_mode_u(entry.get_mode()?);
+                list.insert(path.to_path_buf(), tree_entry(path, entry.oid.to_vec(), mode));
+                continue;
+            }
+            if let Some(entry) = self.gitlink_entry(path) {
+                list.insert(path.to_path_buf(), entry);
+                continue;
+            }
+            let full_path = work_tree.join(path);
+            let stat = match util::stat_file(&full_path) {
+                Ok(stat) if util::is_blob(&stat) => stat,
+                _ => continue,
+            };
+            let mut mode = util::get_mode_stat(&stat);
+            let mut tracked = None;
+            if let Some(entry) = index_entry {
+                mode = self.index.stat_options().mode(entry.get_mode()?, mode);
+                if self.index.stat_change(entry, &stat) == StatChange::Unchanged {
+                    tracked = Some(entry.oid.to_vec());
+                }
+            }
+            let oid = match tracked {
+                Some(oid) => oid,
+                None => self.db.hash_file(&full_path)?,
+            };
+            let mode = util::get_mode_u(mode);
+            list.insert(path.to_path_buf(), tree_entry(path, oid, mode));
+        }
+        Ok(list)
+    }
+
+    // the content of the blob of an entry, read from the working tree when it is not stored.
+    // A submodule is shown as the commit it records.
+    pub(super) fn entry_content(&mut self, entry: &Entry) -> Result<Vec<u8>> {
+        let oid = util::encode_vec(&entry.sha1_hash);
+        if entry.is_gitlink() {
+            return Ok(format!("Subproject commit {}\n", oid).into_bytes());
+        }
+        if let Ok(ObjectType::BlobType { blob }) = self.db.load(&oid) {
+            return Ok(blob.get_content().to_vec());
+        }
+        let path = self.workspace()?.pathname.join(&entry.path);
+        match (util::read_link(&path)?, self.db.converter()) {
+            (Some(target), _) => Ok(target),
+            (None, Some(converter)) => converter.to_git(&entry.path, fs::read(path)?),
+            (None, None) => Ok(fs::read(path)?),
+        }
+    }
+
+    // Prints `changes` in `format`, the submodules in `dirty` have changes of their own in the
+    // working tree.
+    pub(crate) fn print_changes(
+        &mut self,
+        changes: &[Change],
+        format: DiffFormat,
+        dirty: &BTreeSet<PathBuf>,
+    ) -> Result<()> {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        for change in changes {
+            match format {
+                DiffFormat::NameOnly => writeln!(out, "{}", change.path().display())?,
+                DiffFormat::NameStatus => match change.kind {
+                    ChangeKind::Renamed | ChangeKind::Copied => writeln!(
+                        out,
+                        "{}\t{}\t{}",
+                        change.status(),
+                        change.old_path().unwrap_or(change.path()).display(),
+                        change.path().display()
+                    )?,
+                    _ => writeln!(out, "{}\t{}", change.status(), change.path().display())?,
+                },
+                DiffFormat::Patch => {
+                    let patch = self.patch(change, dirty.contains(change.path()))?;
+                    out.write_all(&patch)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the change in the format of `git diff`: the headers then the hunks of the line diff.
+    fn patch(&mut self, change: &Change, dirty: bool) -> Result<Vec<u8>> {
+        let old_path = change.old_path().unwrap_or(change.path()).display().to_string();
+        let new_path = change.path().display().to_string();
+        let short = |entry: &Option<Entry>| match entry {
+            Some(entry) => util::encode_vec(&entry.sha1_hash)[..7].to_string(),
+            None => "0000000".to_string(),
+        };
+        let mut out = patch_header(change);
+        match change.kind {
+            ChangeKind::Renamed => out.push_str(&format!(
+                "similarity index {}%\nrename from {}\nrename to {}\n",
+                change.score, old_path, new_path
+            )),
+            ChangeKind::Copied => out.push_str(&format!(
+                "similarity index {}%\ncopy from {}\ncopy to {}\n",
+                change.score, old_path, new_path
+            )),
+            _ => {}
+        }
+        let same_content = match (&change.old, &change.new) {
+            (Some(old), Some(new)) => old.sha1_hash == new.sha1_hash,
+            _ => false,
+        };
+        if same_content && !dirty {
+            return Ok(out.into_bytes());
+        }
+        let mode = match (&change.old, &change.new) {
+            (Some(old), Some(new)) if old.mode == new.mode => format!(" {}", new.mode),
+            _ => String::new(),
+        };
+        if !same_content {
+            out.push_str(&format!("index {}..{}{}\n", short(&change.old), short(&change.new), mode));
+        }
+
+        let old_data = match &change.old {
+            Some(entry) => self.entry_content(entry)?,
+            None => vec![],
+        };
+        let mut new_data = match &change.new {
+            Some(entry) => self.entry_content(entry)?,
+            None => vec![],
+        };
+        if dirty {
+            new_data.truncate(new_data.len() - 1);
+            new_data.extend_from_slice(b"-dirty\n");
+        }
+        let old_name = change.old.as_ref().map_or("/dev/null".to_string(), |_| format!("a/{}", old_path));
+        let new_name = change.new.as_ref().map_or("/dev/null".to_string(), |_| format!("b/{}", new_path));
+        let binary = |path: &str, data: &[u8]| match self.db.converter() {
+            Some(converter) => converter.is_binary_diff(Path::new(path), data),
+            None => convert::is_binary(data),
+        };
+        if binary(&old_path, &old_data) || binary(&new_path, &new_data) {
+            out.push_str(&format!("Binary files {} and {} differ\n", old_name, new_name));
+            return Ok(out.into_bytes());
+        }
+        out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        let mut out = out.into_bytes();
+        let (a, b) = (diff::lines(&old_data), diff::lines(&new_data));
+        for hunk in diff::hunks(&diff::diff(&a, &b), CONTEXT_LINES) {
+            out.extend_from_slice(hunk.header().as_bytes());
+            out.push(b'\n');
+            for edit in hunk.edits.iter() {
+                let (prefix, line) = match edit.kind {
+                    EditKind::Eql => (b' ', a[edit.a_index.unwrap_or(0)]),
+                    EditKind::Del => (b'-', a[edit.a_index.unwrap_or(0)]),
+                    EditKind::Ins => (b'+', b[edit.b_index.unwrap_or(0)]),
+                };
+                out.push(prefix);
+                out.extend_from_slice(line);
+                if !line.ends_with(b"\n") {
+                    out.extend_from_slice(b"\n\\ No newline at end of file\n");
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+// the `diff --git` line of a change, followed by the lines telling how its mode changed.
+pub(super) fn patch_header(change: &Change) -> String {
+    let old_path = change.old_path().unwrap_or(change.path()).display().to_string();
+    let mut out = format!("diff --git a/{} b/{}\n", old_path, change.path().display());
+    match (&change.old, &change.new) {
+        (None, Some(new)) => out.push_str(&format!("new file mode {}\n", new.mode)),
+        (Some(old), None) => out.push_str(&format!("deleted file mode {}\n", old.mode)),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            out.push_str(&format!("old mode {}\nnew mode {}\n", old.mode, new.mode))
+        }
+        _ => {}
+    }
+    out
+}
@@ -0,0 +1,276 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::RawObject;
+use crate::tree::entry_sort_key;
+use crate::util;
+use crate::{HashAlgorithm, Index};
+
+use super::Command;
+
+const VALID_MODES: [&str; 6] = ["100644", "100755", "120000", "040000", "40000", "160000"];
+
+pub struct Fsck {
+    cmd: Command,
+    // type of every object found in the database, indexed by oid.
+    objects: BTreeMap<String, String>,
+    // objects referenced by every parsed object, with the type they are expected to have.
+    links: HashMap<String, Vec<(String, String)>>,
+    problems: usize,
+}
+
+impl Fsck {
+    pub fn new(cmd: Command) -> Self {
+        Fsck {
+            cmd,
+            objects: BTreeMap::new(),
+            links: HashMap::new(),
+            problems: 0,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        if !self.cmd.repository.git_dir.exists() {
+            return Err(anyhow!("not a git repository (or any parent up to mount point /)"))
+        }
+        for oid in self.cmd.db.list_objects()? {
+            self.check_object(&oid);
+        }
+        let roots = self.collect_roots()?;
+        let reachable = self.walk_reachable(&roots);
+        self.report_dangling(&reachable, &roots);
+        if self.problems > 0 {
+            return Err(anyhow!("fsck found {} problem(s) in the repository", self.problems))
+        }
+        Ok(())
+    }
+
+    fn error(&mut self, message: String) {
+        self.problems += 1;
+        println!("error: {}", message);
+    }
+
+    fn check_object(&mut self, oid: &str) {
+        let raw = match self.cmd.db.read_raw(oid) {
+            Ok(raw) => raw,
+            Err(e) => {
+                self.error(format!("{}: object corrupt or missing: {}", oid, e));
+                return;
+            }
+        };
+        if raw.size != raw.content.len() {
+            self.error(format!(
+                "{}: object size mismatch (header says {}, content is {})",
+                oid,
+                raw.size,
+                raw.content.len()
+            ));
+        }
+        let hash = self.cmd.db.hash_algorithm();
+        let computed = util::encode_vec(&util::hexdigest_vec(&raw.get_data(), hash));
+        if computed != oid {
+            self.error(format!("hash mismatch for {} (content hashes to {})", oid, computed));
+        }
+        let links = match raw.type_.as_str() {
+            "blob" => Ok(vec![]),
+            "tree" => check_tree(&raw, hash),
+            "commit" => check_commit(&raw, hash),
+            "tag" => check_tag(&raw, hash),
+            other => Err(anyhow!("unknown object type '{}'", other)),
+        };
+        match links {
+            Ok(links) => {
+                self.links.insert(oid.to_string(), links);
+            }
+            Err(e) => self.error(format!("in {} {}: {}", raw.type_, oid, e)),
+        }
+        self.objects.insert(oid.to_string(), raw.type_);
+    }
+
+    // the HEAD of every worktree, every ref and every entry of the index of every worktree
+    // are the starting points of the walk.
+    fn collect_roots(&mut self) -> Result<Vec<(String, String)>> {
+        let mut roots = Vec::new();
+        let worktrees = self.cmd.worktrees()?;
+        for worktree in worktrees.iter() {
+            if let Some(head) = &worktree.head {
+                roots.push(("HEAD".to_string(), head.to_string()));
+            }
+        }
+        roots.extend(self.cmd.refs.list_refs()?);
+        // tags may point to annotated tags as well as to any other object
+        let mut roots = roots
+            .into_iter()
+            .map(|(name, oid)| {
+                let expected = match self.objects.get(&oid) {
+                    Some(type_) if name.starts_with("refs/tags/") => type_.to_string(),
+                    _ => "commit".to_string(),
+                };
+                (oid, expected)
+            })
+            .collect::<Vec<_>>();
+        let hash = self.cmd.db.hash_algorithm();
+        for worktree in worktrees.iter() {
+            let index_path = worktree.git_dir.join("index");
+            if !index_path.exists() {
+                continue;
+            }
+            let mut index = Index::new_with_hash(&index_path, hash);
+            index.load()?;
+            // the commits of submodules are in their own repository
+            for entry in index.each_entry()?.iter().filter(|e| e.get_mode().ok() != Some(util::GITLINK_MODE)) {
+                roots.push((util::encode_vec(&entry.oid), "blob".to_string()));
+            }
+        }
+        Ok(roots)
+    }
+
+    fn walk_reachable(&mut self, roots: &[(String, String)]) -> BTreeSet<String> {
+        let mut reachable = BTreeSet::new();
+        let mut missing = BTreeSet::new();
+        let mut work = roots.to_vec();
+        while let Some((oid, expected)) = work.pop() {
+            if reachable.contains(&oid) || missing.contains(&oid) {
+                continue;
+            }
+            match self.objects.get(&oid) {
+                Some(type_) => {
+                    if *type_ != expected {
+                        let message = format!("object {} is a {}, not a {}", oid, type_, expected);
+                        self.error(message);
+                    }
+                    reachable.insert(oid.to_string());
+                    if let Some(links) = self.links.get(&oid) {
+                        work.extend(links.iter().cloned());
+                    }
+                }
+                None => {
+                    self.problems += 1;
+                    println!("missing {} {}", expected, oid);
+                    missing.insert(oid);
+                }
+            }
+        }
+        reachable
+    }
+
+    // an unreachable object is only reported as dangling when no other object points to it, the
+    // rest of the unreachable objects are implied by the dangling ones.
+    fn report_dangling(&self, reachable: &BTreeSet<String>, roots: &[(String, String)]) {
+        let referenced: BTreeSet<&String> = self
+            .links
+            .values()
+            .flatten()
+            .map(|(oid, _)| oid)
+            .chain(roots.iter().map(|(oid, _)| oid))
+            .collect();
+        for (oid, type_) in self.objects.iter() {
+            if !reachable.contains(oid) && !referenced.contains(oid) {
+                println!("dangling {} {}", type_, oid);
+            }
+        }
+    }
+}
+
+fn check_tree(raw: &RawObject, hash: HashAlgorithm) -> Result<Vec<(String, String)>> {
+    let mut links = Vec::new();
+    let mut previous: Option<Vec<u8>> = None;
+    let mut names = BTreeSet::new();
+    let mut rest = &raw.content[..];
+    while !rest.is_empty() {
+        let space = rest
+            .iter()
+            .position(|b| *b == 0x20u8)
+            .ok_or(anyhow!("truncated entry mode"))?;
+        let mode = String::from_utf8(rest[..space].to_vec())?;
+        rest = &rest[space + 1..];
+        let nul = rest
+            .iter()
+            .position(|b| *b == 0x00u8)
+            .ok_or(anyhow!("truncated entry name"))?;
+        let name = String::from_utf8(rest[..nul].to_vec())?;
+        rest = &rest[nul + 1..];
+        if rest.len() < hash.size() {
+            return Err(anyhow!("truncated entry oid for '{}'", name));
+        }
+        let oid = util::encode_vec(&rest[..hash.size()].to_vec());
+        rest = &rest[hash.size()..];
+
+        if !VALID_MODES.contains(&mode.as_str()) {
+            return Err(anyhow!("entry '{}' has an invalid mode {}", name, mode));
+        }
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." || name == ".git" {
+            return Err(anyhow!("entry has an invalid name '{}'", name));
+        }
+        if !names.insert(name.to_string()) {
+            return Err(anyhow!("duplicate entry '{}'", name));
+        }
+        let is_tree = mode == "040000" || mode == "40000";
+        let key = entry_sort_key(&name, is_tree);
+        if previous.as_ref().is_some_and(|p| *p > key) {
+            return Err(anyhow!("entries are not properly sorted at '{}'", name));
+        }
+        previous = Some(key);
+        match mode.as_str() {
+            // gitlinks point to commits of another repository
+            "160000" => {}
+            _ if is_tree => links.push((oid, "tree".to_string())),
+            _ => links.push((oid, "blob".to_string())),
+        }
+    }
+    Ok(links)
+}
+
+fn check_commit(raw: &RawObject, hash: HashAlgorithm) -> Result<Vec<(String, String)>> {
+    let content = String::from_utf8(raw.content.to_vec())?;
+    let mut links = Vec::new();
+    let mut has_author = false;
+    for (n, line) in content.lines().enumerate() {
+        if line.is_empty() {
+            break;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "tree" if n == 0 => links.push((check_oid(value, hash)?, "tree".to_string())),
+            "tree" => return Err(anyhow!("duplicate tree header")),
+            _ if n == 0 => return Err(anyhow!("missing tree header")),
+            "parent" => links.push((check_oid(value, hash)?, "commit".to_string())),
+            "author" => has_author = true,
+            _ => {}
+        }
+    }
+    if links.is_empty() {
+        return Err(anyhow!("missing tree header"));
+    }
+    if !has_author {
+        return Err(anyhow!("missing author header"));
+    }
+    Ok(links)
+}
+
+fn check_tag(raw: &RawObject, hash: HashAlgorithm) -> Result<Vec<(String, String)>> {
+    let content = String::from_utf8_lossy(&raw.content);
+    let header = |name: &str| {
+        content
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| line.strip_prefix(name).and_then(|value| value.strip_prefix(' ')))
+            .ok_or(anyhow!("missing {} header", name))
+    };
+    let object = check_oid(header("object")?, hash)?;
+    let type_ = header("type")?;
+    if !matches!(type_, "commit" | "tree" | "blob" | "tag") {
+        return Err(anyhow!("invalid type '{}'", type_));
+    }
+    header("tag")?;
+    Ok(vec![(object, type_.to_string())])
+}
+
+fn check_oid(value: &str, hash: HashAlgorithm) -> Result<String> {
+    if !hash.is_valid_hex(value) {
+        return Err(anyhow!("invalid object id '{}'", value));
+    }
+    Ok(value.to_string())
+}
@@ -0,0 +1,75 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::refs::HEAD;
+use crate::tree::TreeList;
+use crate::tree_diff::RenameOptions;
+use crate::Revision;
+
+use super::{Command, DiffFormat};
+
+impl Command {
+    // prints the commits reachable from `start`, following the parents of each commit. With
+    // a `format`, the changes of each commit from its first parent follow its message, and
+    // with `show_signature` the check of the signature of each signed commit follows its oid.
+    pub fn log(
+        &mut self,
+        start: Option<&str>,
+        format: Option<DiffFormat>,
+        renames: Option<RenameOptions>,
+        show_signature: bool,
+    ) -> Result<()> {
+        let start = start.unwrap_or(HEAD);
+        if start == HEAD && self.refs.read_head().is_none() {
+            let branch = self.refs.resolve_symbolic(HEAD)?;
+            let branch = branch.strip_prefix("refs/heads/").unwrap_or(&branch).to_string();
+            return Err(anyhow::anyhow!(
+                "your current branch '{}' does not have any commits yet",
+                branch
+            ));
+        }
+        let mut next = Some(Revision::new(&self.refs, &mut self.db).resolve_commit(start)?);
+        let mut first = true;
+        while let Some(oid) = next {
+            let commit = match self.db.load(&oid)? {
+                ObjectType::CommitType { commit } => commit,
+                _ => break,
+            };
+            let commit = commit.clone();
+            if !first {
+                println!();
+            }
+            first = false;
+            println!("commit {}", oid);
+            if show_signature {
+                match self.commit_signature(&oid) {
+                    Ok(Some(check)) => print!("{}", check.report()),
+                    Ok(None) => {}
+                    Err(e) => println!("error: {}", e),
+                }
+            }
+            println!("Author: {} <{}>", commit.author().name(), commit.author().email());
+            println!("Date:   {}", commit.author().readable_time());
+            println!();
+            for line in commit.message().trim_end().lines() {
+                println!("    {}", line);
+            }
+            if let Some(format) = format {
+                let old = match commit.parent() {
+                    Some(parent) => self.commit_tree_list(parent)?,
+                    None => TreeList::new(),
+                };
+                let new = self.commit_tree_list(&oid)?;
+                let changes = self.diff_tree_lists(&old, &new, renames.as_ref())?;
+                if !changes.is_empty() {
+                    println!();
+                    self.print_changes(&changes, format, &BTreeSet::new())?;
+                }
+            }
+            next = commit.parent().cloned();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, Metadata};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::attributes::AttributeState;
+use crate::database::{ObjectReader, ObjectType};
+use crate::merge;
+use crate::sparse::SparseCheckout;
+use crate::tree::{self, TreeList};
+use crate::{util, Author, Blob, Commit, Entry, Object, Revision};
+
+use super::Command;
+
+// Why a path could not be merged automatically.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictKind {
+    Content,
+    ModifyDelete,
+    AddAdd,
+}
+
+// A path that could not be merged, with its entries in the base and on each side, which are
+// recorded as the stages 1 to 3 of the index.
+pub struct Conflict {
+    pub path: PathBuf,
+    pub kind: ConflictKind,
+    pub stages: [Option<Entry>; 3],
+}
+
+// The result of merging two sets of changes to the same tree: the entries to write in the
+// working tree and the paths that need to be resolved by hand, whose entry holds the content
+// with the markers.
+pub struct TreeMerge {
+    pub list: TreeList,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl TreeMerge {
+    pub fn is_conflicted(&self, path: &Path) -> bool {
+        self.conflicts.iter().any(|conflict| conflict.path == path)
+    }
+
+    pub fn print_conflicts(&self) {
+        for Conflict { path, kind, .. } in self.conflicts.iter() {
+            match kind {
+                ConflictKind::Content => {
+                    println!("CONFLICT (content): Merge conflict in {}", path.display())
+                }
+                ConflictKind::AddAdd => {
+                    println!("CONFLICT (add/add): Merge conflict in {}", path.display())
+                }
+                ConflictKind::ModifyDelete => println!(
+                    "CONFLICT (modify/delete): {} deleted in one side and modified in the other",
+                    path.display()
+                ),
+            }
+        }
+    }
+}
+
+// The helpers below move the working tree and the index between the states recorded in
+// trees, as needed by the commands that rewrite them like `stash`.
+impl Command {
+    pub(crate) fn load_index(&mut self) -> Result<()> {
+        if self.repository.git_dir.join("index").exists() {
+            self.index.load()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn commit_tree_list(&mut self, commit: &str) -> Result<TreeList> {
+        let tree = Revision::new(&self.refs, &mut self.db).peel_to_tree(commit)?;
+        self.db.load_tree_list(&tree)
+    }
+
+    // the files of the commit HEAD points to, none if there is no commit yet.
+    pub(crate) fn head_tree_list(&mut self) -> Result<TreeList> {
+        match self.refs.read_head() {
+            Some(head) => self.commit_tree_list(&head),
+            None => Ok(TreeList::new()),
+        }
+    }
+
+    pub(crate) fn index_tree_list(&self) -> Result<TreeList> {
+        let mut list = TreeList::new();
+        for entry in self.index.each_entry()?.iter() {
+            let path = entry.path.to_path_buf();
+            list.insert(path.to_path_buf(), tree_entry(&path, entry.oid.to_vec(), util::get_mode_u(entry.get_mode()?)));
+        }
+        Ok(list)
+    }
+
+    // Stores the current content of `paths` in the database and returns their entries, the
+    // paths missing from the working tree are left out. Submodules are entries of the commit
+    // they have checked out and the files outside of a sparse checkout keep their entry of the
+    // index.
+    pub(crate) fn workspace_tree_list<'a, I>(&self, paths: I) -> Result<TreeList>
+    where
+        I: IntoIterator<Item = &'a PathBuf>,
+    {
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let skipped: HashMap<PathBuf, Entry> = self
+            .index
+            .each_entry()?
+            .iter()
+            .filter(|entry| entry.is_skip_worktree())
+            .map(|entry| {
+                let mode = util::get_mode_u(entry.get_mode()?);
+                Ok((entry.path.to_path_buf(), tree_entry(&entry.path, entry.oid.to_vec(), mode)))
+            })
+            .collect::<Result<_>>()?;
+        let mut list = TreeList::new();
+        let mut files = Vec::new();
+        for path in paths {
+            let full_path = work_tree.join(path);
+            if let Some(entry) = skipped.get(path) {
+                list.insert(path.to_path_buf(), entry.clone());
+            } else if let Some(entry) = self.gitlink_entry(path) {
+                list.insert(path.to_path_buf(), entry);
+            } else if util::stat_file(&full_path).is_ok_and(|stat| util::is_blob(&stat)) {
+                files.push((path, full_path));
+            }
+        }
+        let full_paths: Vec<PathBuf> = files.iter().map(|(_, full_path)| full_path.to_path_buf()).collect();
+        let oids = self.db.store_files(&full_paths)?;
+        for ((path, file), oid) in files.iter().zip(oids) {
+            let mode = util::get_mode_u(util::get_mode_stat(&util::stat_file(file)?));
+            list.insert(path.to_path_buf(), tree_entry(path, oid, mode));
+        }
+        Ok(list)
+    }
+
+    // the files of the working tree that are not in the index.
+    pub(crate) fn untracked_files(&self) -> Result<Vec<PathBuf>> {
+        Ok(self
+            .workspace()?
+            .list_files()?
+            .into_iter()
+            .filter(|path| !self.index.is_tracked_file(&path.display().to_string()))
+            .collect())
+    }
+
+    // the content of the blob `oid`, read without keeping the object around.
+    pub(crate) fn blob_content(&self, oid: &[u8]) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.open_blob(oid)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn open_blob(&self, oid: &[u8]) -> Result<ObjectReader> {
+        let oid = util::encode_vec(&oid.to_vec());
+        let reader = self.db.open_object(&oid)?;
+        if reader.type_ != "blob" {
+            return Err(anyhow!("object {} is not a blob", oid));
+        }
+        Ok(reader)
+    }
+
+    // Writes the file of `entry` to `path` in the working tree, its content streamed from the
+    // database to the file.
+    pub(crate) fn checkout_blob(&self, path: &Path, entry: &Entry) -> Result<Metadata> {
+        let mut reader = self.open_blob(&entry.sha1_hash)?;
+        self.workspace()?.write_stream(path, &mut reader, &entry.mode)
+    }
+
+    // Updates the working tree from the state `from` to the state `to`: files whose entry
+    // changed are written, files that are not in `to` anymore are deleted together with the
+    // directories left empty. The files outside of a sparse checkout are not touched.
+    pub(crate) fn migrate_workspace(&mut self, from: &TreeList, to: &TreeList) -> Result<()> {
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let sparse = self.sparse_checkout()?;
+        let skipped = |path: &Path, entry: &Entry| !is_checked_out(sparse.as_ref(), path, entry);
+        for path in from.keys().filter(|path| !to.contains_key(*path) && !skipped(path, &from[*path])) {
+            let full_path = work_tree.join(path);
+            match util::stat_file(&full_path) {
+                Ok(stat) if util::is_blob(&stat) => {
+                    fs::remove_file(&full_path)?;
+                    if path.ends_with(".gitattributes") {
+                        self.db.converter().inspect(|converter| converter.clear_cache());
+                    }
+                }
+                // the directory of a submodule is only removed when it was not checked out
+                Ok(stat) if stat.is_dir() && from[path].is_gitlink() => {
+                    let _ = fs::remove_dir(&full_path);
+                }
+                _ => {}
+            }
+            remove_empty_parents(&work_tree, &full_path);
+        }
+        for (path, entry) in checkout_order(to) {
+            if same_entry(from.get(path), Some(entry)) || skipped(path, entry) {
+                continue;
+            }
+            let full_path = work_tree.join(path);
+            if entry.is_gitlink() {
+                self.workspace()?.write_file(path, &[], &entry.mode)?;
+                continue;
+            }
+            if util::stat_file(&full_path).is_ok_and(|stat| stat.is_dir()) {
+                fs::remove_dir_all(&full_path)?;
+            }
+            self.checkout_blob(path, entry)?;
+        }
+        Ok(())
+    }
+
+    // Replaces the index with the entries of `list`. The stat data of a file is only recorded
+    // when the working tree holds the same content, so the others show up as changed. The
+    // files outside of a sparse checkout are marked to skip the working tree.
+    pub(crate) fn write_index_list(&mut self, list: &TreeList) -> Result<()> {
+        let sparse = self.sparse_checkout()?;
+        self.index.clear()?;
+        for (path, entry) in list.iter() {
+            self.add_index_entry(path, entry, sparse.as_ref())?;
+        }
+        self.index.update_changed_status();
+        self.index.write_updates()
+    }
+
+    // Records `entry` for `path` in the loaded index, with the stat data of the file when the
+    // working tree holds the same content, or marked to skip the working tree outside of a
+    // sparse checkout.
+    pub(crate) fn add_index_entry(&mut self, path: &Path, entry: &Entry, sparse: Option<&SparseCheckout>) -> Result<()> {
+        let full_path = self.workspace()?.pathname.join(path);
+        let mode = u32::from_str_radix(&entry.mode, 8)?;
+        if !is_checked_out(sparse, path, entry) {
+            self.index.add_without_stat(path.to_path_buf(), entry.sha1_hash.to_vec(), mode)?;
+            self.index.set_skip_worktree(&path.display().to_string(), true);
+            return Ok(());
+        }
+        let stat = util::stat_file(&full_path).ok().filter(util::is_blob);
+        match stat {
+            Some(stat)
+                if self.index.stat_options().mode(mode, util::get_mode_stat(&stat)) == mode
+                    && self.db.hash_file(&full_path)? == entry.sha1_hash =>
+            {
+                self.index.add_with_mode(path.to_path_buf(), entry.sha1_hash.to_vec(), stat, mode)
+            }
+            _ => self.index.add_without_stat(path.to_path_buf(), entry.sha1_hash.to_vec(), mode),
+        }
+    }
+
+    // Records the paths of `conflicts` as unmerged in the index, with an entry for each
+    // stage whose side has the file, in place of their entry.
+    pub(crate) fn write_unmerged(&mut self, conflicts: &[Conflict]) -> Result<()> {
+        for conflict in conflicts {
+            for (stage, entry) in (1..).zip(conflict.stages.iter()) {
+                if let Some(entry) = entry {
+                    let mode = u32::from_str_radix(&entry.mode, 8)?;
+                    self.index.add_unmerged(conflict.path.to_path_buf(), stage, entry.sha1_hash.to_vec(), mode)?;
+                }
+            }
+        }
+        self.index.write_updates()
+    }
+
+    // fails when the index has unmerged paths, with which `action` cannot go on.
+    pub(crate) fn check_unmerged(&self, action: &str) -> Result<()> {
+        match self.index.unmerged_paths().next() {
+            Some(path) => Err(anyhow!(
+                "{} is not possible because you have unmerged files.\nhint: fix them up in the work tree, and then use 'git add <file>'\nhint: as appropriate to mark resolution: {}",
+                action,
+                path
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Applies to `ours` the changes that turn `base` into `theirs`. Files changed on both
+    // sides are merged line by line, the names label the sides of the conflict markers.
+    pub(crate) fn merge_tree_lists(
+        &mut self,
+        base: &TreeList,
+        ours: &TreeList,
+        theirs: &TreeList,
+        names: (&str, &str),
+    ) -> Result<TreeMerge> {
+        let mut list = TreeList::new();
+        let mut conflicts = Vec::new();
+        let paths: BTreeSet<&PathBuf> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+        for path in paths {
+            let (o, a, b) = (base.get(path), ours.get(path), theirs.get(path));
+            let merged = if same_entry(a, b) || same_entry(b, o) {
+                a.cloned()
+            } else if same_entry(a, o) {
+                b.cloned()
+            } else {
+                match (a, b) {
+                    (Some(a), Some(b)) => {
+                        let (entry, clean) = self.merge_entries(path, o, a, b, names)?;
+                        if !clean {
+                            let kind = if o.is_none() { ConflictKind::AddAdd } else { ConflictKind::Content };
+                            let stages = [o.cloned(), Some(a.clone()), Some(b.clone())];
+                            conflicts.push(Conflict { path: path.to_path_buf(), kind, stages });
+                        }
+                        Some(entry)
+                    }
+                    // one side deleted the file the other changed, the changed file is kept
+                    (a, b) => {
+                        let stages = [o.cloned(), a.cloned(), b.cloned()];
+                        let kind = ConflictKind::ModifyDelete;
+                        conflicts.push(Conflict { path: path.to_path_buf(), kind, stages });
+                        a.or(b).cloned()
+                    }
+                }
+            };
+            if let Some(entry) = merged {
+                list.insert(path.to_path_buf(), entry);
+            }
+        }
+        Ok(TreeMerge { list, conflicts })
+    }
+
+    fn merge_entries(
+        &mut self,
+        path: &Path,
+        base: Option<&Entry>,
+        ours: &Entry,
+        theirs: &Entry,
+        names: (&str, &str),
+    ) -> Result<(Entry, bool)> {
+        let base_data = match base {
+            Some(base) => self.blob_content(&base.sha1_hash)?,
+            None => vec![],
+        };
+        let ours_data = self.blob_content(&ours.sha1_hash)?;
+        let theirs_data = self.blob_content(&theirs.sha1_hash)?;
+        // a mode change on one side only is kept
+        let mode = match base {
+            Some(base) if ours.mode == base.mode => theirs.mode.to_string(),
+            _ => ours.mode.to_string(),
+        };
+        let unmerged = self
+            .db
+            .converter()
+            .is_some_and(|converter| converter.attribute(path, "merge") == AttributeState::Unset);
+        if unmerged || [&base_data, &ours_data, &theirs_data].iter().any(|d| d.contains(&0)) {
+            // binary files are not merged, our version is kept
+            return Ok((tree_entry(path, ours.sha1_hash.to_vec(), mode), false));
+        }
+        let result = merge::merge3(&base_data, &ours_data, &theirs_data);
+        let mut blob = Blob::new(result.to_bytes(names.0, names.1), self.db.hash_algorithm())?;
+        self.db.store(&mut blob)?;
+        Ok((tree_entry(path, blob.get_oid()?, mode), result.is_clean()))
+    }
+
+    pub(crate) fn write_tree_list(&self, list: &TreeList) -> Result<String> {
+        tree::write_tree_list(&self.db, list)
+    }
+
+    // stores a commit of the files of `list` and returns its oid.
+    pub(crate) fn write_commit(
+        &mut self,
+        list: &TreeList,
+        parents: Vec<String>,
+        author: &Author,
+        message: &str,
+    ) -> Result<String> {
+        let tree = self.write_tree_list(list)?;
+        let mut commit = Commit::new(
+            tree,
+            author.clone(),
+            format!("{}\n", message),
+            parents,
+            None,
+            self.db.hash_algorithm(),
+        )?;
+        self.db.store(&mut commit)?;
+        Ok(util::encode_vec(&commit.get_oid()?))
+    }
+
+    pub(crate) fn load_commit(&mut self, oid: &str) -> Result<Commit> {
+        match self.db.load(oid)? {
+            ObjectType::CommitType { commit } => Ok(commit.clone()),
+            _ => Err(anyhow!("object {} is not a commit", oid)),
+        }
+    }
+}
+
+pub(crate) fn tree_entry(path: &Path, oid: Vec<u8>, mode: String) -> Entry {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Entry::new(mode, oid, path.to_path_buf(), name, vec![])
+}
+
+// entries are the same file when they have the same mode and content, wherever they come from.
+pub(crate) fn same_entry(a: Option<&Entry>, b: Option<&Entry>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.mode == b.mode && a.sha1_hash == b.sha1_hash,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// The entries of `list` in the order they are checked out: the attributes files first, so
+// the files they apply to are converted with them.
+pub(crate) fn checkout_order(list: &TreeList) -> impl Iterator<Item = (&PathBuf, &Entry)> {
+    let is_attributes = |path: &PathBuf| path.ends_with(".gitattributes");
+    let attributes = list.iter().filter(move |(path, _)| is_attributes(path));
+    attributes.chain(list.iter().filter(move |(path, _)| !is_attributes(path)))
+}
+
+// whether the file of `entry` belongs in the working tree, submodules always do.
+pub(crate) fn is_checked_out(sparse: Option<&SparseCheckout>, path: &Path, entry: &Entry) -> bool {
+    entry.is_gitlink() || sparse.is_none_or(|sparse| sparse.includes(path))
+}
+
+pub(crate) fn remove_empty_parents(work_tree: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(d) = dir {
+        if d == work_tree || fs::remove_dir(d).is_err() {
+            break;
+        }
+        dir = d.parent();
+    }
+}
+
+// the entries of `list` that differ from `other`, in either direction.
+pub(crate) fn changed_paths(list: &TreeList, other: &TreeList) -> BTreeSet<PathBuf> {
+    list.keys()
+        .chain(other.keys())
+        .filter(|path| !same_entry(list.get(*path), other.get(*path)))
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,693 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::diff::{self, Edit, EditKind};
+use crate::editor;
+use crate::tree::TreeList;
+use crate::tree_diff::Change;
+use crate::{convert, util, Blob, Object, Revision};
+
+use super::diff::patch_header;
+use super::migration::remove_empty_parents;
+use super::Command;
+
+const CONTEXT_LINES: usize = 3;
+
+// Where the hunks chosen by `--patch` are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatchTarget {
+    Index,
+    Worktree,
+}
+
+// A way of picking hunks: the target is the old side of the diff shown, or its new side when
+// `reverse` is set, the chosen hunks being undone then. `action` and `place` make the prompts,
+// like `Discard this hunk from worktree`.
+struct PatchMode {
+    target: PatchTarget,
+    reverse: bool,
+    action: &'static str,
+    place: &'static str,
+    // what the edited hunk is marked for
+    marked: &'static str,
+}
+
+const STAGE: PatchMode = PatchMode {
+    target: PatchTarget::Index,
+    reverse: false,
+    action: "Stage",
+    place: "",
+    marked: "staging",
+};
+
+const RESET_HEAD: PatchMode = PatchMode {
+    target: PatchTarget::Index,
+    reverse: true,
+    action: "Unstage",
+    place: "",
+    marked: "unstaging",
+};
+
+const RESET_NOTHEAD: PatchMode = PatchMode {
+    target: PatchTarget::Index,
+    reverse: false,
+    action: "Apply",
+    place: " to index",
+    marked: "applying",
+};
+
+const DISCARD: PatchMode = PatchMode {
+    target: PatchTarget::Worktree,
+    reverse: true,
+    action: "Discard",
+    place: " from worktree",
+    marked: "discarding",
+};
+
+const WORKTREE_NOTHEAD: PatchMode = PatchMode {
+    target: PatchTarget::Worktree,
+    reverse: false,
+    action: "Apply",
+    place: " to worktree",
+    marked: "applying",
+};
+
+impl PatchMode {
+    fn prompt(&self, kind: HunkKind) -> String {
+        let what = match kind {
+            HunkKind::Mode => "mode change",
+            HunkKind::Addition => "addition",
+            HunkKind::Deletion => "deletion",
+            HunkKind::Lines => "this hunk",
+        };
+        format!("{} {}{}", self.action, what, self.place)
+    }
+
+    fn help(&self, options: &str) -> String {
+        let verb = self.action.to_lowercase();
+        let lines = [
+            ('y', format!("y - {} this hunk{}", verb, self.place)),
+            ('n', format!("n - do not {} this hunk{}", verb, self.place)),
+            ('q', format!("q - quit; do not {} this hunk or any of the remaining ones", verb)),
+            ('a', format!("a - {} this hunk and all later hunks in the file", verb)),
+            ('d', format!("d - do not {} this hunk or any of the later hunks in the file", verb)),
+            ('s', "s - split the current hunk into smaller hunks".to_string()),
+            ('e', "e - manually edit the current hunk".to_string()),
+            ('?', "? - print help".to_string()),
+        ];
+        lines
+            .iter()
+            .filter(|(key, _)| options.contains(*key))
+            .map(|(_, line)| format!("{}\n", line))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HunkKind {
+    Mode,
+    Addition,
+    Deletion,
+    Lines,
+}
+
+// What can be picked in the diff of a file: its mode change, its addition or deletion as a
+// whole, or a hunk of lines spanning `start..end` of the edit script. The hunks of lines split
+// from one hunk share the unchanged lines between them, but never a change.
+#[derive(Debug, Clone)]
+struct PatchHunk {
+    kind: HunkKind,
+    start: usize,
+    end: usize,
+    selected: Option<bool>,
+    edited: Option<EditedHunk>,
+}
+
+// A hunk rewritten in the editor: it replaces `start..end` of the edit script, a range which
+// covers every change of the hunk, with its lines.
+#[derive(Debug, Clone)]
+struct EditedHunk {
+    start: usize,
+    end: usize,
+    lines: Vec<(EditKind, Vec<u8>)>,
+}
+
+// The line diff of a changed file and the hunks picked from it.
+struct FilePatch<'a> {
+    a: Vec<&'a [u8]>,
+    b: Vec<&'a [u8]>,
+    edits: Vec<Edit>,
+    hunks: Vec<PatchHunk>,
+}
+
+impl<'a> FilePatch<'a> {
+    fn new(change: &Change, old_data: &'a [u8], new_data: &'a [u8], text: bool) -> Self {
+        let (a, b) = (diff::lines(old_data), diff::lines(new_data));
+        let edits = diff::diff(&a, &b);
+        let whole = |kind| PatchHunk {
+            kind,
+            start: 0,
+            end: edits.len(),
+            selected: None,
+            edited: None,
+        };
+        let mut hunks = Vec::new();
+        match (&change.old, &change.new) {
+            (None, _) => hunks.push(whole(HunkKind::Addition)),
+            (_, None) => hunks.push(whole(HunkKind::Deletion)),
+            (Some(old), Some(new)) => {
+                if old.mode != new.mode {
+                    hunks.push(PatchHunk {
+                        end: 0,
+                        ..whole(HunkKind::Mode)
+                    });
+                }
+                if text {
+                    for hunk in diff::hunks(&edits, CONTEXT_LINES) {
+                        let start = start_index(&edits, &hunk);
+                        hunks.push(PatchHunk {
+                            start,
+                            end: start + hunk.edits.len(),
+                            ..whole(HunkKind::Lines)
+                        });
+                    }
+                }
+            }
+        }
+        FilePatch { a, b, edits, hunks }
+    }
+
+    fn line(&self, edit: &Edit) -> &'a [u8] {
+        match edit.kind {
+            EditKind::Ins => self.b[edit.b_index.unwrap_or(0)],
+            _ => self.a[edit.a_index.unwrap_or(0)],
+        }
+    }
+
+    // the hunk of `start..end` as a unified diff, with its header.
+    fn hunk_text(&self, start: usize, end: usize) -> Vec<u8> {
+        let before = &self.edits[..start];
+        let hunk = diff::Hunk {
+            a_start: before.iter().filter(|e| e.kind != EditKind::Ins).count(),
+            b_start: before.iter().filter(|e| e.kind != EditKind::Del).count(),
+            edits: self.edits[start..end].to_vec(),
+        };
+        let mut out = format!("{}\n", hunk.header()).into_bytes();
+        for edit in hunk.edits.iter() {
+            out.extend_from_slice(&diff_line(edit.kind, self.line(edit)));
+        }
+        out
+    }
+
+    // the runs of changes in `start..end`, as ranges of the edit script.
+    fn change_groups(&self, start: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for n in start..end {
+            if self.edits[n].kind == EditKind::Eql {
+                continue;
+            }
+            match groups.last_mut() {
+                Some(group) if group.1 == n => group.1 = n + 1,
+                _ => groups.push((n, n + 1)),
+            }
+        }
+        groups
+    }
+
+    // Splits the hunk `n` in one hunk for each run of changes, each with all the unchanged
+    // lines up to the next run. The number of hunks made.
+    fn split(&mut self, n: usize) -> usize {
+        let (start, end) = (self.hunks[n].start, self.hunks[n].end);
+        let groups = self.change_groups(start, end);
+        let split: Vec<PatchHunk> = groups
+            .iter()
+            .enumerate()
+            .map(|(k, _)| PatchHunk {
+                kind: HunkKind::Lines,
+                start: if k == 0 { start } else { groups[k - 1].1 },
+                end: groups.get(k + 1).map_or(end, |next| next.0),
+                selected: None,
+                edited: None,
+            })
+            .collect();
+        let count = split.len();
+        self.hunks.splice(n..n + 1, split);
+        count
+    }
+
+    // Checks the hunk `n` rewritten as `lines`: the side of the target must still read as the
+    // target does. The unchanged lines the hunk starts and ends with are left out of the range
+    // it replaces, as long as they are not the ones of its changes.
+    fn edited(&self, n: usize, lines: Vec<(EditKind, Vec<u8>)>, reverse: bool) -> Option<EditedHunk> {
+        let (start, end) = (self.hunks[n].start, self.hunks[n].end);
+        let kept = if reverse { EditKind::Del } else { EditKind::Ins };
+        let target: Vec<&[u8]> = self.edits[start..end]
+            .iter()
+            .filter(|edit| edit.kind != kept)
+            .map(|edit| self.line(edit))
+            .collect();
+        let edited_target: Vec<&[u8]> =
+            lines.iter().filter(|(kind, _)| *kind != kept).map(|(_, line)| line.as_slice()).collect();
+        if target != edited_target {
+            return None;
+        }
+        let groups = self.change_groups(start, end);
+        let (first, last) = (groups.first()?.0, groups.last()?.1);
+        let context = |iter: &mut dyn Iterator<Item = &(EditKind, Vec<u8>)>| {
+            iter.take_while(|(kind, _)| *kind == EditKind::Eql).count()
+        };
+        let leading = context(&mut lines.iter()).min(first - start);
+        let trailing = context(&mut lines.iter().rev()).min(end - last).min(lines.len() - leading);
+        let edited = EditedHunk {
+            start: start + leading,
+            end: end - trailing,
+            lines: lines[leading..lines.len() - trailing].to_vec(),
+        };
+        let overlaps = self.hunks.iter().enumerate().any(|(m, hunk)| {
+            m != n && hunk.edited.as_ref().is_some_and(|other| other.start < edited.end && edited.start < other.end)
+        });
+        if overlaps {
+            return None;
+        }
+        Some(edited)
+    }
+
+    // The content of the target once the chosen hunks of lines are applied to it, `None` when
+    // none of them was.
+    fn apply(&self, reverse: bool) -> Option<Vec<u8>> {
+        let lines: Vec<&PatchHunk> = self
+            .hunks
+            .iter()
+            .filter(|hunk| hunk.kind == HunkKind::Lines && hunk.selected == Some(true))
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        let mut selected = vec![false; self.edits.len()];
+        for hunk in lines.iter().filter(|hunk| hunk.edited.is_none()) {
+            selected[hunk.start..hunk.end].iter_mut().for_each(|s| *s = true);
+        }
+        let mut edited: Vec<&EditedHunk> = lines.iter().filter_map(|hunk| hunk.edited.as_ref()).collect();
+        edited.sort_by_key(|edit| edit.start);
+        let mut edited = edited.into_iter().peekable();
+        // the old side of the edits that are undone or not applied, the new side of the others
+        let side = |kind: EditKind, selected: bool| match kind {
+            EditKind::Eql => true,
+            EditKind::Ins => selected != reverse,
+            EditKind::Del => selected == reverse,
+        };
+        let mut out = Vec::new();
+        let mut n = 0;
+        loop {
+            while let Some(edit) = edited.next_if(|edit| edit.start == n) {
+                for (_, line) in edit.lines.iter().filter(|(kind, _)| side(*kind, true)) {
+                    out.extend_from_slice(line);
+                }
+                n = edit.end;
+            }
+            let edit = match self.edits.get(n) {
+                Some(edit) => edit,
+                None => break,
+            };
+            if side(edit.kind, selected[n]) {
+                out.extend_from_slice(self.line(edit));
+            }
+            n += 1;
+        }
+        Some(out)
+    }
+}
+
+// the index of the edit script where `hunk` starts.
+fn start_index(edits: &[Edit], hunk: &diff::Hunk) -> usize {
+    let (mut a, mut b) = (0, 0);
+    for (n, edit) in edits.iter().enumerate() {
+        if (a, b) == (hunk.a_start, hunk.b_start) {
+            return n;
+        }
+        match edit.kind {
+            EditKind::Eql => {
+                a += 1;
+                b += 1
+            }
+            EditKind::Del => a += 1,
+            EditKind::Ins => b += 1,
+        }
+    }
+    edits.len()
+}
+
+// a line of a unified diff, marking the lines without a newline at the end of the file.
+fn diff_line(kind: EditKind, line: &[u8]) -> Vec<u8> {
+    let prefix = match kind {
+        EditKind::Eql => b' ',
+        EditKind::Del => b'-',
+        EditKind::Ins => b'+',
+    };
+    let mut out = vec![prefix];
+    out.extend_from_slice(line);
+    if !line.ends_with(b"\n") {
+        out.extend_from_slice(b"\n\\ No newline at end of file\n");
+    }
+    out
+}
+
+// The lines of an edited hunk, without the comments and the header. A line that is neither
+// unchanged, removed nor added makes the hunk invalid.
+fn parse_edited_hunk(text: &[u8]) -> Option<Vec<(EditKind, Vec<u8>)>> {
+    let mut lines: Vec<(EditKind, Vec<u8>)> = Vec::new();
+    for line in text.split_inclusive(|b| *b == b'\n') {
+        let (kind, content) = match line.first() {
+            Some(b'#') => continue,
+            Some(b'@') if line.starts_with(b"@@") => continue,
+            Some(b'\\') => {
+                if let Some((_, last)) = lines.last_mut() {
+                    if last.ends_with(b"\n") {
+                        last.pop();
+                    }
+                }
+                continue;
+            }
+            Some(b' ') => (EditKind::Eql, &line[1..]),
+            Some(b'-') => (EditKind::Del, &line[1..]),
+            Some(b'+') => (EditKind::Ins, &line[1..]),
+            Some(b'\n') => (EditKind::Eql, line),
+            _ => return None,
+        };
+        let mut content = content.to_vec();
+        if !content.ends_with(b"\n") {
+            content.push(b'\n');
+        }
+        lines.push((kind, content));
+    }
+    Some(lines)
+}
+
+// the answer read for a prompt, `None` at the end of the input.
+fn read_answer(input: &mut dyn BufRead) -> Result<Option<String>> {
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+impl Command {
+    // `add -p`: picks the hunks of the changes of the working tree to stage.
+    pub fn add_patch(&mut self, paths: &[PathBuf]) -> Result<()> {
+        self.load_index()?;
+        let index = self.index_tree_list()?;
+        let tracked: Vec<PathBuf> = index.keys().cloned().collect();
+        let workspace = self.hashed_workspace_list(&tracked)?;
+        self.run_patch(&STAGE, &index, &workspace, paths)
+    }
+
+    // `reset -p [<tree-ish>] [<paths>]`: picks the hunks of the index to reset to the tree,
+    // HEAD by default. The first argument is the tree unless it names a file.
+    pub fn reset_patch(&mut self, args: &[&str]) -> Result<()> {
+        let (source, paths) = match args.split_first() {
+            Some((first, rest)) if !self.is_path(first)? => (Some(*first), rest),
+            _ => (None, args),
+        };
+        let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        self.restore_patch(source, true, false, &paths)
+    }
+
+    // `restore -p`: picks the hunks of the working tree to discard, or of the index with
+    // `staged`, to restore them from `source`. The working tree is restored from the index and
+    // the index from HEAD by default.
+    pub fn restore_patch(&mut self, source: Option<&str>, staged: bool, worktree: bool, paths: &[PathBuf]) -> Result<()> {
+        if staged && worktree {
+            return Err(anyhow!("--patch is not supported with both --staged and --worktree"));
+        }
+        self.load_index()?;
+        let head = self.refs.read_head();
+        let source = match source {
+            Some(source) => Some(Revision::new(&self.refs, &mut self.db).resolve(source)?),
+            None if staged => head.clone(),
+            None => None,
+        };
+        let from_head = source.is_some() && source == head;
+        let tree = match &source {
+            Some(source) => self.commit_tree_list(source)?,
+            None => TreeList::new(),
+        };
+        let index = self.index_tree_list()?;
+        if staged {
+            if from_head || head.is_none() {
+                return self.run_patch(&RESET_HEAD, &tree, &index, paths);
+            }
+            return self.run_patch(&RESET_NOTHEAD, &index, &tree, paths);
+        }
+        let tracked: Vec<PathBuf> = index.keys().chain(tree.keys()).cloned().collect();
+        let workspace = self.hashed_workspace_list(&tracked)?;
+        match source {
+            None => self.run_patch(&DISCARD, &index, &workspace, paths),
+            Some(_) if from_head => self.run_patch(&DISCARD, &tree, &workspace, paths),
+            Some(_) => self.run_patch(&WORKTREE_NOTHEAD, &workspace, &tree, paths),
+        }
+    }
+
+    // whether `arg` names a file, tracked or in the working tree, rather than a revision.
+    pub(super) fn is_path(&mut self, arg: &str) -> Result<bool> {
+        let path = self.repository.resolve_path(Path::new(arg))?;
+        if self.workspace()?.pathname.join(&path).exists() {
+            return Ok(true);
+        }
+        self.load_index()?;
+        let tracked = self.index_tree_list()?.keys().any(|tracked| tracked.starts_with(&path));
+        Ok(tracked || Revision::new(&self.refs, &mut self.db).resolve(arg).is_err())
+    }
+
+    // Shows the changes from `old` to `new` among `paths` file by file and hunk by hunk, and
+    // applies the hunks picked to the target of `mode`.
+    fn run_patch(&mut self, mode: &PatchMode, old: &TreeList, new: &TreeList, paths: &[PathBuf]) -> Result<()> {
+        let paths = paths
+            .iter()
+            .map(|path| self.repository.resolve_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        let matches = |path: &Path| {
+            paths.is_empty() || paths.iter().any(|spec| spec == Path::new(".") || path.starts_with(spec))
+        };
+        let changes: Vec<Change> = self
+            .diff_tree_lists(old, new, None)?
+            .into_iter()
+            .filter(|change| matches(change.path()))
+            .filter(|change| !change.old.iter().chain(change.new.iter()).any(|entry| entry.is_gitlink()))
+            .collect();
+        if changes.is_empty() {
+            eprintln!("No changes.");
+            return Ok(());
+        }
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        for change in changes.iter() {
+            let old_data = match &change.old {
+                Some(entry) => self.entry_content(entry)?,
+                None => vec![],
+            };
+            let new_data = match &change.new {
+                Some(entry) => self.entry_content(entry)?,
+                None => vec![],
+            };
+            let path = change.path().display().to_string();
+            let binary = |data: &[u8]| match self.db.converter() {
+                Some(converter) => converter.is_binary_diff(Path::new(&path), data),
+                None => convert::is_binary(data),
+            };
+            let links = change.old.iter().chain(change.new.iter()).any(|entry| entry.mode == "120000");
+            let text = !links && !binary(&old_data) && !binary(&new_data);
+            let mut file = FilePatch::new(change, &old_data, &new_data, text);
+            if file.hunks.is_empty() {
+                continue;
+            }
+            let quit = self.pick_hunks(mode, change, &mut file, &mut input)?;
+            self.apply_patch(mode, change, &file)?;
+            if quit {
+                break;
+            }
+        }
+        if mode.target == PatchTarget::Index {
+            self.index.write_updates()?;
+        }
+        Ok(())
+    }
+
+    // Asks which hunks of `file` to apply, until each one is picked or not. Whether the user
+    // quit.
+    fn pick_hunks(&self, mode: &PatchMode, change: &Change, file: &mut FilePatch, input: &mut dyn BufRead) -> Result<bool> {
+        let stdout = io::stdout();
+        let mut header = patch_header(change);
+        let short = |entry: &Option<crate::Entry>| match entry {
+            Some(entry) => util::encode_vec(&entry.sha1_hash)[..7].to_string(),
+            None => "0000000".to_string(),
+        };
+        let same_mode = match (&change.old, &change.new) {
+            (Some(old), Some(new)) if old.mode == new.mode => format!(" {}", new.mode),
+            _ => String::new(),
+        };
+        header.push_str(&format!("index {}..{}{}\n", short(&change.old), short(&change.new), same_mode));
+        let path = change.path().display();
+        let old_name = change.old.as_ref().map_or("/dev/null".to_string(), |_| format!("a/{}", path));
+        let new_name = change.new.as_ref().map_or("/dev/null".to_string(), |_| format!("b/{}", path));
+        header.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        stdout.lock().write_all(header.as_bytes())?;
+
+        let mut n = 0;
+        let mut show = true;
+        while n < file.hunks.len() {
+            if file.hunks[n].selected.is_some() {
+                n += 1;
+                show = true;
+                continue;
+            }
+            let hunk = &file.hunks[n];
+            let splittable = hunk.kind == HunkKind::Lines && file.change_groups(hunk.start, hunk.end).len() > 1;
+            if show && hunk.kind != HunkKind::Mode && hunk.end > hunk.start {
+                stdout.lock().write_all(&file.hunk_text(hunk.start, hunk.end))?;
+            }
+            show = false;
+            let mut options = "y,n,q,a,d".to_string();
+            if splittable {
+                options.push_str(",s");
+            }
+            if hunk.kind == HunkKind::Lines {
+                options.push_str(",e");
+            }
+            options.push_str(",?");
+            print!("({}/{}) {} [{}]? ", n + 1, file.hunks.len(), mode.prompt(hunk.kind), options);
+            io::stdout().flush()?;
+            let answer = match read_answer(input)? {
+                Some(answer) => answer,
+                None => "q".to_string(),
+            };
+            match answer.chars().next().map(|c| c.to_ascii_lowercase()) {
+                Some('y') => file.hunks[n].selected = Some(true),
+                Some('n') => file.hunks[n].selected = Some(false),
+                Some(choice @ ('a' | 'd' | 'q')) => {
+                    for hunk in file.hunks[n..].iter_mut().filter(|hunk| hunk.selected.is_none()) {
+                        hunk.selected = Some(choice == 'a');
+                    }
+                    if choice == 'q' {
+                        return Ok(true);
+                    }
+                }
+                Some('s') if splittable => {
+                    println!("Split into {} hunks.", file.split(n));
+                    show = true;
+                }
+                Some('e') if hunk.kind == HunkKind::Lines => {
+                    if let Some(edited) = self.edit_hunk(mode, file, n, input)? {
+                        file.hunks[n].edited = Some(edited);
+                        file.hunks[n].selected = Some(true);
+                    }
+                    show = true;
+                }
+                _ => print!("{}", mode.help(&options)),
+            }
+        }
+        Ok(false)
+    }
+
+    // Opens the hunk `n` in the editor, again as long as the user asks for it when the result
+    // does not apply. `None` leaves the hunk as it was.
+    fn edit_hunk(&self, mode: &PatchMode, file: &FilePatch, n: usize, input: &mut dyn BufRead) -> Result<Option<EditedHunk>> {
+        let path = self.repository.git_dir.join("addp-hunk-edit.diff");
+        let (removed, added) = if mode.reverse { ('+', '-') } else { ('-', '+') };
+        let mut text = b"# Manual hunk edit mode -- see bottom for a quick guide.\n".to_vec();
+        text.extend_from_slice(&file.hunk_text(file.hunks[n].start, file.hunks[n].end));
+        text.extend_from_slice(
+            format!(
+                "# ---\n\
+                 # To remove '{}' lines, make them ' ' lines (context).\n\
+                 # To remove '{}' lines, delete them.\n\
+                 # Lines starting with # will be removed.\n\
+                 #\n\
+                 # If the patch applies cleanly, the edited hunk will immediately be marked for {}.\n\
+                 # If it does not apply cleanly, you will be given an opportunity to\n\
+                 # edit again.  If all lines of the hunk are removed, then the edit is\n\
+                 # aborted and the hunk is left unchanged.\n",
+                removed, added, mode.marked
+            )
+            .as_bytes(),
+        );
+        fs::write(&path, &text)?;
+        loop {
+            editor::edit_file(&editor::editor(&self.config, false), &path)?;
+            let lines = parse_edited_hunk(&fs::read(&path)?);
+            if lines.as_ref().is_some_and(|lines| lines.is_empty()) {
+                fs::remove_file(&path)?;
+                return Ok(None);
+            }
+            if let Some(edited) = lines.and_then(|lines| file.edited(n, lines, mode.reverse)) {
+                fs::remove_file(&path)?;
+                return Ok(Some(edited));
+            }
+            print!("Your edited hunk does not apply. Edit again (saying \"no\" discards!) [y/n]? ");
+            io::stdout().flush()?;
+            if !read_answer(input)?.is_some_and(|answer| answer.starts_with(['y', 'Y'])) {
+                fs::remove_file(&path)?;
+                return Ok(None);
+            }
+        }
+    }
+
+    // Writes what was picked of `file` to the target: the other side of the file as a whole
+    // for an addition or a deletion, and else its mode and content with the hunks applied.
+    fn apply_patch(&mut self, mode: &PatchMode, change: &Change, file: &FilePatch) -> Result<()> {
+        let (target, other) = if mode.reverse {
+            (&change.new, &change.old)
+        } else {
+            (&change.old, &change.new)
+        };
+        let path = change.path().to_path_buf();
+        let picked = |kind| file.hunks.iter().any(|hunk| hunk.kind == kind && hunk.selected == Some(true));
+        let whole = picked(HunkKind::Addition) || picked(HunkKind::Deletion);
+        let (entry_mode, content) = match (target, other) {
+            (_, None) if whole => return self.remove_target(mode.target, &path),
+            (_, Some(other)) if whole => (other.mode.clone(), self.entry_content(other)?),
+            (Some(target), Some(other)) => {
+                let content = file.apply(mode.reverse);
+                if content.is_none() && !picked(HunkKind::Mode) {
+                    return Ok(());
+                }
+                let entry_mode = if picked(HunkKind::Mode) { &other.mode } else { &target.mode };
+                let content = match content {
+                    Some(content) => content,
+                    None => self.entry_content(target)?,
+                };
+                (entry_mode.clone(), content)
+            }
+            _ => return Ok(()),
+        };
+        match mode.target {
+            PatchTarget::Index => {
+                let mut blob = Blob::new(content, self.db.hash_algorithm())?;
+                self.db.store(&mut blob)?;
+                let entry_mode = u32::from_str_radix(&entry_mode, 8)?;
+                self.index.add_without_stat(path, blob.get_oid()?, entry_mode)?;
+            }
+            PatchTarget::Worktree => {
+                self.workspace()?.write_file(&path, &content, &entry_mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_target(&mut self, target: PatchTarget, path: &Path) -> Result<()> {
+        match target {
+            PatchTarget::Index => self.index.remove(&path.display().to_string()),
+            PatchTarget::Worktree => {
+                let work_tree = self.workspace()?.pathname.to_path_buf();
+                fs::remove_file(work_tree.join(path))?;
+                remove_empty_parents(&work_tree, &work_tree.join(path));
+            }
+        }
+        Ok(())
+    }
+}
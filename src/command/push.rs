@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::refs::{check_ref_name, HEAD};
+use crate::{util, Repository, Revision};
+
+use super::Command;
+
+impl Command {
+    // Sends `<src>:<dst>` to the repository at `path`, a local path to either a bare
+    // repository or a working tree. The objects the target is missing are copied before its
+    // ref is updated, so the ref never points to an incomplete history. The `pre-push` hook,
+    // unless `no_verify`, can still reject the update before anything is sent.
+    pub fn push(&mut self, path: &Path, refspec: Option<&str>, force: bool, no_verify: bool) -> Result<()> {
+        let (src, dst) = self.parse_refspec(refspec)?;
+        let new_oid = Revision::new(&self.refs, &mut self.db).resolve_commit(&src)?;
+
+        let remote = Command::from_repository(Repository::open(path)?)?;
+        if remote.db.hash_algorithm() != self.db.hash_algorithm() {
+            return Err(anyhow!("the receiving repository uses a different object format"));
+        }
+        if !remote.repository.is_bare() && remote.refs.current_ref().as_deref() == Some(&dst) {
+            return Err(anyhow!("refusing to update checked out branch: {}", dst));
+        }
+
+        let old_oid = remote.refs.resolve(&dst);
+        let short = |oid: &str| oid[..7].to_string();
+        let name = dst.strip_prefix("refs/heads/").unwrap_or(&dst).to_string();
+        let src_name = src.strip_prefix("refs/heads/").unwrap_or(&src).to_string();
+        println!("To {}", util::normalize_path(path).display());
+        match &old_oid {
+            Some(old) if *old == new_oid => {
+                println!("Everything up-to-date");
+                return Ok(());
+            }
+            Some(old) if !force && !self.is_ancestor(old, &new_oid)? => {
+                println!(" ! [rejected]        {} -> {} (non-fast-forward)", src_name, name);
+                return Err(anyhow!("failed to push some refs to '{}'", path.display()));
+            }
+            _ => {}
+        }
+
+        if !no_verify {
+            let location = util::normalize_path(path).display().to_string();
+            let zeros = "0".repeat(new_oid.len());
+            let input = format!("{} {} {} {}\n", src, new_oid, dst, old_oid.as_deref().unwrap_or(&zeros));
+            if !self.run_hook("pre-push", &[&location, &location], Some(input.as_bytes()))? {
+                println!(" ! [remote rejected] {} -> {} (pre-push hook declined)", src_name, name);
+                return Err(anyhow!("failed to push some refs to '{}'", path.display()));
+            }
+        }
+        self.send_objects(&new_oid, &remote)?;
+        remote.refs.update_ref(&dst, &new_oid)?;
+        match old_oid {
+            None => println!(" * [new branch]      {} -> {}", src_name, name),
+            Some(old) if force => {
+                println!(" + {}...{} {} -> {} (forced update)", short(&old), short(&new_oid), src_name, name)
+            }
+            Some(old) => println!("   {}..{}  {} -> {}", short(&old), short(&new_oid), src_name, name),
+        }
+        Ok(())
+    }
+
+    // `<src>:<dst>`, `<src>` alone pushes to the ref of the same name and without a refspec
+    // the current branch is pushed.
+    fn parse_refspec(&self, refspec: Option<&str>) -> Result<(String, String)> {
+        let (src, dst) = match refspec {
+            Some(spec) => match spec.split_once(':') {
+                Some((src, dst)) => (src.to_string(), dst.to_string()),
+                None => (spec.to_string(), spec.to_string()),
+            },
+            None => {
+                let current = self
+                    .refs
+                    .current_ref()
+                    .ok_or(anyhow!("you are not currently on a branch"))?;
+                (current.clone(), current)
+            }
+        };
+        let dst = if dst.starts_with("refs/") || dst == HEAD {
+            dst
+        } else {
+            format!("refs/heads/{}", dst)
+        };
+        check_ref_name(&dst)?;
+        Ok((src, dst))
+    }
+
+    fn is_ancestor(&mut self, ancestor: &str, oid: &str) -> Result<bool> {
+        let mut next = Some(oid.to_string());
+        while let Some(oid) = next {
+            if oid == ancestor {
+                return Ok(true);
+            }
+            next = match self.db.load(&oid)? {
+                ObjectType::CommitType { commit } => commit.parent().cloned(),
+                _ => None,
+            };
+        }
+        Ok(false)
+    }
+
+    // copies every object reachable from `oid` that the remote does not have. Objects already
+    // in the remote database are expected to be complete, so the walk stops at them.
+    pub(crate) fn send_objects(&mut self, oid: &str, remote: &Command) -> Result<()> {
+        let mut seen = HashSet::new();
+        let mut work = vec![oid.to_string()];
+        while let Some(oid) = work.pop() {
+            if !seen.insert(oid.clone()) || remote.db.exists(&oid) {
+                continue;
+            }
+            match self.db.load(&oid)? {
+                ObjectType::CommitType { commit } => {
+                    work.push(commit.tree_ref.clone());
+                    work.extend(commit.parent().cloned());
+                }
+                ObjectType::TreeType { tree } => {
+                    // the commits of submodules belong to other repositories
+                    work.extend(
+                        tree.entries
+                            .iter()
+                            .filter(|e| !e.is_gitlink())
+                            .map(|e| util::encode_vec(&e.sha1_hash)),
+                    );
+                }
+                ObjectType::TagType { tag } => work.push(tag.object.clone()),
+                ObjectType::BlobType { .. } => {}
+            }
+            let raw = self.db.read_raw(&oid)?;
+            remote.db.write_object(&hex::decode(&oid)?, raw.get_data())?;
+        }
+        Ok(())
+    }
+}
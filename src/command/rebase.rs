@@ -0,0 +1,498 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::refs::HEAD;
+use crate::tree::TreeList;
+use crate::{editor, Revision};
+
+use super::migration::changed_paths;
+use super::Command;
+
+// The commands of a rebase todo list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TodoCommand {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+    Exec,
+}
+
+impl TodoCommand {
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "p" | "pick" => Ok(TodoCommand::Pick),
+            "r" | "reword" => Ok(TodoCommand::Reword),
+            "e" | "edit" => Ok(TodoCommand::Edit),
+            "s" | "squash" => Ok(TodoCommand::Squash),
+            "f" | "fixup" => Ok(TodoCommand::Fixup),
+            "d" | "drop" => Ok(TodoCommand::Drop),
+            "x" | "exec" => Ok(TodoCommand::Exec),
+            _ => Err(anyhow!("invalid command '{}'", name)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            TodoCommand::Pick => "pick",
+            TodoCommand::Reword => "reword",
+            TodoCommand::Edit => "edit",
+            TodoCommand::Squash => "squash",
+            TodoCommand::Fixup => "fixup",
+            TodoCommand::Drop => "drop",
+            TodoCommand::Exec => "exec",
+        }
+    }
+}
+
+// A line of the todo list: a command with the commit it applies to and its title, or the
+// shell command to run for `exec`.
+#[derive(Debug, Clone)]
+struct TodoLine {
+    command: TodoCommand,
+    arg: String,
+    title: String,
+}
+
+impl TodoLine {
+    fn parse(line: &str) -> Result<Option<Self>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let command = TodoCommand::parse(name)?;
+        let rest = rest.trim();
+        if rest.is_empty() {
+            return Err(anyhow!("missing argument for {}", command.name()));
+        }
+        let (arg, title) = match command {
+            TodoCommand::Exec => (rest, ""),
+            _ => rest.split_once(' ').unwrap_or((rest, "")),
+        };
+        Ok(Some(TodoLine {
+            command,
+            arg: arg.to_string(),
+            title: title.to_string(),
+        }))
+    }
+
+    fn to_line(&self) -> String {
+        match self.command {
+            TodoCommand::Exec => format!("exec {}", self.arg),
+            _ => format!("{} {} {}", self.command.name(), self.arg, self.title),
+        }
+    }
+}
+
+const TODO_HELP: &str = "
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\" but keep only the previous
+#                    commit's log message
+# x, exec <command> = run command (the rest of the line) using shell
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+#
+# If you remove a line here THAT COMMIT WILL BE LOST.
+#
+# However, if you remove everything, the rebase will be aborted.
+#
+";
+
+const COMMIT_HELP: &str = "
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message aborts the commit.
+";
+
+// Moves the `fixup! <title>` and `squash! <title>` commits right after the commit they name,
+// by title or oid prefix, turning them into the matching command.
+fn autosquash(lines: Vec<TodoLine>) -> Vec<TodoLine> {
+    let mut groups: Vec<(TodoLine, Vec<TodoLine>)> = Vec::new();
+    for mut line in lines {
+        let mut subject = line.title.as_str();
+        let mut command = None;
+        while let Some((prefix, rest)) = subject.split_once("! ") {
+            match prefix {
+                "fixup" => command = command.or(Some(TodoCommand::Fixup)),
+                "squash" => command = command.or(Some(TodoCommand::Squash)),
+                _ => break,
+            }
+            subject = rest;
+        }
+        let target = command.and_then(|_| {
+            groups
+                .iter()
+                .position(|(head, _)| head.title == subject || head.arg.starts_with(subject))
+        });
+        match (target, command) {
+            (Some(target), Some(command)) => {
+                line.command = command;
+                groups[target].1.push(line);
+            }
+            _ => groups.push((line, vec![])),
+        }
+    }
+    groups
+        .into_iter()
+        .flat_map(|(head, fixups)| std::iter::once(head).chain(fixups))
+        .collect()
+}
+
+// The state of a rebase lives under `.git/rebase-merge` as it does for git: `head-name` is the
+// branch being rebased, `orig-head` the commit it pointed to and `onto` the new base. HEAD is
+// detached while the commits of `git-rebase-todo` are applied, each one moved to `done` when
+// it starts. `stopped-sha` names the commit that stopped with conflicts and `amend` the commit
+// an `edit` command stopped at.
+impl Command {
+    fn rebase_dir(&self) -> PathBuf {
+        self.repository.git_dir.join("rebase-merge")
+    }
+
+    fn read_rebase_file(&self, name: &str) -> Result<String> {
+        Ok(fs::read_to_string(self.rebase_dir().join(name))?.trim_end().to_string())
+    }
+
+    fn write_rebase_file(&self, name: &str, content: &str) -> Result<()> {
+        fs::write(self.rebase_dir().join(name), format!("{}\n", content))?;
+        Ok(())
+    }
+
+    fn remove_rebase_file(&self, name: &str) -> Result<()> {
+        let path = self.rebase_dir().join(name);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn read_rebase_todo(&self) -> Result<Vec<TodoLine>> {
+        let todo = fs::read_to_string(self.rebase_dir().join("git-rebase-todo")).unwrap_or_default();
+        let mut lines = Vec::new();
+        for line in todo.lines() {
+            lines.extend(TodoLine::parse(line)?);
+        }
+        Ok(lines)
+    }
+
+    fn write_rebase_todo(&self, lines: &[TodoLine]) -> Result<()> {
+        let content: String = lines.iter().map(|line| format!("{}\n", line.to_line())).collect();
+        fs::write(self.rebase_dir().join("git-rebase-todo"), content)?;
+        Ok(())
+    }
+
+    // Replays the commits of the current branch that are not in `upstream` on top of it. With
+    // `interactive` the todo list is first opened in the sequence editor, with `autosquash`
+    // the `fixup!` and `squash!` commits are moved after the commit they amend.
+    pub fn rebase(&mut self, upstream: &str, interactive: bool, autosquash_commits: bool) -> Result<()> {
+        self.workspace()?;
+        if self.rebase_dir().exists() {
+            return Err(anyhow!(
+                "It seems that there is already a rebase-merge directory, and\nI wonder if you are in the middle of another rebase."
+            ));
+        }
+        self.load_index()?;
+        self.check_unmerged("Rebasing")?;
+        let head_list = self.head_tree_list()?;
+        let index_list = self.index_tree_list()?;
+        if !changed_paths(&head_list, &index_list).is_empty() {
+            return Err(anyhow!("cannot rebase: Your index contains uncommitted changes.\nPlease commit or stash them."));
+        }
+        let paths: Vec<PathBuf> = index_list.keys().cloned().collect();
+        let work_list = self.hashed_workspace_list(&paths)?;
+        if !changed_paths(&index_list, &work_list).is_empty() {
+            return Err(anyhow!("cannot rebase: You have unstaged changes.\nPlease commit or stash them."));
+        }
+
+        let head = self
+            .refs
+            .read_head()
+            .ok_or(anyhow!("you do not have the initial commit yet"))?;
+        let head_name = self.refs.current_ref().unwrap_or("detached HEAD".to_string());
+        let onto = Revision::new(&self.refs, &mut self.db).resolve_commit(upstream)?;
+        let mut commits = Vec::new();
+        for oid in self.commit_range(&format!("{}..{}", onto, head))? {
+            // merge commits are dropped, as git does by default
+            if self.load_commit(&oid)?.parents().len() <= 1 {
+                commits.push(oid);
+            }
+        }
+        let base = match commits.first() {
+            Some(first) => self.load_commit(first)?.parent().cloned(),
+            None => Some(head.to_string()),
+        };
+        if !interactive && base.as_ref() == Some(&onto) {
+            let name = head_name.strip_prefix("refs/heads/").unwrap_or(&head_name);
+            println!("Current branch {} is up to date.", name);
+            return Ok(());
+        }
+
+        let mut todo = Vec::new();
+        for oid in commits.iter() {
+            todo.push(TodoLine {
+                command: TodoCommand::Pick,
+                arg: oid[..7].to_string(),
+                title: self.load_commit(oid)?.title_line().to_string(),
+            });
+        }
+        if autosquash_commits {
+            todo = autosquash(todo);
+        }
+
+        fs::create_dir_all(self.rebase_dir())?;
+        self.write_rebase_file("head-name", &head_name)?;
+        self.write_rebase_file("orig-head", &head)?;
+        self.write_rebase_file("onto", &onto)?;
+        self.write_rebase_todo(&todo)?;
+        if interactive {
+            let edited = self.edit_rebase_todo(&todo, &onto, &head);
+            match edited {
+                Ok(lines) if !lines.is_empty() => self.write_rebase_todo(&lines)?,
+                Ok(_) => {
+                    fs::remove_dir_all(self.rebase_dir())?;
+                    return Err(anyhow!("nothing to do"));
+                }
+                Err(e) => {
+                    fs::remove_dir_all(self.rebase_dir())?;
+                    return Err(e);
+                }
+            }
+        }
+
+        self.refs.update_ref(HEAD, &head)?;
+        self.reset_to(Some(&onto))?;
+        self.run_rebase()
+    }
+
+    // Opens the todo list in the sequence editor and returns the lines that were kept, with
+    // the commits they name checked.
+    fn edit_rebase_todo(&mut self, todo: &[TodoLine], onto: &str, head: &str) -> Result<Vec<TodoLine>> {
+        let path = self.rebase_dir().join("git-rebase-todo");
+        let mut content: String = todo.iter().map(|line| format!("{}\n", line.to_line())).collect();
+        content.push_str(&format!(
+            "\n# Rebase {}..{} onto {} ({} commands)\n#{}",
+            &onto[..7],
+            &head[..7],
+            &onto[..7],
+            todo.len(),
+            TODO_HELP
+        ));
+        fs::write(&path, content)?;
+        editor::edit_file(&editor::editor(&self.config, true), &path)?;
+
+        let mut lines = Vec::new();
+        for line in fs::read_to_string(&path)?.lines() {
+            if let Some(mut line) = TodoLine::parse(line)? {
+                if line.command != TodoCommand::Exec {
+                    line.arg = Revision::new(&self.refs, &mut self.db).resolve_commit(&line.arg)?;
+                }
+                lines.push(line);
+            }
+        }
+        let first = lines.iter().find(|l| !matches!(l.command, TodoCommand::Exec | TodoCommand::Drop));
+        if let Some(line) = first.filter(|l| matches!(l.command, TodoCommand::Squash | TodoCommand::Fixup)) {
+            return Err(anyhow!("cannot '{}' without a previous commit", line.command.name()));
+        }
+        Ok(lines)
+    }
+
+    // Executes the todo list until it is empty or a command stops the rebase.
+    fn run_rebase(&mut self) -> Result<()> {
+        loop {
+            let mut todo = self.read_rebase_todo()?;
+            if todo.is_empty() {
+                return self.finish_rebase();
+            }
+            let line = todo.remove(0);
+            self.write_rebase_todo(&todo)?;
+            let mut done = fs::read_to_string(self.rebase_dir().join("done")).unwrap_or_default();
+            done.push_str(&format!("{}\n", line.to_line()));
+            fs::write(self.rebase_dir().join("done"), done)?;
+
+            match line.command {
+                TodoCommand::Drop => continue,
+                TodoCommand::Exec => {
+                    println!("Executing: {}", line.arg);
+                    let status = process::Command::new("sh")
+                        .arg("-c")
+                        .arg(&line.arg)
+                        .current_dir(&self.workspace()?.pathname)
+                        .status()?;
+                    if !status.success() {
+                        return Err(anyhow!(
+                            "Execution failed: {}\nYou can fix the problem, and then run\n\n  git rebase --continue",
+                            line.arg
+                        ));
+                    }
+                }
+                command => {
+                    let oid = Revision::new(&self.refs, &mut self.db).resolve_commit(&line.arg)?;
+                    if !self.rebase_pick(command, &oid)? {
+                        self.write_rebase_file("stopped-sha", &oid)?;
+                        return Err(anyhow!(
+                            "could not apply {}... {}\nhint: Resolve all conflicts manually, mark them as resolved with\nhint: \"git add <conflicted_files>\", then run \"git rebase --continue\".\nhint: To abort and get back to the state before \"git rebase\", run \"git rebase --abort\".",
+                            &oid[..7],
+                            line.title
+                        ));
+                    }
+                    if command == TodoCommand::Edit {
+                        return self.stop_for_edit(&oid);
+                    }
+                }
+            }
+        }
+    }
+
+    // Applies the changes of `commit` on top of HEAD as `command` says, false when it
+    // conflicts.
+    fn rebase_pick(&mut self, command: TodoCommand, commit: &str) -> Result<bool> {
+        let picked = self.load_commit(commit)?;
+        // a commit that is already on top of HEAD is reused as is
+        if matches!(command, TodoCommand::Pick | TodoCommand::Edit) && picked.parent() == self.refs.read_head().as_ref() {
+            self.reset_to(Some(commit))?;
+            return Ok(true);
+        }
+        let (index, clean) = self.merge_commit(commit, false, "rebase")?;
+        if clean {
+            self.commit_rebase_step(command, commit, &index)?;
+        }
+        Ok(clean)
+    }
+
+    // Records the result of applying `commit`: a new commit with its author and message, or
+    // for `squash` and `fixup` a replacement of HEAD combining both.
+    fn commit_rebase_step(&mut self, command: TodoCommand, commit: &str, index: &TreeList) -> Result<()> {
+        let picked = self.load_commit(commit)?;
+        let head = self.refs.read_head().ok_or(anyhow!("HEAD does not point to a commit"))?;
+        let (parents, author, message) = match command {
+            TodoCommand::Squash | TodoCommand::Fixup => {
+                let previous = self.load_commit(&head)?;
+                let message = if command == TodoCommand::Squash {
+                    self.edit_message(&format!(
+                        "# This is a combination of 2 commits.\n# This is the 1st commit message:\n\n{}\n\n# This is the commit message #2:\n\n{}\n",
+                        previous.message().trim_end(),
+                        picked.message().trim_end()
+                    ))?
+                } else {
+                    previous.message().trim_end().to_string()
+                };
+                (previous.parents().to_vec(), previous.author().clone(), message)
+            }
+            TodoCommand::Reword => {
+                let message = self.edit_message(&format!("{}\n", picked.message().trim_end()))?;
+                (vec![head], picked.author().clone(), message)
+            }
+            _ => (vec![head], picked.author().clone(), picked.message().trim_end().to_string()),
+        };
+        let oid = self.write_commit(index, parents, &author, &message)?;
+        self.refs.update_head(oid)
+    }
+
+    // opens `message` in the editor and returns what was written, without the comments.
+    fn edit_message(&self, message: &str) -> Result<String> {
+        let path = self.repository.git_dir.join("COMMIT_EDITMSG");
+        fs::write(&path, format!("{}{}", message, COMMIT_HELP))?;
+        editor::edit_file(&editor::editor(&self.config, false), &path)?;
+        let message = editor::strip_comments(&fs::read_to_string(&path)?);
+        if message.is_empty() {
+            return Err(anyhow!("Aborting commit due to empty commit message."));
+        }
+        Ok(message)
+    }
+
+    fn stop_for_edit(&mut self, commit: &str) -> Result<()> {
+        let head = self.refs.read_head().unwrap_or_default();
+        self.write_rebase_file("amend", &head)?;
+        let title = self.load_commit(commit)?.title_line().to_string();
+        println!(
+            "Stopped at {}...  {}\nYou can amend the commit now, with\n\n  git commit --amend\n\nOnce you are satisfied with your changes, run\n\n  git rebase --continue",
+            &commit[..7],
+            title
+        );
+        Ok(())
+    }
+
+    // points the rebased branch to the last commit and checks it out again.
+    fn finish_rebase(&mut self) -> Result<()> {
+        let head_name = self.read_rebase_file("head-name")?;
+        let head = self.refs.read_head().ok_or(anyhow!("HEAD does not point to a commit"))?;
+        if head_name.starts_with("refs/") {
+            self.refs.update_ref(&head_name, &head)?;
+            self.refs.set_symbolic_ref(HEAD, &head_name)?;
+        }
+        fs::remove_dir_all(self.rebase_dir())?;
+        println!("Successfully rebased and updated {}.", head_name);
+        Ok(())
+    }
+
+    fn check_rebase_in_progress(&self) -> Result<()> {
+        if !self.rebase_dir().exists() {
+            return Err(anyhow!("No rebase in progress?"));
+        }
+        Ok(())
+    }
+
+    // Resumes the rebase: the commit that stopped with conflicts is committed as resolved in
+    // the index, the changes staged after an `edit` stop amend the commit it stopped at.
+    pub fn rebase_continue(&mut self) -> Result<()> {
+        self.check_rebase_in_progress()?;
+        if let Ok(stopped) = self.read_rebase_file("stopped-sha") {
+            let index = self.resolved_index()?;
+            let done = fs::read_to_string(self.rebase_dir().join("done")).unwrap_or_default();
+            let command = match done.lines().last().map(TodoLine::parse) {
+                Some(Ok(Some(line))) => line.command,
+                _ => TodoCommand::Pick,
+            };
+            self.commit_rebase_step(command, &stopped, &index)?;
+            self.remove_rebase_file("stopped-sha")?;
+        } else if let Ok(amend) = self.read_rebase_file("amend") {
+            let index = self.resolved_index()?;
+            let head = self.refs.read_head().unwrap_or_default();
+            if !changed_paths(&self.head_tree_list()?, &index).is_empty() {
+                if head != amend {
+                    return Err(anyhow!(
+                        "you have staged changes in your working tree\nplease commit them first and then run 'git rebase --continue' again"
+                    ));
+                }
+                let previous = self.load_commit(&head)?;
+                let message = previous.message().trim_end().to_string();
+                let oid = self.write_commit(&index, previous.parents().to_vec(), previous.author(), &message)?;
+                self.refs.update_head(oid)?;
+            }
+            self.remove_rebase_file("amend")?;
+        }
+        self.run_rebase()
+    }
+
+    // Drops the commit that stopped with conflicts and goes on with the rest of the list.
+    pub fn rebase_skip(&mut self) -> Result<()> {
+        self.check_rebase_in_progress()?;
+        self.reset_to(None)?;
+        self.remove_rebase_file("stopped-sha")?;
+        self.remove_rebase_file("amend")?;
+        self.run_rebase()
+    }
+
+    // Stops the rebase and checks out the branch as it was before it started.
+    pub fn rebase_abort(&mut self) -> Result<()> {
+        self.check_rebase_in_progress()?;
+        let head_name = self.read_rebase_file("head-name")?;
+        let orig_head = self.read_rebase_file("orig-head")?;
+        if head_name.starts_with("refs/") {
+            self.refs.set_symbolic_ref(HEAD, &head_name)?;
+        }
+        self.reset_to(Some(&orig_head))?;
+        fs::remove_dir_all(self.rebase_dir())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::tree::TreeList;
+use crate::{util, Revision};
+
+use super::migration::{checkout_order, is_checked_out, remove_empty_parents, same_entry, tree_entry};
+use super::Command;
+
+// What `restore` rewrites and from where: the working tree by default, the index with
+// `staged`, from `source` or else the index for the working tree and HEAD for the index.
+// `ours` and `theirs` restore the unmerged files of the working tree from one side of the
+// conflict. With `overlay` the files missing from the source are kept rather than deleted,
+// as `checkout` does.
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    pub source: Option<String>,
+    pub staged: bool,
+    pub worktree: bool,
+    pub ours: bool,
+    pub theirs: bool,
+    pub overlay: bool,
+}
+
+// A file is unmerged while the index holds its stages rather than an entry, until it is
+// added again. Our side of it is stage 2, their side stage 3.
+impl Command {
+    // `restore [--staged] [--worktree] [--source=<tree-ish>] <paths>`.
+    pub fn restore(&mut self, paths: &[PathBuf], options: &RestoreOptions) -> Result<()> {
+        self.restore_paths(paths, options)?;
+        Ok(())
+    }
+
+    // `checkout [<tree-ish>] -- <paths>`: restores the working tree from the index, or both
+    // the index and the working tree from the tree, keeping the files it does not have, then
+    // runs the `post-checkout` hook. A single argument is taken as a path when it names one.
+    pub fn checkout_paths(&mut self, tree_ish: Option<&str>, paths: &[PathBuf], ours: bool, theirs: bool) -> Result<()> {
+        let (tree_ish, paths) = match tree_ish {
+            Some(arg) if paths.is_empty() => {
+                if !self.is_path(arg)? {
+                    return Err(anyhow!(
+                        "only paths can be checked out, switching branches is not supported\nusage: checkout [<tree-ish>] -- <paths>"
+                    ));
+                }
+                (None, vec![PathBuf::from(arg)])
+            }
+            _ => (tree_ish, paths.to_vec()),
+        };
+        let options = RestoreOptions {
+            source: tree_ish.map(String::from),
+            staged: tree_ish.is_some(),
+            worktree: true,
+            ours,
+            theirs,
+            overlay: true,
+        };
+        let count = self.restore_paths(&paths, &options)?;
+        let from = match tree_ish {
+            Some(tree_ish) => {
+                let mut revision = Revision::new(&self.refs, &mut self.db);
+                let oid = revision.resolve(tree_ish)?;
+                revision.peel_to_tree(&oid)?[..7].to_string()
+            }
+            None => "the index".to_string(),
+        };
+        eprintln!("Updated {} path{} from {}", count, if count == 1 { "" } else { "s" }, from);
+
+        let head = self
+            .refs
+            .read_head()
+            .unwrap_or_else(|| "0".repeat(self.db.hash_algorithm().hex_size()));
+        self.run_hook("post-checkout", &[&head, &head, "0"], None)?;
+        Ok(())
+    }
+
+    // Restores the files matching `paths` as `options` say and returns how many files were
+    // restored.
+    fn restore_paths(&mut self, paths: &[PathBuf], options: &RestoreOptions) -> Result<usize> {
+        if paths.is_empty() {
+            return Err(anyhow!("you must specify path(s) to restore"));
+        }
+        if options.ours && options.theirs {
+            return Err(anyhow!("'--ours' and '--theirs' cannot be used together"));
+        }
+        let worktree = options.worktree || !options.staged;
+        let from_index = worktree && !options.staged && options.source.is_none();
+        if (options.ours || options.theirs) && !from_index {
+            return Err(anyhow!("'--ours' and '--theirs' only restore the working tree from the index"));
+        }
+        self.workspace()?;
+        self.load_index()?;
+        let index = self.index_tree_list()?;
+        let source = match &options.source {
+            Some(source) => {
+                let oid = Revision::new(&self.refs, &mut self.db).resolve(source)?;
+                self.commit_tree_list(&oid)?
+            }
+            None if options.staged => self.head_tree_list()?,
+            None => index.clone(),
+        };
+
+        // the unmerged paths are restored from the index only from one side of the conflict
+        let unmerged: BTreeSet<PathBuf> = self.index.unmerged_paths().map(PathBuf::from).collect();
+        let specs = paths
+            .iter()
+            .map(|path| self.repository.resolve_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        let matches = |path: &Path, spec: &Path| spec == Path::new(".") || path.starts_with(spec);
+        // the files of the index are deleted when the source does not have them
+        let known: BTreeSet<&PathBuf> = source
+            .keys()
+            .chain(index.keys().filter(|_| !options.overlay))
+            .chain(unmerged.iter().filter(|_| from_index || !options.overlay))
+            .collect();
+        for (path, spec) in paths.iter().zip(specs.iter()) {
+            if !known.iter().any(|known| matches(known, spec)) {
+                return Err(anyhow!("pathspec '{}' did not match any file(s) known to git", path.display()));
+            }
+        }
+
+        let mut restored = TreeList::new();
+        let mut deleted = Vec::new();
+        for path in known.into_iter().filter(|path| specs.iter().any(|spec| matches(path, spec))) {
+            let mut entry = source.get(path).cloned();
+            if from_index && unmerged.contains(path) {
+                let (stage, name) = match (options.ours, options.theirs) {
+                    (true, _) => (2, "our"),
+                    (_, true) => (3, "their"),
+                    _ => return Err(anyhow!("path '{}' is unmerged", path.display())),
+                };
+                let side = self
+                    .index
+                    .unmerged_entry(&path.display().to_string(), stage)
+                    .ok_or(anyhow!("path '{}' does not have {} version", path.display(), name))?;
+                entry = Some(tree_entry(path, side.oid.to_vec(), util::get_mode_u(side.get_mode()?)));
+            }
+            match entry {
+                Some(entry) => {
+                    restored.insert(path.to_path_buf(), entry);
+                }
+                None if !options.overlay => deleted.push(path.to_path_buf()),
+                None => {}
+            }
+        }
+
+        let sparse = self.sparse_checkout()?;
+        if worktree {
+            let work_tree = self.workspace()?.pathname.to_path_buf();
+            for path in deleted.iter() {
+                let full_path = work_tree.join(path);
+                if util::stat_file(&full_path).is_ok_and(|stat| util::is_blob(&stat)) {
+                    fs::remove_file(&full_path)?;
+                    remove_empty_parents(&work_tree, &full_path);
+                }
+            }
+            for (path, entry) in checkout_order(&restored) {
+                if !is_checked_out(sparse.as_ref(), path, entry) {
+                    continue;
+                }
+                if entry.is_gitlink() {
+                    self.workspace()?.write_file(path, &[], &entry.mode)?;
+                    continue;
+                }
+                let full_path = work_tree.join(path);
+                if util::stat_file(&full_path).is_ok_and(|stat| stat.is_dir()) {
+                    fs::remove_dir_all(&full_path)?;
+                }
+                let stat = self.checkout_blob(path, entry)?;
+                // the stat data of a file restored as it is staged is fresh again
+                if !options.staged && same_entry(index.get(path), Some(entry)) {
+                    let mode = u32::from_str_radix(&entry.mode, 8)?;
+                    self.index.add_with_mode(path.to_path_buf(), entry.sha1_hash.to_vec(), stat, mode)?;
+                }
+            }
+        }
+        // only the paths restored change in the index, the others keep their stages
+        if options.staged {
+            for path in deleted.iter() {
+                self.index.remove(&path.display().to_string());
+            }
+            for (path, entry) in restored.iter() {
+                self.add_index_entry(path, entry, sparse.as_ref())?;
+            }
+        }
+        self.index.write_updates()?;
+        Ok(restored.len() + deleted.len())
+    }
+}
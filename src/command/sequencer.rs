@@ -0,0 +1,359 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::Local;
+
+use crate::tree::TreeList;
+use crate::{Author, Revision};
+
+use super::migration::{changed_paths, same_entry};
+use super::Command;
+
+// What the sequencer does with each commit of its todo list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequencerAction {
+    Pick,
+    Revert,
+}
+
+impl SequencerAction {
+    fn name(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => "pick",
+            SequencerAction::Revert => "revert",
+        }
+    }
+
+    fn command(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => "cherry-pick",
+            SequencerAction::Revert => "revert",
+        }
+    }
+
+    // the file of the git directory naming the commit that stopped with conflicts.
+    fn head_file(&self) -> &'static str {
+        match self {
+            SequencerAction::Pick => "CHERRY_PICK_HEAD",
+            SequencerAction::Revert => "REVERT_HEAD",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "pick" => Ok(SequencerAction::Pick),
+            "revert" => Ok(SequencerAction::Revert),
+            _ => Err(anyhow!("invalid command '{}' in the sequencer todo list", name)),
+        }
+    }
+}
+
+// The state of a cherry-pick or revert is kept under `.git/sequencer` as git does: `head` holds
+// the commit HEAD pointed to when it started, for `--abort`, and `todo` the commits left to
+// apply, one `<action> <oid> <title>` line each. A commit that stopped with conflicts is named
+// by `CHERRY_PICK_HEAD` or `REVERT_HEAD` and its message is in `MERGE_MSG` until it is
+// committed with `--continue` or dropped with `--skip`.
+impl Command {
+    fn sequencer_dir(&self) -> PathBuf {
+        self.repository.git_dir.join("sequencer")
+    }
+
+    // Applies, or undoes for a revert, the commits named by `revisions` on top of HEAD, one
+    // commit each. A revision can be a range `<from>..<to>`, whose commits are applied oldest
+    // first. Cherry-picked commits keep their author, reverts are authored by the given
+    // identity.
+    pub fn sequencer_start(
+        &mut self,
+        action: SequencerAction,
+        revisions: &[&str],
+        author: &str,
+        email: &str,
+    ) -> Result<()> {
+        self.workspace()?;
+        if self.sequencer_dir().exists() {
+            return Err(anyhow!(
+                "a cherry-pick or revert is already in progress\nhint: try \"git {} (--continue | --skip | --abort)\"",
+                action.command()
+            ));
+        }
+        let head = self
+            .refs
+            .read_head()
+            .ok_or(anyhow!("you do not have the initial commit yet"))?;
+        let mut commits = Vec::new();
+        for revision in revisions {
+            commits.extend(self.commit_range(revision)?);
+        }
+        if commits.is_empty() {
+            return Err(anyhow!("empty commit set passed"));
+        }
+        let mut todo = Vec::new();
+        for commit in commits {
+            let title = self.load_commit(&commit)?.title_line().to_string();
+            todo.push(format!("{} {} {}", action.name(), commit, title));
+        }
+        fs::create_dir_all(self.sequencer_dir())?;
+        fs::write(self.sequencer_dir().join("head"), format!("{}\n", head))?;
+        self.write_todo(&todo)?;
+        let result = self.run_sequencer(author, email);
+        // nothing to continue when the first commit could not even be tried
+        if result.is_err() && self.stopped_action().is_none() && self.refs.read_head().as_ref() == Some(&head) {
+            fs::remove_dir_all(self.sequencer_dir())?;
+        }
+        result
+    }
+
+    // the commits of `<from>..<to>` following first parents, oldest first, or the single
+    // commit named by `revision`.
+    pub(crate) fn commit_range(&mut self, revision: &str) -> Result<Vec<String>> {
+        let (from, to) = match revision.split_once("..") {
+            Some(range) => range,
+            None => return Ok(vec![Revision::new(&self.refs, &mut self.db).resolve_commit(revision)?]),
+        };
+        let mut revision = Revision::new(&self.refs, &mut self.db);
+        let to = revision.resolve_commit(if to.is_empty() { "HEAD" } else { to })?;
+        let from = revision.resolve_commit(if from.is_empty() { "HEAD" } else { from })?;
+        let mut excluded = HashSet::new();
+        let mut pending = vec![from];
+        while let Some(oid) = pending.pop() {
+            if excluded.insert(oid.to_string()) {
+                pending.extend(self.load_commit(&oid)?.parents().iter().cloned());
+            }
+        }
+        let mut commits = Vec::new();
+        let mut next = Some(to);
+        while let Some(oid) = next.filter(|oid| !excluded.contains(oid)) {
+            next = self.load_commit(&oid)?.parent().cloned();
+            commits.push(oid);
+        }
+        commits.reverse();
+        Ok(commits)
+    }
+
+    fn read_todo(&self) -> Result<Vec<String>> {
+        let todo = fs::read_to_string(self.sequencer_dir().join("todo")).unwrap_or_default();
+        Ok(todo.lines().filter(|l| !l.trim().is_empty()).map(|l| l.to_string()).collect())
+    }
+
+    fn write_todo(&self, todo: &[String]) -> Result<()> {
+        let content: String = todo.iter().map(|line| format!("{}\n", line)).collect();
+        fs::write(self.sequencer_dir().join("todo"), content)?;
+        Ok(())
+    }
+
+    // Applies the commits of the todo list until it is empty or one of them conflicts.
+    fn run_sequencer(&mut self, author: &str, email: &str) -> Result<()> {
+        loop {
+            let mut todo = self.read_todo()?;
+            if todo.is_empty() {
+                fs::remove_dir_all(self.sequencer_dir())?;
+                return Ok(());
+            }
+            let line = todo.remove(0);
+            let mut parts = line.splitn(3, ' ');
+            let action = SequencerAction::from_name(parts.next().unwrap_or(""))?;
+            let commit = parts
+                .next()
+                .ok_or(anyhow!("invalid line in the sequencer todo list: {}", line))?
+                .to_string();
+            // a commit that cannot be applied because of local changes stays in the list
+            let clean = self.apply_commit(action, &commit, author, email)?;
+            self.write_todo(&todo)?;
+            if !clean {
+                return Err(anyhow!(
+                    "could not {} {}... {}\nhint: after resolving the conflicts, mark the corrected paths\nhint: with 'git add <paths>' and run 'git {} --continue'",
+                    action.name(),
+                    &commit[..7],
+                    parts.next().unwrap_or(""),
+                    action.command()
+                ));
+            }
+        }
+    }
+
+    // Merges the changes of `commit`, or their inverse for a revert, into HEAD and commits
+    // the result. On conflicts the commit is recorded so it can be finished with `--continue`
+    // and false is returned.
+    fn apply_commit(&mut self, action: SequencerAction, commit: &str, author: &str, email: &str) -> Result<bool> {
+        let picked = self.load_commit(commit)?;
+        let (message, author) = match action {
+            SequencerAction::Pick => (picked.message().trim_end().to_string(), picked.author().clone()),
+            SequencerAction::Revert => (
+                format!("Revert \"{}\"\n\nThis reverts commit {}.", picked.title_line(), commit),
+                Author::new(author, email, Local::now()),
+            ),
+        };
+        let (index, clean) = self.merge_commit(commit, action == SequencerAction::Revert, action.command())?;
+        if !clean {
+            fs::write(self.repository.git_dir.join(action.head_file()), format!("{}\n", commit))?;
+            fs::write(self.repository.git_dir.join("MERGE_MSG"), format!("{}\n", message))?;
+            return Ok(false);
+        }
+        self.commit_index(&index, &author, &message)?;
+        Ok(true)
+    }
+
+    // Merges the changes `commit` made to its parent, or their inverse with `revert`, into
+    // HEAD, updating the working tree and the index which is returned with whether the merge
+    // was clean. Conflicting files are left with conflict markers in the working tree and are
+    // unmerged in the index, with an entry for each side. `command` names the operation in
+    // the error when local changes are in the way.
+    pub(crate) fn merge_commit(&mut self, commit: &str, revert: bool, command: &str) -> Result<(TreeList, bool)> {
+        let picked = self.load_commit(commit)?;
+        if picked.parents().len() > 1 {
+            return Err(anyhow!("commit {} is a merge, which {} does not support", commit, command));
+        }
+        let commit_list = self.commit_tree_list(commit)?;
+        let parent_list = match picked.parent() {
+            Some(parent) => self.commit_tree_list(&parent.to_string())?,
+            None => TreeList::new(),
+        };
+        let (base, theirs) = if revert { (commit_list, parent_list) } else { (parent_list, commit_list) };
+
+        self.load_index()?;
+        self.check_unmerged(command)?;
+        let head_list = self.head_tree_list()?;
+        let index_list = self.index_tree_list()?;
+        let label = format!("{} ({})", &commit[..7], picked.title_line());
+        let merge = self.merge_tree_lists(&base, &head_list, &theirs, ("HEAD", &label))?;
+        let touched = changed_paths(&head_list, &merge.list);
+        let paths: Vec<PathBuf> = touched.iter().cloned().collect();
+        let work_list = self.hashed_workspace_list(&paths)?;
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let dirty = touched.iter().any(|path| {
+            !same_entry(head_list.get(path), index_list.get(path))
+                || !same_entry(index_list.get(path), work_list.get(path))
+                || (!index_list.contains_key(path) && work_tree.join(path).exists())
+        });
+        if dirty {
+            return Err(anyhow!(
+                "your local changes would be overwritten by {}.\nhint: commit your changes or stash them to proceed.",
+                command
+            ));
+        }
+
+        self.migrate_workspace(&head_list, &merge.list)?;
+        let mut index = index_list;
+        for path in touched.iter() {
+            match merge.list.get(path) {
+                Some(entry) => index.insert(path.to_path_buf(), entry.clone()),
+                None => index.remove(path),
+            };
+        }
+        self.write_index_list(&index)?;
+        self.write_unmerged(&merge.conflicts)?;
+        merge.print_conflicts();
+        Ok((index, merge.conflicts.is_empty()))
+    }
+
+    // commits `index` on top of HEAD and prints the summary line git prints.
+    pub(crate) fn commit_index(&mut self, index: &TreeList, author: &Author, message: &str) -> Result<String> {
+        let head = self.refs.read_head();
+        let oid = self.write_commit(index, head.into_iter().collect(), author, message)?;
+        self.refs.update_head(oid.to_string())?;
+        let branch = self
+            .refs
+            .current_ref()
+            .map(|r| r.strip_prefix("refs/heads/").unwrap_or(&r).to_string())
+            .unwrap_or("detached HEAD".to_string());
+        println!("[{} {}] {}", branch, &oid[..7], message.lines().next().unwrap_or(""));
+        Ok(oid)
+    }
+
+    // The index once every conflict was resolved, which is when no path is unmerged anymore.
+    pub(crate) fn resolved_index(&mut self) -> Result<TreeList> {
+        self.load_index()?;
+        self.check_unmerged("Committing")?;
+        self.index_tree_list()
+    }
+
+    // the action whose commit stopped with conflicts, if any.
+    fn stopped_action(&self) -> Option<SequencerAction> {
+        [SequencerAction::Pick, SequencerAction::Revert]
+            .iter()
+            .find(|action| self.repository.git_dir.join(action.head_file()).exists())
+            .cloned()
+    }
+
+    fn clear_stopped_commit(&self, action: SequencerAction) -> Result<()> {
+        fs::remove_file(self.repository.git_dir.join(action.head_file()))?;
+        let message = self.repository.git_dir.join("MERGE_MSG");
+        if message.exists() {
+            fs::remove_file(message)?;
+        }
+        Ok(())
+    }
+
+    fn check_in_progress(&self) -> Result<()> {
+        if !self.sequencer_dir().exists() {
+            return Err(anyhow!("no cherry-pick or revert in progress"));
+        }
+        Ok(())
+    }
+
+    // Commits the resolution of the commit that stopped with conflicts, as staged in the
+    // index, then applies the rest of the todo list.
+    pub fn sequencer_continue(&mut self, author: &str, email: &str) -> Result<()> {
+        self.check_in_progress()?;
+        if let Some(action) = self.stopped_action() {
+            let index = self.resolved_index()?;
+            let git_dir = self.repository.git_dir.to_path_buf();
+            let message = fs::read_to_string(git_dir.join("MERGE_MSG"))?;
+            let author = match action {
+                SequencerAction::Pick => {
+                    let commit = fs::read_to_string(git_dir.join(action.head_file()))?;
+                    self.load_commit(commit.trim())?.author().clone()
+                }
+                SequencerAction::Revert => Author::new(author, email, Local::now()),
+            };
+            self.commit_index(&index, &author, message.trim_end())?;
+            self.clear_stopped_commit(action)?;
+        }
+        self.run_sequencer(author, email)
+    }
+
+    // Drops the commit that stopped with conflicts, restoring HEAD in the working tree and
+    // the index, then applies the rest of the todo list.
+    pub fn sequencer_skip(&mut self, author: &str, email: &str) -> Result<()> {
+        self.check_in_progress()?;
+        if let Some(action) = self.stopped_action() {
+            self.reset_to(None)?;
+            self.clear_stopped_commit(action)?;
+        }
+        self.run_sequencer(author, email)
+    }
+
+    // Stops the cherry-pick or revert and moves HEAD back to where it started, restoring the
+    // working tree and the index to it.
+    pub fn sequencer_abort(&mut self) -> Result<()> {
+        self.check_in_progress()?;
+        let head = fs::read_to_string(self.sequencer_dir().join("head"))?;
+        self.reset_to(Some(head.trim()))?;
+        if let Some(action) = self.stopped_action() {
+            self.clear_stopped_commit(action)?;
+        }
+        fs::remove_dir_all(self.sequencer_dir())?;
+        Ok(())
+    }
+
+    // Makes the working tree and the index match `commit`, HEAD by default, moving HEAD to it.
+    pub(crate) fn reset_to(&mut self, commit: Option<&str>) -> Result<()> {
+        self.load_index()?;
+        let index_list = self.index_tree_list()?;
+        // the files left unmerged are in the working tree too
+        let paths: Vec<PathBuf> = index_list.keys().cloned().chain(self.index.unmerged_paths().map(PathBuf::from)).collect();
+        let current = self.hashed_workspace_list(&paths)?;
+        let target = match commit {
+            Some(commit) => {
+                self.refs.update_head(commit.to_string())?;
+                self.commit_tree_list(commit)?
+            }
+            None => self.head_tree_list()?,
+        };
+        self.migrate_workspace(&current, &target)?;
+        self.write_index_list(&target)
+    }
+}
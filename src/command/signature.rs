@@ -0,0 +1,66 @@
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::signing::{self, SignatureCheck};
+use crate::{commit, tag, Revision};
+
+use super::Command;
+
+impl Command {
+    // The check of the signature of the commit `oid`, `None` when it is not signed.
+    pub(crate) fn commit_signature(&self, oid: &str) -> Result<Option<SignatureCheck>> {
+        let raw = self.db.read_raw(oid)?;
+        match commit::split_signature(&raw.content) {
+            Some((payload, signature)) => Ok(Some(signing::verify(&self.config, &payload, &signature)?)),
+            None => Ok(None),
+        }
+    }
+
+    // checks the signatures of the commits, printing what was found about each of them.
+    pub fn verify_commit(&mut self, revisions: &[&str]) -> Result<()> {
+        let mut failed = false;
+        for revision in revisions {
+            let oid = Revision::new(&self.refs, &mut self.db).resolve_commit(revision)?;
+            match self.commit_signature(&oid)? {
+                Some(check) => {
+                    eprint!("{}", check.report());
+                    failed |= !check.good;
+                }
+                None => {
+                    eprintln!("error: commit {} has no signature", oid);
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            return Err(anyhow!("the signature of a commit could not be verified"));
+        }
+        Ok(())
+    }
+
+    pub fn verify_tag(&mut self, names: &[&str]) -> Result<()> {
+        let mut failed = false;
+        for name in names {
+            let oid = Revision::new(&self.refs, &mut self.db).resolve(name)?;
+            let raw = self.db.read_raw(&oid)?;
+            if raw.type_ != "tag" {
+                return Err(anyhow!("{}: cannot verify a non-tag object of type {}.", name, raw.type_));
+            }
+            match tag::split_signature(&raw.content) {
+                Some((payload, signature)) => {
+                    let check = signing::verify(&self.config, &payload, &signature)?;
+                    eprint!("{}", check.report());
+                    failed |= !check.good;
+                }
+                None => {
+                    eprintln!("error: no signature found");
+                    failed = true;
+                }
+            }
+        }
+        if failed {
+            return Err(anyhow!("the signature of a tag could not be verified"));
+        }
+        Ok(())
+    }
+}
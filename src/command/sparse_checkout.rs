@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::sparse::SparseCheckout;
+use crate::util;
+
+use super::migration::{remove_empty_parents, tree_entry};
+use super::Command;
+
+impl Command {
+    // The cone of the sparse checkout of this worktree, when `core.sparseCheckout` is on and
+    // its patterns were written.
+    pub(crate) fn sparse_checkout(&self) -> Result<Option<SparseCheckout>> {
+        let path = self.sparse_checkout_file();
+        if self.config.get_bool("core.sparsecheckout") != Some(true) || !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(SparseCheckout::parse(&fs::read_to_string(path)?)?))
+    }
+
+    fn sparse_checkout_file(&self) -> PathBuf {
+        self.repository.git_dir.join("info").join("sparse-checkout")
+    }
+
+    // Turns the sparse checkout on, keeping the patterns already written. Without them only
+    // the files at the top of the working tree are left.
+    pub fn sparse_checkout_init(&mut self) -> Result<()> {
+        let sparse = match fs::read_to_string(self.sparse_checkout_file()) {
+            Ok(patterns) => SparseCheckout::parse(&patterns)?,
+            Err(_) => SparseCheckout::default(),
+        };
+        self.write_sparse_checkout(&sparse)
+    }
+
+    // replaces the directories of the cone, given relative to the directory the command was
+    // started from.
+    pub fn sparse_checkout_set(&mut self, dirs: &[PathBuf]) -> Result<()> {
+        let dirs = self.resolve_sparse_dirs(dirs)?;
+        self.write_sparse_checkout(&SparseCheckout::new(dirs))
+    }
+
+    pub fn sparse_checkout_add(&mut self, dirs: &[PathBuf]) -> Result<()> {
+        let mut sparse = self.sparse_checkout()?.ok_or(anyhow!("no sparse-checkout to add to"))?;
+        sparse.add(self.resolve_sparse_dirs(dirs)?);
+        self.write_sparse_checkout(&sparse)
+    }
+
+    pub fn sparse_checkout_list(&self) -> Result<()> {
+        let sparse = self.sparse_checkout()?.ok_or(anyhow!("this worktree is not sparse"))?;
+        for dir in sparse.dirs() {
+            println!("{}", dir.display());
+        }
+        Ok(())
+    }
+
+    // updates the working tree again from the patterns, like after they were edited by hand.
+    pub fn sparse_checkout_reapply(&mut self) -> Result<()> {
+        let sparse = self.sparse_checkout()?.ok_or(anyhow!("must be in a sparse-checkout to reapply sparsity patterns"))?;
+        self.apply_sparse_checkout(Some(&sparse))
+    }
+
+    // checks out every file again and turns the sparse checkout off.
+    pub fn sparse_checkout_disable(&mut self) -> Result<()> {
+        self.apply_sparse_checkout(None)?;
+        self.config.set("core.sparsecheckout", "false")?;
+        self.config.save()
+    }
+
+    // the top of the working tree is always in the cone, so it is left out.
+    fn resolve_sparse_dirs(&self, dirs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut resolved = Vec::new();
+        for dir in dirs {
+            let dir = self.repository.resolve_path(dir)?;
+            if !dir.as_os_str().is_empty() {
+                resolved.push(dir);
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn write_sparse_checkout(&mut self, sparse: &SparseCheckout) -> Result<()> {
+        let path = self.sparse_checkout_file();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, sparse.to_patterns())?;
+        self.config.set("core.sparsecheckout", "true")?;
+        self.config.set("core.sparsecheckoutcone", "true")?;
+        self.config.save()?;
+        self.apply_sparse_checkout(Some(sparse))
+    }
+
+    // Brings the working tree in line with `sparse`, or with no sparse checkout: the files
+    // coming into the cone are written and those leaving it are removed and marked to skip the
+    // working tree. A modified file is left where it is, with a warning. Submodules are not
+    // affected.
+    fn apply_sparse_checkout(&mut self, sparse: Option<&SparseCheckout>) -> Result<()> {
+        self.load_index()?;
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let entries: Vec<(PathBuf, Vec<u8>, u32, bool)> = self
+            .index
+            .each_entry()?
+            .iter()
+            .map(|entry| (entry.path.to_path_buf(), entry.oid.to_vec(), entry.get_mode().unwrap_or(0), entry.is_skip_worktree()))
+            .collect();
+        let mut not_up_to_date = Vec::new();
+        for (path, oid, mode, skip) in entries {
+            if mode == util::GITLINK_MODE {
+                continue;
+            }
+            let key = path.display().to_string();
+            let full_path = work_tree.join(&path);
+            let included = sparse.is_none_or(|sparse| sparse.includes(&path));
+            if included && skip {
+                if util::stat_file(&full_path).is_ok() {
+                    self.index.set_skip_worktree(&key, false);
+                } else {
+                    let entry = tree_entry(&path, oid.to_vec(), util::get_mode_u(mode));
+                    let stat = self.checkout_blob(&path, &entry)?;
+                    self.index.add_with_mode(path, oid, stat, mode)?;
+                }
+            } else if !included && !skip {
+                if util::stat_file(&full_path).is_ok_and(|stat| util::is_blob(&stat)) {
+                    if self.db.hash_file(&full_path)? != oid {
+                        not_up_to_date.push(key);
+                        continue;
+                    }
+                    fs::remove_file(&full_path)?;
+                    remove_empty_parents(&work_tree, &full_path);
+                }
+                self.index.set_skip_worktree(&key, true);
+            }
+        }
+        if !not_up_to_date.is_empty() {
+            eprintln!("warning: The following paths are not up to date and were left despite sparse patterns:");
+            for path in not_up_to_date.iter() {
+                eprintln!("\t{}", path);
+            }
+            eprintln!("\nAfter fixing the above paths, you may want to run `git sparse-checkout reapply`.");
+        }
+        self.index.write_updates()
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::Local;
+
+use crate::tree::TreeList;
+use crate::{Author, Revision};
+
+use super::migration::{changed_paths, same_entry};
+use super::Command;
+
+const STASH_REF: &str = "refs/stash";
+
+// The commits `stash push` records: the working tree commit, whose parents are the commit
+// HEAD pointed to, the commit holding the index and optionally the one holding the untracked
+// files, as git does.
+struct StashEntry {
+    base: String,
+    work_tree: String,
+    index: String,
+    untracked: Option<String>,
+}
+
+impl Command {
+    // Saves the changes of the index and the working tree, and the untracked files when
+    // `include_untracked` is set, then resets both to HEAD.
+    pub fn stash_push(
+        &mut self,
+        author: &str,
+        email: &str,
+        message: Option<&str>,
+        include_untracked: bool,
+    ) -> Result<()> {
+        self.workspace()?;
+        self.load_index()?;
+        self.check_unmerged("Stashing")?;
+        let head = self
+            .refs
+            .read_head()
+            .ok_or(anyhow!("you do not have the initial commit yet"))?;
+        let head_list = self.head_tree_list()?;
+        let index_list = self.index_tree_list()?;
+        let index_paths: Vec<PathBuf> = index_list.keys().cloned().collect();
+        let work_list = self.workspace_tree_list(index_paths.iter())?;
+        let untracked = if include_untracked {
+            self.untracked_files()?
+        } else {
+            vec![]
+        };
+        if changed_paths(&head_list, &index_list).is_empty()
+            && changed_paths(&index_list, &work_list).is_empty()
+            && untracked.is_empty()
+        {
+            println!("No local changes to save");
+            return Ok(());
+        }
+
+        let description = self.describe_head(&head)?;
+        let author = Author::new(author, email, Local::now());
+        let index_commit = self.write_commit(&index_list, vec![head.clone()], &author, &format!("index on {}", description))?;
+        let mut parents = vec![head.clone(), index_commit];
+        let untracked_list = self.workspace_tree_list(untracked.iter())?;
+        if !untracked.is_empty() {
+            let message = format!("untracked files on {}", description);
+            parents.push(self.write_commit(&untracked_list, vec![], &author, &message)?);
+        }
+        let message = match message {
+            Some(message) => format!("On {}: {}", description.split(':').next().unwrap_or(""), message),
+            None => format!("WIP on {}", description),
+        };
+        let stash = self.write_commit(&work_list, parents, &author, &message)?;
+
+        let old = self.refs.resolve(STASH_REF);
+        self.refs.update_ref(STASH_REF, &stash)?;
+        self.refs.append_reflog(STASH_REF, old.as_deref(), &stash, &author.to_s(), &message)?;
+
+        let mut current = work_list;
+        current.extend(untracked_list);
+        self.migrate_workspace(&current, &head_list)?;
+        self.write_index_list(&head_list)?;
+        println!("Saved working directory and index state {}", message);
+        Ok(())
+    }
+
+    // `<branch>: <abbreviated oid> <title>`, how stash messages describe HEAD.
+    fn describe_head(&mut self, head: &str) -> Result<String> {
+        let branch = self
+            .refs
+            .current_ref()
+            .map(|r| r.strip_prefix("refs/heads/").unwrap_or(&r).to_string())
+            .unwrap_or("(no branch)".to_string());
+        let title = self.load_commit(head)?.title_line().to_string();
+        Ok(format!("{}: {} {}", branch, &head[..7], title))
+    }
+
+    fn load_stash(&mut self, name: &str) -> Result<StashEntry> {
+        let oid = Revision::new(&self.refs, &mut self.db)
+            .resolve(name)
+            .map_err(|_| anyhow!("{} is not a valid reference", name))?;
+        let parents = self.load_commit(&oid)?.parents().to_vec();
+        if parents.len() < 2 {
+            return Err(anyhow!("'{}' is not a stash-like commit", name));
+        }
+        Ok(StashEntry {
+            base: parents[0].to_string(),
+            work_tree: oid,
+            index: parents[1].to_string(),
+            untracked: parents.get(2).cloned(),
+        })
+    }
+
+    // Re-applies the changes of a stash entry on top of the current HEAD. With `restore_index`
+    // the changes that were staged are staged again, otherwise only new files are. The changes
+    // are merged with those made since the stash was created, conflicting files are left
+    // with conflict markers.
+    pub fn stash_apply(&mut self, name: Option<&str>, restore_index: bool) -> Result<bool> {
+        self.workspace()?;
+        let name = name.unwrap_or("stash@{0}");
+        if self.refs.resolve(STASH_REF).is_none() {
+            return Err(anyhow!("no stash entries found"));
+        }
+        let stash = self.load_stash(name)?;
+        self.load_index()?;
+        let head_list = self.head_tree_list()?;
+        let index_list = self.index_tree_list()?;
+        let work_list = self.workspace_tree_list(index_list.keys())?;
+        let base_list = self.commit_tree_list(&stash.base)?;
+        let stashed_list = self.commit_tree_list(&stash.work_tree)?;
+        let stashed_index = self.commit_tree_list(&stash.index)?;
+        let untracked_list = match &stash.untracked {
+            Some(oid) => self.commit_tree_list(oid)?,
+            None => TreeList::new(),
+        };
+
+        // the files the stash changes must not have local changes that would be lost
+        let touched = changed_paths(&base_list, &stashed_list);
+        let mut dirty: Vec<_> = touched
+            .iter()
+            .filter(|path| {
+                !same_entry(head_list.get(*path), index_list.get(*path))
+                    || !same_entry(index_list.get(*path), work_list.get(*path))
+            })
+            .collect();
+        dirty.sort();
+        if !dirty.is_empty() {
+            let files: String = dirty.iter().map(|p| format!("\t{}\n", p.display())).collect();
+            return Err(anyhow!(
+                "Your local changes to the following files would be overwritten by merge:\n{}Please commit your changes or stash them before you merge.\nAborting",
+                files
+            ));
+        }
+        // neither can the files it creates exist untracked
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        let mut created = untracked_list
+            .keys()
+            .chain(stashed_list.keys().filter(|p| !base_list.contains_key(*p) && !index_list.contains_key(*p)));
+        if let Some(path) = created.find(|path| work_tree.join(path).exists()) {
+            return Err(anyhow!("{} already exists, no checkout", path.display()));
+        }
+
+        let new_index = if restore_index && !changed_paths(&base_list, &stashed_index).is_empty() {
+            let merge = self.merge_tree_lists(&base_list, &index_list, &stashed_index, ("Updated upstream", "Stashed changes"))?;
+            if !merge.conflicts.is_empty() {
+                return Err(anyhow!("Conflicts in index. Try without --index."));
+            }
+            Some(merge.list)
+        } else {
+            None
+        };
+        let merge = self.merge_tree_lists(&base_list, &work_list, &stashed_list, ("Updated upstream", "Stashed changes"))?;
+
+        let mut new_work = merge.list.clone();
+        self.migrate_workspace(&work_list, &new_work)?;
+        self.migrate_workspace(&TreeList::new(), &untracked_list)?;
+        new_work.extend(untracked_list);
+
+        let index = match new_index {
+            Some(list) => list,
+            None => {
+                // the files added by the stash are staged, any other change is left unstaged
+                let mut list = index_list.clone();
+                for path in stashed_list.keys().filter(|p| !base_list.contains_key(*p)) {
+                    if !merge.is_conflicted(path) {
+                        list.insert(path.to_path_buf(), merge.list[path].clone());
+                    }
+                }
+                list
+            }
+        };
+        self.write_index_list(&index)?;
+        self.write_unmerged(&merge.conflicts)?;
+        merge.print_conflicts();
+        Ok(merge.conflicts.is_empty())
+    }
+
+    // applies the stash entry and drops it unless there were conflicts.
+    pub fn stash_pop(&mut self, name: Option<&str>, restore_index: bool) -> Result<()> {
+        if self.stash_apply(name, restore_index)? {
+            self.stash_drop(name)
+        } else {
+            println!("The stash entry is kept in case you need it again.");
+            Err(anyhow!("conflicts while applying the stash"))
+        }
+    }
+
+    pub fn stash_list(&self) -> Result<()> {
+        for (n, entry) in self.refs.read_reflog(STASH_REF)?.iter().rev().enumerate() {
+            println!("stash@{{{}}}: {}", n, entry.message);
+        }
+        Ok(())
+    }
+
+    // removes an entry, `stash@{0}` by default, from the stack kept in the reflog of
+    // `refs/stash`; the ref points to the newest entry left.
+    pub fn stash_drop(&mut self, name: Option<&str>) -> Result<()> {
+        let name = name.unwrap_or("stash@{0}");
+        let n = name
+            .strip_prefix("stash@{")
+            .and_then(|n| n.strip_suffix('}'))
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or(anyhow!("{} is not a stash reference", name))?;
+        let mut entries = self.refs.read_reflog(STASH_REF)?;
+        if n >= entries.len() {
+            return Err(anyhow!("{} is not a valid reference", name));
+        }
+        let removed = entries.remove(entries.len() - 1 - n);
+        self.refs.write_reflog(STASH_REF, &entries)?;
+        match entries.last() {
+            Some(top) => self.refs.update_ref(STASH_REF, &top.new_oid)?,
+            None => self.refs.delete_ref(STASH_REF)?,
+        }
+        println!("Dropped {} ({})", name, removed.new_oid);
+        Ok(())
+    }
+}
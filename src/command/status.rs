@@ -0,0 +1,448 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+
+use anyhow::Result;
+
+use crate::util;
+
+use super::upstream::Tracking;
+use super::{IndexStatus, Status, WorkspaceStatus};
+
+// The output of `status`: the long format meant to be read, `--short`, and the porcelain
+// formats, whose output is stable and whose paths are relative to the top of the working tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFormat {
+    Long,
+    Short,
+    PorcelainV1,
+    PorcelainV2,
+}
+
+impl StatusFormat {
+    // the format of `--porcelain[=<version>]`.
+    pub fn porcelain(version: &str) -> Result<Self> {
+        match version {
+            "v1" | "1" => Ok(StatusFormat::PorcelainV1),
+            "v2" | "2" => Ok(StatusFormat::PorcelainV2),
+            _ => Err(anyhow::anyhow!("unsupported porcelain version '{}'", version)),
+        }
+    }
+}
+
+impl Status {
+    pub(super) fn print(&self, format: StatusFormat, branch: bool, nul: bool) -> Result<()> {
+        let head = self.cmd.borrow().refs.read_head();
+        let tracking = match self.branch_name() {
+            Some(name) if head.is_some() && (branch || format == StatusFormat::Long) => {
+                self.cmd.borrow_mut().tracking(&name)?
+            }
+            _ => None,
+        };
+        // the branch header of the short and porcelain formats, when asked for
+        let header = if branch { Some(tracking.as_ref()) } else { None };
+        let output = match format {
+            StatusFormat::Long => {
+                let hints = self.cmd.borrow().config.get_bool("advice.statushints") != Some(false);
+                self.long_format(tracking.as_ref(), hints)?
+            }
+            StatusFormat::Short => self.short_format(true, header, nul),
+            StatusFormat::PorcelainV1 => self.short_format(false, header, nul),
+            StatusFormat::PorcelainV2 => self.porcelain_v2(header, nul)?,
+        };
+        io::stdout().write_all(output.as_bytes())?;
+        Ok(())
+    }
+
+    // The long format without hints as comments, which follows the message to edit of a
+    // commit.
+    pub(super) fn commit_template(&mut self) -> Result<String> {
+        self.collect()?;
+        Ok(self
+            .long_format(None, false)?
+            .lines()
+            .map(|line| match line {
+                "" => "#\n".to_string(),
+                line if line.starts_with('\t') => format!("#{}\n", line),
+                line => format!("# {}\n", line),
+            })
+            .collect())
+    }
+
+    fn index_code(&self, path: &str) -> Option<char> {
+        self.index_changes.get(path).map(|status| match status {
+            IndexStatus::Added => 'A',
+            IndexStatus::Modified => 'M',
+            IndexStatus::Deleted => 'D',
+            IndexStatus::Renamed => 'R',
+        })
+    }
+
+    fn workspace_code(&self, path: &str) -> Option<char> {
+        self.workspace_changes.get(path).map(|status| match status {
+            WorkspaceStatus::Modified => 'M',
+            WorkspaceStatus::Deleted => 'D',
+        })
+    }
+
+    // which of the base, our and their stages the index has for an unmerged path.
+    fn stages(&self, path: &str) -> [bool; 3] {
+        let cmd = self.cmd.borrow();
+        [1, 2, 3].map(|stage| cmd.index.unmerged_entry(path, stage).is_some())
+    }
+
+    // the branch HEAD is on, `None` when it is detached.
+    fn branch_name(&self) -> Option<String> {
+        let branch = self.cmd.borrow().refs.current_ref()?;
+        Some(branch.strip_prefix("refs/heads/").unwrap_or(&branch).to_string())
+    }
+
+    fn display_path(&self, path: &str) -> String {
+        self.cmd.borrow().repository.display_path(path)
+    }
+
+    // `XY path` for each changed file, X for the index and Y for the working tree, or how
+    // both sides changed an unmerged file, then the untracked files. Renames are
+    // `R  old -> new`, or `R  new` and `old` as separate entries when they are NUL terminated.
+    // With `branch`, a `## branch...upstream [ahead 1]` header.
+    fn short_format(&self, relative: bool, branch: Option<Option<&Tracking>>, nul: bool) -> String {
+        let end = if nul { '\0' } else { '\n' };
+        let show = |path: &str| if relative { self.display_path(path) } else { path.to_string() };
+        let mut out = String::new();
+        if let Some(tracking) = branch {
+            let head = self.cmd.borrow().refs.read_head();
+            match (self.branch_name(), head) {
+                (Some(name), Some(_)) => {
+                    out.push_str(&format!("## {}", name));
+                    if let Some(tracking) = tracking {
+                        out.push_str(&format!("...{}", tracking.name));
+                        match tracking.ahead_behind {
+                            None => out.push_str(" [gone]"),
+                            Some((0, 0)) => {}
+                            Some((ahead, 0)) => out.push_str(&format!(" [ahead {}]", ahead)),
+                            Some((0, behind)) => out.push_str(&format!(" [behind {}]", behind)),
+                            Some((ahead, behind)) => out.push_str(&format!(" [ahead {}, behind {}]", ahead, behind)),
+                        }
+                    }
+                    out.push(end);
+                }
+                (Some(name), None) => out.push_str(&format!("## No commits yet on {}{}", name, end)),
+                (None, _) => out.push_str(&format!("## HEAD (no branch){}", end)),
+            }
+        }
+        let paths: BTreeSet<&String> = self.changed.iter().chain(self.unmerged.iter()).collect();
+        for path in paths {
+            if self.unmerged.contains(path) {
+                out.push_str(&format!("{} {}{}", unmerged_code(self.stages(path)), show(path), end));
+                continue;
+            }
+            let x = self.index_code(path).unwrap_or(' ');
+            let y = self.workspace_code(path).unwrap_or(' ');
+            match self.renamed.get(path) {
+                Some((old, _)) if nul => out.push_str(&format!("{}{} {}\0{}\0", x, y, show(path), show(old))),
+                Some((old, _)) => out.push_str(&format!("{}{} {} -> {}\n", x, y, show(old), show(path))),
+                None => out.push_str(&format!("{}{} {}{}", x, y, show(path), end)),
+            }
+        }
+        for path in self.untracked.iter() {
+            out.push_str(&format!("?? {}{}", show(path), end));
+        }
+        out
+    }
+
+    // Version 2 of the porcelain format: for each changed file, its modes and oids in HEAD,
+    // the index and the working tree, `1 XY N... mH mI mW hH hI path`, or with a `2` and the
+    // score and the old path for renames. Unchanged sides are shown with a `.`. Unmerged files
+    // follow with the modes of their stages and the working tree and the oids of their stages,
+    // `u XY N... m1 m2 m3 mW h1 h2 h3 path`.
+    fn porcelain_v2(&self, branch: Option<Option<&Tracking>>, nul: bool) -> Result<String> {
+        let end = if nul { '\0' } else { '\n' };
+        let separator = if nul { '\0' } else { '\t' };
+        let cmd = self.cmd.borrow();
+        let zero = "0".repeat(self.hash.hex_size());
+        let mut out = String::new();
+        if let Some(tracking) = branch {
+            let head = cmd.refs.read_head();
+            out.push_str(&format!("# branch.oid {}{}", head.as_deref().unwrap_or("(initial)"), end));
+            let name = self.branch_name().unwrap_or_else(|| "(detached)".to_string());
+            out.push_str(&format!("# branch.head {}{}", name, end));
+            if let Some(tracking) = tracking {
+                out.push_str(&format!("# branch.upstream {}{}", tracking.name, end));
+                if let Some((ahead, behind)) = tracking.ahead_behind {
+                    out.push_str(&format!("# branch.ab +{} -{}{}", ahead, behind, end));
+                }
+            }
+        }
+        let mut index = HashMap::new();
+        for entry in cmd.index.each_entry()?.iter() {
+            index.insert(entry.get_path(), (format!("{:06o}", entry.get_mode()?), util::encode_vec(&entry.oid)));
+        }
+        for path in self.changed.iter() {
+            let x = self.index_code(path).unwrap_or('.');
+            let y = self.workspace_code(path).unwrap_or('.');
+            let old = self.renamed.get(path);
+            let head_path = old.map(|(old, _)| old).unwrap_or(path);
+            let (head_mode, head_oid) = match self.head_tree.get(head_path) {
+                Some(entry) => (entry.mode.to_string(), util::encode_vec(&entry.sha1_hash)),
+                None => ("000000".to_string(), zero.to_string()),
+            };
+            let (index_mode, index_oid) = index
+                .get(path)
+                .cloned()
+                .unwrap_or_else(|| ("000000".to_string(), zero.to_string()));
+            let gitlink = format!("{:o}", util::GITLINK_MODE);
+            let is_gitlink = head_mode == gitlink || index_mode == gitlink;
+            let workspace_mode = match (self.workspace_changes.get(path), self.stat.get(path)) {
+                (Some(WorkspaceStatus::Deleted), _) | (_, None) => "000000".to_string(),
+                (_, Some(_)) if index_mode == gitlink => gitlink.to_string(),
+                (_, Some(stat)) => format!("{:06o}", util::get_mode_stat(stat)),
+            };
+            // the submodule field tells how a submodule changed, `N...` is for other files
+            let submodule = if is_gitlink {
+                self.submodules.get(path).copied().unwrap_or_default().porcelain_field()
+            } else {
+                "N...".to_string()
+            };
+            let fields = format!(
+                "{}{} {} {} {} {} {} {}",
+                x, y, submodule, head_mode, index_mode, workspace_mode, head_oid, index_oid
+            );
+            match old {
+                Some((old, score)) => {
+                    out.push_str(&format!("2 {} R{} {}{}{}{}", fields, score, path, separator, old, end))
+                }
+                None => out.push_str(&format!("1 {} {}{}", fields, path, end)),
+            }
+        }
+        for path in self.unmerged.iter() {
+            let mut modes = Vec::new();
+            let mut oids = Vec::new();
+            for stage in 1..=3 {
+                match cmd.index.unmerged_entry(path, stage) {
+                    Some(entry) => {
+                        modes.push(format!("{:06o}", entry.get_mode()?));
+                        oids.push(util::encode_vec(&entry.oid));
+                    }
+                    None => {
+                        modes.push("000000".to_string());
+                        oids.push(zero.to_string());
+                    }
+                }
+            }
+            let workspace_mode = match self.stat.get(path) {
+                Some(stat) => format!("{:06o}", util::get_mode_stat(stat)),
+                None => "000000".to_string(),
+            };
+            let code = unmerged_code(self.stages(path));
+            out.push_str(&format!(
+                "u {} N... {} {} {} {}{}",
+                code,
+                modes.join(" "),
+                workspace_mode,
+                oids.join(" "),
+                path,
+                end
+            ));
+        }
+        for path in self.untracked.iter() {
+            out.push_str(&format!("? {}{}", path, end));
+        }
+        Ok(out)
+    }
+
+    // The format of `git status` without options: the branch, then the staged changes, the
+    // unmerged files, the changes of the working tree and the untracked files, each followed
+    // by hints unless `hints` is false.
+    fn long_format(&self, tracking: Option<&Tracking>, hints: bool) -> Result<String> {
+        let cmd = self.cmd.borrow();
+        let head = cmd.refs.read_head();
+        let mut out = String::new();
+        match (self.branch_name(), &head) {
+            (Some(name), _) => out.push_str(&format!("On branch {}\n", name)),
+            (None, Some(oid)) => out.push_str(&format!("HEAD detached at {}\n", &oid[..7])),
+            (None, None) => out.push_str("Not currently on any branch.\n"),
+        }
+        if let Some(tracking) = tracking {
+            out.push_str(&tracking_message(tracking, hints));
+            out.push('\n');
+        }
+        if cmd.sparse_checkout()?.is_some() {
+            let entries = cmd.index.each_entry()?;
+            if !entries.is_empty() {
+                let skipped = entries.iter().filter(|entry| entry.is_skip_worktree()).count();
+                let present = 100 - 100 * skipped / entries.len();
+                out.push_str(&format!("\nYou are in a sparse checkout with {}% of tracked files present.\n", present));
+            }
+        }
+        if head.is_none() {
+            out.push_str("\nNo commits yet\n\n");
+        }
+
+        let mut staged = Vec::new();
+        let mut unstaged = Vec::new();
+        for path in self.changed.iter() {
+            let label = match self.index_changes.get(path) {
+                Some(IndexStatus::Added) => Some("new file:"),
+                Some(IndexStatus::Modified) => Some("modified:"),
+                Some(IndexStatus::Deleted) => Some("deleted:"),
+                Some(IndexStatus::Renamed) => Some("renamed:"),
+                None => None,
+            };
+            if let Some(label) = label {
+                let name = match self.renamed.get(path) {
+                    Some((old, _)) => format!("{} -> {}", self.display_path(old), self.display_path(path)),
+                    None => self.display_path(path),
+                };
+                staged.push((label, name));
+            }
+            match self.workspace_changes.get(path) {
+                Some(WorkspaceStatus::Modified) => {
+                    let description = self.submodules.get(path).map(|change| change.description()).unwrap_or_default();
+                    unstaged.push(("modified:", format!("{}{}", self.display_path(path), description)))
+                }
+                Some(WorkspaceStatus::Deleted) => unstaged.push(("deleted:", self.display_path(path))),
+                None => {}
+            }
+        }
+
+        let section = |out: &mut String, title: &str, hint_lines: &[&str], entries: &[(&str, String)]| {
+            out.push_str(&format!("{}:\n", title));
+            if hints {
+                for hint in hint_lines {
+                    out.push_str(&format!("  ({})\n", hint));
+                }
+            }
+            for (label, name) in entries {
+                out.push_str(&format!("\t{:<12}{}\n", label, name));
+            }
+            out.push('\n');
+        };
+        if !staged.is_empty() {
+            let unstage = if head.is_some() {
+                "use \"git restore --staged <file>...\" to unstage"
+            } else {
+                "use \"git rm --cached <file>...\" to unstage"
+            };
+            section(&mut out, "Changes to be committed", &[unstage], &staged);
+        }
+        if !self.unmerged.is_empty() {
+            out.push_str("Unmerged paths:\n");
+            let stages: Vec<[bool; 3]> = self.unmerged.iter().map(|path| self.stages(path)).collect();
+            if hints {
+                // a file deleted on one side is resolved by removing it as well
+                if stages.iter().any(|stages| matches!(unmerged_code(*stages), "DD" | "UD" | "DU")) {
+                    out.push_str("  (use \"git add/rm <file>...\" as appropriate to mark resolution)\n");
+                } else {
+                    out.push_str("  (use \"git add <file>...\" to mark resolution)\n");
+                }
+            }
+            for (path, stages) in self.unmerged.iter().zip(stages) {
+                out.push_str(&format!("\t{:<17}{}\n", unmerged_label(stages), self.display_path(path)));
+            }
+            out.push('\n');
+        }
+        if !unstaged.is_empty() {
+            let update = if unstaged.iter().any(|(label, _)| *label == "deleted:") {
+                "use \"git add/rm <file>...\" to update what will be committed"
+            } else {
+                "use \"git add <file>...\" to update what will be committed"
+            };
+            let discard = "use \"git restore <file>...\" to discard changes in working directory";
+            let mut hint_lines = vec![update, discard];
+            if self.submodules.values().any(|change| change.is_dirty()) {
+                hint_lines.push("commit or discard the untracked or modified content in submodules");
+            }
+            section(&mut out, "Changes not staged for commit", &hint_lines, &unstaged);
+        }
+        if !self.untracked.is_empty() {
+            out.push_str("Untracked files:\n");
+            if hints {
+                out.push_str("  (use \"git add <file>...\" to include in what will be committed)\n");
+            }
+            for path in self.untracked.iter() {
+                out.push_str(&format!("\t{}\n", self.display_path(path)));
+            }
+            out.push('\n');
+        }
+
+        if staged.is_empty() {
+            let (message, hint) = if !unstaged.is_empty() || !self.unmerged.is_empty() {
+                ("no changes added to commit", " (use \"git add\" and/or \"git commit -a\")")
+            } else if !self.untracked.is_empty() {
+                ("nothing added to commit but untracked files present", " (use \"git add\" to track)")
+            } else if head.is_none() {
+                ("nothing to commit", " (create/copy files and use \"git add\" to track)")
+            } else {
+                ("nothing to commit, working tree clean", "")
+            };
+            out.push_str(message);
+            if hints {
+                out.push_str(hint);
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+// The `XY` of an unmerged file from the stages it has: `D` for a side that deleted it, `A`
+// for one that added it and `U` for one that modified it.
+fn unmerged_code(stages: [bool; 3]) -> &'static str {
+    match stages {
+        [true, false, false] => "DD",
+        [false, true, false] => "AU",
+        [true, true, false] => "UD",
+        [false, false, true] => "UA",
+        [true, false, true] => "DU",
+        [false, true, true] => "AA",
+        _ => "UU",
+    }
+}
+
+// How both sides changed an unmerged file, as told by the long format.
+fn unmerged_label(stages: [bool; 3]) -> &'static str {
+    match unmerged_code(stages) {
+        "DD" => "both deleted:",
+        "AU" => "added by us:",
+        "UD" => "deleted by them:",
+        "UA" => "added by them:",
+        "DU" => "deleted by us:",
+        "AA" => "both added:",
+        _ => "both modified:",
+    }
+}
+
+// How the branch compares to its upstream, as told by the long format.
+fn tracking_message(tracking: &Tracking, hints: bool) -> String {
+    let commits = |n: usize| if n == 1 { "1 commit".to_string() } else { format!("{} commits", n) };
+    let (message, hint) = match tracking.ahead_behind {
+        None => (
+            format!("Your branch is based on '{}', but the upstream is gone.", tracking.name),
+            "use \"git branch --unset-upstream\" to fixup",
+        ),
+        Some((0, 0)) => (format!("Your branch is up to date with '{}'.", tracking.name), ""),
+        Some((ahead, 0)) => (
+            format!("Your branch is ahead of '{}' by {}.", tracking.name, commits(ahead)),
+            "use \"git push\" to publish your local commits",
+        ),
+        Some((0, behind)) => (
+            format!(
+                "Your branch is behind '{}' by {}, and can be fast-forwarded.",
+                tracking.name,
+                commits(behind)
+            ),
+            "use \"git pull\" to update your local branch",
+        ),
+        Some((ahead, behind)) => (
+            format!(
+                "Your branch and '{}' have diverged,\nand have {} and {} different commits each, respectively.",
+                tracking.name, ahead, behind
+            ),
+            "use \"git pull\" if you want to integrate the remote branch with yours",
+        ),
+    };
+    if hints && !hint.is_empty() {
+        format!("{}\n  ({})\n", message, hint)
+    } else {
+        format!("{}\n", message)
+    }
+}
@@ -0,0 +1,351 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::refs::HEAD;
+use crate::repository::{self, Repository};
+use crate::{util, Config, Entry, HashAlgorithm};
+
+use super::migration::tree_entry;
+use super::{Command, Status};
+
+// How the working tree of a submodule differs from the commit its gitlink records.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SubmoduleChange {
+    // another commit is checked out
+    pub new_commits: bool,
+    // the submodule has changes of its own, to tracked files or untracked ones
+    pub modified: bool,
+    pub untracked: bool,
+}
+
+impl SubmoduleChange {
+    pub fn is_changed(&self) -> bool {
+        self.new_commits || self.is_dirty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.modified || self.untracked
+    }
+
+    // the `S<c><m><u>` field of the version 2 of the porcelain format of `status`.
+    pub fn porcelain_field(&self) -> String {
+        let flag = |set: bool, c: char| if set { c } else { '.' };
+        format!(
+            "S{}{}{}",
+            flag(self.new_commits, 'C'),
+            flag(self.modified, 'M'),
+            flag(self.untracked, 'U')
+        )
+    }
+
+    // what the long format of `status` tells after the path, like ` (new commits)`.
+    pub fn description(&self) -> String {
+        let parts: Vec<&str> = [
+            (self.new_commits, "new commits"),
+            (self.modified, "modified content"),
+            (self.untracked, "untracked content"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, part)| *part)
+        .collect();
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", parts.join(", "))
+        }
+    }
+}
+
+// Compares the submodule checked out at `path` to the commit `oid` recorded for it, looking at
+// the status of the submodule itself for changes of its own. `None` when it is not checked out.
+pub(crate) fn submodule_change(path: &Path, oid: &str) -> Result<Option<SubmoduleChange>> {
+    let head = match repository::checked_out_commit(path) {
+        Some(head) => head,
+        None => return Ok(None),
+    };
+    let mut status = Status::new(Command::from_repository(Repository::open(path)?)?);
+    status.collect()?;
+    Ok(Some(SubmoduleChange {
+        new_commits: head != oid,
+        modified: !status.changed.is_empty(),
+        untracked: !status.untracked.is_empty(),
+    }))
+}
+
+// a submodule declared in `.gitmodules`.
+struct Submodule {
+    name: String,
+    path: PathBuf,
+    url: String,
+}
+
+impl Command {
+    // the gitlink of the submodule checked out at `path` of the working tree, if there is one.
+    pub(crate) fn gitlink_entry(&self, path: &Path) -> Option<Entry> {
+        let full_path = self.workspace().ok()?.pathname.join(path);
+        if !util::is_nested_repository(&full_path) {
+            return None;
+        }
+        let head = repository::checked_out_commit(&full_path)?;
+        Some(tree_entry(path, hex::decode(head).ok()?, "160000".to_string()))
+    }
+
+    fn gitmodules(&self) -> Result<Config> {
+        let mut config = Config::new(&self.workspace()?.pathname.join(".gitmodules"));
+        config.load()?;
+        Ok(config)
+    }
+
+    // The submodules of `.gitmodules` ordered by path, only those at `paths` unless it is
+    // empty. The paths are given relative to the directory the command was started from.
+    fn submodules(&self, paths: &[PathBuf]) -> Result<Vec<Submodule>> {
+        let paths = paths
+            .iter()
+            .map(|path| self.repository.resolve_path(path))
+            .collect::<Result<Vec<_>>>()?;
+        let gitmodules = self.gitmodules()?;
+        let mut submodules = Vec::new();
+        for name in gitmodules.subsections("submodule") {
+            let path = match gitmodules.get(&format!("submodule.{}.path", name)) {
+                Some(path) => PathBuf::from(path),
+                None => continue,
+            };
+            if !paths.is_empty() && !paths.contains(&path) {
+                continue;
+            }
+            let url = gitmodules
+                .get(&format!("submodule.{}.url", name))
+                .ok_or(anyhow!("No url found for submodule path '{}' in .gitmodules", path.display()))?;
+            submodules.push(Submodule { name, path, url });
+        }
+        submodules.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(submodules)
+    }
+
+    // only local repositories can be cloned, relative urls start from the top of the working
+    // tree.
+    fn submodule_url(&self, url: &str) -> Result<String> {
+        let path = util::normalize_path(&self.workspace()?.pathname.join(url));
+        Ok(path.display().to_string())
+    }
+
+    // Clones the repository at `url`, a local path, into `path` of the working tree, a
+    // directory named after the repository by default, then records the submodule in
+    // `.gitmodules` and stages both.
+    pub fn submodule_add(&mut self, url: &str, path: Option<&Path>) -> Result<()> {
+        let path = match path {
+            Some(path) => self.repository.resolve_path(path)?,
+            None => {
+                let name = Path::new(url.trim_end_matches('/'))
+                    .file_name()
+                    .ok_or(anyhow!("cannot guess a path from the url '{}'", url))?
+                    .to_string_lossy()
+                    .trim_end_matches(".git")
+                    .to_string();
+                self.repository.resolve_path(Path::new(&name))?
+            }
+        };
+        let name = path.display().to_string();
+        self.load_index()?;
+        if self.index.is_tracked_file(&name) {
+            return Err(anyhow!("'{}' already exists in the index", name));
+        }
+        let full_path = self.workspace()?.pathname.join(&path);
+        if full_path.exists() && (!full_path.is_dir() || fs::read_dir(&full_path)?.next().is_some()) {
+            return Err(anyhow!("'{}' already exists and is not an empty directory", name));
+        }
+
+        let source = self.submodule_url(url)?;
+        let mut submodule = self.clone_submodule(&name, &path, &source)?;
+        if submodule.refs.read_head().is_some() {
+            submodule.reset_to(None)?;
+        }
+
+        let mut gitmodules = self.gitmodules()?;
+        gitmodules.set(&format!("submodule.{}.path", name), &name)?;
+        gitmodules.set(&format!("submodule.{}.url", name), url)?;
+        gitmodules.save()?;
+        self.config.set(&format!("submodule.{}.url", name), &source)?;
+        self.config.set(&format!("submodule.{}.active", name), "true")?;
+        self.config.save()?;
+        self.stage(vec![PathBuf::from(".gitmodules"), path])
+    }
+
+    // Copies the url of the submodules from `.gitmodules` to the configuration, which is what
+    // makes `update` check them out.
+    pub fn submodule_init(&mut self, paths: &[PathBuf]) -> Result<()> {
+        for submodule in self.submodules(paths)? {
+            let key = format!("submodule.{}.url", submodule.name);
+            if self.config.get(&key).is_some() {
+                continue;
+            }
+            let url = self.submodule_url(&submodule.url)?;
+            self.config.set(&key, &url)?;
+            self.config.set(&format!("submodule.{}.active", submodule.name), "true")?;
+            eprintln!(
+                "Submodule '{}' ({}) registered for path '{}'",
+                submodule.name,
+                url,
+                self.repository.display_path(&submodule.path.display().to_string())
+            );
+        }
+        self.config.save()
+    }
+
+    // Checks out in every initialized submodule the commit recorded in the index, with a
+    // detached HEAD, cloning the submodule first when needed. A submodule whose working tree
+    // was removed is checked out again from its git directory. The source is fetched again
+    // when the commit is missing.
+    pub fn submodule_update(&mut self, paths: &[PathBuf], init: bool) -> Result<()> {
+        if init {
+            self.submodule_init(paths)?;
+        }
+        self.load_index()?;
+        let index = self.index_tree_list()?;
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        for submodule in self.submodules(paths)? {
+            let url = match self.config.get(&format!("submodule.{}.url", submodule.name)) {
+                Some(url) => url,
+                None => continue,
+            };
+            let oid = match index.get(&submodule.path) {
+                Some(entry) if entry.is_gitlink() => util::encode_vec(&entry.sha1_hash),
+                _ => continue,
+            };
+            let full_path = work_tree.join(&submodule.path);
+            let git_dir = self.repository.git_dir.join("modules").join(&submodule.name);
+            let (mut checkout, cloned) = if util::is_nested_repository(&full_path) {
+                (Command::from_repository(Repository::open(&full_path)?)?, false)
+            } else if git_dir.exists() {
+                link_submodule(&git_dir, &full_path)?;
+                (Command::from_repository(Repository::open(&full_path)?)?, true)
+            } else {
+                (self.clone_submodule(&submodule.name, &submodule.path, &url)?, true)
+            };
+            if !cloned && checkout.refs.read_head().as_deref() == Some(oid.as_str()) {
+                continue;
+            }
+            if !checkout.db.exists(&oid) {
+                let mut source = Command::from_repository(Repository::open(Path::new(&url))?)?;
+                checkout.fetch_submodule(&mut source)?;
+            }
+            let display = self.repository.display_path(&submodule.path.display().to_string());
+            if !checkout.db.exists(&oid) {
+                return Err(anyhow!("Unable to find current revision {} in submodule path '{}'", oid, display));
+            }
+            checkout.refs.update_ref(HEAD, &oid)?;
+            checkout.reset_to(None)?;
+            println!("Submodule path '{}': checked out '{}'", display, oid);
+        }
+        Ok(())
+    }
+
+    // One line per submodule: the commit checked out, prefixed with `-` when the submodule is
+    // not checked out and with `+` when it is not the recorded one, the path and the ref at
+    // that commit, if any.
+    pub fn submodule_status(&mut self, paths: &[PathBuf]) -> Result<()> {
+        self.load_index()?;
+        let index = self.index_tree_list()?;
+        let work_tree = self.workspace()?.pathname.to_path_buf();
+        for submodule in self.submodules(paths)? {
+            let recorded = match index.get(&submodule.path) {
+                Some(entry) if entry.is_gitlink() => util::encode_vec(&entry.sha1_hash),
+                _ => continue,
+            };
+            let full_path = work_tree.join(&submodule.path);
+            let display = self.repository.display_path(&submodule.path.display().to_string());
+            let head = match repository::checked_out_commit(&full_path) {
+                Some(head) => head,
+                None => {
+                    println!("-{} {}", recorded, display);
+                    continue;
+                }
+            };
+            let prefix = if head == recorded { ' ' } else { '+' };
+            let checkout = Command::from_repository(Repository::open(&full_path)?)?;
+            let describe = checkout
+                .refs
+                .list_refs()?
+                .into_iter()
+                .find(|(_, oid)| *oid == head)
+                .map(|(name, _)| format!(" ({})", name.strip_prefix("refs/").unwrap_or(&name)))
+                .unwrap_or_default();
+            println!("{}{} {}{}", prefix, head, display, describe);
+        }
+        Ok(())
+    }
+
+    // Clones the local repository `url` for the submodule `name` at `path`. As in git, its git
+    // directory is `modules/<name>` in the one of the superproject, of the worktree here so
+    // that each worktree has its own, and the working tree has a `.git` file pointing to it.
+    // HEAD is left on the branch the source has checked out, without checking it out yet.
+    fn clone_submodule(&mut self, name: &str, path: &Path, url: &str) -> Result<Command> {
+        let mut source = Command::from_repository(Repository::open(Path::new(url))?)?;
+        let work_tree = self.workspace()?.pathname.join(path);
+        let git_dir = self.repository.git_dir.join("modules").join(name);
+        if git_dir.exists() {
+            return Err(anyhow!("a git directory for '{}' is found locally", name));
+        }
+        eprintln!("Cloning into '{}'...", work_tree.display());
+        for dir in ["objects", "refs/heads", "refs/tags"] {
+            fs::create_dir_all(git_dir.join(dir))?;
+        }
+        fs::write(git_dir.join(HEAD), "ref: refs/heads/master\n")?;
+
+        let mut config = Config::new(&git_dir.join("config"));
+        let object_format = source.db.hash_algorithm();
+        if object_format == HashAlgorithm::Sha1 {
+            config.set("core.repositoryformatversion", "0")?;
+        } else {
+            config.set("core.repositoryformatversion", "1")?;
+            config.set("extensions.objectformat", object_format.name())?;
+        }
+        config.set("core.filemode", "true")?;
+        config.set("core.bare", "false")?;
+        config.set("remote.origin.url", url)?;
+        config.set("remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*")?;
+        config.save()?;
+        link_submodule(&git_dir, &work_tree)?;
+
+        let mut submodule = Command::from_repository(Repository::open(&work_tree)?)?;
+        submodule.fetch_submodule(&mut source)?;
+        if let Some(branch) = source.refs.current_ref() {
+            if let Some(oid) = source.refs.resolve(&branch) {
+                submodule.refs.update_ref(&branch, &oid)?;
+                submodule.refs.set_symbolic_ref(HEAD, &branch)?;
+                let short = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+                submodule.config.set(&format!("branch.{}.remote", short), "origin")?;
+                submodule.config.set(&format!("branch.{}.merge", short), &branch)?;
+                submodule.config.save()?;
+            }
+        }
+        Ok(submodule)
+    }
+
+    // copies the branches of `source` as the remote-tracking branches of `origin`.
+    fn fetch_submodule(&self, source: &mut Command) -> Result<()> {
+        for (name, oid) in source.refs.list_refs()? {
+            if let Some(branch) = name.strip_prefix("refs/heads/") {
+                source.send_objects(&oid, self)?;
+                self.refs.update_ref(&format!("refs/remotes/origin/{}", branch), &oid)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Links the working tree of a submodule at `work_tree` and its git directory `git_dir`: the
+// `.git` file of the working tree points to the git directory and `core.worktree` back.
+fn link_submodule(git_dir: &Path, work_tree: &Path) -> Result<()> {
+    fs::create_dir_all(work_tree)?;
+    let gitfile = util::relative_path(git_dir, work_tree);
+    fs::write(work_tree.join(".git"), format!("gitdir: {}\n", gitfile.display()))?;
+    let mut config = Config::new(&git_dir.join("config"));
+    config.load()?;
+    config.set("core.worktree", &util::relative_path(work_tree, git_dir).display().to_string())?;
+    config.save()
+}
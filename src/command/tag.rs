@@ -0,0 +1,67 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use chrono::Local;
+
+use crate::{signing, util, Author, Object, Revision, Tag};
+
+use super::Command;
+
+impl Command {
+    pub fn tag_list(&self) -> Result<()> {
+        for (name, _) in self.refs.list_refs()? {
+            if let Some(tag) = name.strip_prefix("refs/tags/") {
+                println!("{}", tag);
+            }
+        }
+        Ok(())
+    }
+
+    // Creates the tag `name` on `target`, HEAD by default. A tag with a message is an annotated
+    // tag object made by `tagger`, which `sign` signs.
+    pub fn tag_create(
+        &mut self,
+        name: &str,
+        target: Option<&str>,
+        message: Option<&str>,
+        sign: bool,
+        tagger: (&str, &str),
+        force: bool,
+    ) -> Result<()> {
+        let ref_name = format!("refs/tags/{}", name);
+        if !force && self.refs.resolve(&ref_name).is_some() {
+            return Err(anyhow!("tag '{}' already exists", name));
+        }
+        let oid = Revision::new(&self.refs, &mut self.db).resolve(target.unwrap_or("HEAD"))?;
+        let message = match message {
+            Some(message) => message,
+            None if sign => return Err(anyhow!("a message is needed to sign the tag, use -m")),
+            None => return self.refs.update_ref(&ref_name, &oid),
+        };
+        let object_type = self.db.open_object(&oid)?.type_.to_string();
+        let message = if message.ends_with('\n') {
+            message.to_string()
+        } else {
+            format!("{}\n", message)
+        };
+        let tagger = Author::new(tagger.0, tagger.1, Local::now());
+        let hash = self.db.hash_algorithm();
+        let mut tag = Tag::new(&oid, &object_type, name, tagger, &message, hash)?;
+        if sign {
+            let signature = signing::sign(&self.config, &tag.payload())?;
+            tag.set_signature(signature, hash)?;
+        }
+        self.db.store(&mut tag)?;
+        self.refs.update_ref(&ref_name, &util::encode_vec(&tag.get_oid()?))
+    }
+
+    pub fn tag_delete(&mut self, name: &str) -> Result<()> {
+        let ref_name = format!("refs/tags/{}", name);
+        let oid = self
+            .refs
+            .resolve(&ref_name)
+            .ok_or(anyhow!("tag '{}' not found.", name))?;
+        self.refs.delete_ref(&ref_name)?;
+        println!("Deleted tag '{}' (was {})", name, &oid[..7]);
+        Ok(())
+    }
+}
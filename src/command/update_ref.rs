@@ -0,0 +1,49 @@
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::Revision;
+
+use super::Command;
+
+impl Command {
+    // points `name` at the object named by `new_value`. When `old_value` is given the ref is
+    // only updated if it still points to it, an empty or all zero value meaning that the ref
+    // must not exist yet.
+    pub fn update_ref(&mut self, name: &str, new_value: &str, old_value: Option<&str>) -> Result<()> {
+        let name = self.refs.resolve_symbolic(name)?;
+        let oid = Revision::new(&self.refs, &mut self.db).resolve(new_value)?;
+        if !self.db.exists(&oid) {
+            return Err(anyhow!("trying to write ref '{}' with nonexistent object {}", name, oid));
+        }
+        self.check_old_value(&name, old_value)?;
+        self.refs.update_ref(&name, &oid)
+    }
+
+    pub fn delete_ref(&mut self, name: &str, old_value: Option<&str>) -> Result<()> {
+        let name = self.refs.resolve_symbolic(name)?;
+        self.check_old_value(&name, old_value)?;
+        self.refs.delete_ref(&name)
+    }
+
+    fn check_old_value(&mut self, name: &str, old_value: Option<&str>) -> Result<()> {
+        let old_value = match old_value {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let current = self.refs.resolve(name);
+        let expected = if old_value.is_empty() || old_value.chars().all(|c| c == '0') {
+            None
+        } else {
+            Some(Revision::new(&self.refs, &mut self.db).resolve(old_value)?)
+        };
+        if current != expected {
+            return Err(anyhow!(
+                "cannot lock ref '{}': is at {} but expected {}",
+                name,
+                current.as_deref().unwrap_or("nothing"),
+                expected.as_deref().unwrap_or("nothing")
+            ));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+
+use super::Command;
+
+// The branch a local branch tracks and, when it exists, how many commits each has that the
+// other does not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tracking {
+    // the short name of the upstream, like `origin/main`
+    pub name: String,
+    // `None` when the upstream ref does not exist, the upstream is gone
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+// maps `name` through a fetch refspec like `+refs/heads/*:refs/remotes/origin/*`.
+fn map_refspec(refspec: &str, name: &str) -> Option<String> {
+    let (src, dst) = refspec.trim_start_matches('+').split_once(':')?;
+    match (src.split_once('*'), dst.split_once('*')) {
+        (Some((src_prefix, src_suffix)), Some((dst_prefix, dst_suffix))) => {
+            let matched = name.strip_prefix(src_prefix)?.strip_suffix(src_suffix)?;
+            Some(format!("{}{}{}", dst_prefix, matched, dst_suffix))
+        }
+        (None, None) if src == name => Some(dst.to_string()),
+        _ => None,
+    }
+}
+
+impl Command {
+    // The upstream of `branch`, a short name like `main`, from `branch.<name>.remote` and
+    // `branch.<name>.merge`: the ref of the remote the merge ref is fetched into, or a local
+    // branch when the remote is `.`.
+    pub(crate) fn upstream_ref(&self, branch: &str) -> Option<String> {
+        let remote = self.config.get(&format!("branch.{}.remote", branch))?;
+        let merge = self.config.get(&format!("branch.{}.merge", branch))?;
+        if remote == "." {
+            return Some(merge);
+        }
+        let fetched = self
+            .config
+            .get_all(&format!("remote.{}.fetch", remote))
+            .iter()
+            .find_map(|refspec| map_refspec(refspec, &merge));
+        Some(fetched.unwrap_or_else(|| {
+            let name = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+            format!("refs/remotes/{}/{}", remote, name)
+        }))
+    }
+
+    // the upstream of `branch` and how far it is from the branch, if it has an upstream.
+    pub(crate) fn tracking(&mut self, branch: &str) -> Result<Option<Tracking>> {
+        let upstream = match self.upstream_ref(branch) {
+            Some(upstream) => upstream,
+            None => return Ok(None),
+        };
+        let name = ["refs/remotes/", "refs/heads/"]
+            .iter()
+            .find_map(|prefix| upstream.strip_prefix(prefix))
+            .unwrap_or(&upstream)
+            .to_string();
+        let ahead_behind = match (self.refs.resolve(&format!("refs/heads/{}", branch)), self.refs.resolve(&upstream)) {
+            (Some(ours), Some(theirs)) => Some(self.ahead_behind(&ours, &theirs)?),
+            _ => None,
+        };
+        Ok(Some(Tracking { name, ahead_behind }))
+    }
+
+    // The number of commits reachable from `ours` but not from `theirs` and the other way
+    // round, found by walking the history of both through every parent.
+    pub(crate) fn ahead_behind(&mut self, ours: &str, theirs: &str) -> Result<(usize, usize)> {
+        let ours = self.ancestors(ours)?;
+        let theirs = self.ancestors(theirs)?;
+        Ok((ours.difference(&theirs).count(), theirs.difference(&ours).count()))
+    }
+
+    fn ancestors(&mut self, oid: &str) -> Result<HashSet<String>> {
+        let mut seen = HashSet::new();
+        let mut work = vec![oid.to_string()];
+        while let Some(oid) = work.pop() {
+            if !seen.insert(oid.clone()) {
+                continue;
+            }
+            work.extend(self.load_commit(&oid)?.parents().iter().cloned());
+        }
+        Ok(seen)
+    }
+}
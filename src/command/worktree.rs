@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::refs::{RefValue, HEAD};
+use crate::{util, Refs, Repository, Revision};
+
+use super::migration::checkout_order;
+use super::Command;
+
+// A working tree of the repository: the main one, whose git directory is the common
+// directory, or a linked one with an admin directory under `<common dir>/worktrees/<name>`.
+pub struct Worktree {
+    pub path: PathBuf,
+    pub git_dir: PathBuf,
+    pub head: Option<String>,
+    // the branch checked out, `None` when HEAD is detached
+    pub branch: Option<String>,
+    pub bare: bool,
+    // the admin directory of a linked worktree whose directory was deleted
+    pub prunable: bool,
+    pub locked: bool,
+}
+
+impl Worktree {
+    fn load(git_dir: &Path, common_dir: &Path, path: PathBuf, bare: bool) -> Self {
+        let refs = Refs::with_common_dir(git_dir, common_dir);
+        let branch = match refs.read_ref_value(HEAD) {
+            Some(RefValue::Symbolic(target)) => Some(target),
+            _ => None,
+        };
+        Worktree {
+            prunable: !bare && git_dir != common_dir && !path.join(".git").is_file(),
+            locked: git_dir.join("locked").exists(),
+            path,
+            git_dir: git_dir.to_path_buf(),
+            head: refs.read_head(),
+            branch,
+            bare,
+        }
+    }
+}
+
+impl Command {
+    // every worktree of the repository, the main one first.
+    pub fn worktrees(&self) -> Result<Vec<Worktree>> {
+        let common_dir = &self.repository.common_dir;
+        let bare = self.config.get_bool("core.bare") == Some(true);
+        let main_path = if bare {
+            common_dir.to_path_buf()
+        } else if common_dir.file_name() == Some(".git".as_ref()) {
+            common_dir.parent().unwrap_or(common_dir).to_path_buf()
+        } else {
+            self.repository.work_tree.clone().unwrap_or(common_dir.to_path_buf())
+        };
+        let mut worktrees = vec![Worktree::load(common_dir, common_dir, main_path, bare)];
+        let admin_dir = common_dir.join("worktrees");
+        if admin_dir.is_dir() {
+            for git_dir in util::read_dir_sorted(&admin_dir)? {
+                if !git_dir.is_dir() {
+                    continue;
+                }
+                let gitfile = fs::read_to_string(git_dir.join("gitdir")).unwrap_or_default();
+                let path = Path::new(gitfile.trim())
+                    .parent()
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_default();
+                worktrees.push(Worktree::load(&git_dir, common_dir, path, false));
+            }
+        }
+        Ok(worktrees)
+    }
+
+    // Creates a linked worktree at `path` checking out `commitish`, HEAD by default. Without
+    // `new_branch` or `detach`, a branch named after the last component of `path` is checked
+    // out, created from HEAD if it does not exist yet. A branch can only be checked out in
+    // one worktree at a time unless `force` is given.
+    pub fn worktree_add(
+        &mut self,
+        path: &Path,
+        commitish: Option<&str>,
+        new_branch: Option<&str>,
+        detach: bool,
+        force: bool,
+    ) -> Result<()> {
+        let path = util::normalize_path(path);
+        if path.exists() && fs::read_dir(&path)?.next().is_some() {
+            return Err(anyhow!("'{}' already exists", path.display()));
+        }
+        let name = path
+            .file_name()
+            .ok_or(anyhow!("invalid path for a worktree: '{}'", path.display()))?
+            .to_string_lossy()
+            .to_string();
+
+        let local_branch = commitish
+            .map(|c| format!("refs/heads/{}", c))
+            .filter(|r| self.refs.resolve(r).is_some());
+        let (branch, create, description) = match (new_branch, commitish) {
+            (Some(branch), _) => (Some(format!("refs/heads/{}", branch)), true, format!("new branch '{}'", branch)),
+            _ if detach => (None, false, String::new()),
+            (None, Some(c)) => match local_branch {
+                Some(branch) => (Some(branch), false, format!("checking out '{}'", c)),
+                None => (None, false, String::new()),
+            },
+            (None, None) => {
+                let branch = format!("refs/heads/{}", name);
+                match self.refs.resolve(&branch) {
+                    Some(_) => (Some(branch), false, format!("checking out '{}'", name)),
+                    None => (Some(branch), true, format!("new branch '{}'", name)),
+                }
+            }
+        };
+        let commit = Revision::new(&self.refs, &mut self.db).resolve_commit(commitish.unwrap_or(HEAD))?;
+
+        if let Some(branch) = &branch {
+            let short = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+            if create && self.refs.resolve(branch).is_some() {
+                return Err(anyhow!("a branch named '{}' already exists", short));
+            }
+            if !force {
+                let worktrees = self.worktrees()?;
+                if let Some(other) = worktrees.iter().find(|w| !w.bare && w.branch.as_ref() == Some(branch)) {
+                    return Err(anyhow!(
+                        "'{}' is already checked out at '{}'",
+                        short,
+                        other.path.display()
+                    ));
+                }
+            }
+            if create {
+                self.refs.update_ref(branch, &commit)?;
+            }
+        }
+        let description = if description.is_empty() {
+            format!("detached HEAD {}", &commit[..7])
+        } else {
+            description
+        };
+        println!("Preparing worktree ({})", description);
+
+        let git_dir = self.create_admin_dir(&name)?;
+        fs::create_dir_all(&path)?;
+        fs::write(git_dir.join("gitdir"), format!("{}\n", path.join(".git").display()))?;
+        fs::write(git_dir.join("commondir"), "../..\n")?;
+        fs::write(path.join(".git"), format!("gitdir: {}\n", git_dir.display()))?;
+        let refs = Refs::with_common_dir(&git_dir, &self.repository.common_dir);
+        match &branch {
+            Some(branch) => refs.set_symbolic_ref(HEAD, branch)?,
+            None => refs.update_ref(HEAD, &commit)?,
+        }
+
+        let repository = Repository::open(&path)?;
+        let mut worktree = Command::from_repository(repository)?;
+        worktree.populate_work_tree(&commit)?;
+        let zeros = "0".repeat(commit.len());
+        worktree.run_hook("post-checkout", &[&zeros, &commit, "1"], None)?;
+        let title = match worktree.db.load(&commit)? {
+            ObjectType::CommitType { commit } => commit.title_line().to_string(),
+            _ => String::new(),
+        };
+        println!("HEAD is now at {} {}", &commit[..7], title);
+        Ok(())
+    }
+
+    // `<common dir>/worktrees/<name>`, with a numeric suffix if the name is already taken.
+    fn create_admin_dir(&self, name: &str) -> Result<PathBuf> {
+        let admin_dir = self.repository.common_dir.join("worktrees");
+        fs::create_dir_all(&admin_dir)?;
+        let mut candidate = name.to_string();
+        let mut n = 1;
+        loop {
+            let git_dir = admin_dir.join(&candidate);
+            match fs::create_dir(&git_dir) {
+                Ok(()) => return Ok(git_dir),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    candidate = format!("{}{}", name, n);
+                    n += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // writes every file of `commit` into an empty working tree and records them in the index.
+    fn populate_work_tree(&mut self, commit: &str) -> Result<()> {
+        let tree = Revision::new(&self.refs, &mut self.db).peel_to_tree(commit)?;
+        let list = self.db.load_tree_list(&tree)?;
+        for (path, entry) in checkout_order(&list) {
+            let stat = if entry.is_gitlink() {
+                self.workspace()?.write_file(path, &[], &entry.mode)?
+            } else {
+                self.checkout_blob(path, entry)?
+            };
+            let mode = u32::from_str_radix(&entry.mode, 8)?;
+            self.index.add_with_mode(path.to_path_buf(), entry.sha1_hash.clone(), stat, mode)?;
+        }
+        self.index.update_changed_status();
+        self.index.write_updates()
+    }
+
+    pub fn worktree_list(&self, porcelain: bool) -> Result<()> {
+        let worktrees = self.worktrees()?;
+        let width = worktrees
+            .iter()
+            .map(|w| w.path.display().to_string().len())
+            .max()
+            .unwrap_or(0);
+        for worktree in worktrees.iter() {
+            if porcelain {
+                println!("worktree {}", worktree.path.display());
+                if worktree.bare {
+                    println!("bare");
+                } else {
+                    if let Some(head) = &worktree.head {
+                        println!("HEAD {}", head);
+                    }
+                    match &worktree.branch {
+                        Some(branch) => println!("branch {}", branch),
+                        None => println!("detached"),
+                    }
+                }
+                if worktree.locked {
+                    println!("locked");
+                }
+                if worktree.prunable {
+                    println!("prunable");
+                }
+                println!();
+                continue;
+            }
+            let mut line = format!("{:<width$} ", worktree.path.display(), width = width);
+            if worktree.bare {
+                line.push_str("(bare)");
+            } else {
+                let head = worktree.head.as_deref().map_or("0000000", |h| &h[..7]);
+                line.push_str(head);
+                match &worktree.branch {
+                    Some(branch) => {
+                        let short = branch.strip_prefix("refs/heads/").unwrap_or(branch);
+                        line.push_str(&format!(" [{}]", short));
+                    }
+                    None => line.push_str(" (detached HEAD)"),
+                }
+            }
+            if worktree.locked {
+                line.push_str(" locked");
+            }
+            if worktree.prunable {
+                line.push_str(" prunable");
+            }
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    // Deletes a linked worktree and its admin directory. Worktrees with changes, staged or
+    // not, or with untracked files are kept unless `force` is given.
+    pub fn worktree_remove(&self, path: &Path, force: bool) -> Result<()> {
+        let path = util::normalize_path(path);
+        let worktree = self
+            .worktrees()?
+            .into_iter()
+            .skip(1)
+            .find(|w| w.path == path)
+            .ok_or(anyhow!("'{}' is not a working tree", path.display()))?;
+        if worktree.locked && !force {
+            return Err(anyhow!("cannot remove a locked working tree, use --force to override"));
+        }
+        if path.exists() {
+            if !force {
+                let mut command = Command::from_repository(Repository::open(&path)?)?;
+                if !command.is_clean()? {
+                    return Err(anyhow!(
+                        "'{}' contains modified or untracked files, use --force to delete it",
+                        path.display()
+                    ));
+                }
+            }
+            fs::remove_dir_all(&path)?;
+        }
+        fs::remove_dir_all(&worktree.git_dir)?;
+        Ok(())
+    }
+
+    // removes the admin directories of the linked worktrees whose directory no longer exists.
+    pub fn worktree_prune(&self) -> Result<()> {
+        for worktree in self.worktrees()?.into_iter().filter(|w| w.prunable && !w.locked) {
+            let name = worktree.git_dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+            println!("Removing worktrees/{}: gitdir file points to non-existent location", name);
+            fs::remove_dir_all(&worktree.git_dir)?;
+        }
+        let admin_dir = self.repository.common_dir.join("worktrees");
+        if admin_dir.is_dir() && fs::read_dir(&admin_dir)?.next().is_none() {
+            fs::remove_dir(&admin_dir)?;
+        }
+        Ok(())
+    }
+
+    // whether the index matches HEAD and the working tree has neither changes nor untracked
+    // files.
+    fn is_clean(&mut self) -> Result<bool> {
+        let workspace = self.workspace()?;
+        let work_tree = workspace.pathname.to_path_buf();
+        let files = workspace.list_files()?;
+        if self.repository.git_dir.join("index").exists() {
+            self.index.load()?;
+        }
+        let head = match self.refs.read_head() {
+            Some(head) => {
+                let tree = Revision::new(&self.refs, &mut self.db).peel_to_tree(&head)?;
+                self.db.load_tree_list(&tree)?
+            }
+            None => Default::default(),
+        };
+        let entries = self
+            .index
+            .each_entry()?
+            .iter()
+            .map(|e| (e.path.to_path_buf(), e.oid.to_vec()))
+            .collect::<Vec<_>>();
+        if entries.len() != head.len() || files.len() != entries.len() {
+            return Ok(false);
+        }
+        for (path, oid) in entries.iter() {
+            if head.get(path).map(|e| &e.sha1_hash) != Some(oid) {
+                return Ok(false);
+            }
+            let full_path = work_tree.join(path);
+            if !util::stat_file(&full_path).is_ok_and(|stat| util::is_blob(&stat)) || self.db.hash_file(&full_path)? != *oid {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
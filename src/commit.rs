@@ -1,15 +1,21 @@
-use std::{collections::HashMap, io::{BufRead, Cursor, Read}};
+use std::io::{BufRead, Cursor, Read};
 
+use anyhow::anyhow;
 use anyhow::Result;
 
-use crate::{util, Author, Object};
+use crate::{util, Author, HashAlgorithm, Object};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Commit {
     pub tree_ref: String,
     author: Author,
-    parent: Option<String>,
+    // who made the commit, the author unless the commit was amended or rewritten
+    committer: Author,
+    // merge commits, like the ones made by `stash`, have more than one parent
+    parents: Vec<String>,
     message: String,
+    // the armored signature of the `gpgsig` header
+    signature: Option<String>,
     type_: String,
     oid: Vec<u8>,
 }
@@ -33,68 +39,168 @@ impl Commit {
         tree_ref: String, 
         author: Author, 
         message: String, 
-        parent: Option<String>, 
-        oid: Option<Vec<u8>>
+        parents: Vec<String>, 
+        oid: Option<Vec<u8>>,
+        hash: HashAlgorithm
         ) -> Result<Commit> {
-        let digest = oid.unwrap_or({
-            let data_to_write = get_data_to_write(tree_ref.as_str(), &author, message.as_str(), &parent)?;
-            util::hexdigest_vec(&data_to_write)
-        });
+        let digest = match oid {
+            Some(oid) => oid,
+            None => {
+                let data_to_write =
+                    get_data_to_write(tree_ref.as_str(), &author, &author, message.as_str(), &parents, None)?;
+                util::hexdigest_vec(&data_to_write, hash)
+            }
+        };
         Ok(Commit {
             tree_ref,
+            committer: author.clone(),
             author,
-            parent,
+            parents,
             message,
+            signature: None,
             type_: "commit".to_string(),
             oid: digest,
         })
     }
 
 
-    pub fn parse(cursor: &mut Cursor<Vec<u8>>, oid: &str) -> Result<Self> {
-        let mut headers: HashMap<String, String> = HashMap::new();
+    pub fn parse(cursor: &mut Cursor<Vec<u8>>, oid: &str, hash: HashAlgorithm) -> Result<Self> {
+        let mut headers: Vec<(String, String)> = Vec::new();
         loop {
             let mut line = vec![];
-            let _num_read = cursor.read_until(b'\n', &mut line)?;
-
-            // if line.len() == 1 {
-            //     for l in &line {
-            //         println!("line: {:X}", l)
-            //     }
-            //     println!("new_line: {:?}", "\n".as_bytes().to_owned()[0]);
-            //     println!("equal {:?}", line == vec![0b1010])
-            // }
-            if line == vec![0b1010] {
+            let num_read = cursor.read_until(b'\n', &mut line)?;
+            if num_read == 0 || line == vec![0b1010] {
                 break;
             }
-            let elements: Vec<_> = line.strip_suffix("\n".as_bytes()).unwrap().splitn(2, |i| *i == 32).collect();
-            let key = String::from_utf8(elements[0].to_vec())?;
-            let value = String::from_utf8(elements[1].to_vec())?;
-            headers.insert(key,value);
+            let line = String::from_utf8(line)?;
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            // a header value spanning several lines continues on lines starting with a space
+            if let Some(continuation) = line.strip_prefix(' ') {
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push('\n');
+                    value.push_str(continuation);
+                }
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            headers.push((key.to_string(), value.to_string()));
         }
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.to_string())
+                .ok_or(anyhow!("commit {} has no {} header", oid, name))
+        };
         let mut message = vec![];
         cursor.read_to_end(&mut message)?;
         let message = String::from_utf8(message)?;
-        let author = Author::parse(headers.get("author").unwrap())?;
-        let tree = headers.remove("tree").unwrap();
-        let commit = Commit::new(tree, author, message, headers.get("parent").map(|e| e.to_string()), Some(oid.as_bytes().to_owned()))?;
+        let author = Author::parse(&header("author")?)?;
+        let tree = header("tree")?;
+        let parents = headers
+            .iter()
+            .filter(|(key, _)| key == "parent")
+            .map(|(_, value)| value.to_string())
+            .collect();
+        let mut commit = Commit::new(tree, author, message, parents, Some(oid.as_bytes().to_owned()), hash)?;
+        // the commits of older versions name the committer `commiter`
+        if let Ok(committer) = header("committer").or_else(|_| header("commiter")) {
+            commit.committer = Author::parse(&committer)?;
+        }
+        commit.signature = header("gpgsig").ok();
         Ok(commit)
     }
 
+    // what is signed: the content of the commit without its signature.
+    pub fn payload(&self) -> Result<Vec<u8>> {
+        let data = get_data_to_write(
+            &self.tree_ref,
+            &self.author,
+            &self.committer,
+            &self.message,
+            &self.parents,
+            None,
+        )?;
+        let start = data.iter().position(|b| *b == 0).map_or(0, |i| i + 1);
+        Ok(data[start..].to_vec())
+    }
+
+    // adds the signature of the payload as a `gpgsig` header, which changes the oid.
+    pub fn set_signature(&mut self, signature: String, hash: HashAlgorithm) -> Result<()> {
+        self.signature = Some(signature);
+        self.oid = util::hexdigest_vec(&self.get_data_to_write()?, hash);
+        Ok(())
+    }
+
+    // sets who made the commit when it is not its author, which changes the oid.
+    pub fn set_committer(&mut self, committer: Author, hash: HashAlgorithm) -> Result<()> {
+        self.committer = committer;
+        self.oid = util::hexdigest_vec(&self.get_data_to_write()?, hash);
+        Ok(())
+    }
+
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+
+    pub fn author(&self) -> &Author {
+        &self.author
+    }
+
+    pub fn committer(&self) -> &Author {
+        &self.committer
+    }
+
+    // the first parent, the commit this one was made on top of.
+    pub fn parent(&self) -> Option<&String> {
+        self.parents.first()
+    }
+
+    pub fn parents(&self) -> &[String] {
+        &self.parents
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    // the first line of the message
+    pub fn title_line(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
     fn get_data_to_write(&self) -> Result<Vec<u8>> {
-        let data = get_data_to_write(&self.tree_ref, &self.author, self.message.as_str(), &self.parent)?;
+        let data = get_data_to_write(
+            &self.tree_ref,
+            &self.author,
+            &self.committer,
+            self.message.as_str(),
+            &self.parents,
+            self.signature.as_deref(),
+        )?;
         Ok(data)
     }
 }
 
-fn get_data_to_write(tree_ref: &str, author: &Author, message: &str, parent: &Option<String>) -> Result<Vec<u8>> {
+fn get_data_to_write(
+    tree_ref: &str,
+    author: &Author,
+    committer: &Author,
+    message: &str,
+    parents: &[String],
+    signature: Option<&str>,
+) -> Result<Vec<u8>> {
     let mut lines = Vec::new();
     lines.push(format!("tree {}", tree_ref));
-    parent
-        .as_ref()
-        .map(|e| lines.push(format!("parent {}", e)));
+    for parent in parents {
+        lines.push(format!("parent {}", parent));
+    }
     lines.push(format!("author {}", author.to_s()));
-    lines.push(format!("commiter {}", author.to_s()));
+    lines.push(format!("committer {}", committer.to_s()));
+    // the lines of the signature after the first one continue the header
+    if let Some(signature) = signature {
+        lines.push(format!("gpgsig {}", signature.trim_end().replace('\n', "\n ")));
+    }
     lines.push("".to_string());
     lines.push(message.to_string());
     let data_to_write = lines.join("\n").as_bytes().to_vec();
@@ -110,3 +216,32 @@ fn get_data_to_write(tree_ref: &str, author: &Author, message: &str, parent: &Op
     data.extend(&data_to_write);
     Ok(data)
 }
+
+// Splits the raw content of a signed commit in the payload that was signed, the content
+// without its `gpgsig` header, and the signature. `None` when the commit is not signed.
+pub fn split_signature(content: &[u8]) -> Option<(Vec<u8>, String)> {
+    let mut payload = Vec::new();
+    let mut signature: Option<String> = None;
+    let mut in_signature = false;
+    let mut rest = content;
+    while !rest.is_empty() {
+        let end = rest.iter().position(|b| *b == b'\n').map_or(rest.len(), |i| i + 1);
+        let (line, next) = rest.split_at(end);
+        rest = next;
+        if line == b"\n" {
+            payload.extend_from_slice(line);
+            payload.extend_from_slice(rest);
+            break;
+        }
+        if let Some(value) = line.strip_prefix(b"gpgsig ") {
+            signature = Some(String::from_utf8_lossy(value).to_string());
+            in_signature = true;
+        } else if let (true, Some(value)) = (in_signature, line.strip_prefix(b" ")) {
+            signature.get_or_insert_with(String::new).push_str(&String::from_utf8_lossy(value));
+        } else {
+            in_signature = false;
+            payload.extend_from_slice(line);
+        }
+    }
+    signature.map(|signature| (payload, signature))
+}
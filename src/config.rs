@@ -0,0 +1,236 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+// Reader and writer for git's ini-like configuration files. Keys are addressed with the dotted
+// form used by `git config`, e.g. `core.bare` or `branch.main.remote`.
+pub struct Config {
+    pathname: PathBuf,
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    name: String,
+    subsection: Option<String>,
+    entries: Vec<(String, String)>,
+}
+
+impl Section {
+    fn matches(&self, name: &str, subsection: &Option<String>) -> bool {
+        self.name == name && &self.subsection == subsection
+    }
+}
+
+// section and variable names are case insensitive, subsection names are not.
+fn split_key(key: &str) -> Result<(String, Option<String>, String)> {
+    let first = key.find('.').ok_or(anyhow!("key does not contain a section: {}", key))?;
+    let last = key.rfind('.').unwrap();
+    let section = key[..first].to_lowercase();
+    let name = key[last + 1..].to_lowercase();
+    let subsection = if first == last {
+        None
+    } else {
+        Some(key[first + 1..last].to_string())
+    };
+    if section.is_empty() || name.is_empty() {
+        return Err(anyhow!("invalid key: {}", key));
+    }
+    Ok((section, subsection, name))
+}
+
+fn parse_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => value.push(other),
+                None => {}
+            },
+            '#' | ';' if !quoted => break,
+            _ => value.push(c),
+        }
+    }
+    value.trim_end().to_string()
+}
+
+fn format_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    let needs_quotes = escaped.starts_with(' ')
+        || escaped.ends_with(' ')
+        || escaped.contains('#')
+        || escaped.contains(';');
+    if needs_quotes {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+impl Config {
+    pub fn new(pathname: &Path) -> Self {
+        Config {
+            pathname: pathname.to_path_buf(),
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        self.sections = Vec::new();
+        if !self.pathname.exists() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(&self.pathname)?;
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let end = line
+                    .find(']')
+                    .ok_or(anyhow!("bad config line {} in file {:?}", n + 1, self.pathname))?;
+                let header = &line[1..end];
+                let (name, subsection) = match header.find([' ', '"']) {
+                    Some(i) => {
+                        let sub = header[i..].trim().trim_matches('"').replace("\\\"", "\"");
+                        (header[..i].to_lowercase(), Some(sub))
+                    }
+                    // legacy `[section.subsection]` syntax
+                    None => match header.split_once('.') {
+                        Some((name, sub)) => (name.to_lowercase(), Some(sub.to_lowercase())),
+                        None => (header.to_lowercase(), None),
+                    },
+                };
+                self.sections.push(Section {
+                    name,
+                    subsection,
+                    entries: Vec::new(),
+                });
+                continue;
+            }
+            let section = self
+                .sections
+                .last_mut()
+                .ok_or(anyhow!("bad config line {} in file {:?}", n + 1, self.pathname))?;
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim().to_lowercase(), parse_value(value)),
+                // a variable without a value is a boolean true
+                None => (line.to_lowercase(), "true".to_string()),
+            };
+            section.entries.push((key, value));
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let mut content = String::new();
+        for section in self.sections.iter() {
+            match &section.subsection {
+                Some(sub) => content.push_str(&format!(
+                    "[{} \"{}\"]\n",
+                    section.name,
+                    sub.replace('"', "\\\"")
+                )),
+                None => content.push_str(&format!("[{}]\n", section.name)),
+            }
+            for (key, value) in section.entries.iter() {
+                content.push_str(&format!("\t{} = {}\n", key, format_value(value)));
+            }
+        }
+        if let Some(parent) = self.pathname.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.pathname, content)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.get_all(key).pop()
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        let (section, subsection, name) = match split_key(key) {
+            Ok(parts) => parts,
+            Err(_) => return vec![],
+        };
+        self.sections
+            .iter()
+            .filter(|s| s.matches(&section, &subsection))
+            .flat_map(|s| s.entries.iter())
+            .filter(|(k, _)| *k == name)
+            .map(|(_, v)| v.to_string())
+            .collect()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.get(key).map(|value| {
+            matches!(value.to_lowercase().as_str(), "true" | "yes" | "on" | "1")
+        })
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get(key).and_then(|value| value.parse::<i64>().ok())
+    }
+
+    // replaces every existing value of the key, creating the section if needed.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.unset(key)?;
+        self.add(key, value)
+    }
+
+    // appends a value for multi-valued keys such as `remote.origin.fetch`.
+    pub fn add(&mut self, key: &str, value: &str) -> Result<()> {
+        let (section, subsection, name) = split_key(key)?;
+        let position = self.sections.iter().rposition(|s| s.matches(&section, &subsection));
+        let entry = (name, value.to_string());
+        match position {
+            Some(i) => self.sections[i].entries.push(entry),
+            None => self.sections.push(Section {
+                name: section,
+                subsection,
+                entries: vec![entry],
+            }),
+        }
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<()> {
+        let (section, subsection, name) = split_key(key)?;
+        for s in self.sections.iter_mut().filter(|s| s.matches(&section, &subsection)) {
+            s.entries.retain(|(k, _)| *k != name);
+        }
+        Ok(())
+    }
+
+    pub fn remove_section(&mut self, section: &str, subsection: Option<&str>) {
+        let section = section.to_lowercase();
+        let subsection = subsection.map(|s| s.to_string());
+        self.sections.retain(|s| !s.matches(&section, &subsection));
+    }
+
+    // names of every subsection of a section, e.g. the branches configured under `branch`.
+    pub fn subsections(&self, section: &str) -> Vec<String> {
+        let section = section.to_lowercase();
+        let mut names: Vec<String> = Vec::new();
+        for s in self.sections.iter().filter(|s| s.name == section) {
+            if let Some(sub) = &s.subsection {
+                if !names.contains(sub) {
+                    names.push(sub.to_string());
+                }
+            }
+        }
+        names
+    }
+}
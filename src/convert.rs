@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::attributes::{AttributeState, Attributes};
+use crate::filter::{self, FilterCommand, FilterDriver, FilterProcess};
+use crate::lfs::LfsStore;
+use crate::Config;
+
+// How the line endings of a file are normalized when it is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TextAction {
+    // stored as it is
+    None,
+    // CRLF is stored as LF
+    Text,
+    // like `Text` unless the content looks binary
+    Auto,
+}
+
+// Converts the content of the files of a working tree between their form in the working
+// tree and the one stored in the database, following their attributes and `core.autocrlf`
+// and `core.eol`. A file with a `filter` attribute also goes through the driver it names,
+// before the line endings are normalized and after they are converted back.
+pub struct Converter {
+    work_tree: PathBuf,
+    attributes: Attributes,
+    autocrlf: Option<String>,
+    eol: Option<String>,
+    filters: HashMap<String, FilterDriver>,
+    // the long-running filters started so far, by the name of their driver
+    processes: Mutex<HashMap<String, FilterProcess>>,
+    lfs: LfsStore,
+}
+
+impl Converter {
+    pub fn new(work_tree: &Path, git_dir: &Path, config: &Config) -> Self {
+        Converter {
+            work_tree: work_tree.to_path_buf(),
+            attributes: Attributes::new(work_tree, git_dir),
+            autocrlf: config.get("core.autocrlf").map(|value| value.to_lowercase()),
+            eol: config.get("core.eol").map(|value| value.to_lowercase()),
+            filters: FilterDriver::from_config(config),
+            processes: Mutex::new(HashMap::new()),
+            lfs: LfsStore::new(git_dir),
+        }
+    }
+
+    pub fn clear_cache(&self) {
+        self.attributes.clear_cache();
+    }
+
+    // the path relative to the top of the working tree, absolute ones outside of it have no
+    // attributes.
+    fn relative<'a>(&self, path: &'a Path) -> Option<&'a Path> {
+        if path.is_absolute() {
+            path.strip_prefix(&self.work_tree).ok()
+        } else {
+            Some(path)
+        }
+    }
+
+    fn attributes(&self, path: &Path) -> HashMap<String, AttributeState> {
+        match self.relative(path) {
+            Some(path) => self.attributes.get(path),
+            None => HashMap::new(),
+        }
+    }
+
+    // the value of the attribute `name` of the file at `path`.
+    pub fn attribute(&self, path: &Path, name: &str) -> AttributeState {
+        self.attributes(path).remove(name).unwrap_or(AttributeState::Unspecified)
+    }
+
+    // What `text` and `eol` ask for, with `core.autocrlf` for the files without them, and
+    // whether LF becomes CRLF in the working tree.
+    fn text_action(&self, path: &Path) -> (TextAction, bool) {
+        let attributes = self.attributes(path);
+        let eol = match attributes.get("eol") {
+            Some(AttributeState::Value(eol)) => Some(eol.as_str()),
+            _ => None,
+        };
+        let autocrlf = self.autocrlf.as_deref();
+        let action = match attributes.get("text") {
+            Some(AttributeState::Set) => TextAction::Text,
+            Some(AttributeState::Unset) => TextAction::None,
+            Some(AttributeState::Value(value)) if value == "auto" => TextAction::Auto,
+            _ if eol.is_some() => TextAction::Text,
+            _ if matches!(autocrlf, Some("true") | Some("input")) => TextAction::Auto,
+            _ => TextAction::None,
+        };
+        let crlf = match (eol, autocrlf) {
+            (Some(eol), _) => eol == "crlf",
+            (None, Some("true")) => true,
+            (None, Some("input")) => false,
+            (None, _) => self.eol.as_deref() == Some("crlf"),
+        };
+        (action, crlf && action != TextAction::None)
+    }
+
+    // the name of the filter driver of the file at `path`, `lfs` is built in when it is not
+    // configured.
+    fn filter(&self, path: &Path) -> Option<String> {
+        match self.attribute(path, "filter") {
+            AttributeState::Value(name) if self.filters.contains_key(&name) || name == "lfs" => Some(name),
+            _ => None,
+        }
+    }
+
+    // whether `to_git` may change the content of the file at `path`, otherwise it can be
+    // stored as it is read.
+    pub fn converts_to_git(&self, path: &Path) -> bool {
+        self.text_action(path).0 != TextAction::None || self.filter(path).is_some()
+    }
+
+    // whether the content of the file at `path` changes when it is written out.
+    pub fn converts_to_worktree(&self, path: &Path) -> bool {
+        self.text_action(path).1 || self.filter(path).is_some()
+    }
+
+    // the content of the file at `path` as it is stored.
+    pub fn to_git(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>> {
+        let data = self.apply_filter(path, FilterCommand::Clean, data)?;
+        Ok(match self.text_action(path).0 {
+            TextAction::None => data,
+            TextAction::Auto if is_binary(&data) => data,
+            _ => crlf_to_lf(&data),
+        })
+    }
+
+    // the content of a blob as it is written to the file at `path`.
+    pub fn to_worktree(&self, path: &Path, data: Vec<u8>) -> Result<Vec<u8>> {
+        let data = match self.text_action(path) {
+            (TextAction::Auto, true) if is_binary(&data) || data.windows(2).any(|w| w == b"\r\n") => data,
+            (_, true) => lf_to_crlf(&data),
+            _ => data,
+        };
+        self.apply_filter(path, FilterCommand::Smudge, data)
+    }
+
+    // Runs the filter driver of the file at `path` on `data`. Without a command for this way
+    // the content is left as it is, as it is when the command fails unless the driver is
+    // required.
+    fn apply_filter(&self, path: &Path, command: FilterCommand, data: Vec<u8>) -> Result<Vec<u8>> {
+        let (name, relative) = match (self.filter(path), self.relative(path)) {
+            (Some(name), Some(relative)) => (name, relative.to_string_lossy().to_string()),
+            _ => return Ok(data),
+        };
+        let driver = match self.filters.get(&name) {
+            Some(driver) => driver,
+            None => {
+                return match command {
+                    FilterCommand::Clean => self.lfs.clean(data),
+                    FilterCommand::Smudge => self.lfs.smudge(data),
+                }
+            }
+        };
+        let filtered = match (&driver.process, driver.command(command)) {
+            (Some(process), _) => self.run_process(&name, process, command, &relative, &data),
+            (None, Some(run)) => filter::run_command(run, &self.work_tree, &relative, &data).map(Some),
+            (None, None) => Ok(None),
+        };
+        match filtered {
+            Ok(Some(filtered)) => Ok(filtered),
+            Ok(None) if !driver.required => Ok(data),
+            Err(e) if !driver.required => {
+                eprintln!("error: {}", e);
+                Ok(data)
+            }
+            _ => Err(anyhow!("{}: {} filter '{}' failed", relative, command.name(), name)),
+        }
+    }
+
+    // Filters `data` with the long-running filter of the driver `name`, started the first time
+    // it is needed. `None` when the filter does not support `command`.
+    fn run_process(
+        &self,
+        name: &str,
+        process: &str,
+        command: FilterCommand,
+        path: &str,
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        let mut processes = self.processes.lock().unwrap();
+        if !processes.contains_key(name) {
+            processes.insert(name.to_string(), FilterProcess::start(process, &self.work_tree)?);
+        }
+        let filter = match processes.get_mut(name) {
+            Some(filter) if filter.supports(command) => filter,
+            _ => return Ok(None),
+        };
+        match filter.filter(command, path, data) {
+            Ok(filtered) => Ok(Some(filtered)),
+            Err(e) => {
+                // a filter which stopped talking the protocol is started again next time
+                if e.downcast_ref::<std::io::Error>().is_some() {
+                    processes.remove(name);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // Whether `diff` shows the file as binary: `-diff` makes it so and `diff` shows it as
+    // text, without them it depends on the content.
+    pub fn is_binary_diff(&self, path: &Path, data: &[u8]) -> bool {
+        match self.attribute(path, "diff") {
+            AttributeState::Unset => true,
+            AttributeState::Set => false,
+            _ => is_binary(data),
+        }
+    }
+}
+
+// like git, content with a NUL byte in its first 8000 bytes is binary.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.iter().take(8000).any(|b| *b == 0)
+}
+
+fn crlf_to_lf(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, b) in data.iter().enumerate() {
+        if *b == b'\r' && data.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        out.push(*b);
+    }
+    out
+}
+
+fn lf_to_crlf(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, b) in data.iter().enumerate() {
+        if *b == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(*b);
+    }
+    out
+}
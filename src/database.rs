@@ -2,35 +2,154 @@ use anyhow::Result;
 use anyhow::anyhow;
 use libflate::zlib::{Decoder, Encoder};
 use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::{BufRead, Cursor, Read};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::thread;
 use std::{
     fs,
     io::{self, Write},
     path::PathBuf,
 };
 
-use crate::{Blob, Commit, Object, Tree, util};
+use crate::convert::Converter;
+use crate::tree::TreeList;
+use crate::{Blob, Commit, HashAlgorithm, Object, Tag, Tree, util};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn blob_header(size: u64) -> Vec<u8> {
+    format!("blob {}\0", size).into_bytes()
+}
+
+fn copy_chunks<R, F>(reader: &mut R, mut f: F) -> Result<u64>
+where
+    R: Read,
+    F: FnMut(&[u8]) -> io::Result<()>,
+{
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        f(&buf[..n])?;
+        total += n as u64;
+    }
+}
+
+// computes the oid a file would have as a blob without storing it, the blob of a symbolic
+// link holds its target.
+pub fn hash_file(path: &Path, hash: HashAlgorithm) -> Result<Vec<u8>> {
+    if let Some(target) = util::read_link(path)? {
+        let mut context = hash.context();
+        context.update(&blob_header(target.len() as u64));
+        context.update(&target);
+        return Ok(context.finish().as_ref().to_vec());
+    }
+    let mut file = File::open(path).map_err(|_| anyhow!("open ('{:?}'): Permission denied", path))?;
+    let size = file.metadata()?.len();
+    let mut context = hash.context();
+    context.update(&blob_header(size));
+    let copied = copy_chunks(&mut file, |chunk| {
+        context.update(chunk);
+        Ok(())
+    })?;
+    if copied != size {
+        return Err(anyhow!("file {:?} changed while it was being hashed", path));
+    }
+    Ok(context.finish().as_ref().to_vec())
+}
+
+// Hashes a file of the working tree as it is stored, after the conversions `converter` asks
+// for, if any.
+pub fn hash_worktree_file(path: &Path, hash: HashAlgorithm, converter: Option<&Converter>) -> Result<Vec<u8>> {
+    match converter.filter(|converter| converter.converts_to_git(path)) {
+        Some(converter) if !fs::symlink_metadata(path)?.file_type().is_symlink() => {
+            let data = converter.to_git(path, fs::read(path)?)?;
+            Blob::new(data, hash)?.get_oid()
+        }
+        _ => hash_file(path, hash),
+    }
+}
 
 pub struct Database {
     pathname: PathBuf,
-    objects: HashMap<String, ObjectType>
+    hash: HashAlgorithm,
+    objects: HashMap<String, ObjectType>,
+    // the conversions of the files of the working tree when they are stored
+    converter: Option<Arc<Converter>>,
+}
+
+// The decoded content of a loose object together with the values declared in its
+// `<type> <size>\0` header, before any interpretation of the content takes place.
+pub struct RawObject {
+    pub type_: String,
+    pub size: usize,
+    pub content: Vec<u8>,
+}
+
+impl RawObject {
+    pub fn get_data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(self.type_.as_bytes());
+        data.push(0x20u8);
+        data.extend_from_slice(self.size.to_string().as_bytes());
+        data.push(0x00u8);
+        data.extend_from_slice(&self.content);
+        data
+    }
+}
+
+// Streams the content of an object out of the database.
+pub struct ObjectReader {
+    pub type_: String,
+    pub size: u64,
+    decoder: BufReader<Decoder<BufReader<File>>>,
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
 }
 
 pub enum ObjectType {
     CommitType{ commit: Commit},
     BlobType{blob: Blob},
-    TreeType{tree: Tree}
+    TreeType{tree: Tree},
+    TagType{tag: Tag}
 }
 
 impl Database {
-    pub fn new(path_buf: &PathBuf) -> Self {
+    pub fn new(path_buf: &PathBuf, hash: HashAlgorithm) -> Self {
         Database {
             pathname: path_buf.into(),
-            objects: HashMap::new()
+            hash,
+            objects: HashMap::new(),
+            converter: None,
         }
     }
 
+    pub fn set_converter(&mut self, converter: Arc<Converter>) {
+        self.converter = Some(converter);
+    }
+
+    pub fn converter(&self) -> Option<Arc<Converter>> {
+        self.converter.clone()
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash
+    }
+
     // pub fn store(&self, object: &mut dyn Object) -> Result<()> {
     pub fn store<W: Object>(&self, object: &mut W) -> Result<()> {
         let data = object.get_data()?;
@@ -38,24 +157,153 @@ impl Database {
     }
 
     pub fn write_object(&self, oid: &Vec<u8>, content: Vec<u8>) -> Result<()> {
-        let oid_s = util::encode_vec(&oid);
-        let (a, b) = oid_s.split_at(2);
-        let path = &self.pathname.join(a);
-        if !path.exists() {
-            fs::create_dir_all(&path).expect("unable to create path");
-            let file_content = path.join(b);
-            let mut file = OpenOptions::new()
-                .read(true)
-                .create(true)
-                .write(true)
-                .open(&file_content)?;
-
-            let mut encoder = Encoder::new(Vec::new())?;
+        let oid_s = util::encode_vec(oid);
+        let file_content = self.object_path(&oid_s);
+        if !file_content.exists() {
+            let (temp_path, file) = self.create_temp_file()?;
+            let mut encoder = Encoder::new(BufWriter::new(file))?;
             io::copy(&mut &content[..], &mut encoder)?;
-            let encode_data = encoder.finish().into_result()?;
+            encoder.finish().into_result()?.flush()?;
+            self.rename_temp_file(&temp_path, &oid_s)?;
+        }
+        Ok(())
+    }
 
-            file.write_all(&encode_data)?
+    // Hashes and compresses a file as a blob in a single pass over fixed size chunks, so the
+    // content never has to be held in memory. The object is written to a temporary file first
+    // and only moved to its final name once its oid is known.
+    pub fn store_file(&self, path: &Path) -> Result<Vec<u8>> {
+        if let Some(target) = util::read_link(path)? {
+            let mut blob = Blob::new(target, self.hash)?;
+            self.store(&mut blob)?;
+            return blob.get_oid();
+        }
+        // converted files are read whole, the others are streamed
+        if let Some(converter) = self.converter.as_ref().filter(|converter| converter.converts_to_git(path)) {
+            let mut blob = Blob::new(converter.to_git(path, fs::read(path)?)?, self.hash)?;
+            self.store(&mut blob)?;
+            return blob.get_oid();
+        }
+        let mut file = File::open(path).map_err(|_| anyhow!("open ('{:?}'): Permission denied", path))?;
+        let size = file.metadata()?.len();
+        let (temp_path, temp_file) = self.create_temp_file()?;
+        let mut encoder = Encoder::new(BufWriter::new(temp_file))?;
+        let mut context = self.hash.context();
+        let header = blob_header(size);
+        context.update(&header);
+        encoder.write_all(&header)?;
+        let copied = copy_chunks(&mut file, |chunk| {
+            context.update(chunk);
+            encoder.write_all(chunk)
+        });
+        let finished = encoder.finish().into_result();
+        let result = copied.and_then(|copied| {
+            if copied != size {
+                return Err(anyhow!("file {:?} changed while it was being stored", path));
+            }
+            finished?.flush()?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            fs::remove_file(&temp_path)?;
+            return Err(e);
         }
+        let oid = context.finish().as_ref().to_vec();
+        let oid_s = util::encode_vec(&oid);
+        if self.object_path(&oid_s).exists() {
+            fs::remove_file(&temp_path)?;
+        } else {
+            self.rename_temp_file(&temp_path, &oid_s)?;
+        }
+        Ok(oid)
+    }
+
+    // Stores every file on a pool of threads. The oids are returned in the same order as the
+    // paths, and when several files fail the error of the first one in that order is reported,
+    // so the result does not depend on how the work was scheduled.
+    pub fn store_files(&self, paths: &[PathBuf]) -> Result<Vec<Vec<u8>>> {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(paths.len());
+        if workers <= 1 {
+            return paths.iter().map(|path| self.store_file(path)).collect();
+        }
+        let next = AtomicUsize::new(0);
+        let mut results = thread::scope(|scope| {
+            let handles = (0..workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, AtomicOrdering::SeqCst);
+                            if i >= paths.len() {
+                                break;
+                            }
+                            done.push((i, self.store_file(&paths[i])));
+                        }
+                        done
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("a worker storing files panicked"))
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(i, _)| *i);
+        results.into_iter().map(|(_, oid)| oid).collect()
+    }
+
+    pub fn hash_file(&self, path: &Path) -> Result<Vec<u8>> {
+        hash_worktree_file(path, self.hash, self.converter.as_deref())
+    }
+
+    // Opens an object for reading without inflating it completely, the header is consumed so
+    // the reader yields the content of the object only.
+    pub fn open_object(&self, oid: &str) -> Result<ObjectReader> {
+        let file = File::open(self.object_path(oid))
+            .map_err(|_| anyhow!("unable to read object {}", oid))?;
+        let mut decoder = BufReader::new(Decoder::new(BufReader::new(file))?);
+        let mut type_object = vec![];
+        decoder.read_until(0x20u8, &mut type_object)?;
+        let type_object = String::from_utf8(type_object)?.trim().to_string();
+        let mut length = vec![];
+        decoder.read_until(0x00u8, &mut length)?;
+        length.pop();
+        let size = String::from_utf8(length)?
+            .parse::<u64>()
+            .map_err(|_| anyhow!("object {} has an invalid size in its header", oid))?;
+        Ok(ObjectReader {
+            type_: type_object,
+            size,
+            decoder,
+        })
+    }
+
+    // the name of temporary files has to be unique even if several threads of the same
+    // process are writing objects at the same time.
+    fn create_temp_file(&self) -> Result<(PathBuf, File)> {
+        fs::create_dir_all(&self.pathname)?;
+        loop {
+            let n = TEMP_COUNTER.fetch_add(1, AtomicOrdering::SeqCst);
+            let temp_path = self
+                .pathname
+                .join(format!("tmp_obj_{}_{}", std::process::id(), n));
+            match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+                Ok(file) => return Ok((temp_path, file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn rename_temp_file(&self, temp_path: &Path, oid: &str) -> Result<()> {
+        let object_path = self.object_path(oid);
+        if let Some(dir) = object_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::rename(temp_path, &object_path)?;
         Ok(())
     }
 
@@ -70,41 +318,107 @@ impl Database {
         }
     }
 
-    pub fn read_object(&self, oid: &str) -> Result<ObjectType> {
+    // every entry that is not a tree in the tree `oid` and its subtrees, indexed by path.
+    pub fn load_tree_list(&mut self, oid: &str) -> Result<TreeList> {
+        let mut list = TreeList::new();
+        let mut work = vec![(oid.to_string(), PathBuf::new())];
+        while let Some((oid, prefix)) = work.pop() {
+            let entries = match self.load(&oid)? {
+                ObjectType::TreeType { tree } => tree.entries.clone(),
+                _ => return Err(anyhow!("object {} is not a tree", oid)),
+            };
+            for mut entry in entries {
+                let path = prefix.join(&entry.name);
+                entry.path = path.to_path_buf();
+                if entry.is_tree() {
+                    work.push((util::encode_vec(&entry.sha1_hash), path));
+                } else {
+                    list.insert(path, entry);
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    pub fn object_path(&self, oid: &str) -> PathBuf {
         let (dir, file) = oid.split_at(2);
-        let mut path_to_file = self.pathname.to_path_buf();
-        path_to_file.push(dir);
-        path_to_file.push(file);
-        let file = fs::read(path_to_file)?;
+        self.pathname.join(dir).join(file)
+    }
+
+    pub fn exists(&self, oid: &str) -> bool {
+        self.hash.is_valid_hex(oid) && self.object_path(oid).exists()
+    }
+
+    // lists every loose object stored under the objects directory, sorted by oid.
+    pub fn list_objects(&self) -> Result<Vec<String>> {
+        let mut oids = Vec::new();
+        if !self.pathname.exists() {
+            return Ok(oids);
+        }
+        for dir in fs::read_dir(&self.pathname)? {
+            let dir = dir?;
+            let prefix = dir.file_name().to_string_lossy().to_string();
+            if prefix.len() != 2 || !dir.file_type()?.is_dir() || hex::decode(&prefix).is_err() {
+                continue;
+            }
+            for file in fs::read_dir(dir.path())? {
+                let file = file?;
+                let rest = file.file_name().to_string_lossy().to_string();
+                if self.hash.is_valid_hex(&format!("{}{}", prefix, rest)) {
+                    oids.push(format!("{}{}", prefix, rest));
+                }
+            }
+        }
+        oids.sort();
+        Ok(oids)
+    }
+
+    pub fn read_raw(&self, oid: &str) -> Result<RawObject> {
+        let file = fs::read(self.object_path(oid))?;
         let mut decoder = Decoder::new(&file[..])?;
         let mut decode_data = Vec::new();
         decoder.read_to_end(&mut decode_data)?;
-        let mut cursor = Cursor::new(decode_data.clone());
+        let mut cursor = Cursor::new(decode_data);
         let mut type_object = vec![];
         cursor.read_until(0x20u8, &mut type_object)?;
-        let type_object= String::from_utf8(type_object)?;
-        // println!("type_object: {:?}", type_object.trim());
+        let type_object = String::from_utf8(type_object)?.trim().to_string();
         let mut length = vec![];
         cursor.read_until(0x00u8, &mut length)?;
-        // let length = String::from_utf8(length)?;
-        // println!("length: {:?}", length);
-        match type_object.trim().as_ref() {
+        if length.pop() != Some(0x00u8) {
+            return Err(anyhow!("object {} has a malformed header", oid));
+        }
+        let size = String::from_utf8(length)?
+            .parse::<usize>()
+            .map_err(|_| anyhow!("object {} has an invalid size in its header", oid))?;
+        let mut content = Vec::new();
+        cursor.read_to_end(&mut content)?;
+        Ok(RawObject {
+            type_: type_object,
+            size,
+            content,
+        })
+    }
+
+    pub fn read_object(&self, oid: &str) -> Result<ObjectType> {
+        let raw = self.read_raw(oid)?;
+        let mut cursor = Cursor::new(raw.content);
+        match raw.type_.as_ref() {
             "commit" => { 
-                let commit = Commit::parse(&mut cursor, oid)?;
+                let commit = Commit::parse(&mut cursor, oid, self.hash)?;
                 Ok(ObjectType::CommitType{commit})
             } ,
             "blob" => { 
-                let blob = Blob::parse(&mut cursor)?;
+                let blob = Blob::parse(&mut cursor, self.hash)?;
                 Ok(ObjectType::BlobType{blob})
 
             },
             "tree" => { 
-                let tree = Tree::parse(&mut cursor, oid.as_bytes().to_vec())?; 
+                let tree = Tree::parse(&mut cursor, oid.as_bytes().to_vec(), self.hash)?; 
                 Ok(ObjectType::TreeType{tree})
             },
+            "tag" => Ok(ObjectType::TagType { tag: Tag::parse(&mut cursor, oid)? }),
             _ => {
-                println!("1: {}", type_object);
-                return Err(anyhow!("unknow object type"))
+                Err(anyhow!("unknow object type"))
             }
         }
     }
@@ -0,0 +1,166 @@
+// Line diffs computed with the Myers algorithm, the one git uses by default.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Eql,
+    Ins,
+    Del,
+}
+
+// One step of the edit script turning `a` into `b`, with the index of the element in `a`
+// (for deletions and equal elements) and in `b` (for insertions and equal elements).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub a_index: Option<usize>,
+    pub b_index: Option<usize>,
+}
+
+// splits `data` into lines, each one keeping its trailing newline.
+pub fn lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|b| *b == b'\n').collect()
+}
+
+// the shortest edit script from `a` to `b`.
+pub fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let trace = shortest_edit(a, b);
+    backtrack(a.len(), b.len(), &trace)
+}
+
+// the `v` array of every step of the search, needed to walk the path backwards.
+fn shortest_edit<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Vec<isize>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max as usize + 1;
+    let mut v = vec![0isize; 2 * offset + 1];
+    let mut trace = Vec::new();
+    for d in 0..=max {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let index = |k: isize| (k + offset as isize) as usize;
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                return trace;
+            }
+            k += 2;
+        }
+    }
+    trace
+}
+
+fn backtrack(n: usize, m: usize, trace: &[Vec<isize>]) -> Vec<Edit> {
+    let offset = (n + m) as isize + 1;
+    let index = |k: isize| (k + offset) as usize;
+    let (mut x, mut y) = (n as isize, m as isize);
+    let mut edits = Vec::new();
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit {
+                kind: EditKind::Eql,
+                a_index: Some(x as usize),
+                b_index: Some(y as usize),
+            });
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit {
+                    kind: EditKind::Ins,
+                    a_index: None,
+                    b_index: Some(prev_y as usize),
+                });
+            } else {
+                edits.push(Edit {
+                    kind: EditKind::Del,
+                    a_index: Some(prev_x as usize),
+                    b_index: None,
+                });
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+// A region of an edit script with the unchanged lines around its changes, as shown by a
+// unified diff. The starts are the number of lines of each side before the hunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub a_start: usize,
+    pub b_start: usize,
+    pub edits: Vec<Edit>,
+}
+
+impl Hunk {
+    pub fn a_len(&self) -> usize {
+        self.edits.iter().filter(|e| e.kind != EditKind::Ins).count()
+    }
+
+    pub fn b_len(&self) -> usize {
+        self.edits.iter().filter(|e| e.kind != EditKind::Del).count()
+    }
+
+    // `@@ -<start>,<len> +<start>,<len> @@`, git leaves out the lengths of 1 and counts
+    // from the line before for empty sides.
+    pub fn header(&self) -> String {
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, len),
+        };
+        format!(
+            "@@ -{} +{} @@",
+            range(self.a_start, self.a_len()),
+            range(self.b_start, self.b_len())
+        )
+    }
+}
+
+// groups the changes of `edits` into hunks with `context` unchanged lines around them, the
+// changes closer than twice that share a hunk.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let changes: Vec<usize> = (0..edits.len()).filter(|n| edits[*n].kind != EditKind::Eql).collect();
+    let mut hunks = Vec::new();
+    let mut n = 0;
+    while n < changes.len() {
+        let first = changes[n];
+        let mut last = first;
+        while n + 1 < changes.len() && changes[n + 1] - last <= 2 * context + 1 {
+            n += 1;
+            last = changes[n];
+        }
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(edits.len());
+        let before = &edits[..start];
+        hunks.push(Hunk {
+            a_start: before.iter().filter(|e| e.kind != EditKind::Ins).count(),
+            b_start: before.iter().filter(|e| e.kind != EditKind::Del).count(),
+            edits: edits[start..end].to_vec(),
+        });
+        n += 1;
+    }
+    hunks
+}
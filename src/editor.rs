@@ -0,0 +1,133 @@
+use std::env;
+use std::path::Path;
+use std::process;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::Config;
+
+// The editor to run, looked up like git does. The todo list of an interactive rebase is
+// edited with `$GIT_SEQUENCE_EDITOR` or `sequence.editor` first when `sequence` is set.
+pub fn editor(config: &Config, sequence: bool) -> String {
+    let sequence_editor = if sequence {
+        env::var("GIT_SEQUENCE_EDITOR").ok().or(config.get("sequence.editor"))
+    } else {
+        None
+    };
+    sequence_editor
+        .or(env::var("GIT_EDITOR").ok())
+        .or(config.get("core.editor"))
+        .or(env::var("VISUAL").ok())
+        .or(env::var("EDITOR").ok())
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or("vi".to_string())
+}
+
+// Runs `editor` on `path` through the shell, so it can hold arguments. `:` leaves the file
+// as it is.
+pub fn edit_file(editor: &str, path: &Path) -> Result<()> {
+    if editor == ":" {
+        return Ok(());
+    }
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(editor)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow!("There was a problem with the editor '{}'.", editor));
+    }
+    Ok(())
+}
+
+// the text without its comment lines and surrounding blank lines.
+pub fn strip_comments(text: &str) -> String {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.trim_end())
+        .collect();
+    let text = lines.join("\n");
+    text.trim_matches('\n').to_string()
+}
+
+// The line of `--cleanup=scissors` below which an edited message is dropped.
+pub const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+// How a commit message is cleaned up, `--cleanup=<mode>`. `Default` strips the comments of an
+// edited message and only the whitespace of a message given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cleanup {
+    #[default]
+    Default,
+    Strip,
+    Whitespace,
+    Verbatim,
+    Scissors,
+}
+
+impl Cleanup {
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode {
+            "default" => Ok(Cleanup::Default),
+            "strip" => Ok(Cleanup::Strip),
+            "whitespace" => Ok(Cleanup::Whitespace),
+            "verbatim" => Ok(Cleanup::Verbatim),
+            "scissors" => Ok(Cleanup::Scissors),
+            _ => Err(anyhow!("Invalid cleanup mode {}", mode)),
+        }
+    }
+
+    // the mode once `Default` is settled by whether the message was `edited`.
+    pub fn resolve(self, edited: bool) -> Self {
+        match self {
+            Cleanup::Default if edited => Cleanup::Strip,
+            Cleanup::Default => Cleanup::Whitespace,
+            mode => mode,
+        }
+    }
+
+    // The message without what the mode removes: the comment lines for `Strip`, what follows
+    // the scissors line of an edited message for `Scissors`, and for every mode but `Verbatim`
+    // the trailing spaces, the repeated blank lines and the blank lines around the message.
+    pub fn apply(self, text: &str, edited: bool) -> String {
+        let mode = self.resolve(edited);
+        if mode == Cleanup::Verbatim {
+            return text.to_string();
+        }
+        let mut lines = Vec::new();
+        for line in text.lines() {
+            if mode == Cleanup::Scissors && edited && line == SCISSORS {
+                break;
+            }
+            if mode == Cleanup::Strip && line.starts_with('#') {
+                continue;
+            }
+            let line = line.trim_end();
+            if line.is_empty() && lines.last().is_none_or(|last: &&str| last.is_empty()) {
+                continue;
+            }
+            lines.push(line);
+        }
+        lines.join("\n").trim_end_matches('\n').to_string()
+    }
+
+    // the comment that follows the message to edit, telling what happens to the comments.
+    pub fn help(self) -> String {
+        match self.resolve(true) {
+            Cleanup::Strip => "# Please enter the commit message for your changes. Lines starting\n\
+                 # with '#' will be ignored, and an empty message aborts the commit.\n"
+                .to_string(),
+            Cleanup::Scissors => format!(
+                "{}\n# Do not modify or remove the line above.\n# Everything below it will be ignored.\n",
+                SCISSORS
+            ),
+            _ => "# Please enter the commit message for your changes. Lines starting\n\
+                  # with '#' will be kept; you may remove them yourself if you want to.\n\
+                  # An empty message aborts the commit.\n"
+                .to_string(),
+        }
+    }
+}
@@ -1,10 +1,12 @@
+use std::collections::HashMap;
 use std::os::unix::prelude::MetadataExt;
 use std::{fmt::Display, path::PathBuf};
 
+use anyhow::anyhow;
 use anyhow::Result;
 
-use crate::{Database, Object, util};
-use crate::{Blob, Tree};
+use crate::{repository, util, Database, Object};
+use crate::Tree;
 
 #[derive(Eq, Clone, PartialEq, PartialOrd, Debug)]
 pub struct Entry {
@@ -47,7 +49,19 @@ impl Entry {
     }
 
     pub fn from_file(path: PathBuf, db: &Database) -> Result<Self> {
-        let metadata = path.metadata()?;
+        let files = util::list_entry_files(&path)?;
+        let stored = files.iter().cloned().zip(db.store_files(&files)?).collect();
+        Entry::from_stored_file(path, db, &stored)
+    }
+
+    // builds the entry of a file or directory whose regular files have already been stored in
+    // the database, `stored` maps each of those files to its oid.
+    pub fn from_stored_file(
+        path: PathBuf,
+        db: &Database,
+        stored: &HashMap<PathBuf, Vec<u8>>,
+    ) -> Result<Self> {
+        let metadata = path.symlink_metadata()?;
         let filetype = metadata.file_type();
 
         let mut mode = String::new();
@@ -63,19 +77,28 @@ impl Entry {
             } else {
                 mode.push_str("100644")
             }
-            let data = util::read_file(path.clone())?;
-            let mut blob = Blob::new(data)?;
-            sha1_hash = blob.get_oid()?;
-            db.store(&mut blob)?;
+            sha1_hash = match stored.get(&path) {
+                Some(oid) => oid.to_vec(),
+                None => db.store_file(&path)?,
+            };
         } else if filetype.is_symlink() {
             mode.push_str("120000");
-            let data = util::read_file(path.clone())?;
-            let mut blob = Blob::new(data)?;
-            sha1_hash = blob.get_oid()?;
-            db.store(&mut blob)?;
+            sha1_hash = match stored.get(&path) {
+                Some(oid) => oid.to_vec(),
+                None => db.store_file(&path)?,
+            };
+        } else if filetype.is_dir() && util::is_nested_repository(&path) {
+            mode.push_str("160000");
+            let head = repository::checked_out_commit(&path)
+                .ok_or(anyhow!("'{}' does not have a commit checked out", path.display()))?;
+            sha1_hash = hex::decode(head)?;
         } else if filetype.is_dir() {
             mode.push_str("040000");
-            let tree = Tree::new(path.clone(), db)?;
+            let mut children = vec![];
+            for child in util::read_dir_sorted(&path)? {
+                children.push(Entry::from_stored_file(child, db, stored)?);
+            }
+            let tree = Tree::new_with_entries(children, db.hash_algorithm())?;
             entries = tree.entries;
             sha1_hash = tree.sha1_hash;
         }
@@ -109,7 +132,12 @@ impl Entry {
     }
 
     pub fn is_tree(&self) -> bool {
-        self.mode == "040000"
+        self.mode == "040000" || self.mode == "40000"
+    }
+
+    // a submodule, whose oid is a commit of another repository.
+    pub fn is_gitlink(&self) -> bool {
+        self.mode == "160000"
     }
 }
 
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::process::{self, Child, ChildStdin, ChildStdout, Stdio};
+use std::thread;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::Config;
+
+// Which way the content goes through a filter: `Clean` when it is stored, `Smudge` when it
+// is checked out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterCommand {
+    Clean,
+    Smudge,
+}
+
+impl FilterCommand {
+    pub fn name(&self) -> &str {
+        match self {
+            FilterCommand::Clean => "clean",
+            FilterCommand::Smudge => "smudge",
+        }
+    }
+}
+
+// A filter driver configured as `filter.<name>` for the files whose `filter` attribute names
+// it: a command for each way, or a long-running `process` doing both. When the driver is
+// `required` a failure stops the command instead of leaving the content as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterDriver {
+    pub clean: Option<String>,
+    pub smudge: Option<String>,
+    pub process: Option<String>,
+    pub required: bool,
+}
+
+impl FilterDriver {
+    // the drivers configured, by name.
+    pub fn from_config(config: &Config) -> HashMap<String, FilterDriver> {
+        config
+            .subsections("filter")
+            .into_iter()
+            .map(|name| {
+                let key = |key: &str| format!("filter.{}.{}", name, key);
+                let driver = FilterDriver {
+                    clean: config.get(&key("clean")),
+                    smudge: config.get(&key("smudge")),
+                    process: config.get(&key("process")),
+                    required: config.get_bool(&key("required")).unwrap_or(false),
+                };
+                (name, driver)
+            })
+            .collect()
+    }
+
+    pub fn command(&self, command: FilterCommand) -> Option<&String> {
+        match command {
+            FilterCommand::Clean => self.clean.as_ref(),
+            FilterCommand::Smudge => self.smudge.as_ref(),
+        }
+    }
+}
+
+// Runs a `clean` or `smudge` command through the shell from `dir`, with `data` as its input.
+// `%f` stands for the path of the file, passed as an argument so it needs no quoting.
+pub fn run_command(command: &str, dir: &Path, path: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let mut child = process::Command::new("sh")
+        .arg("-c")
+        .arg(command.replace("%f", "\"$1\""))
+        .arg(command)
+        .arg(path)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|_| anyhow!("cannot fork to run external filter '{}'", command))?;
+    let mut stdin = child.stdin.take().ok_or(anyhow!("cannot feed the input to external filter '{}'", command))?;
+    // the input is written while the output is read, the filter may not read all of it
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(data));
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(anyhow!("external filter '{}' failed {}", command, output.status.code().unwrap_or(-1)));
+    }
+    Ok(output.stdout)
+}
+
+// the longest data a pkt-line can hold.
+const MAX_PACKET_DATA: usize = 65516;
+
+fn write_packet(writer: &mut impl Write, data: &[u8]) -> Result<()> {
+    writer.write_all(format!("{:04x}", data.len() + 4).as_bytes())?;
+    writer.write_all(data)?;
+    Ok(())
+}
+
+fn write_flush(writer: &mut impl Write) -> Result<()> {
+    writer.write_all(b"0000")?;
+    writer.flush()?;
+    Ok(())
+}
+
+// the next pkt-line, `None` for a flush packet.
+fn read_packet(reader: &mut impl Read) -> Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length)?;
+    let length = usize::from_str_radix(std::str::from_utf8(&length)?, 16)?;
+    match length {
+        0 => Ok(None),
+        1..=3 => Err(anyhow!("protocol error: bad line length {}", length)),
+        _ => {
+            let mut data = vec![0; length - 4];
+            reader.read_exact(&mut data)?;
+            Ok(Some(data))
+        }
+    }
+}
+
+// the text packets up to the next flush, without their line feed.
+fn read_list(reader: &mut impl Read) -> Result<Vec<String>> {
+    let mut list = Vec::new();
+    while let Some(packet) = read_packet(reader)? {
+        let line = String::from_utf8_lossy(&packet);
+        list.push(line.strip_suffix('\n').unwrap_or(&line).to_string());
+    }
+    Ok(list)
+}
+
+// the value of the last `status=` of a list, when there is one.
+fn status(list: &[String]) -> Option<&str> {
+    list.iter().rev().find_map(|line| line.strip_prefix("status="))
+}
+
+// A long-running filter started once for all the files, talking version 2 of the filter
+// protocol of git over its standard input and output: a handshake with the capabilities it
+// supports, then for each file the command and the path, followed by the content, and the
+// status and the filtered content in return.
+pub struct FilterProcess {
+    command: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    capabilities: Vec<String>,
+}
+
+impl FilterProcess {
+    pub fn start(command: &str, dir: &Path) -> Result<Self> {
+        let mut child = process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|_| anyhow!("cannot fork to run external filter '{}'", command))?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().map(BufReader::new);
+        let (stdin, stdout) = match (stdin, stdout) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(anyhow!("cannot talk to external filter '{}'", command)),
+        };
+        let mut process = FilterProcess {
+            command: command.to_string(),
+            child,
+            stdin: Some(stdin),
+            stdout,
+            capabilities: Vec::new(),
+        };
+        process
+            .handshake()
+            .map_err(|e| anyhow!("initialization for external filter '{}' failed: {}", command, e))?;
+        Ok(process)
+    }
+
+    fn handshake(&mut self) -> Result<()> {
+        let stdin = self.stdin.as_mut().ok_or(anyhow!("the filter was closed"))?;
+        write_packet(stdin, b"git-filter-client\n")?;
+        write_packet(stdin, b"version=2\n")?;
+        write_flush(stdin)?;
+        let welcome = read_list(&mut self.stdout)?;
+        if welcome.first().map(|line| line.as_str()) != Some("git-filter-server") {
+            return Err(anyhow!("unexpected welcome {:?}", welcome));
+        }
+        if !welcome.iter().any(|line| line == "version=2") {
+            return Err(anyhow!("unsupported protocol version"));
+        }
+        write_packet(stdin, b"capability=clean\n")?;
+        write_packet(stdin, b"capability=smudge\n")?;
+        write_flush(stdin)?;
+        self.capabilities = read_list(&mut self.stdout)?
+            .iter()
+            .filter_map(|line| line.strip_prefix("capability="))
+            .map(|capability| capability.to_string())
+            .collect();
+        Ok(())
+    }
+
+    pub fn supports(&self, command: FilterCommand) -> bool {
+        self.capabilities.iter().any(|capability| capability == command.name())
+    }
+
+    // Filters the content of the file at `path`. A filter answering `abort` is not asked to
+    // run the command again.
+    pub fn filter(&mut self, command: FilterCommand, path: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let stdin = self.stdin.as_mut().ok_or(anyhow!("the filter was closed"))?;
+        write_packet(stdin, format!("command={}\n", command.name()).as_bytes())?;
+        write_packet(stdin, format!("pathname={}\n", path).as_bytes())?;
+        write_flush(stdin)?;
+        for chunk in data.chunks(MAX_PACKET_DATA) {
+            write_packet(stdin, chunk)?;
+        }
+        write_flush(stdin)?;
+
+        let mut result = status(&read_list(&mut self.stdout)?).map(|status| status.to_string());
+        let mut output = Vec::new();
+        if result.as_deref() == Some("success") {
+            while let Some(packet) = read_packet(&mut self.stdout)? {
+                output.extend(packet);
+            }
+            // an empty list keeps the status given first
+            if let Some(status) = status(&read_list(&mut self.stdout)?) {
+                result = Some(status.to_string());
+            }
+        }
+        match result.as_deref() {
+            Some("success") => Ok(output),
+            Some("abort") => {
+                self.capabilities.retain(|capability| capability != command.name());
+                Err(anyhow!("external filter '{}' aborted the {} of '{}'", self.command, command.name(), path))
+            }
+            _ => Err(anyhow!("external filter '{}' failed to {} '{}'", self.command, command.name(), path)),
+        }
+    }
+}
+
+impl Drop for FilterProcess {
+    // closing its input tells the filter to exit.
+    fn drop(&mut self) {
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
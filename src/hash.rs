@@ -0,0 +1,57 @@
+use anyhow::anyhow;
+use anyhow::Result;
+use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
+
+// The algorithm used to name objects, selected per repository through `extensions.objectFormat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(anyhow!("unknown hash algorithm '{}'", name)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    // size in bytes of a raw object id
+    pub fn size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    pub fn hex_size(&self) -> usize {
+        self.size() * 2
+    }
+
+    pub fn context(&self) -> Context {
+        match self {
+            HashAlgorithm::Sha1 => Context::new(&SHA1_FOR_LEGACY_USE_ONLY),
+            HashAlgorithm::Sha256 => Context::new(&SHA256),
+        }
+    }
+
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        let mut context = self.context();
+        context.update(data);
+        context.finish().as_ref().to_vec()
+    }
+
+    pub fn is_valid_hex(&self, oid: &str) -> bool {
+        oid.len() == self.hex_size() && hex::decode(oid).is_ok()
+    }
+}
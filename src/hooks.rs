@@ -0,0 +1,81 @@
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{self, Stdio};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::{Config, Repository};
+
+// The hooks of a repository: the executables of its hooks directory, named after the events
+// they are run for. They run from the top of the working tree, or the git directory of a
+// bare repository, and what they print goes to the standard error.
+#[derive(Debug, Clone)]
+pub struct Hooks {
+    dir: PathBuf,
+    work_dir: PathBuf,
+    env: Vec<(String, String)>,
+}
+
+impl Hooks {
+    // `core.hooksPath`, relative to where the hooks run, or `hooks` of the common directory.
+    pub fn new(repository: &Repository, config: &Config) -> Self {
+        let work_dir = repository.work_tree.clone().unwrap_or(repository.git_dir.clone());
+        let dir = match config.get("core.hookspath") {
+            Some(path) => work_dir.join(path),
+            None => repository.common_dir.join("hooks"),
+        };
+        Hooks {
+            dir,
+            work_dir,
+            env: vec![("GIT_DIR".to_string(), repository.git_dir.display().to_string())],
+        }
+    }
+
+    // adds a variable to the environment of the hooks, like `GIT_INDEX_FILE`.
+    pub fn with_env(mut self, name: &str, value: &str) -> Self {
+        self.env.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // The executable of the hook `name`. A hook which is not executable is ignored, with a
+    // hint like git gives.
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        let path = self.dir.join(name);
+        let metadata = path.metadata().ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        if metadata.permissions().mode() & 0o111 == 0 {
+            eprintln!(
+                "hint: The '{}' hook was ignored because it's not set as executable.",
+                name
+            );
+            return None;
+        }
+        Some(path)
+    }
+
+    // Runs the hook `name` with `args` and `input` on its standard input. Whether it succeeded,
+    // which a missing hook always does.
+    pub fn run(&self, name: &str, args: &[&str], input: Option<&[u8]>) -> Result<bool> {
+        let path = match self.path(name) {
+            Some(path) => path,
+            None => return Ok(true),
+        };
+        let mut child = process::Command::new(&path)
+            .args(args)
+            .current_dir(&self.work_dir)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::from(io::stderr()))
+            .spawn()
+            .map_err(|e| anyhow!("cannot run the {} hook: {}", name, e))?;
+        if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+            // the hook may exit without reading its input
+            let _ = stdin.write_all(input);
+        }
+        Ok(child.wait()?.success())
+    }
+}
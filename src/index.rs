@@ -4,11 +4,13 @@ use std::cell::Ref;
 use std::cell::RefCell;
 use std::cell::RefMut;
 use std::char;
+use std::collections::BTreeMap;
 use std::collections::BTreeSet;
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
 use std::fs::Metadata;
 use std::fs::OpenOptions;
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::{io::Write, os::unix::prelude::MetadataExt, path::PathBuf, u16, u8, usize};
 
@@ -16,12 +18,77 @@ use anyhow::Result;
 
 use crate::util;
 use crate::Checksum;
+use crate::Config;
+use crate::HashAlgorithm;
 
 pub struct Index {
     pathname: PathBuf,
+    hash: HashAlgorithm,
     entries: HashMap<String, RefCell<EntryAdd>>,
     keys: BTreeSet<String>,
+    // the entries of the paths a merge left unmerged, by path and ordered by stage: 1 for the
+    // common ancestor, 2 for our side and 3 for theirs. Such a path has no stage 0 entry.
+    unmerged: BTreeMap<String, Vec<EntryAdd>>,
     changed: bool,
+    // the seconds of the mtime of the index file when it was loaded
+    timestamp: Option<u32>,
+    stat_options: StatOptions,
+}
+
+// How much of the stat data of a file is trusted to tell it did not change, from
+// `core.trustctime` and `core.checkStat`. With `minimal`, only the whole seconds of the mtime
+// are compared besides the mode and the size. Without `symlinks`, from `core.symlinks`,
+// symbolic links are checked out as plain files holding their target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatOptions {
+    pub trust_ctime: bool,
+    pub minimal: bool,
+    pub symlinks: bool,
+}
+
+impl Default for StatOptions {
+    fn default() -> Self {
+        StatOptions {
+            trust_ctime: true,
+            minimal: false,
+            symlinks: true,
+        }
+    }
+}
+
+impl StatOptions {
+    pub fn from_config(config: &Config) -> Self {
+        StatOptions {
+            trust_ctime: config.get_bool("core.trustctime").unwrap_or(true),
+            minimal: config.get("core.checkstat").is_some_and(|v| v.eq_ignore_ascii_case("minimal")),
+            symlinks: config.get_bool("core.symlinks").unwrap_or(true),
+        }
+    }
+
+    // The mode of a file whose stat gives `mode` for an entry recorded with `recorded`: the
+    // plain file standing for a symbolic link keeps the mode of the link.
+    pub fn mode(&self, recorded: u32, mode: u32) -> u32 {
+        if !self.symlinks && recorded == util::SYMLINK_MODE {
+            recorded
+        } else {
+            mode
+        }
+    }
+}
+
+// What the stat data of a file says about the entry it is checked against: the file changed,
+// it did not, or only its content can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatChange {
+    Changed,
+    Unchanged,
+    Unknown,
+}
+
+// The index keeps the seconds of the times in 32 bits, as git does, so they wrap around in
+// 2106: a file is racy when its mtime is not older than the index, compared modulo 2^32.
+fn is_racy_at(mtime: u32, timestamp: u32) -> bool {
+    (timestamp.wrapping_sub(mtime) as i32) <= 0
 }
 
 #[derive(Clone, Debug)]
@@ -38,35 +105,58 @@ pub struct EntryAdd {
     size: u32,
     pub oid: Vec<u8>,
     flags: u16,
+    // the flags of version 3 of the index, only written when one is set
+    extended_flags: u16,
     pub path: PathBuf,
 }
 
 const ENTRY_BLOCK: usize = 8;
 const MAX_PATH_SIZE: u16 = 0xfff;
+const EXTENDED_FLAG: u16 = 0x4000;
+// the file is left out of the working tree by a sparse checkout
+const SKIP_WORKTREE_FLAG: u16 = 0x4000;
+// the merge stage of the entry, 0 unless the path is unmerged
+const STAGE_MASK: u16 = 0x3000;
+const STAGE_SHIFT: u16 = 12;
 
 impl EntryAdd {
 
 
-    pub fn update_entry_stat(&mut self, stat: &Metadata) -> () {
+    pub fn update_entry_stat(&mut self, stat: &Metadata, options: StatOptions) -> () {
         self.ctime =  stat.ctime() as u32;
         self.ctime_nsec =  stat.ctime_nsec() as u32;
         self.mtime =  stat.mtime() as u32;
         self.mtime_nsec = stat.mtime_nsec() as u32;
         self.dev = stat.dev() as u32;
         self.ino = stat.ino() as u32;
-        self.mode = util::get_mode_stat(stat);
+        self.mode = options.mode(self.mode, util::get_mode_stat(stat));
         self.uid =  stat.uid() as u32;
         self.gid = stat.gid() as u32;
         self.size = stat.size() as u32;
     }
 
-    pub fn times_match(&self, stat: &Metadata) -> bool {
-        self.ctime == (stat.ctime() as u32) && self.ctime_nsec == (stat.ctime_nsec() as u32) &&
-            self.mtime == (stat.mtime() as u32) && self.mtime_nsec == (stat.mtime_nsec() as u32)
+    // whether the times and the identity of the file are the ones recorded. The 64-bit values
+    // of the file are truncated to the 32 bits the index keeps.
+    pub fn stat_data_match(&self, stat: &Metadata, options: StatOptions) -> bool {
+        if self.mtime != stat.mtime() as u32 {
+            return false;
+        }
+        if options.minimal {
+            return true;
+        }
+        let ctime_match = !options.trust_ctime
+            || (self.ctime == stat.ctime() as u32 && self.ctime_nsec == stat.ctime_nsec() as u32);
+        ctime_match
+            && self.mtime_nsec == stat.mtime_nsec() as u32
+            && self.ino == stat.ino() as u32
+            && self.uid == stat.uid()
+            && self.gid == stat.gid()
     }
-    pub fn is_stat_match(&self, stat: &Metadata) -> bool {
-        (self.mode == util::get_mode_stat(stat)) &&
-        self.size == (stat.size() as u32) || self.size == 0
+
+    // whether the mode and the size are the ones recorded, a size of 0 being unknown.
+    pub fn is_stat_match(&self, stat: &Metadata, options: StatOptions) -> bool {
+        self.mode == options.mode(self.mode, util::get_mode_stat(stat))
+            && (self.size == 0 || self.size == stat.size() as u32)
     }
     pub fn get_name(&self) -> String {
         let path = self.path.to_path_buf();
@@ -81,6 +171,22 @@ impl EntryAdd {
         let mode = self.mode;
         Ok(mode)
     }
+
+    pub fn stage(&self) -> u16 {
+        (self.flags & STAGE_MASK) >> STAGE_SHIFT
+    }
+
+    pub fn is_skip_worktree(&self) -> bool {
+        self.extended_flags & SKIP_WORKTREE_FLAG != 0
+    }
+
+    fn set_skip_worktree(&mut self, skip: bool) {
+        if skip {
+            self.extended_flags |= SKIP_WORKTREE_FLAG;
+        } else {
+            self.extended_flags &= !SKIP_WORKTREE_FLAG;
+        }
+    }
     pub fn get_data_to_tree(&self) -> Result<Vec<u8>> {
         let mut data = Vec::new();
         let mode = util::get_mode(self.path.to_path_buf())?;
@@ -102,7 +208,10 @@ impl EntryAdd {
         Ok(data)
     }
 
-    fn get_data(&self) -> Result<Vec<u8>> {
+    // the entry as it is written in the index file; a smudged entry gets a size of 0 so its
+    // content is compared the next time.
+    fn get_data(&self, smudge: bool) -> Result<Vec<u8>> {
+        let size = if smudge { 0 } else { self.size };
         let mut data = Vec::new();
         data.extend_from_slice(&self.ctime.to_be_bytes());
         data.extend_from_slice(&self.ctime_nsec.to_be_bytes());
@@ -113,9 +222,14 @@ impl EntryAdd {
         data.extend_from_slice(&self.mode.to_be_bytes());
         data.extend_from_slice(&self.uid.to_be_bytes());
         data.extend_from_slice(&self.gid.to_be_bytes());
-        data.extend_from_slice(&self.size.to_be_bytes());
+        data.extend_from_slice(&size.to_be_bytes());
         data.extend_from_slice(&self.oid);
-        data.extend_from_slice(&self.flags.to_be_bytes());
+        if self.extended_flags == 0 {
+            data.extend_from_slice(&(self.flags & !EXTENDED_FLAG).to_be_bytes());
+        } else {
+            data.extend_from_slice(&(self.flags | EXTENDED_FLAG).to_be_bytes());
+            data.extend_from_slice(&self.extended_flags.to_be_bytes());
+        }
         data.extend_from_slice(&self.path.to_str().expect("unable to get str ref").to_string().as_bytes());
         data.push(0x00);
         while data.len() % ENTRY_BLOCK != 0 {
@@ -125,42 +239,56 @@ impl EntryAdd {
     }
 
     fn create(pathname: PathBuf, oid: Vec<u8>, stat: Metadata) -> Result<Self> {
+        let mut entry = Self::create_without_stat(pathname, oid, util::get_mode_stat(&stat));
+        entry.update_entry_stat(&stat, StatOptions::default());
+        Ok(entry)
+    }
+
+    // An entry whose stat information is unknown, like one taken from a tree that differs
+    // from the file in the working tree. The empty stat data forces the next status to
+    // compare the content of the file.
+    fn create_without_stat(pathname: PathBuf, oid: Vec<u8>, mode: u32) -> Self {
         let path = pathname.to_str().expect("unable to get str ref");
-        let mode =  util::get_mode(pathname.to_path_buf())?;
-        let flags = std::cmp::min(path.bytes().len() as u16, MAX_PATH_SIZE);
-        let entry = EntryAdd {
-            ctime: stat.ctime() as u32,
-            ctime_nsec: stat.ctime_nsec() as u32,
-            mtime: stat.mtime() as u32,
-            mtime_nsec: stat.mtime_nsec() as u32,
-            dev: stat.dev() as u32,
-            ino: stat.ino() as u32,
+        let flags = std::cmp::min(path.len() as u16, MAX_PATH_SIZE);
+        EntryAdd {
+            ctime: 0,
+            ctime_nsec: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            dev: 0,
+            ino: 0,
             mode,
-            uid: stat.uid() as u32,
-            gid: stat.gid() as u32,
-            size: stat.size() as u32,
+            uid: 0,
+            gid: 0,
+            size: 0,
             path: pathname,
             oid,
             flags,
-        };
-        Ok(entry)
+            extended_flags: 0,
+        }
     }
 
     pub fn key(&self) -> String {
         self.path.to_str().expect("unable to get str ref").to_string()
     }
 
-    fn parse(entry: Vec<u8>) -> Result<EntryAdd> {
+    fn parse(entry: Vec<u8>, hash: HashAlgorithm) -> Result<EntryAdd> {
         let mut stats = Vec::new();
         let (numbers_vec, tail) = entry.split_at(40);
-        let (oid, tail) = tail.split_at(20);
+        let (oid, tail) = tail.split_at(hash.size());
         let oid = oid.to_vec();
-        let (mut flag_vec, path_vec) = tail.split_at(2);
+        let (mut flag_vec, tail) = tail.split_at(2);
+        let flags = flag_vec.read_u16::<BigEndian>()?;
+        let (extended_flags, path_vec) = if flags & EXTENDED_FLAG != 0 {
+            let (mut extended_vec, tail) = tail.split_at(2);
+            (extended_vec.read_u16::<BigEndian>()?, tail)
+        } else {
+            (0, tail)
+        };
         let path = String::from_utf8(path_vec.to_vec())?
             .trim_matches(char::from(0))
             .to_string();
         let path = Path::new(&path).to_path_buf();
-        let flags = flag_vec.read_u16::<BigEndian>()?;
         for mut chunk in numbers_vec.chunks_exact(4) {
             stats.push(chunk.read_u32::<BigEndian>()?)
         }
@@ -178,6 +306,7 @@ impl EntryAdd {
             path,
             oid,
             flags,
+            extended_flags,
         };
         Ok(e)
     }
@@ -186,27 +315,46 @@ impl EntryAdd {
 const HEADER_SIZE: usize = 12;
 const SIGNATURE: &str = "DIRC";
 const VERSION: u32 = 2;
-const ENTRY_MIN_SIZE: usize = 64;
+// the version with extended flags, used when an entry has one
+const EXTENDED_VERSION: u32 = 3;
+
+// stat fields, oid, flags and at least one NUL byte padded to the entry block size; 64 bytes
+// for sha1 and 80 for sha256.
+fn entry_min_size(hash: HashAlgorithm) -> usize {
+    (40 + hash.size() + 2 + ENTRY_BLOCK) / ENTRY_BLOCK * ENTRY_BLOCK
+}
+
 impl Index {
     pub fn new(pathname: &PathBuf) -> Self {
+        Index::new_with_hash(pathname, HashAlgorithm::default())
+    }
+
+    pub fn new_with_hash(pathname: &Path, hash: HashAlgorithm) -> Self {
         Index {
             pathname: pathname.to_path_buf(),
+            hash,
             entries: HashMap::new(),
             keys: BTreeSet::new(),
+            unmerged: BTreeMap::new(),
             changed: false,
+            timestamp: None,
+            stat_options: StatOptions::default(),
         }
     }
 
-    fn clear(&mut self) -> Result<()> {
+    pub fn clear(&mut self) -> Result<()> {
         self.entries = HashMap::new();
         self.keys = BTreeSet::new();
+        self.unmerged = BTreeMap::new();
         self.changed = false;
         Ok(())
     }
 
     pub fn load(&mut self) -> Result<()> {
         self.clear()?;
-        let mut reader = Checksum::new(File::open(&self.pathname)?);
+        let file = File::open(&self.pathname)?;
+        self.timestamp = Some(file.metadata()?.mtime() as u32);
+        let mut reader = Checksum::new(file, self.hash);
         let count = &self.read_header(&mut reader)?;
         self.read_entries(&mut reader, *count)?;
         reader.verify_checksum()?;
@@ -214,12 +362,17 @@ impl Index {
     }
 
     pub fn read_entries(&mut self, reader: &mut Checksum, count: u32) -> Result<()> {
+        // the path starts after the flags, and the extended flags when there are some; the
+        // entry ends with the block holding its NUL byte
+        let flags_start = 40 + self.hash.size();
         for _ in 0..count {
-            let mut entry = reader.read(ENTRY_MIN_SIZE, true)?;
-            while *entry.last().unwrap() != 0u8 {
+            let mut entry = reader.read(entry_min_size(self.hash), true)?;
+            let flags = u16::from_be_bytes([entry[flags_start], entry[flags_start + 1]]);
+            let path_start = if flags & EXTENDED_FLAG != 0 { flags_start + 4 } else { flags_start + 2 };
+            while !entry[path_start..].contains(&0u8) {
                 entry.extend_from_slice(&reader.read(ENTRY_BLOCK, true)?)
             }
-            self.store_entry(EntryAdd::parse(entry)?)?;
+            self.store_entry(EntryAdd::parse(entry, self.hash)?)?;
         }
 
         Ok(())
@@ -241,7 +394,7 @@ impl Index {
                 SIGNATURE, signature
             )));
         }
-        if version != VERSION {
+        if version != VERSION && version != EXTENDED_VERSION {
             return Err(anyhow!(format!(
                 "Version: expected: {} but found {}",
                 VERSION, version
@@ -251,6 +404,15 @@ impl Index {
     }
 
     fn store_entry(&mut self, entry: EntryAdd) -> Result<()> {
+        if entry.stage() != 0 {
+            let stages = self.unmerged.entry(entry.key()).or_default();
+            stages.retain(|stage| stage.stage() != entry.stage());
+            stages.push(entry);
+            stages.sort_by_key(|stage| stage.stage());
+            return Ok(());
+        }
+        // a path is resolved once it has an entry of its own
+        self.unmerged.remove(&entry.key());
         //TODO find a better way that cloning the entry
         self.keys.insert(entry.clone().key());
         self.entries.insert(entry.clone().key(), RefCell::new(entry));
@@ -258,13 +420,76 @@ impl Index {
     }
 
     pub fn add(&mut self, pathname: PathBuf, oid: Vec<u8>, stat: Metadata) -> Result<()> {
-        let entry = EntryAdd::create(pathname.clone(), oid, stat)?;
+        let mut mode = util::get_mode_stat(&stat);
+        if let Some(recorded) = pathname.to_str().and_then(|key| self.entries.get(key)) {
+            mode = self.stat_options.mode(recorded.borrow().mode, mode);
+        }
+        self.add_with_mode(pathname, oid, stat, mode)
+    }
+
+    // adds a file whose mode is known, like the one of a tree entry, rather than taken from
+    // its stat data.
+    pub fn add_with_mode(&mut self, pathname: PathBuf, oid: Vec<u8>, stat: Metadata, mode: u32) -> Result<()> {
+        let mut entry = EntryAdd::create(pathname, oid, stat)?;
+        entry.mode = mode;
         self.discard_conflicts(&entry)?;
         self.store_entry(entry)?;
         self.changed = true;
         Ok(())
     }
 
+    pub fn add_without_stat(&mut self, pathname: PathBuf, oid: Vec<u8>, mode: u32) -> Result<()> {
+        let entry = EntryAdd::create_without_stat(pathname, oid, mode);
+        self.discard_conflicts(&entry)?;
+        self.store_entry(entry)?;
+        self.changed = true;
+        Ok(())
+    }
+
+    // Records the entry of `stage` of a path left unmerged by a merge, which replaces the
+    // entry of the path until it is added again.
+    pub fn add_unmerged(&mut self, pathname: PathBuf, stage: u16, oid: Vec<u8>, mode: u32) -> Result<()> {
+        let mut entry = EntryAdd::create_without_stat(pathname, oid, mode);
+        entry.flags |= (stage << STAGE_SHIFT) & STAGE_MASK;
+        self.keys.remove(&entry.key());
+        self.entries.remove(&entry.key());
+        self.store_entry(entry)?;
+        self.changed = true;
+        Ok(())
+    }
+
+    // the paths left unmerged, in order.
+    pub fn unmerged_paths(&self) -> impl Iterator<Item = &String> {
+        self.unmerged.keys()
+    }
+
+    pub fn is_unmerged(&self, path: &str) -> bool {
+        self.unmerged.contains_key(path)
+    }
+
+    // the entry of `stage` of an unmerged path, if that side has the file.
+    pub fn unmerged_entry(&self, path: &str, stage: u16) -> Option<&EntryAdd> {
+        self.unmerged.get(path)?.iter().find(|entry| entry.stage() == stage)
+    }
+
+    // marks the entry of `path` as left out of the working tree, or not anymore.
+    pub fn set_skip_worktree(&mut self, path: &str, skip: bool) {
+        if let Some(entry) = self.entries.get(path) {
+            let mut entry = entry.borrow_mut();
+            if entry.is_skip_worktree() != skip {
+                entry.set_skip_worktree(skip);
+                self.changed = true;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.keys.remove(path);
+        if self.entries.remove(path).is_some() | self.unmerged.remove(path).is_some() {
+            self.changed = true;
+        }
+    }
+
     pub fn discard_conflicts(&mut self, entry: &EntryAdd) -> Result<()> {
         for parent in entry.path.ancestors() {
             let parent_str = parent.to_str().ok_or(anyhow!("unable to get parent filename"))?;
@@ -272,14 +497,16 @@ impl Index {
             self.entries.remove(parent_str);
         }
         let entry_name = entry.path.to_str().ok_or(anyhow!("unable to get filename"))?;
+        let dir_prefix = format!("{}/", entry_name);
 
         // TODO these two methods iterate over all the elements which can be really expensive
         // depending on how many keys the collections have.
         // The alternative would be to create another HashMap where it should be stored:
         // {Parent -> [Child]}, so if it would be a conflicting file name with a dir name, it
         // would be necessary to get the corresponding [child] list to remove from the self.entries.
-        self.entries.retain(|k, _| !k.contains(entry_name));
-        self.keys.retain(|k| !k.contains(entry_name));
+        self.entries.retain(|k, _| !k.starts_with(&dir_prefix));
+        self.keys.retain(|k| !k.starts_with(&dir_prefix));
+        self.unmerged.retain(|k, _| !k.starts_with(&dir_prefix));
         Ok(())
     }
 
@@ -301,33 +528,95 @@ impl Index {
         Ok(entries)
     }
 
+    // Writes the index when it changed. Entries whose file was modified in the same second
+    // as the index file are racy: a later change in that second would keep the same stat data.
+    // They are written smudged, so the next check compares their content.
     pub fn write_updates(&self) -> Result<()> {
         if self.changed {
-            let mut data = Vec::new();
-            let mode: i32 = 2;
-            let len: i32 = *&self.entries.len() as i32;
-            data.extend_from_slice("DIRC".as_bytes());
-            data.extend_from_slice(&mode.to_be_bytes());
-            data.extend_from_slice(&len.to_be_bytes());
-            let entries = self.each_entry()?;
-            for v in entries {
-                data.extend_from_slice(&v.get_data()?);
+            self.write_entries(None)?;
+            let written = fs::metadata(&self.pathname)?.mtime() as u32;
+            let racy = self
+                .each_entry()?
+                .iter()
+                .any(|entry| entry.size != 0 && is_racy_at(entry.mtime, written));
+            if racy {
+                self.write_entries(Some(written))?;
             }
-            let oid = util::hexdigest_vec(&data);
-            let mut data_to_write = data;
-            data_to_write.extend_from_slice(&oid);
-
-            let mut file = OpenOptions::new()
-                .read(true)
-                .create(true)
-                .write(true)
-                .open(&self.pathname)?;
+        }
+        Ok(())
+    }
 
-            file.write_all(&data_to_write)?;
+    fn write_entries(&self, smudge_from: Option<u32>) -> Result<()> {
+        let mut data = Vec::new();
+        let merged = self.each_entry()?;
+        // the stages of the unmerged paths are written in the order of the paths
+        let mut entries: Vec<&EntryAdd> = merged
+            .iter()
+            .map(|entry| &**entry)
+            .chain(self.unmerged.values().flatten())
+            .collect();
+        entries.sort_by(|a, b| {
+            let path = |entry: &EntryAdd| entry.path.as_os_str().as_bytes().to_vec();
+            path(a).cmp(&path(b)).then(a.stage().cmp(&b.stage()))
+        });
+        let version = if entries.iter().any(|entry| entry.extended_flags != 0) {
+            EXTENDED_VERSION
+        } else {
+            VERSION
+        };
+        let len: i32 = entries.len() as i32;
+        data.extend_from_slice("DIRC".as_bytes());
+        data.extend_from_slice(&version.to_be_bytes());
+        data.extend_from_slice(&len.to_be_bytes());
+        for v in entries {
+            let smudge = smudge_from.is_some_and(|timestamp| is_racy_at(v.mtime, timestamp));
+            data.extend_from_slice(&v.get_data(smudge)?);
         }
+        let oid = util::hexdigest_vec(&data, self.hash);
+        let mut data_to_write = data;
+        data_to_write.extend_from_slice(&oid);
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.pathname)?;
+
+        file.write_all(&data_to_write)?;
         Ok(())
     }
 
+    pub fn set_stat_options(&mut self, options: StatOptions) {
+        self.stat_options = options;
+    }
+
+    pub fn stat_options(&self) -> StatOptions {
+        self.stat_options
+    }
+
+    // whether the file of `entry` may have changed in the same second the index was written,
+    // after its stat data was recorded.
+    pub fn is_racy(&self, entry: &EntryAdd) -> bool {
+        self.timestamp.is_some_and(|timestamp| is_racy_at(entry.mtime, timestamp))
+    }
+
+    // Compares the file of `entry` to it using the stat data alone. A different mode or size
+    // means the file changed; an unknown size, different times or a racy entry leave it to the
+    // content.
+    pub fn stat_change(&self, entry: &EntryAdd, stat: &Metadata) -> StatChange {
+        if !entry.is_stat_match(stat, self.stat_options) {
+            return StatChange::Changed;
+        }
+        if entry.size == 0 && stat.size() != 0 {
+            return StatChange::Unknown;
+        }
+        if !entry.stat_data_match(stat, self.stat_options) || self.is_racy(entry) {
+            return StatChange::Unknown;
+        }
+        StatChange::Unchanged
+    }
+
     pub fn is_tracked(&self, path: PathBuf) -> bool {
         // this checks for filename or dirs
         // self.entries.contains_key(&path.to_str().unwrap().to_string())
@@ -335,11 +624,18 @@ impl Index {
         // is neccesary to create an aux data structure to save parent directories.  
         // any is a short-circuting function therefore will stop when the closure returns a true.
         let entry_name = path.to_str().expect("Unable to get &str reference from Path");
-        self.is_tracked_file(path.display().to_string().as_str()) || self.entries.iter().any(|(key, _)| key.contains(entry_name))
+        self.is_tracked_file(path.display().to_string().as_str())
+            || self.entries.iter().any(|(key, _)| key.contains(entry_name))
+            || self.unmerged.keys().any(|key| key.contains(entry_name))
     }
 
     pub fn is_tracked_file(&self, file: &str) -> bool {
-        self.entries.contains_key(file)
+        self.entries.contains_key(file) || self.unmerged.contains_key(file)
+    }
+
+    // the mode of the entry of `path`, if it is tracked.
+    pub fn entry_mode(&self, path: &str) -> Option<u32> {
+        self.entries.get(path).map(|entry| entry.borrow().mode)
     }
 
     pub fn update_changed_status(&mut self) -> () {
@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::util;
+use crate::HashAlgorithm;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+// The small file committed in place of a large one: the sha256 of its content and its size,
+// in the format of Git LFS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+    pub oid: String,
+    pub size: u64,
+}
+
+impl Pointer {
+    pub fn new(data: &[u8]) -> Self {
+        Pointer {
+            oid: util::encode_vec(&HashAlgorithm::Sha256.digest(data)),
+            size: data.len() as u64,
+        }
+    }
+
+    // reads a pointer, any other content is `None`.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.strip_suffix('\n')?.split('\n');
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let oid = lines.next()?.strip_prefix("oid sha256:")?;
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        if lines.next().is_some() || !HashAlgorithm::Sha256.is_valid_hex(oid) {
+            return None;
+        }
+        Some(Pointer {
+            oid: oid.to_string(),
+            size,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format!("{}\noid sha256:{}\nsize {}\n", POINTER_VERSION, self.oid, self.size).into_bytes()
+    }
+}
+
+// The content of the large files, named by their sha256 under `lfs/objects` of the git
+// directory, as the built-in `lfs` filter stores them.
+#[derive(Debug, Clone)]
+pub struct LfsStore {
+    dir: PathBuf,
+}
+
+impl LfsStore {
+    pub fn new(git_dir: &Path) -> Self {
+        LfsStore {
+            dir: git_dir.join("lfs").join("objects"),
+        }
+    }
+
+    pub fn object_path(&self, oid: &str) -> PathBuf {
+        self.dir.join(&oid[0..2]).join(&oid[2..4]).join(oid)
+    }
+
+    // Stores `data` and returns the pointer to commit instead. Content which already is a
+    // pointer is left as it is.
+    pub fn clean(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        if Pointer::parse(&data).is_some() {
+            return Ok(data);
+        }
+        let pointer = Pointer::new(&data);
+        let path = self.object_path(&pointer.oid);
+        if !path.exists() {
+            let dir = path.parent().unwrap_or(&self.dir);
+            fs::create_dir_all(dir)?;
+            let temp_path = dir.join(format!("{}.tmp", pointer.oid));
+            fs::write(&temp_path, &data)?;
+            // the same content may be stored by another thread at the same time
+            if let Err(e) = fs::rename(&temp_path, &path) {
+                if !path.exists() {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(pointer.to_bytes())
+    }
+
+    // Replaces a pointer with the content it points to. A pointer whose content is not in
+    // the store is checked out as it is.
+    pub fn smudge(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match Pointer::parse(&data) {
+            Some(pointer) if self.object_path(&pointer.oid).exists() => Ok(fs::read(self.object_path(&pointer.oid))?),
+            _ => Ok(data),
+        }
+    }
+}
@@ -1,13 +1,30 @@
+pub mod attributes;
 mod author;
+pub mod blame;
 mod blob;
 mod checksum;
 mod commit;
+mod config;
+mod convert;
 mod database;
+pub mod diff;
+mod editor;
 mod entry;
+pub mod filter;
+mod hash;
+pub mod hooks;
 mod index;
+pub mod lfs;
+pub mod merge;
 mod object;
 mod refs;
+mod repository;
+mod revision;
+pub mod signing;
+pub mod sparse;
+mod tag;
 mod tree;
+pub mod tree_diff;
 mod command;
 pub mod util;
 mod workspace;
@@ -15,12 +32,19 @@ pub use author::Author;
 pub use blob::Blob;
 pub use checksum::Checksum;
 pub use commit::Commit;
+pub use config::Config;
 pub use database::Database;
+pub use editor::Cleanup;
 pub use entry::Entry;
+pub use hash::HashAlgorithm;
 pub use index::EntryAdd;
 pub use index::Index;
+pub use index::{StatChange, StatOptions};
 pub use object::Object;
 pub use refs::Refs;
+pub use repository::Repository;
+pub use revision::Revision;
+pub use tag::Tag;
 pub use tree::Tree;
 pub use workspace::Workspace;
-pub use command::Command;
+pub use command::{CatFileMode, Command, CommitOptions, DiffFormat, RestoreOptions, SequencerAction, StatusFormat, Worktree};
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::diff::{self, EditKind};
+
+// A chunk of a three-way merge: lines every side agrees on, or a region where the two sides
+// changed the base differently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chunk<'a> {
+    Clean(Vec<&'a [u8]>),
+    Conflict {
+        base: Vec<&'a [u8]>,
+        ours: Vec<&'a [u8]>,
+        theirs: Vec<&'a [u8]>,
+    },
+}
+
+pub struct Merge3<'a> {
+    pub chunks: Vec<Chunk<'a>>,
+}
+
+impl<'a> Merge3<'a> {
+    pub fn is_clean(&self) -> bool {
+        self.chunks.iter().all(|c| matches!(c, Chunk::Clean(_)))
+    }
+
+    // the merged content, conflicts are written between markers naming each side.
+    pub fn to_bytes(&self, ours_name: &str, theirs_name: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in self.chunks.iter() {
+            match chunk {
+                Chunk::Clean(lines) => lines.iter().for_each(|l| out.extend_from_slice(l)),
+                Chunk::Conflict { ours, theirs, .. } => {
+                    out.extend_from_slice(format!("<<<<<<< {}\n", ours_name).as_bytes());
+                    write_side(&mut out, ours);
+                    out.extend_from_slice(b"=======\n");
+                    write_side(&mut out, theirs);
+                    out.extend_from_slice(format!(">>>>>>> {}\n", theirs_name).as_bytes());
+                }
+            }
+        }
+        out
+    }
+}
+
+fn write_side(out: &mut Vec<u8>, lines: &[&[u8]]) {
+    for line in lines {
+        out.extend_from_slice(line);
+    }
+    if lines.last().is_some_and(|l| !l.ends_with(b"\n")) {
+        out.push(b'\n');
+    }
+}
+
+// for every line of the base kept by the other side, the index it has there.
+fn matches(base: &[&[u8]], other: &[&[u8]]) -> HashMap<usize, usize> {
+    diff::diff(base, other)
+        .into_iter()
+        .filter(|e| e.kind == EditKind::Eql)
+        .filter_map(|e| Some((e.a_index?, e.b_index?)))
+        .collect()
+}
+
+// Merges the changes `ours` and `theirs` made to `base` with the diff3 algorithm: the regions
+// where both sides keep the same base lines are aligned and whatever is between them is
+// taken from the side that changed it, or is a conflict when both did.
+pub fn merge3<'a>(base: &'a [u8], ours: &'a [u8], theirs: &'a [u8]) -> Merge3<'a> {
+    let (o, a, b) = (diff::lines(base), diff::lines(ours), diff::lines(theirs));
+    let (match_a, match_b) = (matches(&o, &a), matches(&o, &b));
+    let mut chunks = Vec::new();
+    let (mut line_o, mut line_a, mut line_b) = (0, 0, 0);
+    loop {
+        // the number of lines from the current position that are the same on every side
+        let mut i = 0;
+        while line_o + i < o.len()
+            && match_a.get(&(line_o + i)) == Some(&(line_a + i))
+            && match_b.get(&(line_o + i)) == Some(&(line_b + i))
+        {
+            i += 1;
+        }
+        if i > 0 {
+            chunks.push(Chunk::Clean(o[line_o..line_o + i].to_vec()));
+            line_o += i;
+            line_a += i;
+            line_b += i;
+            continue;
+        }
+        // the next base line both sides kept closes the differing region
+        let next = (line_o..o.len()).find(|n| match_a.contains_key(n) && match_b.contains_key(n));
+        let (end_o, end_a, end_b) = match next {
+            Some(n) => (n, match_a[&n], match_b[&n]),
+            None => (o.len(), a.len(), b.len()),
+        };
+        if (end_o, end_a, end_b) == (line_o, line_a, line_b) {
+            break;
+        }
+        chunks.push(differing_chunk(
+            &o[line_o..end_o],
+            &a[line_a..end_a],
+            &b[line_b..end_b],
+        ));
+        line_o = end_o;
+        line_a = end_a;
+        line_b = end_b;
+    }
+    Merge3 { chunks }
+}
+
+fn differing_chunk<'a>(o: &[&'a [u8]], a: &[&'a [u8]], b: &[&'a [u8]]) -> Chunk<'a> {
+    if a == o {
+        Chunk::Clean(b.to_vec())
+    } else if b == o || a == b {
+        Chunk::Clean(a.to_vec())
+    } else {
+        Chunk::Conflict {
+            base: o.to_vec(),
+            ours: a.to_vec(),
+            theirs: b.to_vec(),
+        }
+    }
+}
@@ -1,37 +1,348 @@
 use std::{
     fs::{self, OpenOptions},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
+use anyhow::anyhow;
 use anyhow::Result;
 
+use crate::hooks::Hooks;
+
+// HEAD and the pseudo refs belong to a working tree and live in its git directory, while the
+// branches and tags under `refs/` are shared by every worktree through the common directory.
 pub struct Refs {
     pathname: PathBuf,
+    common_dir: PathBuf,
+    // the hooks told about each update, through `reference-transaction`
+    hooks: Option<Hooks>,
+}
+
+const SYMREF_PREFIX: &str = "ref: ";
+pub const HEAD: &str = "HEAD";
+
+// One line of a reflog: the values of the ref before and after an update, who made it and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old_oid: String,
+    pub new_oid: String,
+    pub identity: String,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let (head, message) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = head.splitn(3, ' ');
+        Some(ReflogEntry {
+            old_oid: parts.next()?.to_string(),
+            new_oid: parts.next()?.to_string(),
+            identity: parts.next()?.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{} {} {}\t{}\n", self.old_oid, self.new_oid, self.identity, self.message)
+    }
+}
+
+// What a ref file contains: either an oid or the name of another ref.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefValue {
+    Oid(String),
+    Symbolic(String),
 }
 
 impl Refs {
     pub fn new(path_buf: &PathBuf) -> Self {
         Refs {
             pathname: path_buf.into(),
+            common_dir: path_buf.into(),
+            hooks: None,
+        }
+    }
+
+    pub fn with_common_dir(git_dir: &Path, common_dir: &Path) -> Self {
+        Refs {
+            pathname: git_dir.to_path_buf(),
+            common_dir: common_dir.to_path_buf(),
+            hooks: None,
         }
     }
 
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = Some(hooks);
+    }
+
+    // updates the branch HEAD points to, or HEAD itself when it is detached.
     pub fn update_head(&self, oid: String) -> Result<()> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .create(true)
-            .write(true)
-            .open(&self.head_path())?;
-        let res = file.write_all(&oid.as_bytes().to_vec())?;
-        Ok(res)
+        let name = self.resolve_symbolic(HEAD)?;
+        self.update_ref(&name, &oid)
     }
 
     pub fn read_head(&self) -> Option<String> {
-        fs::read_to_string(&self.head_path()).ok()
+        self.resolve(HEAD)
     }
 
     pub fn head_path(&self) -> PathBuf {
-        self.pathname.join("HEAD")
+        self.pathname.join(HEAD)
+    }
+
+    pub fn refs_path(&self) -> PathBuf {
+        self.common_dir.join("refs")
+    }
+
+    pub fn ref_path(&self, name: &str) -> PathBuf {
+        if is_per_worktree_ref(name) {
+            self.pathname.join(name)
+        } else {
+            self.common_dir.join(name)
+        }
+    }
+
+    pub fn read_ref_value(&self, name: &str) -> Option<RefValue> {
+        let content = fs::read_to_string(self.ref_path(name)).ok()?;
+        let content = content.trim();
+        match content.strip_prefix(SYMREF_PREFIX) {
+            Some(target) => Some(RefValue::Symbolic(target.trim().to_string())),
+            None if content.is_empty() => None,
+            None => Some(RefValue::Oid(content.to_string())),
+        }
+    }
+
+    // follows symbolic refs until an oid is found, `None` when the chain ends in a ref that
+    // does not exist yet, like the branch of a repository without commits.
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            match self.read_ref_value(&name)? {
+                RefValue::Oid(oid) => return Some(oid),
+                RefValue::Symbolic(target) => name = target,
+            }
+        }
+        None
+    }
+
+    // the last ref of a chain of symbolic refs, which is the one that has to be updated.
+    pub fn resolve_symbolic(&self, name: &str) -> Result<String> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            match self.read_ref_value(&name) {
+                Some(RefValue::Symbolic(target)) => name = target,
+                _ => return Ok(name),
+            }
+        }
+        Err(anyhow!("too many levels of symbolic refs at {}", name))
+    }
+
+    // the branch HEAD points to, e.g. `refs/heads/master`, or `None` if it is detached.
+    pub fn current_ref(&self) -> Option<String> {
+        match self.read_ref_value(HEAD) {
+            Some(RefValue::Symbolic(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn update_ref(&self, name: &str, oid: &str) -> Result<()> {
+        check_ref_name(name)?;
+        let old_oid = self.resolve(name).unwrap_or_else(|| zero_oid(oid));
+        self.transaction(&old_oid, oid, name, || self.write_ref_file(name, &format!("{}\n", oid)))
+    }
+
+    pub fn set_symbolic_ref(&self, name: &str, target: &str) -> Result<()> {
+        check_ref_name(target)?;
+        self.write_ref_file(name, &format!("{}{}\n", SYMREF_PREFIX, target))
+    }
+
+    pub fn delete_ref(&self, name: &str) -> Result<()> {
+        check_ref_name(name)?;
+        let path = self.ref_path(name);
+        if !path.exists() {
+            return Err(anyhow!("ref {} does not exist", name));
+        }
+        let old_oid = self.resolve(name).unwrap_or_default();
+        self.transaction(&old_oid, &zero_oid(&old_oid), name, || Ok(fs::remove_file(&path)?))?;
+        // remove the directories that became empty, up to `refs`
+        let mut dir = path.parent();
+        while let Some(d) = dir {
+            if d == self.refs_path() || fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+        Ok(())
+    }
+
+    // Makes the update of `name` from `old_oid` to `new_oid` with `update`, once the
+    // `reference-transaction` hook accepted it in the `prepared` state. The hook is then told
+    // whether the update was `committed` or `aborted`.
+    fn transaction<F>(&self, old_oid: &str, new_oid: &str, name: &str, update: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let hooks = match &self.hooks {
+            Some(hooks) => hooks,
+            None => return update(),
+        };
+        let input = format!("{} {} {}\n", old_oid, new_oid, name);
+        let run = |state: &str| hooks.run("reference-transaction", &[state], Some(input.as_bytes()));
+        if !run("prepared")? {
+            run("aborted")?;
+            return Err(anyhow!("in 'prepared' phase, update aborted by the reference-transaction hook"));
+        }
+        match update() {
+            Ok(()) => {
+                run("committed")?;
+                Ok(())
+            }
+            Err(e) => {
+                run("aborted")?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn reflog_path(&self, name: &str) -> PathBuf {
+        if is_per_worktree_ref(name) {
+            self.pathname.join("logs").join(name)
+        } else {
+            self.common_dir.join("logs").join(name)
+        }
+    }
+
+    // the entries of the reflog of `name`, oldest first.
+    pub fn read_reflog(&self, name: &str) -> Result<Vec<ReflogEntry>> {
+        let path = self.reflog_path(name);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .filter_map(ReflogEntry::parse)
+            .collect())
+    }
+
+    // records an update of `name`; `identity` is formatted like the author of a commit.
+    pub fn append_reflog(&self, name: &str, old_oid: Option<&str>, new_oid: &str, identity: &str, message: &str) -> Result<()> {
+        let path = self.reflog_path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let entry = ReflogEntry {
+            old_oid: old_oid.map_or_else(|| "0".repeat(new_oid.len()), |oid| oid.to_string()),
+            new_oid: new_oid.to_string(),
+            identity: identity.to_string(),
+            // the message is a single line in the log
+            message: message.lines().next().unwrap_or("").to_string(),
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(entry.to_line().as_bytes())?;
+        Ok(())
+    }
+
+    // replaces the whole reflog of `name`, the log is deleted when there are no entries left.
+    pub fn write_reflog(&self, name: &str, entries: &[ReflogEntry]) -> Result<()> {
+        let path = self.reflog_path(name);
+        if entries.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        let content: String = entries.iter().map(|e| e.to_line()).collect();
+        let lock_path = lock_path(&path);
+        fs::write(&lock_path, content)?;
+        fs::rename(&lock_path, &path)?;
+        Ok(())
+    }
+
+    // Writes the new value to `<ref>.lock` first and renames it over the ref, so readers
+    // never see a partially written ref and concurrent writers fail instead of racing.
+    fn write_ref_file(&self, name: &str, content: &str) -> Result<()> {
+        let path = self.ref_path(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock_path = lock_path(&path);
+        let mut lock = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&lock_path)
+            .map_err(|_| anyhow!("unable to create '{}': File exists", lock_path.display()))?;
+        let written = lock.write_all(content.as_bytes());
+        if let Err(e) = written {
+            fs::remove_file(&lock_path)?;
+            return Err(e.into());
+        }
+        fs::rename(&lock_path, &path)?;
+        Ok(())
+    }
+
+    // walks `.git/refs` and returns every ref name with the oid it points to.
+    pub fn list_refs(&self) -> Result<Vec<(String, String)>> {
+        let mut refs = Vec::new();
+        let mut work = vec![self.refs_path()];
+        while let Some(dir) = work.pop() {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    work.push(path);
+                } else {
+                    let name = path
+                        .strip_prefix(&self.common_dir)?
+                        .display()
+                        .to_string();
+                    if name.ends_with(".lock") {
+                        continue;
+                    }
+                    if let Some(oid) = self.resolve(&name) {
+                        refs.push((name, oid));
+                    }
+                }
+            }
+        }
+        refs.sort();
+        Ok(refs)
+    }
+}
+
+const MAX_SYMREF_DEPTH: usize = 5;
+
+// the all-zero oid standing for a missing ref, as long as `oid`.
+fn zero_oid(oid: &str) -> String {
+    "0".repeat(oid.len().max(40))
+}
+
+fn is_per_worktree_ref(name: &str) -> bool {
+    !name.starts_with("refs/") || name.starts_with("refs/worktree/") || name.starts_with("refs/bisect/")
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+// a subset of the rules of `git check-ref-format`.
+pub fn check_ref_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && (name == HEAD || name.starts_with("refs/"))
+        && !name.ends_with('/')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("//")
+        && !name.contains("@{")
+        && !name.split('/').any(|c| c.is_empty() || c.starts_with('.'))
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c));
+    if !valid {
+        return Err(anyhow!("'{}' is not a valid ref name", name));
     }
+    Ok(())
 }
@@ -0,0 +1,255 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::refs::HEAD;
+use crate::util;
+use crate::{Config, Refs};
+
+// Where the repository used by a command lives: its git directory, the directory holding
+// the objects, refs and config shared by every working tree, the top of its working tree, if
+// it is not bare, and the directory the command was started from, relative to that working
+// tree. `common_dir` is the git directory itself except for linked worktrees.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    pub git_dir: PathBuf,
+    pub common_dir: PathBuf,
+    pub work_tree: Option<PathBuf>,
+    pub prefix: PathBuf,
+}
+
+const NOT_A_REPOSITORY: &str = "not a git repository (or any parent up to mount point /)";
+
+impl Repository {
+    // Finds the repository for `cwd` the same way git does: `GIT_DIR` wins when it is set,
+    // otherwise every directory from `cwd` up to the root, or up to the closest directory
+    // listed in `GIT_CEILING_DIRECTORIES`, is checked for a `.git` directory or a `.git` file
+    // pointing to one, or for being a bare repository itself. `core.bare` removes the working
+    // tree, while `GIT_WORK_TREE` and `core.worktree` override it.
+    pub fn discover(cwd: &Path) -> Result<Self> {
+        let cwd = util::normalize_path(cwd);
+        let (git_dir, mut work_tree) = match env::var_os("GIT_DIR") {
+            Some(git_dir) => {
+                let git_dir = util::normalize_path(&cwd.join(git_dir));
+                if !is_git_dir(&git_dir) {
+                    return Err(anyhow!("not a git repository: '{}'", git_dir.display()));
+                }
+                // without any other hint the current directory is the top of the working tree
+                (git_dir, Some(cwd.to_path_buf()))
+            }
+            None => find_git_dir(&cwd)?,
+        };
+
+        let common_dir = common_dir(&git_dir)?;
+        work_tree = configured_work_tree(&git_dir, &common_dir, work_tree)?;
+        if let Some(path) = env::var_os("GIT_WORK_TREE") {
+            work_tree = Some(util::normalize_path(&cwd.join(path)));
+        }
+
+        let prefix = work_tree
+            .as_ref()
+            .and_then(|work_tree| cwd.strip_prefix(work_tree).ok())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        Ok(Repository {
+            git_dir,
+            common_dir,
+            work_tree,
+            prefix,
+        })
+    }
+
+    // Opens the repository at `path`, a working tree or a bare repository, without searching
+    // the parent directories or looking at the environment, as needed for the receiving side
+    // of a push.
+    pub fn open(path: &Path) -> Result<Self> {
+        let path = util::normalize_path(path);
+        let dot_git = path.join(".git");
+        let (git_dir, work_tree) = if dot_git.is_dir() && is_git_dir(&dot_git) {
+            (dot_git, Some(path))
+        } else if dot_git.is_file() {
+            (read_gitfile(&dot_git)?, Some(path))
+        } else if is_bare_git_dir(&path) {
+            (path, None)
+        } else {
+            return Err(anyhow!("'{}' does not appear to be a git repository", path.display()));
+        };
+        let common_dir = common_dir(&git_dir)?;
+        let work_tree = configured_work_tree(&git_dir, &common_dir, work_tree)?;
+        Ok(Repository {
+            git_dir,
+            common_dir,
+            work_tree,
+            prefix: PathBuf::new(),
+        })
+    }
+
+    // the layout of a repository created at `path`, without any discovery.
+    pub fn at(path: &Path) -> Self {
+        Repository {
+            git_dir: path.join(".git"),
+            common_dir: path.join(".git"),
+            work_tree: Some(path.to_path_buf()),
+            prefix: PathBuf::new(),
+        }
+    }
+
+    // a bare repository stored directly at `path`.
+    pub fn bare(path: &Path) -> Self {
+        Repository {
+            git_dir: path.to_path_buf(),
+            common_dir: path.to_path_buf(),
+            work_tree: None,
+            prefix: PathBuf::new(),
+        }
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.work_tree.is_none()
+    }
+
+    // whether this is a worktree added with `worktree add` rather than the main one.
+    pub fn is_linked_worktree(&self) -> bool {
+        self.git_dir != self.common_dir
+    }
+
+    pub fn work_tree(&self) -> Result<&PathBuf> {
+        self.work_tree
+            .as_ref()
+            .ok_or(anyhow!("this operation must be run in a work tree"))
+    }
+
+    // Turns a path given by the user, relative to the directory the command was started
+    // from, into a path relative to the top of the working tree.
+    pub fn resolve_path(&self, path: &Path) -> Result<PathBuf> {
+        let work_tree = self.work_tree()?;
+        let absolute = util::normalize_path(&work_tree.join(&self.prefix).join(path));
+        let relative = absolute.strip_prefix(work_tree).map_err(|_| {
+            anyhow!("{}: '{}' is outside repository", path.display(), absolute.display())
+        })?;
+        if relative.as_os_str().is_empty() {
+            Ok(PathBuf::from("."))
+        } else {
+            Ok(relative.to_path_buf())
+        }
+    }
+
+    // Formats a path relative to the top of the working tree as a path relative to the
+    // directory the command was started from, for output.
+    pub fn display_path(&self, path: &str) -> String {
+        let trailing_slash = path.ends_with('/');
+        let relative = util::relative_path(Path::new(path), &self.prefix);
+        let mut display = relative.display().to_string();
+        if trailing_slash && !display.ends_with('/') {
+            display.push('/');
+        }
+        display
+    }
+}
+
+// The commit checked out in the repository whose working tree is `path`, which is what the
+// gitlink of a submodule records. `None` when there is no repository there or it has no
+// commit yet.
+pub fn checked_out_commit(path: &Path) -> Option<String> {
+    let repository = Repository::open(path).ok()?;
+    Refs::with_common_dir(&repository.git_dir, &repository.common_dir).resolve(HEAD)
+}
+
+pub fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() || (path.join("objects").is_dir() && path.join("refs").is_dir())
+}
+
+// the admin directory of a linked worktree names the git directory it shares objects, refs
+// and config with in its `commondir` file.
+pub fn common_dir(git_dir: &Path) -> Result<PathBuf> {
+    let path = git_dir.join("commondir");
+    if !path.is_file() {
+        return Ok(git_dir.to_path_buf());
+    }
+    let content = fs::read_to_string(&path)?;
+    Ok(util::normalize_path(&git_dir.join(content.trim())))
+}
+
+// The working tree once `core.bare` and `core.worktree` are taken into account. Those
+// settings describe the main working tree, so they are ignored by linked worktrees.
+fn configured_work_tree(
+    git_dir: &Path,
+    common_dir: &Path,
+    work_tree: Option<PathBuf>,
+) -> Result<Option<PathBuf>> {
+    if git_dir != common_dir {
+        return Ok(work_tree);
+    }
+    let mut config = Config::new(&git_dir.join("config"));
+    config.load()?;
+    if let Some(path) = config.get("core.worktree") {
+        return Ok(Some(util::normalize_path(&git_dir.join(path))));
+    }
+    if config.get_bool("core.bare") == Some(true) {
+        return Ok(None);
+    }
+    Ok(work_tree)
+}
+
+// a directory is only taken for a bare repository when it has every piece of one.
+fn is_bare_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+// reads the `gitdir: <path>` line of a `.git` file, relative paths are resolved from the
+// directory containing the file.
+pub fn read_gitfile(path: &Path) -> Result<PathBuf> {
+    let content = fs::read_to_string(path)?;
+    let target = content
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("gitdir:"))
+        .ok_or(anyhow!("invalid gitfile format: {}", path.display()))?
+        .trim();
+    let base = path.parent().unwrap_or_else(|| Path::new("/"));
+    Ok(util::normalize_path(&base.join(target)))
+}
+
+fn ceiling_directories() -> Vec<PathBuf> {
+    env::var("GIT_CEILING_DIRECTORIES")
+        .map(|value| {
+            value
+                .split(':')
+                .filter(|dir| !dir.is_empty() && Path::new(dir).is_absolute())
+                .map(|dir| util::normalize_path(Path::new(dir)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn find_git_dir(cwd: &Path) -> Result<(PathBuf, Option<PathBuf>)> {
+    let ceilings = ceiling_directories();
+    // the deepest ceiling above the current directory stops the search before reaching it
+    let ceiling = ceilings
+        .iter()
+        .filter(|ceiling| cwd.starts_with(ceiling) && cwd != ceiling.as_path())
+        .max_by_key(|ceiling| ceiling.components().count());
+    for dir in cwd.ancestors() {
+        if ceiling.is_some_and(|ceiling| dir == ceiling.as_path()) {
+            break;
+        }
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() && is_git_dir(&dot_git) {
+            return Ok((dot_git, Some(dir.to_path_buf())));
+        }
+        if dot_git.is_file() {
+            let git_dir = read_gitfile(&dot_git)?;
+            if !is_git_dir(&git_dir) {
+                return Err(anyhow!("not a git repository: {}", git_dir.display()));
+            }
+            return Ok((git_dir, Some(dir.to_path_buf())));
+        }
+        if is_bare_git_dir(dir) {
+            return Ok((dir.to_path_buf(), None));
+        }
+    }
+    Err(anyhow!(NOT_A_REPOSITORY))
+}
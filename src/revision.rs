@@ -0,0 +1,179 @@
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::database::ObjectType;
+use crate::refs::HEAD;
+use crate::{util, Database, Refs};
+
+// Resolves the revision expressions accepted by the commands: full or abbreviated oids, ref
+// names, `@`, the `^`, `^<n>` and `~<n>` suffixes and `<rev>:<path>` to name an entry of the
+// tree of a commit.
+pub struct Revision<'a> {
+    refs: &'a Refs,
+    db: &'a mut Database,
+}
+
+const MIN_ABBREV: usize = 4;
+
+impl<'a> Revision<'a> {
+    pub fn new(refs: &'a Refs, db: &'a mut Database) -> Self {
+        Revision { refs, db }
+    }
+
+    pub fn resolve(&mut self, expr: &str) -> Result<String> {
+        if let Some((rev, path)) = expr.split_once(':') {
+            let rev = if rev.is_empty() { HEAD } else { rev };
+            let oid = self.resolve(rev)?;
+            let tree = self.peel_to_tree(&oid)?;
+            return self.lookup_path(&tree, path);
+        }
+        let split = expr.find(['^', '~']).unwrap_or(expr.len());
+        let (name, mut suffix) = expr.split_at(split);
+        let mut oid = self.resolve_name(name)?;
+        while !suffix.is_empty() {
+            let op = suffix.as_bytes()[0];
+            let digits = suffix[1..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(suffix.len(), |n| n + 1);
+            let count = match &suffix[1..digits] {
+                "" => 1,
+                n => n.parse::<usize>()?,
+            };
+            suffix = &suffix[digits..];
+            oid = match op {
+                b'^' if count == 0 => self.peel_to_commit(&oid)?,
+                b'^' if count == 1 => self.parent(&oid)?,
+                b'^' => return Err(anyhow!("commit {} has no parent number {}", oid, count)),
+                _ => {
+                    for _ in 0..count {
+                        oid = self.parent(&oid)?;
+                    }
+                    oid
+                }
+            };
+        }
+        Ok(oid)
+    }
+
+    // resolves an expression that has to name a commit
+    pub fn resolve_commit(&mut self, expr: &str) -> Result<String> {
+        let oid = self.resolve(expr)?;
+        self.peel_to_commit(&oid)
+            .map_err(|_| anyhow!("'{}' is not a commit", expr))
+    }
+
+    fn resolve_name(&mut self, name: &str) -> Result<String> {
+        let name = if name == "@" { HEAD } else { name };
+        if let Some((base, entry)) = name.split_once("@{") {
+            return self.resolve_reflog_entry(base, entry);
+        }
+        let hash = self.db.hash_algorithm();
+        if hash.is_valid_hex(name) {
+            return Ok(name.to_string());
+        }
+        if let Some(oid) = self.full_ref_name(name).and_then(|r| self.refs.resolve(&r)) {
+            return Ok(oid);
+        }
+        if name.len() >= MIN_ABBREV && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            let matches = self
+                .db
+                .list_objects()?
+                .into_iter()
+                .filter(|oid| oid.starts_with(&name.to_lowercase()))
+                .collect::<Vec<_>>();
+            match matches.len() {
+                1 => return Ok(matches[0].to_string()),
+                n if n > 1 => return Err(anyhow!("short object ID {} is ambiguous", name)),
+                _ => {}
+            }
+        }
+        Err(anyhow!(
+            "ambiguous argument '{}': unknown revision or path not in the working tree",
+            name
+        ))
+    }
+
+    // the first existing ref among the places git looks for a short name like `master`.
+    pub fn full_ref_name(&self, name: &str) -> Option<String> {
+        let candidates = [
+            name.to_string(),
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+            format!("refs/remotes/{}", name),
+            format!("refs/remotes/{}/HEAD", name),
+        ];
+        candidates
+            .iter()
+            .find(|candidate| self.refs.resolve(candidate).is_some())
+            .cloned()
+    }
+
+    // `<ref>@{<n>}`, the value `ref` had `n` updates ago according to its reflog.
+    fn resolve_reflog_entry(&mut self, base: &str, entry: &str) -> Result<String> {
+        let base = if base.is_empty() { HEAD } else { base };
+        let n = entry
+            .strip_suffix('}')
+            .and_then(|n| n.parse::<usize>().ok())
+            .ok_or(anyhow!("invalid reflog entry '{}@{{{}'", base, entry))?;
+        let name = self
+            .full_ref_name(base)
+            .ok_or(anyhow!("ambiguous argument '{}': unknown revision", base))?;
+        let entries = self.refs.read_reflog(&name)?;
+        entries
+            .iter()
+            .rev()
+            .nth(n)
+            .map(|e| e.new_oid.to_string())
+            .ok_or(anyhow!("log for '{}' only has {} entries", base, entries.len()))
+    }
+
+    pub fn parent(&mut self, oid: &str) -> Result<String> {
+        match self.db.load(oid)? {
+            ObjectType::CommitType { commit } => commit
+                .parent()
+                .cloned()
+                .ok_or(anyhow!("commit {} has no parent", oid)),
+            _ => Err(anyhow!("object {} is not a commit", oid)),
+        }
+    }
+
+    fn peel_to_commit(&mut self, oid: &str) -> Result<String> {
+        match self.db.load(oid)? {
+            ObjectType::CommitType { .. } => Ok(oid.to_string()),
+            ObjectType::TagType { tag } => {
+                let object = tag.object.to_string();
+                self.peel_to_commit(&object)
+            }
+            _ => Err(anyhow!("object {} is not a commit", oid)),
+        }
+    }
+
+    pub fn peel_to_tree(&mut self, oid: &str) -> Result<String> {
+        match self.db.load(oid)? {
+            ObjectType::CommitType { commit } => Ok(commit.tree_ref.to_string()),
+            ObjectType::TreeType { .. } => Ok(oid.to_string()),
+            ObjectType::TagType { tag } => {
+                let object = tag.object.to_string();
+                self.peel_to_tree(&object)
+            }
+            ObjectType::BlobType { .. } => Err(anyhow!("object {} is not a tree", oid)),
+        }
+    }
+
+    fn lookup_path(&mut self, tree: &str, path: &str) -> Result<String> {
+        let mut oid = tree.to_string();
+        for name in path.split('/').filter(|c| !c.is_empty()) {
+            let entries = match self.db.load(&oid)? {
+                ObjectType::TreeType { tree } => tree.entries.clone(),
+                _ => return Err(anyhow!("path '{}' does not exist", path)),
+            };
+            let entry = entries
+                .iter()
+                .find(|e| e.name == name)
+                .ok_or(anyhow!("path '{}' does not exist", path))?;
+            oid = util::encode_vec(&entry.sha1_hash);
+        }
+        Ok(oid)
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+// The patterns of a sparse checkout in cone mode: the files at the top of the working tree,
+// everything under the directories of the cone and the files directly in their parent
+// directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SparseCheckout {
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl SparseCheckout {
+    pub fn new<I: IntoIterator<Item = PathBuf>>(dirs: I) -> Self {
+        let mut sparse = SparseCheckout::default();
+        sparse.add(dirs);
+        sparse
+    }
+
+    // Reads the patterns written for a cone: `/*` and `!/*/` for the top directory, then
+    // `/dir/` for each directory, followed by `!/dir/*/` when only the files directly in it
+    // are included because it is the parent of another one.
+    pub fn parse(patterns: &str) -> Result<Self> {
+        let lines: Vec<&str> = patterns
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let invalid = |line: &str| anyhow!("unable to parse the sparse-checkout pattern '{}' in cone mode", line);
+        match lines.as_slice() {
+            ["/*", "!/*/", ..] => {}
+            [line, ..] => return Err(invalid(line)),
+            [] => return Ok(SparseCheckout::default()),
+        }
+        let mut included = BTreeSet::new();
+        let mut parents = BTreeSet::new();
+        for line in lines[2..].iter() {
+            if let Some(dir) = line.strip_prefix("!/").and_then(|dir| dir.strip_suffix("/*/")) {
+                if !included.contains(Path::new(dir)) {
+                    return Err(invalid(line));
+                }
+                parents.insert(PathBuf::from(dir));
+            } else if let Some(dir) = line.strip_prefix('/').and_then(|dir| dir.strip_suffix('/')) {
+                if dir.is_empty() || dir.contains('*') {
+                    return Err(invalid(line));
+                }
+                included.insert(PathBuf::from(dir));
+            } else {
+                return Err(invalid(line));
+            }
+        }
+        Ok(SparseCheckout::new(included.difference(&parents).cloned()))
+    }
+
+    // adds the directories to the cone, those already under another one are left out.
+    pub fn add<I: IntoIterator<Item = PathBuf>>(&mut self, dirs: I) {
+        self.dirs.extend(dirs);
+        let dirs = std::mem::take(&mut self.dirs);
+        self.dirs = dirs
+            .iter()
+            .filter(|dir| !dir.ancestors().skip(1).any(|parent| dirs.contains(parent)))
+            .cloned()
+            .collect();
+    }
+
+    // the directories of the cone, as `sparse-checkout list` shows them.
+    pub fn dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.dirs.iter()
+    }
+
+    // the directories holding the ones of the cone, whose files are included too.
+    fn parents(&self) -> BTreeSet<&Path> {
+        self.dirs
+            .iter()
+            .flat_map(|dir| dir.ancestors().skip(1))
+            .filter(|parent| *parent != Path::new(""))
+            .collect()
+    }
+
+    // whether the file at `path`, relative to the top of the working tree, is checked out.
+    pub fn includes(&self, path: &Path) -> bool {
+        let path: PathBuf = path.components().filter(|c| *c != Component::CurDir).collect();
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => return true,
+        };
+        dir.ancestors().any(|dir| self.dirs.contains(dir)) || self.parents().contains(dir)
+    }
+
+    // the content of `info/sparse-checkout` for the cone.
+    pub fn to_patterns(&self) -> String {
+        let parents = self.parents();
+        let all: BTreeSet<&Path> = parents.iter().cloned().chain(self.dirs.iter().map(|dir| dir.as_path())).collect();
+        let mut out = String::from("/*\n!/*/\n");
+        for dir in all {
+            out.push_str(&format!("/{}/\n", dir.display()));
+            if !self.dirs.contains(dir) {
+                out.push_str(&format!("!/{}/*/\n", dir.display()));
+            }
+        }
+        out
+    }
+}
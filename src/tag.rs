@@ -0,0 +1,119 @@
+use std::io::{Cursor, Read};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::signing;
+use crate::{util, Author, HashAlgorithm, Object};
+
+// An annotated tag: the object it names, the name of the tag, who made it and a message. The
+// signature of a signed tag follows its message.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub object: String,
+    pub object_type: String,
+    pub name: String,
+    tagger: Author,
+    message: String,
+    signature: Option<String>,
+    oid: Vec<u8>,
+}
+
+impl Object for Tag {
+    fn get_data(&self) -> Result<Vec<u8>> {
+        let mut content = self.payload();
+        if let Some(signature) = &self.signature {
+            content.extend_from_slice(signature.as_bytes());
+        }
+        let mut data = format!("tag {}\0", content.len()).into_bytes();
+        data.extend(content);
+        Ok(data)
+    }
+
+    fn type_(&self) -> &str {
+        "tag"
+    }
+
+    fn get_oid(&self) -> Result<Vec<u8>> {
+        Ok(self.oid.to_vec())
+    }
+}
+
+impl Tag {
+    pub fn new(object: &str, object_type: &str, name: &str, tagger: Author, message: &str, hash: HashAlgorithm) -> Result<Self> {
+        let mut tag = Tag {
+            object: object.to_string(),
+            object_type: object_type.to_string(),
+            name: name.to_string(),
+            tagger,
+            message: message.to_string(),
+            signature: None,
+            oid: Vec::new(),
+        };
+        tag.oid = util::hexdigest_vec(&tag.get_data()?, hash);
+        Ok(tag)
+    }
+
+    pub fn parse(cursor: &mut Cursor<Vec<u8>>, oid: &str) -> Result<Self> {
+        let mut content = Vec::new();
+        cursor.read_to_end(&mut content)?;
+        let (payload, signature) = match split_signature(&content) {
+            Some((payload, signature)) => (payload, Some(signature)),
+            None => (content, None),
+        };
+        let text = String::from_utf8(payload)?;
+        let (headers, message) = text.split_once("\n\n").unwrap_or((&text, ""));
+        let header = |name: &str| {
+            headers
+                .lines()
+                .find_map(|line| line.strip_prefix(name).and_then(|value| value.strip_prefix(' ')))
+                .ok_or(anyhow!("tag {} has no {} header", oid, name))
+        };
+        Ok(Tag {
+            object: header("object")?.to_string(),
+            object_type: header("type")?.to_string(),
+            name: header("tag")?.to_string(),
+            tagger: Author::parse(header("tagger")?)?,
+            message: message.to_string(),
+            signature,
+            oid: oid.as_bytes().to_vec(),
+        })
+    }
+
+    pub fn tagger(&self) -> &Author {
+        &self.tagger
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    // what is signed: the content of the tag up to its signature.
+    pub fn payload(&self) -> Vec<u8> {
+        format!(
+            "object {}\ntype {}\ntag {}\ntagger {}\n\n{}",
+            self.object,
+            self.object_type,
+            self.name,
+            self.tagger.to_s(),
+            self.message
+        )
+        .into_bytes()
+    }
+
+    // appends the signature of the payload, which changes the oid.
+    pub fn set_signature(&mut self, signature: String, hash: HashAlgorithm) -> Result<()> {
+        self.signature = Some(signature);
+        self.oid = util::hexdigest_vec(&self.get_data()?, hash);
+        Ok(())
+    }
+}
+
+// Splits the raw content of a signed tag in its payload and the signature following it.
+pub fn split_signature(content: &[u8]) -> Option<(Vec<u8>, String)> {
+    let start = signing::find_armor(content)?;
+    Some((
+        content[..start].to_vec(),
+        String::from_utf8_lossy(&content[start..]).to_string(),
+    ))
+}
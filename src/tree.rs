@@ -5,27 +5,32 @@ use crate::util::TreeAux;
 use crate::Entry;
 use crate::{
     util::{self, TreeEntryAux},
-    Database, Object,
+    Database, HashAlgorithm, Object,
 };
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::path::Path;
 use std::{
     io::{BufRead, Cursor, Read},
     path::PathBuf,
 };
 
+// the entries of a tree and its subtrees that are not trees themselves, indexed by path.
+pub type TreeList = BTreeMap<PathBuf, Entry>;
+
 #[derive(Eq, Clone, PartialEq, PartialOrd, Debug)]
 pub struct Tree {
     pub entries: Vec<Entry>,
     pub sha1_hash: Vec<u8>,
 }
 
-pub fn build_add_tree(root: TreeAux) -> Result<Tree> {
+pub fn build_add_tree(root: TreeAux, hash: HashAlgorithm) -> Result<Tree> {
     let mut entries = Vec::new();
     for (entry, aux) in root.entries {
         match aux {
             TreeEntryAux::TreeLeafAux { entry } => entries.push(entry),
             TreeEntryAux::TreeBranchAux { tree } => {
-                let tree = build_add_tree(tree)?;
+                let tree = build_add_tree(tree, hash)?;
                 let name = entry
                     .file_name()
                     .expect("Expected a name")
@@ -44,7 +49,41 @@ pub fn build_add_tree(root: TreeAux) -> Result<Tree> {
         }
     }
 
-    Tree::new_with_entries(entries)
+    Tree::new_with_entries(entries, hash)
+}
+
+// Stores the trees needed to hold every entry of `list`, the inverse of
+// `Database::load_tree_list`, and returns the oid of the root tree.
+pub fn write_tree_list(db: &Database, list: &TreeList) -> Result<String> {
+    let mut root = TreeAux::new();
+    for (path, entry) in list.iter() {
+        let mut ancestors: Vec<_> = path
+            .ancestors()
+            .skip(1)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| p.to_path_buf())
+            .collect();
+        ancestors.reverse();
+        let mode = u32::from_str_radix(&entry.mode, 8)?;
+        root.add_entry(ancestors, path.to_path_buf(), Some(entry.sha1_hash.to_vec()), mode)?;
+    }
+    let mut tree = build_add_tree(root, db.hash_algorithm())?;
+    tree.save_tree(db)?;
+    Ok(util::encode_vec(&tree.sha1_hash))
+}
+
+// git sorts tree entries as if the name of a subtree had a trailing slash, so `a.txt` comes
+// before the directory `a` even though `a` is a prefix of it.
+pub fn entry_sort_key(name: &str, is_tree: bool) -> Vec<u8> {
+    let mut key = name.as_bytes().to_vec();
+    if is_tree {
+        key.push(b'/');
+    }
+    key
+}
+
+pub fn compare_entries(a: &Entry, b: &Entry) -> Ordering {
+    entry_sort_key(&a.name, a.is_tree()).cmp(&entry_sort_key(&b.name, b.is_tree()))
 }
 
 impl Object for Tree {
@@ -62,10 +101,10 @@ impl Object for Tree {
 }
 
 impl Tree {
-    pub fn new_with_entries(entries: Vec<Entry>) -> Result<Self> {
+    pub fn new_with_entries(entries: Vec<Entry>, hash: HashAlgorithm) -> Result<Self> {
         let mut entries = entries;
 
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries.sort_by(compare_entries);
 
         let mut entries_data = vec![];
         for entry in &entries.clone() {
@@ -82,7 +121,7 @@ impl Tree {
         data.push(0x00u8);
         data.extend(entries_data);
 
-        let sha1_hash = util::hexdigest_vec(&data);
+        let sha1_hash = util::hexdigest_vec(&data, hash);
 
         let tree = Tree { entries, sha1_hash };
 
@@ -90,50 +129,14 @@ impl Tree {
     }
 
     pub fn new(path: PathBuf, db: &Database) -> Result<Self> {
-        let mut paths: Vec<PathBuf> = vec![];
-        let mut dir = std::fs::read_dir(path)?;
-        while let Some(Ok(entry)) = dir.next() {
-            let fpath = entry.path();
-            if fpath.starts_with(".git") {
-                continue;
-            }
-            paths.push(fpath)
-        }
-
-        let mut entries: Vec<Entry> = vec![];
-        for path in paths {
-            let entry = Entry::from_file(path, db)?;
-            entries.push(entry);
-        }
-
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
-
-        let mut entries_data = vec![];
-        for entry in &entries.clone() {
-            entries_data.extend(entry.data());
-        }
-
-        let length = entries_data.len();
-
-        let mut data = vec![];
-
-        data.extend_from_slice("tree".as_bytes());
-        data.push(0x20u8);
-        data.extend_from_slice(length.to_string().as_bytes());
-        data.push(0x00u8);
-        data.extend(entries_data);
-
-        let sha1_hash = util::hexdigest_vec(&data);
-
-        let tree = Tree { entries, sha1_hash };
-
-        Ok(tree)
+        let entry = Entry::from_file(path, db)?;
+        Tree::new_with_entries(entry.entries, db.hash_algorithm())
     }
 
     pub fn save_tree(&mut self, db: &Database) -> Result<()> {
         for e in self.entries.iter_mut() {
             if e.is_tree() {
-                let mut tree = Tree::new_with_entries(e.entries.clone())?;
+                let mut tree = Tree::new_with_entries(e.entries.clone(), db.hash_algorithm())?;
                 tree.save_tree(&db)?;
             }
         }
@@ -143,7 +146,7 @@ impl Tree {
 
     pub fn get_data_to_write(&self) -> Result<Vec<u8>> {
         let mut final_entries = self.entries.to_vec();
-        final_entries.sort_by(|a, b| a.name.cmp(&b.name));
+        final_entries.sort_by(compare_entries);
 
         let mut entries_data = vec![];
         for entry in &final_entries.clone() {
@@ -162,7 +165,7 @@ impl Tree {
         Ok(data)
     }
 
-    pub fn parse(cursor: &mut Cursor<Vec<u8>>, sha1_hash: Vec<u8>) -> Result<Self> {
+    pub fn parse(cursor: &mut Cursor<Vec<u8>>, sha1_hash: Vec<u8>, hash: HashAlgorithm) -> Result<Self> {
         let mut entries = vec![];
         loop {
             let mut mode = vec![];
@@ -180,7 +183,7 @@ impl Tree {
             name.pop();
             // println!("name after pop: {:?}", name);
 
-            let mut sha1_hash: [u8; 20] = [0; 20];
+            let mut sha1_hash = vec![0; hash.size()];
             cursor.read_exact(&mut sha1_hash)?;
             // println!("hash: {:?}", sha1_hash);
             let entry = Entry::new(mode, sha1_hash.to_vec(), Path::new("").to_path_buf(), name, vec![]);
@@ -195,17 +198,24 @@ impl Tree {
 
     pub fn new_from_files(paths: Vec<PathBuf>, db: &Database) -> Result<Self> {
         let paths = util::flatten_dot(paths)?;
-        let mut root_tree_entries = vec![];
+        let mut files = vec![];
         for path in &paths {
-            if !path.exists() {
+            if path.symlink_metadata().is_err() {
                 return Err(anyhow!(format!(
                     "pathspec {:?} did not match any files",
                     &path
                 )));
             }
-            let entry = Entry::from_file(path.to_path_buf(), db)?;
+            files.extend(util::list_entry_files(path)?);
+        }
+        // storing the files is the expensive part of adding them, so it happens for all of
+        // them at once before the entries are assembled.
+        let stored = files.iter().cloned().zip(db.store_files(&files)?).collect();
+        let mut root_tree_entries = vec![];
+        for path in &paths {
+            let entry = Entry::from_stored_file(path.to_path_buf(), db, &stored)?;
             root_tree_entries.push(entry);
         }
-        Tree::new_with_entries(root_tree_entries)
+        Tree::new_with_entries(root_tree_entries, db.hash_algorithm())
     }
 }
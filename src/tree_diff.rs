@@ -0,0 +1,253 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::diff::{self, EditKind};
+use crate::tree::TreeList;
+use crate::Entry;
+
+// How a file changed between two trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+}
+
+// A changed file, with its entry on each side; the path of an entry is where the file is in
+// that tree.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub old: Option<Entry>,
+    pub new: Option<Entry>,
+    // how similar, in percent, the files of a rename or a copy are
+    pub score: u32,
+}
+
+impl Change {
+    pub fn old_path(&self) -> Option<&Path> {
+        self.old.as_ref().map(|e| e.path.as_path())
+    }
+
+    pub fn new_path(&self) -> Option<&Path> {
+        self.new.as_ref().map(|e| e.path.as_path())
+    }
+
+    // the path the change is listed under, where the file ends up.
+    pub fn path(&self) -> &Path {
+        self.new_path().or(self.old_path()).unwrap_or(Path::new(""))
+    }
+
+    // the status letter of `--name-status`, followed by the score for renames and copies.
+    pub fn status(&self) -> String {
+        match self.kind {
+            ChangeKind::Added => "A".to_string(),
+            ChangeKind::Deleted => "D".to_string(),
+            ChangeKind::Modified => "M".to_string(),
+            ChangeKind::Renamed => format!("R{:03}", self.score),
+            ChangeKind::Copied => format!("C{:03}", self.score),
+        }
+    }
+}
+
+// How renames and copies are detected: the minimum similarity of a pair of files and the
+// maximum number of files to compare for inexact renames, like `-M<n>` and `-l<n>`; a limit of
+// 0 means no limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    pub threshold: u32,
+    pub limit: usize,
+    pub copies: bool,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        RenameOptions {
+            threshold: 50,
+            limit: 1000,
+            copies: false,
+        }
+    }
+}
+
+// Parses the similarity of `-M<n>` and `-C<n>` as git does: `90%` is a percentage, digits
+// alone are the decimals of a fraction so `9` and `90` both mean 90%.
+pub fn parse_threshold(value: &str) -> Result<u32> {
+    let invalid = || anyhow!("invalid similarity threshold '{}'", value);
+    if let Some(percent) = value.strip_suffix('%') {
+        let percent = percent.parse::<u32>().map_err(|_| invalid())?;
+        return if percent <= 100 { Ok(percent) } else { Err(invalid()) };
+    }
+    let digits = value.strip_prefix("0.").unwrap_or(value);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let padded = format!("{:0<2}", &digits[..digits.len().min(2)]);
+    padded.parse::<u32>().map_err(|_| invalid())
+}
+
+// How much of the content of `a` is kept in `b`, in percent of the larger of both.
+fn similarity(a: &[u8], b: &[u8]) -> u32 {
+    let max = a.len().max(b.len());
+    if a.is_empty() || b.is_empty() {
+        return 0;
+    }
+    let (lines_a, lines_b) = (diff::lines(a), diff::lines(b));
+    let common: usize = diff::diff(&lines_a, &lines_b)
+        .iter()
+        .filter(|e| e.kind == EditKind::Eql)
+        .filter_map(|e| e.a_index)
+        .map(|n| lines_a[n].len())
+        .sum();
+    (common * 100 / max) as u32
+}
+
+// Lists the files that differ from `old` to `new`, by path. With `renames`, the files deleted
+// whose content, or most of it, was added under another path are listed as renamed: pairs
+// with the same oid first, then the most similar pairs above the threshold. Copies, when
+// asked for, are looked for among the modified files. `load` reads the content of an entry.
+pub fn diff_lists<F>(old: &TreeList, new: &TreeList, renames: Option<&RenameOptions>, mut load: F) -> Result<Vec<Change>>
+where
+    F: FnMut(&Entry) -> Result<Vec<u8>>,
+{
+    let paths: BTreeSet<&PathBuf> = old.keys().chain(new.keys()).collect();
+    let mut changes = Vec::new();
+    let mut deleted = Vec::new();
+    let mut added = Vec::new();
+    for path in paths {
+        match (old.get(path), new.get(path)) {
+            (Some(a), Some(b)) if a.sha1_hash == b.sha1_hash && a.mode == b.mode => {}
+            (Some(a), Some(b)) => changes.push(Change {
+                kind: ChangeKind::Modified,
+                old: Some(a.clone()),
+                new: Some(b.clone()),
+                score: 0,
+            }),
+            (Some(a), None) => deleted.push(a.clone()),
+            (None, Some(b)) => added.push(b.clone()),
+            (None, None) => {}
+        }
+    }
+
+    if let Some(options) = renames {
+        // submodules are neither renamed nor copied
+        let (added_gitlinks, files): (Vec<_>, Vec<_>) = added.drain(..).partition(Entry::is_gitlink);
+        added = files;
+        let (deleted_gitlinks, files): (Vec<_>, Vec<_>) = deleted.drain(..).partition(Entry::is_gitlink);
+        deleted = files;
+
+        // exact renames
+        let mut remaining = Vec::new();
+        for entry in added.drain(..) {
+            match deleted.iter().position(|d| d.sha1_hash == entry.sha1_hash) {
+                Some(n) => changes.push(Change {
+                    kind: ChangeKind::Renamed,
+                    old: Some(deleted.remove(n)),
+                    new: Some(entry),
+                    score: 100,
+                }),
+                None => remaining.push(entry),
+            }
+        }
+        added = remaining;
+
+        let sources: Vec<(Entry, bool)> = deleted
+            .iter()
+            .map(|e| (e.clone(), true))
+            .chain(
+                changes
+                    .iter()
+                    .filter(|c| options.copies && c.kind == ChangeKind::Modified)
+                    .filter_map(|c| c.old.clone())
+                    .filter(|e| !e.is_gitlink())
+                    .map(|e| (e, false)),
+            )
+            .collect();
+        if !added.is_empty() && !sources.is_empty() {
+            if options.limit > 0 && added.len() * sources.len() > options.limit * options.limit {
+                eprintln!("warning: inexact rename detection was skipped due to too many files.");
+            } else {
+                let pairs = score_pairs(&added, &sources, options.threshold, &mut load)?;
+                let (mut used_added, mut used_deleted) = (BTreeSet::new(), BTreeSet::new());
+                for (score, a, s) in pairs {
+                    let is_rename = sources[s].1;
+                    if used_added.contains(&a) || (is_rename && used_deleted.contains(&s)) {
+                        continue;
+                    }
+                    used_added.insert(a);
+                    if is_rename {
+                        used_deleted.insert(s);
+                    }
+                    changes.push(Change {
+                        kind: if is_rename { ChangeKind::Renamed } else { ChangeKind::Copied },
+                        old: Some(sources[s].0.clone()),
+                        new: Some(added[a].clone()),
+                        score,
+                    });
+                }
+                added = added
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(n, _)| !used_added.contains(n))
+                    .map(|(_, e)| e)
+                    .collect();
+                deleted = deleted
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(n, _)| !used_deleted.contains(n))
+                    .map(|(_, e)| e)
+                    .collect();
+            }
+        }
+        added.extend(added_gitlinks);
+        deleted.extend(deleted_gitlinks);
+    }
+
+    changes.extend(added.into_iter().map(|e| Change {
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(e),
+        score: 0,
+    }));
+    changes.extend(deleted.into_iter().map(|e| Change {
+        kind: ChangeKind::Deleted,
+        old: Some(e),
+        new: None,
+        score: 0,
+    }));
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(changes)
+}
+
+// every pair of an added file and a source at least `threshold` similar, most similar first.
+fn score_pairs<F>(added: &[Entry], sources: &[(Entry, bool)], threshold: u32, load: &mut F) -> Result<Vec<(u32, usize, usize)>>
+where
+    F: FnMut(&Entry) -> Result<Vec<u8>>,
+{
+    let mut source_data = Vec::new();
+    for (entry, _) in sources {
+        source_data.push(load(entry)?);
+    }
+    let mut pairs = Vec::new();
+    for (a, entry) in added.iter().enumerate() {
+        let data = load(entry)?;
+        for (s, source) in source_data.iter().enumerate() {
+            // files whose sizes are too far apart cannot be similar enough
+            let (min, max) = (data.len().min(source.len()), data.len().max(source.len()));
+            if max == 0 || (min * 100) < (max * threshold as usize) {
+                continue;
+            }
+            let score = similarity(source, &data);
+            if score >= threshold {
+                pairs.push((score, a, s));
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.0.cmp(&x.0).then(x.1.cmp(&y.1)).then(x.2.cmp(&y.2)));
+    Ok(pairs)
+}
@@ -1,18 +1,19 @@
 use anyhow::Result;
 use anyhow::anyhow;
 use data_encoding::HEXLOWER;
-use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY};
 use std::fs::File;
 use std::io::Write;
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::prelude::PermissionsExt;
 use std::{
     collections::HashMap,
     env::current_dir,
     fs::{self, Metadata},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use crate::Entry;
+use crate::HashAlgorithm;
 
 #[derive(Debug)]
 pub enum TreeEntryAux {
@@ -77,9 +78,10 @@ pub fn read_file(path: PathBuf) -> Result<Vec<u8>> {
     Ok(res)
 }
 
+// the stat data of a file of the working tree, symbolic links are not followed.
 pub fn stat_file(path: &PathBuf) -> Result<Metadata> {
     let msg = format!("stat ('{:?}'): Permission denied", &path);
-    let metadata = fs::metadata(path).map_err(|_| anyhow!(msg))?;
+    let metadata = fs::symlink_metadata(path).map_err(|_| anyhow!(msg))?;
     Ok(metadata)
 }
 
@@ -89,18 +91,40 @@ pub fn is_executable(entry: &PathBuf) -> Result<bool> {
     Ok((unix_mode & 0o001) != 0)
 }
 
+// The target of `path` when it is a symbolic link, which is the content git stores for it
+// as a blob, and `None` for anything else.
+pub fn read_link(path: &Path) -> Result<Option<Vec<u8>>> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            Ok(Some(fs::read_link(path)?.into_os_string().into_vec()))
+        }
+        _ => Ok(None),
+    }
+}
+
+// whether the directory `path` is the working tree of another repository, like a submodule.
+pub fn is_nested_repository(path: &Path) -> bool {
+    path.join(".git").exists()
+}
+
+// whether `stat`, taken without following links, is of a file stored as a blob: a regular
+// file or a symbolic link.
+pub fn is_blob(stat: &Metadata) -> bool {
+    stat.is_file() || stat.file_type().is_symlink()
+}
+
 const REGULAR_MODE: u32 = 0o100644;
 const EXECUTABLE_MODE: u32 = 0o100755;
+pub const SYMLINK_MODE: u32 = 0o120000;
+pub const GITLINK_MODE: u32 = 0o160000;
 pub fn get_mode(path_buf: PathBuf) -> Result<u32> {
-    let entry = &path_buf;
-    if is_executable(entry)? {
-        Ok(EXECUTABLE_MODE)
-    } else {
-        Ok(REGULAR_MODE)
-    }
+    Ok(get_mode_stat(&stat_file(&path_buf)?))
 }
 
 pub fn get_mode_u(mode_u: u32) -> String {
+    if mode_u == SYMLINK_MODE || mode_u == GITLINK_MODE {
+        return format!("{:o}", mode_u);
+    }
     let mut mode = String::new();
     let is_executable = (mode_u & 0o001) != 0;
     if is_executable {
@@ -112,6 +136,9 @@ pub fn get_mode_u(mode_u: u32) -> String {
 }
 
 pub fn get_mode_stat(stat: &Metadata) -> u32 {
+    if stat.file_type().is_symlink() {
+        return SYMLINK_MODE;
+    }
     let unix_mode = stat.permissions().mode();
     let is_executable = (unix_mode & 0o001) != 0;
     if is_executable {
@@ -125,14 +152,8 @@ pub fn encode_vec(data: &Vec<u8>) -> String {
     HEXLOWER.encode(data)
 }
 
-pub fn hexdigest_vec(data: &Vec<u8>) -> Vec<u8> {
-    let mut context = Context::new(&SHA1_FOR_LEGACY_USE_ONLY);
-
-    context.update(&data);
-
-    let digest = context.finish();
-
-    digest.as_ref().to_vec()
+pub fn hexdigest_vec(data: &Vec<u8>, hash: HashAlgorithm) -> Vec<u8> {
+    hash.digest(data)
 }
 
 pub fn flatten_dot(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
@@ -165,6 +186,74 @@ pub fn flatten_dot(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
 }
 
 
+// Resolves `.` and `..` components without touching the file system, so it also works for
+// paths that do not exist yet.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+// `path` as seen from `base`, both of them relative to the same directory.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<_> = path.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = path.iter().zip(base.iter()).take_while(|(a, b)| a == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for component in &path[common..] {
+        relative.push(component.as_os_str());
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+// entries of a directory except `.git`, in name order.
+pub fn read_dir_sorted(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = fs::read_dir(path)?
+        .filter(|e| match e {
+            Ok(p) => p.file_name() != ".git",
+            Err(_e) => true,
+        })
+        .map(|e| e.map(|e| path.join(e.file_name())))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+// Files that have to be stored to create the entry of `path`: the path itself when it is a
+// file or a symbolic link, or every one of them below it when it is a directory. Nested
+// repositories are entries of their own, nothing of them is stored.
+pub fn list_entry_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut res = Vec::new();
+    let mut work = vec![path.to_path_buf()];
+    while let Some(path) = work.pop() {
+        let metadata = path.symlink_metadata()?;
+        if is_blob(&metadata) {
+            res.push(path);
+        } else if metadata.is_dir() && !is_nested_repository(&path) {
+            let mut children = read_dir_sorted(&path)?;
+            children.reverse();
+            work.extend(children);
+        }
+    }
+    Ok(res)
+}
+
 pub fn list_files(path: &PathBuf) -> Result<Vec<PathBuf>> {
     let mut res = Vec::new();
     let mut work = vec![path.to_path_buf()];
@@ -178,9 +267,9 @@ pub fn list_files(path: &PathBuf) -> Result<Vec<PathBuf>> {
         for entry in filtered {
             let entry = entry?;
             let file_type = entry.file_type()?;
-            if file_type.is_file() {
+            if file_type.is_file() || file_type.is_symlink() {
                 res.push(entry.path())
-            } else if file_type.is_dir() {
+            } else if file_type.is_dir() && !is_nested_repository(&entry.path()) {
                 work.push(entry.path())
             }
         }
@@ -3,24 +3,36 @@ use std::collections::HashMap;
 use std::fs;
 use std::fs::DirEntry;
 use std::fs::Metadata;
+use std::io::{self, Read};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{symlink, PermissionsExt};
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Result;
 
+use crate::convert::Converter;
 use crate::{
     util::{self, TreeAux},
-    EntryAdd, Index, Tree,
+    Entry, EntryAdd, Index, Tree,
 };
 
 pub struct Workspace {
     pub pathname: PathBuf,
+    // whether symbolic links are created as such, `core.symlinks`
+    pub symlinks: bool,
+    // the conversions of the content of the files written, like their line endings
+    pub converter: Option<Arc<Converter>>,
 }
 
 impl Workspace {
     pub fn new(path_buf: &PathBuf) -> Self {
         Workspace {
             pathname: path_buf.into(),
+            symlinks: true,
+            converter: None,
         }
     }
 
@@ -60,7 +72,7 @@ impl Workspace {
             let mut ancestors: Vec<_> = e
                 .path
                 .ancestors()
-                .filter(|en| en.to_path_buf() != e.path && en.exists())
+                .filter(|en| en.to_path_buf() != e.path && !en.as_os_str().is_empty())
                 .map(|e| e.to_path_buf())
                 .collect();
             ancestors.reverse();
@@ -69,25 +81,75 @@ impl Workspace {
         Ok(root)
     }
 
-    pub fn create_index_entry(&self, tree: &Tree, index: &mut Index) -> Result<()> {
+    // Writes a file of the working tree with the content and mode of a tree entry. The content
+    // of a symbolic link is its target, it is written to a plain file when links are disabled.
+    // Gitlinks have no content.
+    pub fn write_file(&self, path: &Path, data: &[u8], mode: &str) -> Result<Metadata> {
+        self.write_stream(path, &mut &data[..], mode)
+    }
+
+    // Writes a file as `write_file` does with the content read from `data`, which is copied
+    // to the file as it is read unless it has to be converted.
+    pub fn write_stream<R: Read>(&self, path: &Path, data: &mut R, mode: &str) -> Result<Metadata> {
+        let full_path = self.pathname.join(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // an existing link is replaced rather than written through
+        if fs::symlink_metadata(&full_path).is_ok_and(|stat| stat.file_type().is_symlink()) {
+            fs::remove_file(&full_path)?;
+        }
+        // the directory of a submodule, left empty until the submodule is checked out
+        if mode == "160000" {
+            fs::create_dir_all(&full_path)?;
+            return util::stat_file(&full_path);
+        }
+        if mode == "120000" && self.symlinks {
+            let mut target = Vec::new();
+            data.read_to_end(&mut target)?;
+            symlink(OsStr::from_bytes(&target), &full_path)?;
+            return util::stat_file(&full_path);
+        }
+        match &self.converter {
+            Some(converter) if converter.converts_to_worktree(path) => {
+                let mut content = Vec::new();
+                data.read_to_end(&mut content)?;
+                fs::write(&full_path, converter.to_worktree(path, content)?)?;
+            }
+            _ => {
+                io::copy(data, &mut fs::File::create(&full_path)?)?;
+            }
+        }
+        // the attributes of the files written next may have changed
+        if path.file_name() == Some(OsStr::new(".gitattributes")) {
+            if let Some(converter) = &self.converter {
+                converter.clear_cache();
+            }
+        }
+        let permissions = if mode == "100755" { 0o755 } else { 0o644 };
+        fs::set_permissions(&full_path, fs::Permissions::from_mode(permissions))?;
+        util::stat_file(&full_path)
+    }
+
+    // records in `index` the files of `tree` for which `include` holds.
+    pub fn create_index_entry<F>(&self, tree: &Tree, index: &mut Index, include: F) -> Result<()>
+    where
+        F: Fn(&Entry) -> bool,
+    {
         let mut work = tree.entries.clone();
         while let Some(entry) = work.pop() {
             if entry.is_tree() {
                 work.append(&mut entry.entries.clone())
-            } else {
-                let stat = util::stat_file(&entry.path.canonicalize()?)?;
-                index.add(entry.path.to_path_buf(), entry.sha1_hash.clone(), stat)?;
+            } else if include(&entry) {
+                let stat = util::stat_file(&entry.path)?;
+                if entry.is_gitlink() {
+                    index.add_with_mode(entry.path.to_path_buf(), entry.sha1_hash.clone(), stat, util::GITLINK_MODE)?;
+                } else {
+                    index.add(entry.path.to_path_buf(), entry.sha1_hash.clone(), stat)?;
+                }
             }
         }
         Ok(())
     }
-
-    pub fn get_git_path(&self) -> PathBuf {
-        self.pathname.join(".git")
-    }
-
-    pub fn get_db_path(&self) -> PathBuf {
-        self.get_git_path().join("objects")
-    }
 }
 
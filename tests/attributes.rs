@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use gitclone::attributes::{wildmatch, AttributeState, Attributes};
+use tempfile::TempDir;
+
+#[test]
+fn wildmatch_follows_the_glob_rules_of_git() {
+    assert!(wildmatch(b"*.txt", b"a.txt"));
+    assert!(!wildmatch(b"*.txt", b"dir/a.txt"));
+    assert!(wildmatch(b"dir/*.txt", b"dir/a.txt"));
+    assert!(wildmatch(b"**/a.txt", b"a.txt"));
+    assert!(wildmatch(b"**/a.txt", b"x/y/a.txt"));
+    assert!(wildmatch(b"dir/**", b"dir/x/y"));
+    assert!(wildmatch(b"a?c", b"abc"));
+    assert!(!wildmatch(b"a?c", b"a/c"));
+    assert!(wildmatch(b"[a-c]x", b"bx"));
+    assert!(!wildmatch(b"[!a-c]x", b"bx"));
+    assert!(wildmatch(b"\\*", b"*"));
+}
+
+fn attributes_of(attributes: &Attributes, path: &str) -> HashMap<String, AttributeState> {
+    attributes.get(Path::new(path))
+}
+
+#[test]
+fn deeper_files_and_later_lines_take_precedence() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let work_tree = temp_dir.path();
+    fs::create_dir_all(work_tree.join(".git/info")).unwrap();
+    fs::create_dir_all(work_tree.join("sub")).unwrap();
+    fs::write(
+        work_tree.join(".gitattributes"),
+        "# comment\n[attr]crlf-text text eol=crlf\n*.txt text\nspecial.txt -text\n*.dat binary\n*.win crlf-text\n",
+    )
+    .unwrap();
+    fs::write(work_tree.join("sub/.gitattributes"), "*.txt !text diff=plain\n").unwrap();
+    fs::write(work_tree.join(".git/info/attributes"), "sub/keep.txt text=auto\n").unwrap();
+    let attributes = Attributes::new(work_tree, &work_tree.join(".git"));
+
+    assert_eq!(attributes_of(&attributes, "a.txt").get("text"), Some(&AttributeState::Set));
+    assert_eq!(attributes_of(&attributes, "special.txt").get("text"), Some(&AttributeState::Unset));
+    let sub = attributes_of(&attributes, "sub/a.txt");
+    assert_eq!(sub.get("text"), Some(&AttributeState::Unspecified));
+    assert_eq!(sub.get("diff"), Some(&AttributeState::Value("plain".to_string())));
+    assert_eq!(
+        attributes_of(&attributes, "sub/keep.txt").get("text"),
+        Some(&AttributeState::Value("auto".to_string()))
+    );
+
+    let binary = attributes_of(&attributes, "deep/x.dat");
+    assert_eq!(binary.get("binary"), Some(&AttributeState::Set));
+    assert_eq!(binary.get("diff"), Some(&AttributeState::Unset));
+    assert_eq!(binary.get("merge"), Some(&AttributeState::Unset));
+    assert_eq!(binary.get("text"), Some(&AttributeState::Unset));
+    let win = attributes_of(&attributes, "a.win");
+    assert_eq!(win.get("eol"), Some(&AttributeState::Value("crlf".to_string())));
+    assert!(attributes_of(&attributes, "other").is_empty());
+}
@@ -9,6 +9,8 @@ use assert_cmd::prelude::*;
 use gitclone::{util, Command};
 use tempfile::{TempDir, tempdir};
 
+use super::helpers::{git, init_committed, write};
+
 #[test]
 fn cli_add_without_init() {
     let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
@@ -245,3 +247,64 @@ fn adds_an_executable_file_to_index() {
         .collect::<Vec<_>>();
     assert_eq!(entries, vec![(0o100755, "hello.txt".to_string())])
 }
+
+#[test]
+fn adds_many_nested_files_to_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let files = (0..50)
+        .map(|i| (Path::new(&format!("dir_{}", i % 5)).join(format!("file_{}.txt", i)), format!("content {}", i)))
+        .collect::<Vec<_>>();
+    util::write_file(&temp_dir.path().to_owned(), files.iter().map(|(p, c)| (p.to_path_buf(), c.as_bytes())).collect()).unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "."])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+
+    let mut command = Command::new(temp_dir.path().to_path_buf()).unwrap();
+    command.index.load().unwrap();
+    let entries = command
+        .index
+        .each_entry()
+        .unwrap()
+        .iter()
+        .map(|e| e.get_path())
+        .collect::<Vec<_>>();
+    let mut expected = files.iter().map(|(p, _)| p.display().to_string()).collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(entries, expected);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["fsck"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+}
+
+#[test]
+fn cli_add_removes_the_files_deleted_from_the_working_tree() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_committed(&temp_dir, &[("a.txt", "a\n"), ("dir/b.txt", "b\n"), ("dir/c.txt", "c\n")]);
+    fs::remove_file(dir.join("a.txt")).unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git(&dir, &["status", "--porcelain"]).success().stdout("D  a.txt\n");
+
+    fs::remove_dir_all(dir.join("dir")).unwrap();
+    write(&dir, "d.txt", "d\n");
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["status", "--porcelain"])
+        .success()
+        .stdout("D  a.txt\nA  d.txt\nD  dir/b.txt\nD  dir/c.txt\n");
+    git(&dir, &["add", "a.txt"])
+        .failure()
+        .stderr(contains("did not match any files"));
+}
@@ -0,0 +1,67 @@
+use std::fs;
+
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, head_blob, init_repository};
+
+#[test]
+fn text_files_are_stored_with_lf_and_checked_out_with_their_eol() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir, &[]);
+    fs::write(dir.join(".gitattributes"), "*.txt text eol=crlf\n*.raw -text\n").unwrap();
+    fs::write(dir.join("a.txt"), "one\r\ntwo\r\n").unwrap();
+    fs::write(dir.join("b.raw"), "one\r\n").unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+    assert_eq!(head_blob(&dir, "a.txt"), b"one\ntwo\n");
+    assert_eq!(head_blob(&dir, "b.raw"), b"one\r\n");
+
+    git(&dir, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    assert_eq!(fs::read(other.join("a.txt")).unwrap(), b"one\r\ntwo\r\n");
+    assert_eq!(fs::read(other.join("b.raw")).unwrap(), b"one\r\n");
+    git(&other, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    // the diff of the working tree is made on the normalized content
+    fs::write(dir.join("a.txt"), "one\r\nthree\r\n").unwrap();
+    git(&dir, &["diff"]).success().stdout(contains(" one\n-two\n+three\n"));
+}
+
+#[test]
+fn autocrlf_normalizes_the_files_without_attributes() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir, &[("core.autocrlf", "true")]);
+    fs::write(dir.join("a.txt"), "one\r\n").unwrap();
+    fs::write(dir.join("data"), b"\0\r\n").unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    assert_eq!(head_blob(&dir, "a.txt"), b"one\n");
+    // binary content is left as it is
+    assert_eq!(head_blob(&dir, "data"), b"\0\r\n");
+
+    git(&dir, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    assert_eq!(fs::read(other.join("a.txt")).unwrap(), b"one\r\n");
+    assert_eq!(fs::read(other.join("data")).unwrap(), b"\0\r\n");
+}
+
+#[test]
+fn diff_follows_the_diff_attribute() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir, &[]);
+    fs::write(dir.join(".gitattributes"), "*.lock binary\n*.dat diff\n").unwrap();
+    fs::write(dir.join("a.lock"), "one\n").unwrap();
+    fs::write(dir.join("b.dat"), b"one\0\n").unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+
+    fs::write(dir.join("a.lock"), "two\n").unwrap();
+    fs::write(dir.join("b.dat"), b"two\0\n").unwrap();
+    git(&dir, &["diff"])
+        .success()
+        .stdout(contains("Binary files a/a.lock and b/a.lock differ\n"))
+        .stdout(contains("-one\0\n+two\0\n"));
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+use predicates::str::{contains, is_match};
+
+use tempfile::TempDir;
+
+use super::helpers::{commit, git};
+
+// a bare repository `remote.git` next to a working tree `work` with two commits on master.
+fn init_remote_and_work(temp_dir: &TempDir) -> (std::path::PathBuf, std::path::PathBuf) {
+    let remote = temp_dir.path().join("remote.git");
+    let work = temp_dir.path().join("work");
+    git(temp_dir.path(), &["init", "--bare", "remote.git"]).success();
+    git(temp_dir.path(), &["init", "work"]).success();
+    commit(&work, "a.txt", "one\n", "first");
+    commit(&work, "a.txt", "two\n", "second");
+    (remote, work)
+}
+
+#[test]
+fn init_bare_creates_the_repository_in_place() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    git(temp_dir.path(), &["init", "--bare", "remote.git"]).success();
+    let remote = temp_dir.path().join("remote.git");
+    assert!(remote.join("objects").is_dir());
+    assert!(remote.join("refs").join("heads").is_dir());
+    assert!(!remote.join(".git").exists());
+    assert_eq!(fs::read_to_string(remote.join("HEAD")).unwrap(), "ref: refs/heads/master\n");
+    assert!(fs::read_to_string(remote.join("config")).unwrap().contains("bare = true"));
+}
+
+#[test]
+fn commands_that_need_a_work_tree_fail_in_a_bare_repository() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    git(temp_dir.path(), &["init", "--bare", "remote.git"]).success();
+    let remote = temp_dir.path().join("remote.git");
+    for args in [vec!["status"], vec!["add", "a.txt"], vec!["commit", "-m", "message"]] {
+        git(&remote, &args)
+            .failure()
+            .stderr(contains("this operation must be run in a work tree"));
+    }
+}
+
+#[test]
+fn push_to_a_bare_repository_and_read_it_back() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (remote, work) = init_remote_and_work(&temp_dir);
+    git(&work, &["push", "../remote.git"])
+        .success()
+        .stdout(contains(" * [new branch]      master -> master"));
+    git(&remote, &["log"])
+        .success()
+        .stdout(is_match("(?s)^commit [0-9a-f]{40}\nAuthor: .*\n\n    second\n\ncommit [0-9a-f]{40}\n.*\n    first\n$").unwrap());
+    git(&remote, &["cat-file", "-t", "HEAD~1"]).success().stdout("commit\n");
+    git(&remote, &["cat-file", "-s", "master:a.txt"]).success().stdout("4\n");
+    git(&remote, &["cat-file", "-p", "HEAD:a.txt"]).success().stdout("two\n");
+    git(&remote, &["cat-file", "-p", "HEAD:"])
+        .success()
+        .stdout(is_match("^100644 blob [0-9a-f]{40}\ta.txt\n$").unwrap());
+    git(&remote, &["fsck"]).success();
+    git(&work, &["push", "../remote.git"]).success().stdout(contains("Everything up-to-date"));
+}
+
+#[test]
+fn push_rejects_non_fast_forward_updates_unless_forced() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (remote, work) = init_remote_and_work(&temp_dir);
+    git(&work, &["push", "../remote.git"]).success();
+    git(&work, &["update-ref", "refs/heads/old", "HEAD~1"]).success();
+    git(&work, &["push", "../remote.git", "old:master"])
+        .failure()
+        .stdout(contains("[rejected]"));
+    git(&work, &["push", "-f", "../remote.git", "old:master"])
+        .success()
+        .stdout(contains("(forced update)"));
+    let old = fs::read_to_string(work.join(".git/refs/heads/old")).unwrap();
+    assert_eq!(fs::read_to_string(remote.join("refs/heads/master")).unwrap(), old);
+}
+
+#[test]
+fn push_refuses_to_update_the_checked_out_branch() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (_, work) = init_remote_and_work(&temp_dir);
+    git(temp_dir.path(), &["init", "other"]).success();
+    git(&work, &["push", "../other"])
+        .failure()
+        .stderr(contains("refusing to update checked out branch"));
+    git(&work, &["push", "../other", "master:topic"]).success();
+}
+
+#[test]
+fn update_ref_checks_the_old_value_and_deletes() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (remote, work) = init_remote_and_work(&temp_dir);
+    git(&work, &["push", "../remote.git"]).success();
+    git(&remote, &["update-ref", "refs/heads/topic", "master~1"]).success();
+    git(&remote, &["update-ref", "refs/heads/topic", "master", "master"])
+        .failure()
+        .stderr(contains("cannot lock ref 'refs/heads/topic'"));
+    git(&remote, &["update-ref", "refs/heads/topic", "master", "master~1"]).success();
+    git(&remote, &["update-ref", "-d", "refs/heads/topic"]).success();
+    assert!(!remote.join("refs/heads/topic").exists());
+}
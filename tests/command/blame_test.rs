@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use assert_cmd::prelude::*;
+use gitclone::{util, Database, HashAlgorithm};
+use predicates::str::{contains, is_match};
+
+use tempfile::TempDir;
+
+use super::helpers::{commit, git};
+
+fn oid(dir: &Path, revision: &str) -> String {
+    let log = process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(["log", revision])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let log = String::from_utf8(log.stdout).unwrap();
+    log.lines().next().unwrap().trim_start_matches("commit ").to_string()
+}
+
+fn init_repository(temp_dir: &TempDir) {
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    commit(dir, "f.txt", "a\nb\nc\nd\n", "first");
+    commit(dir, "f.txt", "a\nB\nc\nd\ne\n", "second");
+    commit(dir, "f.txt", "x\na\nB\nd\ne\n", "third");
+}
+
+#[test]
+fn blame_attributes_each_line_to_its_commit() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    let (first, second, third) = (oid(dir, "HEAD~2"), oid(dir, "HEAD~1"), oid(dir, "HEAD"));
+    let expected = [
+        (&third[..8], 1, "x"),
+        (&format!("^{}", &first[..7]), 2, "a"),
+        (&second[..8], 3, "B"),
+        (&format!("^{}", &first[..7]), 4, "d"),
+        (&second[..8], 5, "e"),
+    ];
+    let assert = git(dir, &["blame", "f.txt"]).success();
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), expected.len());
+    for (line, (oid, number, content)) in lines.iter().zip(expected.iter()) {
+        assert!(line.starts_with(&format!("{} (t ", oid)), "{}", line);
+        assert!(line.ends_with(&format!(" {}) {}", number, content)), "{}", line);
+    }
+
+    // an older revision and a range of lines
+    git(dir, &["blame", "-L", "2,+1", "HEAD~1", "f.txt"])
+        .success()
+        .stdout(is_match(format!("^{} \\(t .* 2\\) B\n$", &second[..8])).unwrap());
+}
+
+#[test]
+fn porcelain_output() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    let (first, second) = (oid(dir, "HEAD~2"), oid(dir, "HEAD~1"));
+    git(dir, &["blame", "--porcelain", "-L", "4,5", "f.txt"])
+        .success()
+        .stdout(contains(format!("{} 4 4 1\nauthor t\nauthor-mail <t@e>\n", first)))
+        .stdout(contains("summary first\nboundary\nfilename f.txt\n\td\n"))
+        .stdout(contains(format!("{} 5 5 1\nauthor t\n", second)))
+        .stdout(contains(format!("summary second\nprevious {} f.txt\nfilename f.txt\n\te\n", first)));
+    git(dir, &["blame", "-L", "7,8", "f.txt"])
+        .failure()
+        .stderr(contains("file has only 5 lines"));
+}
+
+#[test]
+fn porcelain_output_names_the_committer() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    // HEAD made again by someone else than its author
+    let assert = git(dir, &["cat-file", "-p", "HEAD"]).success();
+    let content = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let content = content
+        .lines()
+        .map(|line| match line.split_once(' ') {
+            Some(("commiter" | "committer", _)) => "committer c <c@e> 1700000300 +0100".to_string(),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut data = format!("commit {}\0", content.len()).into_bytes();
+    data.extend_from_slice(content.as_bytes());
+    let oid = util::hexdigest_vec(&data, HashAlgorithm::Sha1);
+    Database::new(&dir.join(".git/objects"), HashAlgorithm::Sha1).write_object(&oid, data).unwrap();
+    fs::write(dir.join(".git/refs/heads/master"), format!("{}\n", util::encode_vec(&oid))).unwrap();
+
+    git(dir, &["blame", "--porcelain", "-L", "1,1", "f.txt"])
+        .success()
+        .stdout(contains("author t\nauthor-mail <t@e>\n"))
+        .stdout(contains("committer c\ncommitter-mail <c@e>\ncommitter-time 1700000300\n"));
+}
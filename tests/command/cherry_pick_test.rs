@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use predicates::str::contains;
+
+use tempfile::TempDir;
+
+use super::helpers::{commit, git, write};
+
+// four commits on master and a worktree with the branch `side` at the first one.
+fn init_repository(temp_dir: &TempDir) -> std::path::PathBuf {
+    let dir = temp_dir.path().join("main");
+    fs::create_dir(&dir).unwrap();
+    git(&dir, &["init"]).success();
+    commit(&dir, "f.txt", "a\nb\nc\n", "first");
+    commit(&dir, "f.txt", "a\nB\nc\n", "second");
+    commit(&dir, "n.txt", "n\n", "third");
+    commit(&dir, "f.txt", "A\nB\nc\n", "fourth");
+    git(&dir, &["worktree", "add", "-b", "side", "../side", "master~3"]).success();
+    temp_dir.path().join("side")
+}
+
+fn head(dir: &Path) -> String {
+    let output = process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(["cat-file", "-p", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn cherry_pick_keeps_the_author_and_message() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["cherry-pick", "master~1"])
+        .success()
+        .stdout(contains("[side ").and(contains("] third")));
+    assert_eq!(fs::read_to_string(dir.join("n.txt")).unwrap(), "n\n");
+    assert!(head(&dir).ends_with("\nthird\n"));
+    assert!(!dir.join("../main/.git/worktrees/side/sequencer").exists());
+}
+
+#[test]
+fn a_range_without_a_start_picks_the_commits_missing_from_head() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["cherry-pick", "..master"]).success();
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "A\nB\nc\n");
+    assert_eq!(fs::read_to_string(dir.join("n.txt")).unwrap(), "n\n");
+    assert!(head(&dir).ends_with("\nfourth\n"));
+}
+
+#[test]
+fn conflicts_stop_the_sequence_until_continue() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    let git_dir = temp_dir.path().join("main/.git/worktrees/side");
+    git(&dir, &["cherry-pick", "master~2..master"])
+        .failure()
+        .stdout(contains("CONFLICT (content): Merge conflict in f.txt"))
+        .stderr(contains("could not pick"));
+    assert!(git_dir.join("CHERRY_PICK_HEAD").exists());
+    assert!(fs::read_to_string(dir.join("f.txt")).unwrap().contains("<<<<<<< HEAD\n"));
+    git(&dir, &["cherry-pick", "--continue"])
+        .failure()
+        .stderr(contains("unmerged files"));
+
+    write(&dir, "f.txt", "A\nb\nc\n");
+    git(&dir, &["add", "f.txt"]).success();
+    git(&dir, &["cherry-pick", "--continue"]).success();
+    assert!(head(&dir).ends_with("\nfourth\n"));
+    assert!(!git_dir.join("CHERRY_PICK_HEAD").exists());
+    assert!(!git_dir.join("sequencer").exists());
+    git(&dir, &["cherry-pick", "--continue"])
+        .failure()
+        .stderr(contains("no cherry-pick or revert in progress"));
+}
+
+#[test]
+fn adding_a_path_resolves_it_whatever_its_content() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["cherry-pick", "master"]).failure();
+    write(&dir, "f.txt", "A\nb\nc\n");
+    git(&dir, &["commit", "-m", "resolved"])
+        .failure()
+        .stderr(contains("Committing is not possible because you have unmerged files."));
+
+    // a resolution may keep lines that look like markers, the path is added all the same
+    write(&dir, "f.txt", "<<<<<<< ours\nA\n>>>>>>> theirs\n");
+    git(&dir, &["add", "f.txt"]).success();
+    git(&dir, &["cherry-pick", "--continue"]).success();
+    assert!(head(&dir).ends_with("\nfourth\n"));
+}
+
+#[test]
+fn skip_and_abort() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["cherry-pick", "master", "master~1"]).failure();
+    git(&dir, &["cherry-pick", "--skip"]).success();
+    assert!(head(&dir).ends_with("\nthird\n"));
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "a\nb\nc\n");
+
+    // abort goes back to where the sequence started
+    let start = head(&dir);
+    git(&dir, &["cherry-pick", "master"]).failure();
+    git(&dir, &["cherry-pick", "--abort"]).success();
+    assert_eq!(head(&dir), start);
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "a\nb\nc\n");
+    git(&dir, &["status", "--porcelain"]).success().stdout("");
+}
+
+#[test]
+fn revert_undoes_a_commit() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path().join("main");
+    init_repository(&temp_dir);
+    git(&dir, &["revert", "HEAD~1"])
+        .success()
+        .stdout(contains("Revert \"third\""));
+    assert!(!dir.join("n.txt").exists());
+    assert!(head(&dir).contains("Revert \"third\"\n\nThis reverts commit "));
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "A\nB\nc\n");
+}
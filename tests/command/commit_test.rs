@@ -1,10 +1,12 @@
-use std::{env, path::Path, process};
+use std::{env, fs, os::unix::fs::PermissionsExt, path::Path, process};
 use assert_cmd::prelude::*;
-use gitclone::util;
+use gitclone::{util, Config};
 use predicates::str::{contains, is_empty, starts_with};
 
 use tempfile::TempDir;
 
+use super::helpers::{git, git_with_env, init_main, init_repository, write};
+
 
 #[test]
 fn commit_test() {
@@ -35,3 +37,133 @@ fn commit_test() {
         .success()
         .stdout(contains("commit message"));
 }
+
+// runs `git-clone` with `script` as the editor, which edits the file given as `$1`.
+fn git_with_editor(dir: &Path, args: &[&str], script: &str) -> assert_cmd::assert::Assert {
+    let editor = dir.parent().unwrap().join("editor.sh");
+    fs::write(&editor, format!("#!/bin/sh\n{}", script)).unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    git_with_env(dir, args, &[("GIT_EDITOR", &editor.display().to_string())])
+}
+
+fn cat_head(dir: &Path) -> String {
+    String::from_utf8(git(dir, &["cat-file", "-p", "HEAD"]).success().get_output().stdout.clone()).unwrap()
+}
+
+#[test]
+fn commit_without_a_message_opens_the_editor_on_a_commented_template() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git_with_editor(
+        &dir,
+        &["commit"],
+        "cp \"$1\" ../template\nprintf 'title  \\n\\n\\n# a comment\\nbody\\n' > \"$1\"\n",
+    )
+    .success();
+    let template = fs::read_to_string(temp_dir.path().join("template")).unwrap();
+    assert!(template.starts_with("\n# Please enter the commit message for your changes."));
+    assert!(template.contains("# On branch master\n"));
+    assert!(template.contains("# Changes to be committed:\n#\tnew file:   a.txt\n"));
+    assert!(cat_head(&dir).ends_with("\n\ntitle\n\nbody"));
+
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git_with_editor(&dir, &["commit"], "printf '# only a comment\\n' > \"$1\"\n")
+        .failure()
+        .stderr(contains("Aborting commit due to empty commit message."));
+}
+
+#[test]
+fn amend_replaces_the_tip_keeping_its_parents_and_author() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git(&dir, &["commit", "-m", "first"]).success();
+    let first = fs::read_to_string(dir.join(".git/refs/heads/master")).unwrap();
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git(&dir, &["commit", "-m", "second", "--author", "Other", "--email", "other@example.com"]).success();
+
+    fs::write(dir.join("b.txt"), "three\n").unwrap();
+    git(&dir, &["add", "b.txt"]).success();
+    git(&dir, &["commit", "--amend", "--no-edit"]).success();
+    let amended = cat_head(&dir);
+    assert!(amended.contains(&format!("parent {}", first.trim())));
+    assert!(amended.contains("\nauthor Other <other@example.com> "));
+    assert!(!amended.contains("\ncommitter Other"));
+    assert!(amended.ends_with("\n\nsecond"));
+
+    git_with_editor(&dir, &["commit", "--amend"], "cp \"$1\" ../template\n").success();
+    let template = fs::read_to_string(temp_dir.path().join("template")).unwrap();
+    assert!(template.starts_with("second\n\n# Please enter"));
+    git(&dir, &["commit", "--amend", "--reset-author", "-m", "reworded"]).success();
+    let reworded = cat_head(&dir);
+    assert!(reworded.contains(&format!("parent {}", first.trim())));
+    assert!(!reworded.contains("\nauthor Other"));
+    assert!(reworded.ends_with("\n\nreworded"));
+}
+
+#[test]
+fn a_commit_without_changes_needs_allow_empty() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git(&dir, &["commit", "-m", "first"]).success();
+    git(&dir, &["commit", "-m", "again"])
+        .failure()
+        .stdout(contains("nothing to commit, working tree clean"))
+        .stderr(contains("nothing to commit"));
+    git(&dir, &["commit", "--amend", "-m", "again"])
+        .success();
+    git(&dir, &["commit", "--allow-empty", "-m", "empty"]).success();
+    assert!(cat_head(&dir).ends_with("\n\nempty"));
+    git(&dir, &["commit", "--amend", "-m", "still empty"])
+        .failure()
+        .stderr(contains("You asked to amend the most recent commit, but doing so would make"));
+}
+
+#[test]
+fn the_message_file_is_cleaned_up_by_the_cleanup_mode() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    fs::write(temp_dir.path().join("message"), "\n\ntitle   \n\n\n# kept\nbody\n\n").unwrap();
+    git(&dir, &["commit", "-F", "../message"]).success();
+    assert!(cat_head(&dir).ends_with("\n\ntitle\n\n# kept\nbody"));
+
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git(&dir, &["commit", "--cleanup=strip", "-F", "../message"]).success();
+    assert!(cat_head(&dir).ends_with("\n\ntitle\n\nbody"));
+
+    fs::write(dir.join("a.txt"), "three\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    assert_cmd::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["commit", "--cleanup=verbatim", "-F", "-"])
+        .current_dir(&dir)
+        .write_stdin("  from stdin  \n")
+        .assert()
+        .success();
+    assert!(cat_head(&dir).ends_with("\n\n  from stdin  \n"));
+}
+
+#[test]
+fn the_identity_comes_from_the_environment_or_the_configuration() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir, &[]);
+    write(&dir, "a.txt", "one\n");
+    git(&dir, &["add", "a.txt"]).success();
+    let anonymous = [("GIT_AUTHOR_NAME", ""), ("GIT_AUTHOR_EMAIL", "")];
+    git_with_env(&dir, &["commit", "-m", "first"], &anonymous)
+        .failure()
+        .stderr(contains("Author identity unknown"));
+
+    let mut config = Config::new(&dir.join(".git/config"));
+    config.load().unwrap();
+    config.set("user.name", "Configured").unwrap();
+    config.set("user.email", "configured@e").unwrap();
+    config.save().unwrap();
+    git_with_env(&dir, &["commit", "-m", "first"], &anonymous).success();
+    assert!(cat_head(&dir).contains("\nauthor Configured <configured@e> "));
+
+    git_with_env(&dir, &["commit", "--allow-empty", "-m", "second"], &[("GIT_AUTHOR_NAME", "Env")]).success();
+    assert!(cat_head(&dir).contains("\nauthor Env <"));
+}
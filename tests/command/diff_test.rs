@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::Path;
+
+use predicates::str::{contains, ends_with, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, write};
+
+fn numbers() -> String {
+    (1..=20).map(|n| format!("{}\n", n)).collect()
+}
+
+fn init_repository(temp_dir: &TempDir) {
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    write(dir, "a.txt", &numbers());
+    write(dir, "b.txt", "x\ny\n");
+    git(dir, &["add", "a.txt", "b.txt"]).success();
+    git(dir, &["commit", "-m", "first"]).success();
+}
+
+// moves a.txt to c.txt, changing one of its lines, and stages the move.
+fn stage_rename(dir: &Path) {
+    fs::remove_file(dir.join("a.txt")).unwrap();
+    write(dir, "c.txt", &numbers().replace("\n5\n", "\nfive\n"));
+    git(dir, &["add", "a.txt", "c.txt"]).success();
+}
+
+#[test]
+fn diff_shows_unstaged_and_staged_changes() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    git(dir, &["diff"]).success().stdout(is_empty());
+
+    write(dir, "b.txt", "x\nz");
+    git(dir, &["diff"]).success().stdout(contains(
+        "diff --git a/b.txt b/b.txt\nindex b77b4eb..",
+    ));
+    git(dir, &["diff"])
+        .success()
+        .stdout(contains(" 100644\n--- a/b.txt\n+++ b/b.txt\n@@ -1,2 +1,2 @@\n x\n-y\n+z\n\\ No newline at end of file\n"));
+    git(dir, &["diff", "--cached"]).success().stdout(is_empty());
+
+    write(dir, "new.txt", "new\n");
+    git(dir, &["add", "new.txt"]).success();
+    git(dir, &["diff", "--cached"]).success().stdout(
+        "diff --git a/new.txt b/new.txt\n\
+         new file mode 100644\n\
+         index 0000000..3e75765\n\
+         --- /dev/null\n\
+         +++ b/new.txt\n\
+         @@ -0,0 +1 @@\n\
+         +new\n",
+    );
+    git(dir, &["diff", "--name-status", "HEAD"])
+        .success()
+        .stdout("M\tb.txt\nA\tnew.txt\n");
+}
+
+#[test]
+fn renames_are_detected_by_similarity() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    stage_rename(dir);
+
+    git(dir, &["diff", "--cached", "--name-status"])
+        .success()
+        .stdout("R090\ta.txt\tc.txt\n");
+    git(dir, &["diff", "--cached"]).success().stdout(contains(
+        "diff --git a/a.txt b/c.txt\nsimilarity index 90%\nrename from a.txt\nrename to c.txt\n",
+    ));
+    git(dir, &["diff", "--cached", "--name-status", "-M95%"])
+        .success()
+        .stdout("D\ta.txt\nA\tc.txt\n");
+    git(dir, &["diff", "--cached", "--name-status", "--no-renames"])
+        .success()
+        .stdout("D\ta.txt\nA\tc.txt\n");
+    git(dir, &["status", "--short"]).success().stdout("R  a.txt -> c.txt\n");
+
+    git(dir, &["commit", "-m", "rename"]).success();
+    git(dir, &["log", "--name-status"])
+        .success()
+        .stdout(contains("    rename\n\nR090\ta.txt\tc.txt\n"))
+        .stdout(contains("    first\n\nA\ta.txt\nA\tb.txt\n"));
+    git(dir, &["diff", "--name-only", "HEAD~1", "HEAD"])
+        .success()
+        .stdout("c.txt\n");
+}
+
+#[test]
+fn copies_and_exact_renames() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    write(dir, "a.txt", &numbers().replace("20\n", "twenty\n"));
+    write(dir, "copy.txt", &numbers());
+    git(dir, &["add", "a.txt", "copy.txt"]).success();
+    git(dir, &["diff", "--cached", "--name-status"])
+        .success()
+        .stdout("M\ta.txt\nA\tcopy.txt\n");
+    git(dir, &["diff", "--cached", "--name-status", "-C"])
+        .success()
+        .stdout("M\ta.txt\nC100\ta.txt\tcopy.txt\n");
+
+    fs::rename(dir.join("b.txt"), dir.join("d.txt")).unwrap();
+    git(dir, &["add", "."]).success();
+    git(dir, &["status", "--porcelain"]).success().stdout(contains("R  b.txt -> d.txt\n"));
+    git(dir, &["diff", "--cached", "--name-status"])
+        .success()
+        .stdout(contains("R100\tb.txt\td.txt\n"));
+    git(dir, &["diff", "--cached"])
+        .success()
+        .stdout(ends_with("similarity index 100%\nrename from b.txt\nrename to d.txt\n"));
+}
@@ -0,0 +1,126 @@
+use std::fs;
+use std::{path::Path, process};
+use assert_cmd::prelude::*;
+use gitclone::{util, Command};
+use predicates::str::{contains, is_empty, is_match};
+
+use tempfile::TempDir;
+
+fn init_with_files(temp_dir: &TempDir) {
+    let _paths =
+        util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("a").join("1.txt"), "one".as_bytes()), (Path::new("a").join("2.txt"), "two".as_bytes()), (Path::new("b.txt").to_path_buf(), "b".as_bytes())]).unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init"])
+        .current_dir(temp_dir)
+        .assert()
+        .success();
+}
+
+#[test]
+fn status_from_a_subdirectory_prints_relative_paths() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_files(&temp_dir);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "a/1.txt"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["status", "--short"])
+        .current_dir(temp_dir.path().join("a"))
+        .assert()
+        .success()
+        .stdout(is_match("^(A  1.txt\n\\?\\? 2.txt\n\\?\\? ../b.txt\n)$").unwrap());
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["-C", "a", "status", "--short"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_match("^(A  1.txt\n\\?\\? 2.txt\n\\?\\? ../b.txt\n)$").unwrap());
+}
+
+#[test]
+fn add_resolves_paths_from_a_subdirectory() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_files(&temp_dir);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "2.txt", "../b.txt"])
+        .current_dir(temp_dir.path().join("a"))
+        .assert()
+        .success()
+        .stdout(is_empty());
+    let mut command = Command::new(temp_dir.path().to_path_buf()).unwrap();
+    command.index.load().unwrap();
+    let entries = command
+        .index
+        .each_entry()
+        .unwrap()
+        .iter()
+        .map(|e| e.get_path())
+        .collect::<Vec<_>>();
+    assert_eq!(entries, vec!["a/2.txt".to_string(), "b.txt".to_string()]);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "../../outside.txt"])
+        .current_dir(temp_dir.path().join("a"))
+        .assert()
+        .failure()
+        .stderr(contains("is outside repository"));
+}
+
+#[test]
+fn honors_git_dir_and_git_work_tree() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_files(&temp_dir);
+    let elsewhere = TempDir::new().expect("unable to create a temporary working directory");
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["status", "--short"])
+        .current_dir(&elsewhere)
+        .env("GIT_DIR", temp_dir.path().join(".git"))
+        .env("GIT_WORK_TREE", temp_dir.path())
+        .assert()
+        .success()
+        .stdout(is_match("^(\\?\\? a/\n\\?\\? b.txt\n)$").unwrap());
+}
+
+#[test]
+fn follows_gitdir_files() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_files(&temp_dir);
+    fs::rename(temp_dir.path().join(".git"), temp_dir.path().join("separate.git")).unwrap();
+    fs::write(temp_dir.path().join(".git"), "gitdir: separate.git\n").unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "b.txt"])
+        .current_dir(temp_dir.path().join("a"))
+        .assert()
+        .failure()
+        .stderr(contains("did not match any files"));
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "../b.txt"])
+        .current_dir(temp_dir.path().join("a"))
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("separate.git/index").exists());
+}
+
+#[test]
+fn stops_at_ceiling_directories() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_files(&temp_dir);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["status", "--short"])
+        .current_dir(temp_dir.path().join("a"))
+        .env("GIT_CEILING_DIRECTORIES", temp_dir.path())
+        .assert()
+        .failure()
+        .stderr(contains("not a git repository"));
+}
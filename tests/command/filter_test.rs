@@ -0,0 +1,169 @@
+use std::fs;
+
+use gitclone::lfs::Pointer;
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, head_blob, init_repository};
+
+#[test]
+fn clean_and_smudge_commands_filter_the_content() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(
+        &temp_dir,
+        &[
+            ("filter.upper.clean", "tr a-z A-Z"),
+            ("filter.upper.smudge", "tr A-Z a-z"),
+            ("filter.named.clean", "echo %f"),
+        ],
+    );
+    fs::write(dir.join(".gitattributes"), "*.txt filter=upper\n*.name filter=named\n").unwrap();
+    fs::write(dir.join("a.txt"), "hello\n").unwrap();
+    fs::write(dir.join("my file.name"), "content\n").unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    assert_eq!(head_blob(&dir, "a.txt"), b"HELLO\n");
+    assert_eq!(head_blob(&dir, "my file.name"), b"my file.name\n");
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    git(&dir, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    assert_eq!(fs::read(other.join("a.txt")).unwrap(), b"hello\n");
+    // without a smudge command the blob is checked out as it is
+    assert_eq!(fs::read(other.join("my file.name")).unwrap(), b"my file.name\n");
+
+    fs::write(dir.join("a.txt"), "hello\nworld\n").unwrap();
+    git(&dir, &["diff"]).success().stdout(contains(" HELLO\n+WORLD\n"));
+}
+
+#[test]
+fn a_failing_filter_stops_only_when_it_is_required() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(
+        &temp_dir,
+        &[
+            ("filter.broken.clean", "false"),
+            ("filter.strict.clean", "false"),
+            ("filter.strict.required", "true"),
+        ],
+    );
+    fs::write(dir.join(".gitattributes"), "a.txt filter=broken\nb.txt filter=strict\n").unwrap();
+    fs::write(dir.join("a.txt"), "a\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    git(&dir, &["add", "a.txt"])
+        .success()
+        .stderr(contains("error: external filter 'false' failed 1\n"));
+    git(&dir, &["add", "b.txt"])
+        .failure()
+        .stderr(contains("b.txt: clean filter 'strict' failed"));
+    git(&dir, &["commit", "-m", "first"]).success();
+    assert_eq!(head_blob(&dir, "a.txt"), b"a\n");
+}
+
+// A filter talking the process protocol, which upper-cases on clean and lower-cases on
+// smudge and logs the commands it is given.
+const PROCESS_FILTER: &str = r#"
+import sys
+
+def read_packet():
+    length = int(sys.stdin.buffer.read(4), 16)
+    return None if length == 0 else sys.stdin.buffer.read(length - 4)
+
+def read_list():
+    lines = []
+    while (packet := read_packet()) is not None:
+        lines.append(packet.decode().rstrip("\n"))
+    return lines
+
+def write_packet(data):
+    sys.stdout.buffer.write(b"%04x" % (len(data) + 4) + data)
+
+def flush():
+    sys.stdout.buffer.write(b"0000")
+    sys.stdout.buffer.flush()
+
+read_list()
+write_packet(b"git-filter-server\n")
+write_packet(b"version=2\n")
+flush()
+read_list()
+write_packet(b"capability=clean\n")
+write_packet(b"capability=smudge\n")
+flush()
+log = open(sys.argv[1], "a")
+while True:
+    try:
+        request = dict(line.split("=", 1) for line in read_list())
+    except ValueError:
+        break
+    content = b""
+    while (packet := read_packet()) is not None:
+        content += packet
+    log.write("%s %s\n" % (request["command"], request["pathname"]))
+    log.flush()
+    if request["pathname"] == "bad.txt":
+        write_packet(b"status=error\n")
+        flush()
+        continue
+    write_packet(b"status=success\n")
+    flush()
+    write_packet(content.upper() if request["command"] == "clean" else content.lower())
+    flush()
+    flush()
+"#;
+
+#[test]
+fn a_long_running_filter_handles_every_file() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let script = temp_dir.path().join("filter.py");
+    let log = temp_dir.path().join("filter.log");
+    fs::write(&script, PROCESS_FILTER).unwrap();
+    let process = format!("python3 {} {}", script.display(), log.display());
+    let dir = init_repository(&temp_dir, &[("filter.proc.process", &process)]);
+    fs::write(dir.join(".gitattributes"), "*.txt filter=proc\n").unwrap();
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    fs::write(dir.join("b.txt"), "two\n").unwrap();
+    fs::write(dir.join("bad.txt"), "three\n").unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    assert_eq!(head_blob(&dir, "a.txt"), b"ONE\n");
+    assert_eq!(head_blob(&dir, "b.txt"), b"TWO\n");
+    assert_eq!(head_blob(&dir, "bad.txt"), b"three\n");
+    let logged = fs::read_to_string(&log).unwrap();
+    assert!(logged.contains("clean a.txt\n") && logged.contains("clean b.txt\n"));
+
+    fs::remove_file(&log).unwrap();
+    git(&dir, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    assert_eq!(fs::read(other.join("a.txt")).unwrap(), b"one\n");
+    assert_eq!(fs::read(other.join("b.txt")).unwrap(), b"two\n");
+    assert!(fs::read_to_string(&log).unwrap().contains("smudge a.txt\n"));
+}
+
+#[test]
+fn the_lfs_filter_commits_pointers_to_a_local_store() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir, &[]);
+    let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(dir.join(".gitattributes"), "*.bin filter=lfs diff=lfs merge=lfs -text\n").unwrap();
+    fs::write(dir.join("big.bin"), &content).unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+
+    let pointer = Pointer::new(&content);
+    assert_eq!(pointer.size, 100_000);
+    assert_eq!(head_blob(&dir, "big.bin"), pointer.to_bytes());
+    let stored = dir
+        .join(".git/lfs/objects")
+        .join(&pointer.oid[0..2])
+        .join(&pointer.oid[2..4])
+        .join(&pointer.oid);
+    assert_eq!(fs::read(stored).unwrap(), content);
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    git(&dir, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    assert_eq!(fs::read(other.join("big.bin")).unwrap(), content);
+    git(&other, &["status", "--porcelain"]).success().stdout(is_empty());
+}
@@ -0,0 +1,97 @@
+use std::fs::{self, Permissions};
+use std::os::unix::prelude::PermissionsExt;
+use std::{path::Path, process};
+use assert_cmd::prelude::*;
+use gitclone::util;
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+fn init_and_commit(temp_dir: &TempDir) {
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init"])
+        .current_dir(temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("Initialized empty Jit repository in"));
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "."])
+        .current_dir(temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["commit", "-m", "commit message"])
+        .current_dir(temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("commit message"));
+}
+
+#[test]
+fn reports_nothing_for_a_healthy_repository() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let _paths =
+        util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "hello".as_bytes()), (Path::new("a/b").join("nested.txt"), "world".as_bytes())]).unwrap();
+    init_and_commit(&temp_dir);
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["fsck"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+}
+
+#[test]
+fn reports_dangling_blobs() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let _paths =
+        util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "hello".as_bytes())]).unwrap();
+    init_and_commit(&temp_dir);
+    util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "changed".as_bytes())]).unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "file.txt"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "changed again".as_bytes())]).unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "file.txt"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    // the blob of "changed" is neither in a commit nor in the index anymore
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["fsck"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("dangling blob 21fb1eca31e64cd3914025058b21992ab76edcf9"));
+}
+
+#[test]
+fn reports_corrupt_and_missing_objects() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let _paths =
+        util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "hello".as_bytes())]).unwrap();
+    init_and_commit(&temp_dir);
+    // blob of "hello"
+    let blob = temp_dir.path().join(".git/objects/b6/fc4c620b67d95f953a5c1c1230aaab5db5a1b0");
+    fs::set_permissions(&blob, Permissions::from_mode(0o644)).unwrap();
+    fs::write(&blob, "not a zlib stream").unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["fsck"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(contains("error: b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0: object corrupt or missing"))
+        .stdout(contains("missing blob b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0"));
+}
@@ -0,0 +1,97 @@
+// What the tests of the commands share: running `git-clone` and building the repositories
+// they start from.
+use std::path::{Path, PathBuf};
+use std::process;
+
+use assert_cmd::assert::Assert;
+use assert_cmd::prelude::*;
+use gitclone::{util, Config};
+use tempfile::TempDir;
+
+// runs `git-clone` with `args` in `dir`.
+pub fn git(dir: &Path, args: &[&str]) -> Assert {
+    git_with_env(dir, args, &[])
+}
+
+// runs `git-clone` with the environment variables `env` set, like its editors.
+pub fn git_with_env(dir: &Path, args: &[&str], env: &[(&str, &str)]) -> Assert {
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(args)
+        .envs(env.iter().cloned())
+        .current_dir(dir)
+        .assert()
+}
+
+// runs `git-clone` with `answers` on its standard input.
+pub fn git_answering(dir: &Path, args: &[&str], answers: &str) -> Assert {
+    assert_cmd::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(args)
+        .current_dir(dir)
+        .write_stdin(answers)
+        .assert()
+}
+
+// writes `content` to `path` in `dir`, creating the directories it is in.
+pub fn write(dir: &Path, path: &str, content: &str) {
+    util::write_file(&dir.to_path_buf(), vec![(Path::new(path).to_path_buf(), content.as_bytes())]).unwrap();
+}
+
+// writes `content` to `path` and commits it alone with `message`.
+pub fn commit(dir: &Path, path: &str, content: &str, message: &str) {
+    write(dir, path, content);
+    git(dir, &["add", path]).success();
+    git(dir, &["commit", "-m", message]).success();
+}
+
+// a repository `main` in `temp_dir` with `config` set in its configuration.
+pub fn init_repository(temp_dir: &TempDir, config: &[(&str, &str)]) -> PathBuf {
+    git(temp_dir.path(), &["init", "main"]).success();
+    let dir = temp_dir.path().join("main");
+    if !config.is_empty() {
+        let mut repository_config = Config::new(&dir.join(".git/config"));
+        repository_config.load().unwrap();
+        for (key, value) in config {
+            repository_config.set(key, value).unwrap();
+        }
+        repository_config.save().unwrap();
+    }
+    dir
+}
+
+// a repository `main` in `temp_dir` with `files` added but not committed.
+pub fn init_main(temp_dir: &TempDir, files: &[(&str, &str)]) -> PathBuf {
+    let dir = init_repository(temp_dir, &[]);
+    for (path, content) in files {
+        write(&dir, path, content);
+    }
+    let mut args = vec!["add"];
+    args.extend(files.iter().map(|(path, _)| *path));
+    git(&dir, &args).success();
+    dir
+}
+
+// a repository `main` in `temp_dir` with `files` committed as `first`.
+pub fn init_committed(temp_dir: &TempDir, files: &[(&str, &str)]) -> PathBuf {
+    let dir = init_main(temp_dir, files);
+    git(&dir, &["commit", "-m", "first"]).success();
+    dir
+}
+
+// the content of the blob of `path` in HEAD.
+pub fn head_blob(dir: &Path, path: &str) -> Vec<u8> {
+    let commit = git(dir, &["cat-file", "-p", "HEAD"]).success().get_output().stdout.clone();
+    let commit = String::from_utf8(commit).unwrap();
+    let tree = commit.lines().find_map(|line| line.strip_prefix("tree ")).unwrap();
+    let tree = git(dir, &["cat-file", "-p", tree]).success().get_output().stdout.clone();
+    // each line is `<mode> <type> <oid>\t<path>`
+    let tree = String::from_utf8(tree).unwrap();
+    let oid = tree
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .find(|(_, name)| *name == path)
+        .and_then(|(entry, _)| entry.split(' ').nth(2))
+        .unwrap();
+    git(dir, &["cat-file", "-p", oid]).success().get_output().stdout.clone()
+}
@@ -0,0 +1,140 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use gitclone::Config;
+use predicates::str::contains;
+
+use tempfile::TempDir;
+
+use super::helpers::{git, init_main};
+
+// writes the shell script `hook` in the hooks directory `hooks`.
+fn write_hook(hooks: &Path, name: &str, script: &str) {
+    fs::create_dir_all(hooks).unwrap();
+    let path = hooks.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{}", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn log(dir: &Path) -> String {
+    String::from_utf8(git(dir, &["log"]).success().get_output().stdout.clone()).unwrap()
+}
+
+#[test]
+fn a_failing_pre_commit_hook_aborts_the_commit_unless_no_verify() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    let hooks = dir.join(".git/hooks");
+    write_hook(&hooks, "pre-commit", "echo \"index $GIT_INDEX_FILE\"\nexit 1\n");
+    git(&dir, &["commit", "-m", "first"])
+        .failure()
+        .stderr(contains(format!("index {}", dir.join(".git/index").display())))
+        .stderr(contains("the pre-commit hook declined the commit"));
+    assert!(!dir.join(".git/refs/heads/master").exists());
+    git(&dir, &["commit", "--no-verify", "-m", "first"]).success();
+    assert!(log(&dir).contains("first"));
+}
+
+#[test]
+fn commit_message_hooks_can_edit_or_reject_the_message() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    let hooks = dir.join(".git/hooks");
+    write_hook(&hooks, "prepare-commit-msg", "echo \"$2\" > ../prepare-args\n");
+    write_hook(&hooks, "commit-msg", "grep -q bad \"$1\" && exit 1\necho 'Signed-off-by: t' >> \"$1\"\n");
+    write_hook(&hooks, "post-commit", "touch ../post-commit\n");
+
+    git(&dir, &["commit", "-m", "bad message"])
+        .failure()
+        .stderr(contains("the commit-msg hook declined the commit"));
+    git(&dir, &["commit", "-m", "first"]).success();
+    assert_eq!(fs::read_to_string(temp_dir.path().join("prepare-args")).unwrap(), "message\n");
+    assert!(log(&dir).contains("Signed-off-by: t"));
+    assert!(temp_dir.path().join("post-commit").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/COMMIT_EDITMSG")).unwrap(),
+        "first\nSigned-off-by: t\n"
+    );
+}
+
+#[test]
+fn hooks_are_found_in_core_hooks_path_and_must_be_executable() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    let mut config = Config::new(&dir.join(".git/config"));
+    config.load().unwrap();
+    config.set("core.hooksPath", "my-hooks").unwrap();
+    config.save().unwrap();
+    write_hook(&dir.join(".git/hooks"), "pre-commit", "exit 1\n");
+    write_hook(&dir.join("my-hooks"), "commit-msg", "exit 1\n");
+    fs::set_permissions(dir.join("my-hooks/commit-msg"), fs::Permissions::from_mode(0o644)).unwrap();
+    git(&dir, &["commit", "-m", "first"])
+        .success()
+        .stderr(contains("hint: The 'commit-msg' hook was ignored because it's not set as executable."));
+}
+
+#[test]
+fn the_reference_transaction_hook_sees_and_can_abort_ref_updates() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git(&dir, &["commit", "-m", "first"]).success();
+    let hooks = dir.join(".git/hooks");
+    write_hook(
+        &hooks,
+        "reference-transaction",
+        "while read old new name; do echo \"$1 $name\" >> ../transactions; done\n\
+         test \"$1\" = prepared && test -e ../refuse && exit 1\nexit 0\n",
+    );
+    git(&dir, &["update-ref", "refs/heads/topic", "HEAD"]).success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("transactions")).unwrap(),
+        "prepared refs/heads/topic\ncommitted refs/heads/topic\n"
+    );
+    fs::write(temp_dir.path().join("refuse"), "").unwrap();
+    git(&dir, &["update-ref", "refs/heads/other", "HEAD"])
+        .failure()
+        .stderr(contains("in 'prepared' phase, update aborted by the reference-transaction hook"));
+    assert!(!dir.join(".git/refs/heads/other").exists());
+}
+
+#[test]
+fn the_pre_push_hook_is_given_the_refs_and_can_reject_the_push() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git(&dir, &["commit", "-m", "first"]).success();
+    git(temp_dir.path(), &["init", "--bare", "remote.git"]).success();
+    let head = fs::read_to_string(dir.join(".git/refs/heads/master")).unwrap();
+    write_hook(&dir.join(".git/hooks"), "pre-push", "echo \"$1\" > ../pushed\ncat >> ../pushed\nexit 1\n");
+
+    git(&dir, &["push", "../remote.git"])
+        .failure()
+        .stdout(contains("(pre-push hook declined)"))
+        .stderr(contains("failed to push some refs to"));
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("pushed")).unwrap(),
+        format!(
+            "{}\nrefs/heads/master {} refs/heads/master {}\n",
+            temp_dir.path().join("remote.git").display(),
+            head.trim(),
+            "0".repeat(40)
+        )
+    );
+    assert!(!temp_dir.path().join("remote.git/refs/heads/master").exists());
+    git(&dir, &["push", "--no-verify", "../remote.git"]).success();
+    assert!(temp_dir.path().join("remote.git/refs/heads/master").exists());
+}
+
+#[test]
+fn worktree_add_runs_the_post_checkout_hook() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_main(&temp_dir, &[("a.txt", "one\n")]);
+    git(&dir, &["commit", "-m", "first"]).success();
+    let head = fs::read_to_string(dir.join(".git/refs/heads/master")).unwrap();
+    write_hook(&dir.join(".git/hooks"), "post-checkout", "echo \"$@\" > post-checkout\n");
+    git(&dir, &["worktree", "add", "../feature"]).success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("feature/post-checkout")).unwrap(),
+        format!("{} {} 1\n", "0".repeat(40), head.trim())
+    );
+}
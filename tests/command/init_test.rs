@@ -0,0 +1,80 @@
+use std::fs;
+use std::{path::Path, process};
+use assert_cmd::prelude::*;
+use gitclone::util;
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+#[test]
+fn writes_the_object_format_extension() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init", "--object-format=sha256"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("Initialized empty Jit repository in"));
+    let config = fs::read_to_string(temp_dir.path().join(".git/config")).unwrap();
+    assert!(config.contains("repositoryformatversion = 1"));
+    assert!(config.contains("[extensions]\n\tobjectformat = sha256"));
+}
+
+#[test]
+fn rejects_unknown_object_formats() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init", "--object-format=md5"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(contains("unknown hash algorithm 'md5'"));
+}
+
+#[test]
+fn stores_objects_named_with_sha256() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let _paths =
+        util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("hello.txt").to_path_buf(), "hello".as_bytes()), (Path::new("dir").join("world.txt"), "world".as_bytes())]).unwrap();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["init", "--object-format=sha256"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "."])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+    // sha256 of "blob 5\0hello"
+    assert!(temp_dir
+        .path()
+        .join(".git/objects/8a/ec4e4876f854f688d0ebfc8f37598f38e5fd6903cccc850ca36591175aeb60")
+        .exists());
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["commit", "-m", "commit message"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(contains("commit message"));
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["status", "--porcelain"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+    process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["fsck"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(is_empty());
+}
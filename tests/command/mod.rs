@@ -1,3 +1,23 @@
+mod helpers;
 mod status_test;
 mod add_test;
 mod commit_test;
+mod fsck_test;
+mod init_test;
+mod discovery_test;
+mod bare_test;
+mod worktree_test;
+mod stash_test;
+mod cherry_pick_test;
+mod rebase_test;
+mod blame_test;
+mod diff_test;
+mod symlink_test;
+mod submodule_test;
+mod sparse_checkout_test;
+mod attributes_test;
+mod filter_test;
+mod signing_test;
+mod hooks_test;
+mod patch_test;
+mod restore_test;
@@ -0,0 +1,147 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use predicates::str::contains;
+
+use tempfile::TempDir;
+
+use super::helpers::{git, git_answering, init_committed};
+
+fn numbers(replace: &[(usize, &str)]) -> String {
+    (1..=12)
+        .map(|n| match replace.iter().find(|(line, _)| *line == n) {
+            Some((_, text)) => format!("{}\n", text),
+            None => format!("{}\n", n),
+        })
+        .collect()
+}
+
+// a repository `main` with `f.txt` committed as the numbers from 1 to 12.
+fn init_numbers(temp_dir: &TempDir) -> PathBuf {
+    init_committed(temp_dir, &[("f.txt", &numbers(&[]))])
+}
+
+fn output(assert: assert_cmd::assert::Assert) -> String {
+    String::from_utf8(assert.get_output().stdout.clone()).unwrap()
+}
+
+fn staged(dir: &Path) -> String {
+    output(git(dir, &["diff", "--cached"]).success())
+}
+
+fn unstaged(dir: &Path) -> String {
+    output(git(dir, &["diff"]).success())
+}
+
+#[test]
+fn add_patch_stages_the_hunks_picked() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("f.txt"), numbers(&[(2, "two"), (11, "eleven")])).unwrap();
+    git_answering(&dir, &["add", "-p"], "y\nn\n")
+        .success()
+        .stdout(contains("diff --git a/f.txt b/f.txt\n"))
+        .stdout(contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n"))
+        .stdout(contains("(1/2) Stage this hunk [y,n,q,a,d,e,?]? "))
+        .stdout(contains("(2/2) Stage this hunk [y,n,q,a,d,e,?]? "));
+    assert!(staged(&dir).contains("+two\n"));
+    assert!(!staged(&dir).contains("+eleven\n"));
+    assert!(unstaged(&dir).contains("+eleven\n"));
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), numbers(&[(2, "two"), (11, "eleven")]));
+}
+
+#[test]
+fn a_hunk_can_be_split_and_the_rest_skipped() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("f.txt"), numbers(&[(3, "three"), (7, "seven"), (9, "nine")])).unwrap();
+    git_answering(&dir, &["add", "-p"], "s\nn\ny\nd\n")
+        .success()
+        .stdout(contains("(1/1) Stage this hunk [y,n,q,a,d,s,e,?]? Split into 3 hunks.\n"))
+        .stdout(contains("@@ -4,5 +4,5 @@\n 4\n 5\n 6\n-7\n+seven\n 8\n"))
+        .stdout(contains("(3/3) Stage this hunk"));
+    let staged = staged(&dir);
+    assert!(staged.contains("+seven\n"));
+    assert!(!staged.contains("+three\n"));
+    assert!(!staged.contains("+nine\n"));
+}
+
+#[test]
+fn an_edited_hunk_is_staged_as_written() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("f.txt"), numbers(&[(2, "two"), (3, "three")])).unwrap();
+    let editor = temp_dir.path().join("editor.sh");
+    fs::write(&editor, "#!/bin/sh\nsed -i -e 's/^+two$/+TWO/' -e 's/^-3$/ 3/' -e '/^+three$/d' \"$1\"\n").unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    assert_cmd::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(&["add", "-p"])
+        .current_dir(&dir)
+        .env("GIT_EDITOR", &editor)
+        .write_stdin("e\n")
+        .assert()
+        .success();
+    assert!(staged(&dir).contains(" 1\n-2\n 3\n+TWO\n 4\n"));
+    assert!(!dir.join(".git/addp-hunk-edit.diff").exists());
+}
+
+#[test]
+fn quitting_leaves_the_remaining_hunks_and_files() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("g.txt"), "one\n").unwrap();
+    git(&dir, &["add", "g.txt"]).success();
+    git(&dir, &["commit", "-m", "second"]).success();
+    fs::write(dir.join("f.txt"), numbers(&[(2, "two"), (11, "eleven")])).unwrap();
+    fs::remove_file(dir.join("g.txt")).unwrap();
+    git_answering(&dir, &["add", "-p"], "y\nq\n").success();
+    let staged = staged(&dir);
+    assert!(staged.contains("+two\n"));
+    assert!(!staged.contains("+eleven\n"));
+    assert!(!staged.contains("g.txt"));
+
+    git_answering(&dir, &["add", "-p", "g.txt"], "?\ny\n")
+        .success()
+        .stdout(contains("deleted file mode 100644\n"))
+        .stdout(contains("(1/1) Stage deletion [y,n,q,a,d,?]? y - stage this hunk\n"));
+    assert!(self::staged(&dir).contains("deleted file mode 100644"));
+}
+
+#[test]
+fn reset_patch_unstages_the_hunks_picked() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("f.txt"), numbers(&[(2, "two"), (11, "eleven")])).unwrap();
+    fs::write(dir.join("new.txt"), "new\n").unwrap();
+    git(&dir, &["add", "f.txt", "new.txt"]).success();
+    git_answering(&dir, &["reset", "-p"], "n\ny\ny\n")
+        .success()
+        .stdout(contains("(1/2) Unstage this hunk [y,n,q,a,d,e,?]? "))
+        .stdout(contains("(1/1) Unstage addition [y,n,q,a,d,?]? "));
+    let staged = staged(&dir);
+    assert!(staged.contains("+two\n"));
+    assert!(!staged.contains("+eleven\n"));
+    assert!(!staged.contains("new.txt"));
+    assert!(dir.join("new.txt").exists());
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), numbers(&[(2, "two"), (11, "eleven")]));
+}
+
+#[test]
+fn restore_patch_discards_hunks_from_the_working_tree() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_numbers(&temp_dir);
+    fs::write(dir.join("f.txt"), numbers(&[(2, "two"), (11, "eleven")])).unwrap();
+    git_answering(&dir, &["restore", "-p"], "y\nn\n")
+        .success()
+        .stdout(contains("(1/2) Discard this hunk from worktree [y,n,q,a,d,e,?]? "));
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), numbers(&[(11, "eleven")]));
+
+    git(&dir, &["add", "f.txt"]).success();
+    git_answering(&dir, &["restore", "--staged", "-p", "f.txt"], "y\n")
+        .success()
+        .stdout(contains("Unstage this hunk"));
+    assert!(staged(&dir).is_empty());
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), numbers(&[(11, "eleven")]));
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use assert_cmd::prelude::*;
+use predicates::str::contains;
+
+use tempfile::TempDir;
+
+use super::helpers::{commit, git, git_with_env, write};
+
+fn git_with_editors(dir: &Path, args: &[&str], sequence_editor: &str, editor: &str) -> assert_cmd::assert::Assert {
+    git_with_env(dir, args, &[("GIT_SEQUENCE_EDITOR", sequence_editor), ("GIT_EDITOR", editor)])
+}
+
+// the titles of the commits reachable from HEAD, newest first.
+fn titles(dir: &Path) -> Vec<String> {
+    let output = process::Command::cargo_bin("git-clone")
+        .unwrap()
+        .args(["log"])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    let log = String::from_utf8(output.stdout).unwrap();
+    let mut titles = Vec::new();
+    for block in log.split("\ncommit ") {
+        if let Some((_, message)) = block.split_once("\n\n") {
+            titles.push(message.lines().next().unwrap_or("").trim().to_string());
+        }
+    }
+    titles
+}
+
+// master with `first` and `main1`, and a worktree with the branch `side` forked at `first`
+// holding `side1`, `side2` and a fixup of `side1`.
+fn init_repository(temp_dir: &TempDir) -> (PathBuf, PathBuf) {
+    let main = temp_dir.path().join("main");
+    fs::create_dir(&main).unwrap();
+    git(&main, &["init"]).success();
+    commit(&main, "f.txt", "a\nb\nc\n", "first");
+    git(&main, &["worktree", "add", "-b", "side", "../side"]).success();
+    commit(&main, "m.txt", "m\n", "main1");
+    let side = temp_dir.path().join("side");
+    commit(&side, "s.txt", "s\n", "side1");
+    commit(&side, "f.txt", "a\nb\nC\n", "side2");
+    commit(&side, "s.txt", "s2\n", "fixup! side1");
+    (main, side)
+}
+
+#[test]
+fn rebase_replays_the_commits_on_the_upstream() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (main, side) = init_repository(&temp_dir);
+    git(&side, &["rebase", "master"])
+        .success()
+        .stdout(contains("Successfully rebased and updated refs/heads/side."));
+    assert_eq!(titles(&side), ["fixup! side1", "side2", "side1", "main1", "first"]);
+    assert_eq!(fs::read_to_string(side.join("m.txt")).unwrap(), "m\n");
+    assert_eq!(
+        fs::read_to_string(main.join(".git/worktrees/side/HEAD")).unwrap(),
+        "ref: refs/heads/side\n"
+    );
+    assert!(!main.join(".git/worktrees/side/rebase-merge").exists());
+    git(&side, &["status", "--porcelain"]).success().stdout("");
+    git(&side, &["rebase", "master"])
+        .success()
+        .stdout(contains("Current branch side is up to date."));
+}
+
+#[test]
+fn autosquash_and_interactive_editing() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (_, side) = init_repository(&temp_dir);
+    git_with_editors(
+        &side,
+        &["rebase", "-i", "--autosquash", "master"],
+        "sed -i -e 's/^pick \\(.*\\) side2$/reword \\1 side2/'",
+        "sed -i -e 's/side2/side two/'",
+    )
+    .success();
+    assert_eq!(titles(&side), ["side two", "side1", "main1", "first"]);
+    assert_eq!(fs::read_to_string(side.join("s.txt")).unwrap(), "s2\n");
+
+    // dropping every commit but one, then running a command after it
+    git_with_editors(
+        &side,
+        &["rebase", "-i", "master"],
+        "sed -i -e '/side1/d' -e '$a exec touch executed'",
+        ":",
+    )
+    .success()
+    .stdout(contains("Executing: touch executed"));
+    assert_eq!(titles(&side), ["side two", "main1", "first"]);
+    assert!(side.join("executed").exists());
+    assert!(!side.join("s.txt").exists());
+}
+
+#[test]
+fn conflicts_can_be_resolved_or_aborted() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (main, side) = init_repository(&temp_dir);
+    commit(&main, "f.txt", "A\nb\nc2\n", "main2");
+    let side_head = fs::read_to_string(main.join(".git/refs/heads/side")).unwrap();
+
+    git(&side, &["rebase", "master"])
+        .failure()
+        .stdout(contains("CONFLICT (content): Merge conflict in f.txt"))
+        .stderr(contains("could not apply"));
+    git(&side, &["rebase", "--abort"]).success();
+    assert_eq!(fs::read_to_string(main.join(".git/refs/heads/side")).unwrap(), side_head);
+    assert_eq!(
+        fs::read_to_string(main.join(".git/worktrees/side/HEAD")).unwrap(),
+        "ref: refs/heads/side\n"
+    );
+    assert_eq!(fs::read_to_string(side.join("f.txt")).unwrap(), "a\nb\nC\n");
+    assert!(!side.join("m.txt").exists());
+
+    git(&side, &["rebase", "master"]).failure();
+    git(&side, &["rebase", "--continue"])
+        .failure()
+        .stderr(contains("unmerged files"));
+    write(&side, "f.txt", "A\nb\nC\n");
+    git(&side, &["add", "f.txt"]).success();
+    git(&side, &["rebase", "--continue"]).success();
+    assert_eq!(titles(&side), ["fixup! side1", "side2", "side1", "main2", "main1", "first"]);
+    git(&side, &["rebase", "--continue"])
+        .failure()
+        .stderr(contains("No rebase in progress?"));
+}
+
+#[test]
+fn edit_stops_to_amend_the_commit() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (_, side) = init_repository(&temp_dir);
+    git_with_editors(&side, &["rebase", "-i", "master"], "sed -i -e 's/^pick\\(.*side1\\)$/edit\\1/'", ":")
+        .success()
+        .stdout(contains("Stopped at"));
+    assert_eq!(titles(&side), ["side1", "main1", "first"]);
+    write(&side, "s.txt", "amended\n");
+    git(&side, &["add", "s.txt"]).success();
+    git(&side, &["rebase", "--continue"]).failure().stdout(contains("CONFLICT"));
+    git(&side, &["rebase", "--skip"]).success();
+    assert_eq!(titles(&side), ["side2", "side1", "main1", "first"]);
+    assert_eq!(fs::read_to_string(side.join("s.txt")).unwrap(), "amended\n");
+}
+
+// the number of loose objects of the repository at `main`.
+fn object_count(main: &Path) -> usize {
+    fs::read_dir(main.join(".git/objects"))
+        .unwrap()
+        .flatten()
+        .filter(|dir| dir.file_name().len() == 2)
+        .map(|dir| fs::read_dir(dir.path()).unwrap().count())
+        .sum()
+}
+
+#[test]
+fn unstaged_changes_stop_the_rebase_without_being_stored() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let (main, side) = init_repository(&temp_dir);
+    let objects = object_count(&main);
+    write(&side, "f.txt", "dirty\n");
+    git(&side, &["rebase", "master"])
+        .failure()
+        .stderr(contains("cannot rebase: You have unstaged changes."));
+    assert_eq!(object_count(&main), objects);
+}
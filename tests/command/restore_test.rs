@@ -0,0 +1,143 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use predicates::str::contains;
+
+use tempfile::TempDir;
+
+use super::helpers::{git, init_main};
+
+// a repository `main` with `a.txt` and the executable `run.sh` committed.
+fn init_with_script(temp_dir: &TempDir) -> PathBuf {
+    let dir = init_main(temp_dir, &[("a.txt", "one\n"), ("run.sh", "#!/bin/sh\n")]);
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    git(&dir, &["add", "run.sh"]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    dir
+}
+
+fn status(dir: &Path) -> String {
+    String::from_utf8(git(dir, &["status", "--short"]).success().get_output().stdout.clone()).unwrap()
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).unwrap().permissions().mode() & 0o111 != 0
+}
+
+#[test]
+fn restore_rewrites_files_from_the_index_with_their_mode() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_with_script(&temp_dir);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    fs::write(dir.join("a.txt"), "three\n").unwrap();
+    fs::write(dir.join("run.sh"), "#!/bin/sh\nexit 1\n").unwrap();
+    fs::set_permissions(dir.join("run.sh"), fs::Permissions::from_mode(0o644)).unwrap();
+
+    git(&dir, &["restore", "a.txt", "run.sh"]).success();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "two\n");
+    assert_eq!(fs::read_to_string(dir.join("run.sh")).unwrap(), "#!/bin/sh\n");
+    assert!(is_executable(&dir.join("run.sh")));
+    assert_eq!(status(&dir), "M  a.txt\n");
+
+    git(&dir, &["restore", "missing.txt"])
+        .failure()
+        .stderr(contains("pathspec 'missing.txt' did not match any file(s) known to git"));
+    git(&dir, &["restore"]).failure().stderr(contains("you must specify path(s) to restore"));
+}
+
+#[test]
+fn restore_staged_resets_the_index_and_keeps_the_working_tree() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_with_script(&temp_dir);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    fs::write(dir.join("new.txt"), "new\n").unwrap();
+    git(&dir, &["add", "a.txt", "new.txt"]).success();
+
+    git(&dir, &["restore", "--staged", "."]).success();
+    assert_eq!(status(&dir), " M a.txt\n?? new.txt\n");
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "two\n");
+}
+
+#[test]
+fn restore_from_a_source_deletes_the_files_it_does_not_have() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_with_script(&temp_dir);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    git(&dir, &["add", "a.txt", "b.txt"]).success();
+    git(&dir, &["commit", "-m", "second"]).success();
+
+    git(&dir, &["restore", "--source=HEAD~1", "--staged", "--worktree", "."]).success();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+    assert!(!dir.join("b.txt").exists());
+    assert_eq!(status(&dir), "M  a.txt\nD  b.txt\n");
+
+    git(&dir, &["restore", "--source", "HEAD", "b.txt"]).success();
+    assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b\n");
+    assert_eq!(status(&dir), "M  a.txt\nD  b.txt\n?? b.txt\n");
+}
+
+#[test]
+fn checkout_paths_restores_from_the_index_or_a_tree() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_with_script(&temp_dir);
+    fs::write(dir.join("a.txt"), "two\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git(&dir, &["commit", "-m", "second"]).success();
+    fs::write(dir.join("hook.sh"), "#!/bin/sh\necho \"$@\" > ../post-checkout\n").unwrap();
+    fs::set_permissions(dir.join("hook.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::create_dir_all(dir.join(".git/hooks")).unwrap();
+    fs::rename(dir.join("hook.sh"), dir.join(".git/hooks/post-checkout")).unwrap();
+
+    fs::write(dir.join("a.txt"), "three\n").unwrap();
+    git(&dir, &["checkout", "--", "a.txt"])
+        .success()
+        .stderr(contains("Updated 1 path from the index"));
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "two\n");
+    let head = fs::read_to_string(dir.join(".git/refs/heads/master")).unwrap();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("post-checkout")).unwrap(),
+        format!("{} {} 0\n", head.trim(), head.trim())
+    );
+
+    git(&dir, &["checkout", "HEAD~1", "--", "a.txt"]).success();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\n");
+    assert_eq!(status(&dir), "M  a.txt\n");
+    git(&dir, &["checkout", "master"])
+        .failure()
+        .stderr(contains("only paths can be checked out"));
+
+    // restore does not run the hook
+    fs::remove_file(temp_dir.path().join("post-checkout")).unwrap();
+    git(&dir, &["restore", "--staged", "--worktree", "a.txt"]).success();
+    assert!(!temp_dir.path().join("post-checkout").exists());
+}
+
+#[test]
+fn unmerged_paths_are_only_restored_from_one_side() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_with_script(&temp_dir);
+    git(&dir, &["update-ref", "refs/heads/side", "HEAD"]).success();
+    fs::write(dir.join("a.txt"), "ours\n").unwrap();
+    git(&dir, &["add", "a.txt"]).success();
+    git(&dir, &["commit", "-m", "ours"]).success();
+    git(&dir, &["worktree", "add", "../side", "side"]).success();
+    let side = temp_dir.path().join("side");
+    fs::write(side.join("a.txt"), "theirs\n").unwrap();
+    git(&side, &["add", "a.txt"]).success();
+    git(&side, &["commit", "-m", "theirs"]).success();
+    let theirs = fs::read_to_string(dir.join(".git/refs/heads/side")).unwrap();
+    git(&dir, &["cherry-pick", theirs.trim()]).failure();
+
+    git(&dir, &["restore", "a.txt"]).failure().stderr(contains("path 'a.txt' is unmerged"));
+    git(&dir, &["checkout", "--", "a.txt"]).failure().stderr(contains("path 'a.txt' is unmerged"));
+    assert!(fs::read_to_string(dir.join("a.txt")).unwrap().contains("<<<<<<< HEAD"));
+    git(&dir, &["restore", "--theirs", "a.txt"]).success();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "theirs\n");
+    git(&dir, &["checkout", "--ours", "--", "a.txt"]).success();
+    assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "ours\n");
+    git(&dir, &["restore", "--staged", "--worktree", "a.txt"]).success();
+    assert_eq!(status(&dir), "");
+}
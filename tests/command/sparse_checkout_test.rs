@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, init_committed};
+
+const FILES: [&str; 5] = ["top.txt", "a/x.txt", "a/b/y.txt", "a/c/z.txt", "d/w.txt"];
+
+// a repository with a commit of the files above, each holding its own name.
+fn init_repository(temp_dir: &TempDir) -> PathBuf {
+    let contents: Vec<String> = FILES.iter().map(|file| format!("{}\n", file)).collect();
+    let files: Vec<(&str, &str)> = FILES.iter().cloned().zip(contents.iter().map(String::as_str)).collect();
+    init_committed(temp_dir, &files)
+}
+
+fn checked_out(dir: &Path) -> Vec<&'static str> {
+    FILES.iter().cloned().filter(|file| dir.join(file).exists()).collect()
+}
+
+#[test]
+fn set_and_add_check_out_the_cone_only() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+
+    git(&dir, &["sparse-checkout", "init", "--cone"]).success();
+    assert_eq!(checked_out(&dir), vec!["top.txt"]);
+    git(&dir, &["sparse-checkout", "set", "a/b"]).success();
+    assert_eq!(checked_out(&dir), vec!["top.txt", "a/x.txt", "a/b/y.txt"]);
+    assert_eq!(
+        fs::read_to_string(dir.join(".git/info/sparse-checkout")).unwrap(),
+        "/*\n!/*/\n/a/\n!/a/*/\n/a/b/\n"
+    );
+    git(&dir, &["sparse-checkout", "add", "d"]).success();
+    assert_eq!(checked_out(&dir), vec!["top.txt", "a/x.txt", "a/b/y.txt", "d/w.txt"]);
+    git(&dir, &["sparse-checkout", "list"]).success().stdout("a/b\nd\n");
+
+    git(&dir, &["sparse-checkout", "disable"]).success();
+    assert_eq!(checked_out(&dir), FILES.to_vec());
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+    git(&dir, &["sparse-checkout", "list"]).failure();
+}
+
+#[test]
+fn status_and_diff_ignore_the_files_outside_of_the_cone() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["sparse-checkout", "set", "a/b"]).success();
+
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+    git(&dir, &["status"])
+        .success()
+        .stdout(contains("You are in a sparse checkout with 60% of tracked files present.\n"));
+    git(&dir, &["diff"]).success().stdout(is_empty());
+
+    fs::write(dir.join("a/b/y.txt"), "changed\n").unwrap();
+    git(&dir, &["status", "--porcelain"]).success().stdout(" M a/b/y.txt\n");
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "change"]).success();
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+    // the files outside of the cone are still in the commit
+    git(&dir, &["diff", "HEAD~1", "HEAD", "--name-status"]).success().stdout("M\ta/b/y.txt\n");
+}
+
+#[test]
+fn add_refuses_paths_outside_of_the_cone() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    git(&dir, &["sparse-checkout", "set", "a/b"]).success();
+
+    fs::create_dir_all(dir.join("d")).unwrap();
+    fs::write(dir.join("d/w.txt"), "changed\n").unwrap();
+    git(&dir, &["add", "d/w.txt"])
+        .failure()
+        .stderr(contains("outside of your sparse-checkout definition"))
+        .stderr(contains("\nd/w.txt\n"));
+    git(&dir, &["diff", "--cached"]).success().stdout(is_empty());
+}
+
+#[test]
+fn modified_files_are_left_when_they_leave_the_cone() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = init_repository(&temp_dir);
+    fs::write(dir.join("d/w.txt"), "changed\n").unwrap();
+
+    git(&dir, &["sparse-checkout", "set", "a"])
+        .success()
+        .stderr(contains("not up to date and were left despite sparse patterns:\n\td/w.txt\n"));
+    assert_eq!(checked_out(&dir), vec!["top.txt", "a/x.txt", "a/b/y.txt", "a/c/z.txt", "d/w.txt"]);
+    git(&dir, &["status", "--porcelain"]).success().stdout(" M d/w.txt\n");
+}
@@ -0,0 +1,121 @@
+use std::fs;
+
+use gitclone::{Blob, Command, HashAlgorithm, Object};
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, write};
+
+fn init_repository(temp_dir: &TempDir) {
+    git(temp_dir.path(), &["init"]).success();
+    write(temp_dir.path(), "f.txt", "a\nb\nc\n");
+    write(temp_dir.path(), "s.txt", "s\n");
+    git(temp_dir.path(), &["add", "f.txt", "s.txt"]).success();
+    git(temp_dir.path(), &["commit", "-m", "first"]).success();
+}
+
+#[test]
+fn push_saves_the_changes_and_resets_to_head() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    write(dir, "f.txt", "a\nchanged\nc\n");
+    write(dir, "new.txt", "new\n");
+    git(dir, &["add", "new.txt"]).success();
+    git(dir, &["stash"])
+        .success()
+        .stdout(contains("Saved working directory and index state WIP on master:"));
+    git(dir, &["status", "--porcelain"]).success().stdout(is_empty());
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "a\nb\nc\n");
+    assert!(!dir.join("new.txt").exists());
+    git(dir, &["stash", "list"])
+        .success()
+        .stdout(contains("stash@{0}: WIP on master:"));
+    // the stash commit has HEAD and the index commit as parents, the stack is the reflog
+    let stash = fs::read_to_string(dir.join(".git/refs/stash")).unwrap();
+    let log = fs::read_to_string(dir.join(".git/logs/refs/stash")).unwrap();
+    assert!(log.contains(stash.trim()));
+    git(dir, &["cat-file", "-p", "stash@{0}"])
+        .success()
+        .stdout(predicates::str::is_match("(?s)^tree .*\nparent .*\nparent .*\nauthor ").unwrap());
+
+    git(dir, &["stash", "pop"]).success().stdout(contains("Dropped stash@{0}"));
+    assert_eq!(fs::read_to_string(dir.join("f.txt")).unwrap(), "a\nchanged\nc\n");
+    assert_eq!(fs::read_to_string(dir.join("new.txt")).unwrap(), "new\n");
+    git(dir, &["stash", "list"]).success().stdout(is_empty());
+    assert!(!dir.join(".git/refs/stash").exists());
+}
+
+#[test]
+fn nothing_to_stash() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_repository(&temp_dir);
+    git(temp_dir.path(), &["stash"]).success().stdout("No local changes to save\n");
+}
+
+#[test]
+fn include_untracked_and_restore_the_index() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    write(dir, "s.txt", "staged\n");
+    git(dir, &["add", "s.txt"]).success();
+    write(dir, "s.txt", "staged and changed\n");
+    write(dir, "untracked.txt", "u\n");
+    git(dir, &["stash", "push", "-u", "-m", "work in progress"]).success();
+    assert!(!dir.join("untracked.txt").exists());
+    git(dir, &["stash", "list"])
+        .success()
+        .stdout("stash@{0}: On master: work in progress\n");
+
+    write(dir, "untracked.txt", "in the way\n");
+    git(dir, &["stash", "apply"]).failure().stderr(contains("untracked.txt already exists, no checkout"));
+    fs::remove_file(dir.join("untracked.txt")).unwrap();
+    git(dir, &["stash", "apply", "--index"]).success();
+    assert_eq!(fs::read_to_string(dir.join("untracked.txt")).unwrap(), "u\n");
+    assert_eq!(fs::read_to_string(dir.join("s.txt")).unwrap(), "staged and changed\n");
+    // the staged version is back in the index
+    let mut command = Command::new(dir.to_path_buf()).unwrap();
+    command.index.load().unwrap();
+    let staged = Blob::new(b"staged\n".to_vec(), HashAlgorithm::Sha1).unwrap();
+    let entries = command.index.each_entry().unwrap();
+    let entry = entries.iter().find(|e| e.get_path() == "s.txt").unwrap();
+    assert_eq!(entry.oid, staged.get_oid().unwrap());
+    drop(entries);
+    git(dir, &["stash", "drop"]).success();
+}
+
+#[test]
+fn pop_keeps_the_stash_on_conflicts() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    write(dir, "f.txt", "a\nstashed\nc\n");
+    git(dir, &["stash"]).success();
+    write(dir, "f.txt", "a\ncommitted\nc\n");
+    git(dir, &["add", "f.txt"]).success();
+    git(dir, &["commit", "-m", "second"]).success();
+    git(dir, &["stash", "pop"])
+        .failure()
+        .stdout(contains("CONFLICT (content): Merge conflict in f.txt"))
+        .stdout(contains("The stash entry is kept"));
+    assert_eq!(
+        fs::read_to_string(dir.join("f.txt")).unwrap(),
+        "a\n<<<<<<< Updated upstream\ncommitted\n=======\nstashed\n>>>>>>> Stashed changes\nc\n"
+    );
+    git(dir, &["stash", "list"]).success().stdout(contains("stash@{0}"));
+}
+
+#[test]
+fn apply_refuses_to_overwrite_local_changes() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path();
+    init_repository(&temp_dir);
+    write(dir, "f.txt", "a\nstashed\nc\n");
+    git(dir, &["stash"]).success();
+    write(dir, "f.txt", "a\nlocal\nc\n");
+    git(dir, &["stash", "apply"])
+        .failure()
+        .stderr(contains("Your local changes to the following files would be overwritten"));
+}
@@ -8,6 +8,7 @@ use predicates::str::contains;
 
 use tempfile::TempDir;
 
+use super::helpers::git;
 
 #[test]
 fn list_untracked_files_in_name_order() {
@@ -24,7 +25,7 @@ fn list_untracked_files_in_name_order() {
         .stdout(contains("Initialized empty Jit repository in"));
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -68,7 +69,7 @@ fn list_files_as_untracked_if_they_are_not_in_index() {
         util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("file.txt").to_path_buf(), "".as_bytes())]).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -89,7 +90,7 @@ fn list_untracked_directories_not_their_contents() {
         .stdout(contains("Initialized empty Jit repository in"));
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -127,7 +128,7 @@ fn list_untracked_files_inside_tracked_directories() {
         util::write_file(&temp_dir.path().to_owned(), vec![(Path::new("a/").join("outer.txt"), "".as_bytes()), (Path::new("a/b/c/").join("file.txt"), "".as_bytes())]).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -147,7 +148,7 @@ fn does_not_list_empty_untracked_directories() {
     std::fs::create_dir(temp_dir.path().join("outer")).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -168,7 +169,7 @@ fn list_untracked_directories_that_indirectly_contain_files() {
         .stdout(contains("Initialized empty Jit repository in"));
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -209,7 +210,7 @@ fn prints_nothing_when_no_files_are_changed() {
         .stdout(contains("commit message"));
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -254,7 +255,7 @@ fn reports_files_with_modified_contents() {
         ]).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -299,7 +300,7 @@ fn reports_files_with_changed_modes() {
     fs::set_permissions(paths[1].to_path_buf(), perms).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -343,7 +344,7 @@ fn reports_modified_files_with_unchanged_size() {
         vec![(Path::new("a/b/").join("3.txt"), "hello".as_bytes())]).unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -386,7 +387,7 @@ fn prints_nothing_if_a_file_is_touched() {
     let _f = OpenOptions::new().write(true).truncate(false).open("1.txt").unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -428,7 +429,7 @@ fn reports_deleted_files() {
     fs::remove_file("a/2.txt").unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -471,7 +472,7 @@ fn reports_files_in_deleted_directories() {
     fs::remove_dir_all("a").unwrap();
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
@@ -529,11 +530,11 @@ fn reports_a_file_added_to_a_tracked_directory() {
 
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(is_match("^( A a/4.txt\\n)$").unwrap());
+        .stdout(is_match("^(A  a/4.txt\\n)$").unwrap());
 }
 
 
@@ -586,11 +587,11 @@ fn reports_a_file_added_to_an_untracked_directory() {
 
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(is_match("^( A d/e/5.txt\\n)$").unwrap());
+        .stdout(is_match("^(A  d/e/5.txt\\n)$").unwrap());
 }
 
 
@@ -640,11 +641,11 @@ fn reports_modified_modes() {
 
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(is_match("^( M 1.txt\\n)$").unwrap());
+        .stdout(is_match("^(M  1.txt\\n)$").unwrap());
 }
 
 
@@ -694,11 +695,11 @@ fn reports_deleted_files_head_index() {
 
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(is_match("^( D 1.txt\\n)$").unwrap());
+        .stdout(is_match("^(D  1.txt\\n)$").unwrap());
 }
 
 
@@ -748,9 +749,225 @@ fn reports_all_deleted_files_inside_directories() {
 
     process::Command::cargo_bin("git-clone")
         .unwrap()
-        .args(&["status"])
+        .args(&["status", "--porcelain"])
         .current_dir(&temp_dir)
         .assert()
         .success()
-        .stdout(is_match("^( D a/2.txt\\n D a/b/3.txt\\n)$").unwrap());
+        .stdout(is_match("^(D  a/2.txt\\nD  a/b/3.txt\\n)$").unwrap());
+}
+
+// a commit with 1.txt and 2.txt, then 1.txt changed, 3.txt staged and 4.txt untracked.
+fn init_with_changes(temp_dir: &TempDir) {
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    util::write_file(&dir.to_path_buf(), vec![
+        (Path::new("1.txt").to_path_buf(), "one\n".as_bytes()),
+        (Path::new("2.txt").to_path_buf(), "two\n".as_bytes()),
+    ]).unwrap();
+    git(dir, &["add", "."]).success();
+    git(dir, &["commit", "-m", "first"]).success();
+    util::write_file(&dir.to_path_buf(), vec![
+        (Path::new("1.txt").to_path_buf(), "changed\n".as_bytes()),
+        (Path::new("3.txt").to_path_buf(), "three\n".as_bytes()),
+    ]).unwrap();
+    git(dir, &["add", "3.txt"]).success();
+    fs::remove_file(dir.join("2.txt")).unwrap();
+    util::write_file(&dir.to_path_buf(), vec![(Path::new("4.txt").to_path_buf(), "".as_bytes())]).unwrap();
+}
+
+#[test]
+fn long_format_lists_the_changes_by_section() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    git(dir, &["status"])
+        .success()
+        .stdout("On branch master\n\nNo commits yet\n\nnothing to commit (create/copy files and use \"git add\" to track)\n");
+
+    init_with_changes(&temp_dir);
+    git(dir, &["status"]).success().stdout(
+        "On branch master\n\
+         Changes to be committed:\n  \
+           (use \"git restore --staged <file>...\" to unstage)\n\
+         \tnew file:   3.txt\n\
+         \n\
+         Changes not staged for commit:\n  \
+           (use \"git add/rm <file>...\" to update what will be committed)\n  \
+           (use \"git restore <file>...\" to discard changes in working directory)\n\
+         \tmodified:   1.txt\n\
+         \tdeleted:    2.txt\n\
+         \n\
+         Untracked files:\n  \
+           (use \"git add <file>...\" to include in what will be committed)\n\
+         \t4.txt\n\
+         \n",
+    );
+    util::write_file(&dir.to_path_buf(), vec![(Path::new("2.txt").to_path_buf(), "two\n".as_bytes())]).unwrap();
+    fs::remove_file(dir.join("4.txt")).unwrap();
+    git(dir, &["add", "."]).success();
+    git(dir, &["commit", "-m", "second"]).success();
+    git(dir, &["status"])
+        .success()
+        .stdout("On branch master\nnothing to commit, working tree clean\n");
+}
+
+#[test]
+fn porcelain_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    init_with_changes(&temp_dir);
+    git(dir, &["status", "-s", "-b"])
+        .success()
+        .stdout("## master\n M 1.txt\n D 2.txt\nA  3.txt\n?? 4.txt\n");
+    git(dir, &["status", "-z"])
+        .success()
+        .stdout(" M 1.txt\0 D 2.txt\0A  3.txt\0?? 4.txt\0");
+
+    let one = "5626abf0f72e58d7a153368ba57db4c673c0e171";
+    let two = "f719efd430d52bcfc8566a43b2eb655688d38871";
+    let three = "2bdf67abb163a4ffb2d7f3f0880c9fe5068ce782";
+    let zero = "0".repeat(40);
+    let assert = git(dir, &["status", "--porcelain=v2", "--branch"]).success();
+    let output = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[0].starts_with("# branch.oid "));
+    assert_eq!(lines[1], "# branch.head master");
+    assert_eq!(lines[2], format!("1 .M N... 100644 100644 100644 {} {} 1.txt", one, one));
+    assert_eq!(lines[3], format!("1 .D N... 100644 100644 000000 {} {} 2.txt", two, two));
+    assert_eq!(lines[4], format!("1 A. N... 000000 100644 100644 {} {} 3.txt", zero, three));
+    assert_eq!(lines[5], "? 4.txt");
+}
+
+#[test]
+fn reports_how_far_the_branch_is_from_its_upstream() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    let commit = |content: &str| {
+        util::write_file(&dir.to_path_buf(), vec![(Path::new("1.txt").to_path_buf(), content.as_bytes())]).unwrap();
+        git(dir, &["add", "1.txt"]).success();
+        git(dir, &["commit", "-m", content]).success();
+    };
+    commit("one");
+    let mut config = gitclone::Config::new(&dir.join(".git/config"));
+    config.load().unwrap();
+    config.set("branch.master.remote", "origin").unwrap();
+    config.set("branch.master.merge", "refs/heads/master").unwrap();
+    config.save().unwrap();
+
+    git(dir, &["status", "-sb"]).success().stdout("## master...origin/master [gone]\n");
+    git(dir, &["status"]).success().stdout(contains(
+        "On branch master\nYour branch is based on 'origin/master', but the upstream is gone.\n",
+    ));
+
+    git(dir, &["update-ref", "refs/remotes/origin/master", "HEAD"]).success();
+    git(dir, &["status"]).success().stdout(
+        "On branch master\nYour branch is up to date with 'origin/master'.\n\nnothing to commit, working tree clean\n",
+    );
+
+    commit("two");
+    commit("three");
+    git(dir, &["status", "-sb"]).success().stdout("## master...origin/master [ahead 2]\n");
+    git(dir, &["status"]).success().stdout(contains(
+        "Your branch is ahead of 'origin/master' by 2 commits.\n  (use \"git push\" to publish your local commits)\n\n",
+    ));
+
+    git(dir, &["update-ref", "refs/remotes/origin/master", "HEAD"]).success();
+    git(dir, &["update-ref", "refs/heads/master", "HEAD~2"]).success();
+    git(dir, &["status", "--porcelain=v2", "--branch"])
+        .success()
+        .stdout(contains("# branch.head master\n# branch.upstream origin/master\n# branch.ab +0 -2\n"));
+
+    commit("four");
+    git(dir, &["status", "-sb"]).success().stdout("## master...origin/master [ahead 1, behind 2]\n");
+    git(dir, &["status"]).success().stdout(contains(
+        "Your branch and 'origin/master' have diverged,\nand have 1 and 2 different commits each, respectively.\n",
+    ));
+}
+
+#[test]
+fn reports_changes_made_in_the_same_second_as_the_index() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    // only the seconds of the mtime and the size are left to tell the file changed
+    let mut config = gitclone::Config::new(&dir.join(".git/config"));
+    config.load().unwrap();
+    config.set("core.checkStat", "minimal").unwrap();
+    config.save().unwrap();
+    let file = dir.join("1.txt");
+    fs::write(&file, "one").unwrap();
+    git(dir, &["add", "1.txt"]).success();
+    git(dir, &["status", "--porcelain"]).success().stdout("A  1.txt\n");
+
+    let mtime = fs::metadata(&file).unwrap().modified().unwrap();
+    fs::write(&file, "two").unwrap();
+    fs::File::options().write(true).open(&file).unwrap().set_modified(mtime).unwrap();
+    git(dir, &["status", "--porcelain"]).success().stdout("AM 1.txt\n");
+}
+
+#[test]
+fn reports_mode_changes_of_empty_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path();
+    git(dir, &["init"]).success();
+    util::write_file(&dir.to_path_buf(), vec![(Path::new("empty.txt").to_path_buf(), "".as_bytes())]).unwrap();
+    git(dir, &["add", "empty.txt"]).success();
+    git(dir, &["commit", "-m", "empty"]).success();
+    fs::set_permissions(dir.join("empty.txt"), Permissions::from_mode(0o755)).unwrap();
+    git(dir, &["status", "--porcelain"]).success().stdout(" M empty.txt\n");
+}
+
+#[test]
+fn reports_the_unmerged_files_of_a_conflicting_cherry_pick() {
+    let temp_dir = TempDir::new().unwrap();
+    let dir = temp_dir.path().join("main");
+    git(temp_dir.path(), &["init", "main"]).success();
+    fs::write(dir.join("a.txt"), "one\n").unwrap();
+    fs::write(dir.join("b.txt"), "b\n").unwrap();
+    git(&dir, &["add", "a.txt", "b.txt"]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+    git(&dir, &["update-ref", "refs/heads/side", "HEAD"]).success();
+    fs::write(dir.join("a.txt"), "ours\n").unwrap();
+    fs::write(dir.join("b.txt"), "b2\n").unwrap();
+    git(&dir, &["add", "a.txt", "b.txt"]).success();
+    git(&dir, &["commit", "-m", "ours"]).success();
+    // their side changes a.txt, deletes b.txt and adds n.txt
+    git(&dir, &["worktree", "add", "../side", "side"]).success();
+    let side = temp_dir.path().join("side");
+    fs::write(side.join("a.txt"), "theirs\n").unwrap();
+    fs::write(side.join("n.txt"), "n\n").unwrap();
+    fs::remove_file(side.join("b.txt")).unwrap();
+    git(&side, &["add", "a.txt", "b.txt", "n.txt"]).success();
+    git(&side, &["commit", "-m", "theirs"]).success();
+    git(&dir, &["cherry-pick", "side"]).failure();
+
+    git(&dir, &["status", "--short"]).success().stdout("UU a.txt\nUD b.txt\nA  n.txt\n");
+    let zero = "0".repeat(40);
+    git(&dir, &["status", "--porcelain=v2"]).success().stdout(format!(
+        "1 A. N... 000000 100644 100644 {} 8ba3a16384aacc37d01564b28401755ce8053f51 n.txt\n\
+         u UU N... 100644 100644 100644 100644 5626abf0f72e58d7a153368ba57db4c673c0e171 \
+         b19a1e93bec1317dc6097229e12afaffbfa74dc2 950b81b7eee953d050aa05a641f8e056c85dd1bd a.txt\n\
+         u UD N... 100644 100644 000000 100644 61780798228d17af2d34fce4cfbdf35556832472 \
+         e6bfff5c1d0f0ecd501552b43a1e13d8008abc31 {} b.txt\n",
+        zero, zero
+    ));
+    git(&dir, &["status"]).success().stdout(contains(
+        "Unmerged paths:\n  \
+           (use \"git add/rm <file>...\" as appropriate to mark resolution)\n\
+         \tboth modified:   a.txt\n\
+         \tdeleted by them: b.txt\n\
+         \n",
+    ));
+
+    // a file added again is merged, the others are still unmerged
+    fs::write(dir.join("b.txt"), "b2\n").unwrap();
+    git(&dir, &["add", "b.txt"]).success();
+    git(&dir, &["status"]).success().stdout(contains(
+        "Unmerged paths:\n  \
+           (use \"git add <file>...\" to mark resolution)\n\
+         \tboth modified:   a.txt\n\
+         \n",
+    ));
+    git(&dir, &["status", "--porcelain"]).success().stdout("UU a.txt\nA  n.txt\n");
 }
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::Path;
+
+use gitclone::util;
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{commit, git};
+
+fn head(dir: &Path) -> String {
+    let output = git(dir, &["log"]).success().get_output().stdout.clone();
+    String::from_utf8(output).unwrap()[7..47].to_string()
+}
+
+// a repository `lib` with one commit and a repository `main` with `lib` added as the submodule
+// `sub` and committed.
+fn init_with_submodule(temp_dir: &TempDir) {
+    let lib = temp_dir.path().join("lib");
+    let main = temp_dir.path().join("main");
+    fs::create_dir(&lib).unwrap();
+    fs::create_dir(&main).unwrap();
+    git(&lib, &["init"]).success();
+    commit(&lib, "l.txt", "lib\n", "lib");
+    git(&main, &["init"]).success();
+    commit(&main, "a.txt", "a\n", "first");
+    git(&main, &["submodule", "add", "../lib", "sub"]).success();
+    git(&main, &["commit", "-m", "add sub"]).success();
+}
+
+#[test]
+fn add_records_the_submodule_as_a_gitlink() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_submodule(&temp_dir);
+    let main = temp_dir.path().join("main");
+    let lib_head = head(&temp_dir.path().join("lib"));
+
+    assert_eq!(
+        fs::read_to_string(main.join(".gitmodules")).unwrap(),
+        "[submodule \"sub\"]\n\tpath = sub\n\turl = ../lib\n"
+    );
+    assert_eq!(fs::read_to_string(main.join("sub/.git")).unwrap(), "gitdir: ../.git/modules/sub\n");
+    assert_eq!(fs::read_to_string(main.join("sub/l.txt")).unwrap(), "lib\n");
+    let commit = git(&main, &["cat-file", "-p", "HEAD"]).success().get_output().stdout.clone();
+    let tree = String::from_utf8(commit).unwrap()[5..45].to_string();
+    git(&main, &["cat-file", "-p", &tree])
+        .success()
+        .stdout(contains(format!("160000 commit {}\tsub", lib_head)));
+    git(&main, &["status", "--porcelain"]).success().stdout(is_empty());
+    git(&main, &["fsck"]).success();
+    git(&main, &["submodule", "status"])
+        .success()
+        .stdout(format!(" {} sub (heads/master)\n", lib_head));
+}
+
+#[test]
+fn status_and_diff_show_the_changes_of_submodules() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_submodule(&temp_dir);
+    let main = temp_dir.path().join("main");
+    let sub = main.join("sub");
+    let old = head(&sub);
+
+    fs::write(sub.join("l.txt"), "changed\n").unwrap();
+    git(&main, &["status", "--porcelain"]).success().stdout(" M sub\n");
+    git(&main, &["status"]).success().stdout(contains("\tmodified:   sub (modified content)\n"));
+    git(&main, &["status", "--porcelain=v2"]).success().stdout(format!(
+        "1 .M S.M. 160000 160000 160000 {} {} sub\n",
+        old, old
+    ));
+    git(&main, &["diff"]).success().stdout(format!(
+        "diff --git a/sub b/sub\n\
+         --- a/sub\n\
+         +++ b/sub\n\
+         @@ -1 +1 @@\n\
+         -Subproject commit {}\n\
+         +Subproject commit {}-dirty\n",
+        old, old
+    ));
+
+    git(&sub, &["add", "l.txt"]).success();
+    git(&sub, &["commit", "-m", "change"]).success();
+    let new = head(&sub);
+    git(&main, &["status"]).success().stdout(contains("\tmodified:   sub (new commits)\n"));
+    git(&main, &["diff"])
+        .success()
+        .stdout(contains(format!("index {}..{} 160000\n", &old[..7], &new[..7])));
+    git(&main, &["add", "sub"]).success();
+    git(&main, &["status", "--porcelain"]).success().stdout("M  sub\n");
+    git(&main, &["diff", "--cached", "--name-status"]).success().stdout("M\tsub\n");
+}
+
+#[test]
+fn update_checks_out_the_recorded_commit() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_submodule(&temp_dir);
+    let main = temp_dir.path().join("main");
+    let lib = temp_dir.path().join("lib");
+    let recorded = head(&lib);
+    // the source moves on after the commit was recorded
+    commit(&lib, "l.txt", "newer\n", "newer");
+
+    git(&main, &["worktree", "add", "../other"]).success();
+    let other = temp_dir.path().join("other");
+    git(&other, &["submodule", "status"])
+        .success()
+        .stdout(format!("-{} sub\n", recorded));
+    git(&other, &["submodule", "update", "--init"])
+        .success()
+        .stdout(format!("Submodule path 'sub': checked out '{}'\n", recorded));
+    assert_eq!(fs::read_to_string(other.join("sub/l.txt")).unwrap(), "lib\n");
+    git(&other, &["status", "--porcelain"]).success().stdout(is_empty());
+    git(&other.join("sub"), &["status", "--porcelain=v2", "--branch"])
+        .success()
+        .stdout(contains("# branch.head (detached)\n"));
+    git(&other, &["submodule", "update"]).success().stdout(is_empty());
+}
+
+#[test]
+fn update_checks_out_a_removed_submodule_from_its_git_directory() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_submodule(&temp_dir);
+    let main = temp_dir.path().join("main");
+    let recorded = head(&temp_dir.path().join("lib"));
+    fs::remove_dir_all(main.join("sub")).unwrap();
+
+    git(&main, &["submodule", "update"])
+        .success()
+        .stdout(format!("Submodule path 'sub': checked out '{}'\n", recorded))
+        .stderr(is_empty());
+    assert_eq!(fs::read_to_string(main.join("sub/.git")).unwrap(), "gitdir: ../.git/modules/sub\n");
+    assert_eq!(fs::read_to_string(main.join("sub/l.txt")).unwrap(), "lib\n");
+    git(&main, &["status", "--porcelain"]).success().stdout(is_empty());
+    git(&main.join("sub"), &["status", "--porcelain"]).success().stdout(is_empty());
+}
+
+#[test]
+fn init_registers_the_url_of_gitmodules() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_submodule(&temp_dir);
+    let main = temp_dir.path().join("main");
+    let mut config = gitclone::Config::new(&main.join(".git/config"));
+    config.load().unwrap();
+    assert_eq!(config.get("submodule.sub.active").as_deref(), Some("true"));
+    config.remove_section("submodule", Some("sub"));
+    config.save().unwrap();
+
+    let lib = util::normalize_path(&temp_dir.path().join("lib"));
+    git(&main, &["submodule", "init"]).success().stderr(format!(
+        "Submodule 'sub' ({}) registered for path 'sub'\n",
+        lib.display()
+    ));
+    let mut config = gitclone::Config::new(&main.join(".git/config"));
+    config.load().unwrap();
+    assert_eq!(config.get("submodule.sub.url"), Some(lib.display().to_string()));
+}
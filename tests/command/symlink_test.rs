@@ -0,0 +1,104 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+use gitclone::Config;
+use predicates::str::{contains, is_empty};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, write};
+
+// a repository whose first commit has a file and a link to it.
+fn init_with_link(temp_dir: &TempDir) {
+    let dir = temp_dir.path().join("main");
+    fs::create_dir(&dir).unwrap();
+    git(&dir, &["init"]).success();
+    write(&dir, "a.txt", "hello\n");
+    symlink("a.txt", dir.join("link")).unwrap();
+    git(&dir, &["add", "."]).success();
+    git(&dir, &["commit", "-m", "first"]).success();
+}
+
+#[test]
+fn symlinks_are_stored_as_their_target() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let dir = temp_dir.path().join("main");
+    fs::create_dir(&dir).unwrap();
+    git(&dir, &["init"]).success();
+    // a dangling link can be added too
+    symlink("missing.txt", dir.join("link")).unwrap();
+    git(&dir, &["add", "link"]).success();
+    git(&dir, &["diff", "--cached"]).success().stdout(
+        "diff --git a/link b/link\n\
+         new file mode 120000\n\
+         index 0000000..a568808\n\
+         --- /dev/null\n\
+         +++ b/link\n\
+         @@ -0,0 +1 @@\n\
+         +missing.txt\n\
+         \\ No newline at end of file\n",
+    );
+    git(&dir, &["commit", "-m", "link"]).success();
+    let commit = git(&dir, &["cat-file", "-p", "HEAD"]).success().get_output().stdout.clone();
+    let tree = String::from_utf8(commit).unwrap()[5..45].to_string();
+    git(&dir, &["cat-file", "-p", &tree])
+        .success()
+        .stdout(contains("120000 blob a568808"));
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+}
+
+#[test]
+fn status_tracks_the_target_of_links() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_link(&temp_dir);
+    let dir = temp_dir.path().join("main");
+    git(&dir, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    // changing the file the link points to does not change the link
+    write(&dir, "a.txt", "bye\n");
+    git(&dir, &["status", "--porcelain"]).success().stdout(" M a.txt\n");
+
+    fs::remove_file(dir.join("link")).unwrap();
+    symlink("b.txt", dir.join("link")).unwrap();
+    git(&dir, &["status", "--porcelain"]).success().stdout(" M a.txt\n M link\n");
+    git(&dir, &["diff"]).success().stdout(contains("-a.txt\n\\ No newline at end of file\n+b.txt\n"));
+}
+
+#[test]
+fn checkout_recreates_links() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_link(&temp_dir);
+    let dir = temp_dir.path().join("main");
+    git(&dir, &["worktree", "add", "-b", "side", "../side"]).success();
+    let side = temp_dir.path().join("side");
+    assert_eq!(fs::read_link(side.join("link")).unwrap(), Path::new("a.txt"));
+    git(&side, &["status", "--porcelain"]).success().stdout(is_empty());
+}
+
+#[test]
+fn links_are_plain_files_without_core_symlinks() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    init_with_link(&temp_dir);
+    let dir = temp_dir.path().join("main");
+    let mut config = Config::new(&dir.join(".git/config"));
+    config.load().unwrap();
+    config.set("core.symlinks", "false").unwrap();
+    config.save().unwrap();
+
+    git(&dir, &["worktree", "add", "-b", "side", "../side"]).success();
+    let side = temp_dir.path().join("side");
+    let stat = fs::symlink_metadata(side.join("link")).unwrap();
+    assert!(stat.is_file());
+    assert_eq!(fs::read(side.join("link")).unwrap(), b"a.txt");
+    git(&side, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    // the plain file keeps the mode of the link when it is added again
+    fs::write(side.join("link"), "b.txt").unwrap();
+    git(&side, &["status", "--porcelain"]).success().stdout(" M link\n");
+    git(&side, &["add", "link"]).success();
+    git(&side, &["diff", "--cached", "--name-status"]).success().stdout("M\tlink\n");
+    git(&side, &["diff", "--cached"])
+        .success()
+        .stdout(contains("index 8d14cbf..19acdd8 120000\n"));
+}
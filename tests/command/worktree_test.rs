@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gitclone::util;
+use predicates::str::{contains, is_empty, is_match};
+
+use tempfile::TempDir;
+
+use super::helpers::{git, init_committed};
+
+// a repository `main` with one commit on master.
+fn init_main(temp_dir: &TempDir) -> PathBuf {
+    init_committed(temp_dir, &[("a.txt", "one\n"), ("d/b.txt", "two\n")])
+}
+
+#[test]
+fn add_creates_a_linked_worktree_sharing_the_objects() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let main = init_main(&temp_dir);
+    git(&main, &["worktree", "add", "../feature"])
+        .success()
+        .stdout(contains("Preparing worktree (new branch 'feature')"));
+    let feature = temp_dir.path().join("feature");
+    let admin = main.join(".git").join("worktrees").join("feature");
+    assert_eq!(
+        fs::read_to_string(feature.join(".git")).unwrap(),
+        format!("gitdir: {}\n", admin.display())
+    );
+    assert_eq!(fs::read_to_string(admin.join("HEAD")).unwrap(), "ref: refs/heads/feature\n");
+    assert!(admin.join("index").is_file());
+    assert!(!feature.join("objects").exists());
+    assert_eq!(fs::read_to_string(feature.join("d").join("b.txt")).unwrap(), "two\n");
+    git(&feature, &["status", "--porcelain"]).success().stdout(is_empty());
+
+    util::write_file(&feature, vec![(Path::new("a.txt").to_path_buf(), "changed\n".as_bytes())]).unwrap();
+    git(&feature, &["add", "a.txt"]).success();
+    git(&feature, &["commit", "-m", "on feature"]).success();
+    // the new commit is visible from the main worktree, HEAD of main is untouched
+    git(&main, &["log", "feature"]).success().stdout(contains("on feature"));
+    git(&main, &["log"]).success().stdout(contains("on feature").count(0));
+    git(&main, &["fsck"]).success();
+}
+
+#[test]
+fn a_branch_cannot_be_checked_out_twice() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let main = init_main(&temp_dir);
+    git(&main, &["worktree", "add", "../other", "master"])
+        .failure()
+        .stderr(contains("'master' is already checked out at"));
+    git(&main, &["worktree", "add", "-b", "topic", "../topic"]).success();
+    git(&main, &["worktree", "add", "../again", "topic"])
+        .failure()
+        .stderr(contains("'topic' is already checked out at"));
+    git(&main, &["worktree", "add", "--detach", "../detached", "topic"])
+        .success()
+        .stdout(contains("detached HEAD"));
+}
+
+#[test]
+fn list_remove_and_prune() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let main = init_main(&temp_dir);
+    git(&main, &["worktree", "add", "../one"]).success();
+    git(&main, &["worktree", "add", "../two"]).success();
+    git(&main, &["worktree", "list"])
+        .success()
+        .stdout(is_match("^.*/main [0-9a-f]{7} \\[master\\]\n.*/one  [0-9a-f]{7} \\[one\\]\n.*/two  [0-9a-f]{7} \\[two\\]\n$").unwrap());
+
+    let one = temp_dir.path().join("one");
+    util::write_file(&one, vec![(Path::new("new.txt").to_path_buf(), "new\n".as_bytes())]).unwrap();
+    git(&main, &["worktree", "remove", "../one"])
+        .failure()
+        .stderr(contains("contains modified or untracked files"));
+    git(&main, &["worktree", "remove", "--force", "../one"]).success();
+    assert!(!one.exists());
+    assert!(!main.join(".git/worktrees/one").exists());
+
+    fs::remove_dir_all(temp_dir.path().join("two")).unwrap();
+    git(&main, &["worktree", "list", "--porcelain"]).success().stdout(contains("prunable"));
+    git(&main, &["worktree", "prune"])
+        .success()
+        .stdout(contains("Removing worktrees/two"));
+    assert!(!main.join(".git/worktrees").exists());
+}
@@ -0,0 +1,78 @@
+use std::fs;
+use std::io::Read;
+
+use anyhow::Result;
+use gitclone::{Blob, Database, HashAlgorithm, Object};
+use ring::rand::{SecureRandom, SystemRandom};
+use tempfile::TempDir;
+
+// Should store a file in chunks with the same oid as the in-memory blob
+#[test]
+fn store_a_file_larger_than_a_chunk() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let file = temp_dir.path().join("large.bin");
+    let rng = SystemRandom::new();
+    let mut content = vec![0u8; 300 * 1024 + 7];
+    rng.fill(&mut content).unwrap();
+    fs::write(&file, &content)?;
+
+    let db = Database::new(&temp_dir.path().join("objects"), HashAlgorithm::Sha1);
+    let oid = db.store_file(&file)?;
+    let blob = Blob::new(content.to_vec(), HashAlgorithm::Sha1)?;
+    assert_eq!(oid, blob.get_oid()?);
+    assert_eq!(oid, db.hash_file(&file)?);
+
+    let mut reader = db.open_object(&hex::encode(&oid))?;
+    assert_eq!(reader.type_, "blob");
+    assert_eq!(reader.size, content.len() as u64);
+    let mut stored = Vec::new();
+    reader.read_to_end(&mut stored)?;
+    assert_eq!(stored, content);
+    Ok(())
+}
+
+// Should not leave temporary files behind when the object already exists
+#[test]
+fn store_the_same_file_twice() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let file = temp_dir.path().join("hello.txt");
+    fs::write(&file, "hello")?;
+
+    let db = Database::new(&temp_dir.path().join("objects"), HashAlgorithm::Sha1);
+    let first = db.store_file(&file)?;
+    let second = db.store_file(&file)?;
+    assert_eq!(first, second);
+    assert_eq!(db.list_objects()?, vec!["b6fc4c620b67d95f953a5c1c1230aaab5db5a1b0".to_string()]);
+    let leftovers = fs::read_dir(temp_dir.path().join("objects"))?
+        .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with("tmp_obj_"))
+        .count();
+    assert_eq!(leftovers, 0);
+    Ok(())
+}
+
+// Should store files concurrently and return their oids in the order of the paths
+#[test]
+fn store_many_files_concurrently() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let paths = (0..64)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("file_{}.txt", i));
+            fs::write(&path, format!("content {}", i % 16)).unwrap();
+            path
+        })
+        .collect::<Vec<_>>();
+
+    let db = Database::new(&temp_dir.path().join("objects"), HashAlgorithm::Sha1);
+    let oids = db.store_files(&paths)?;
+    assert_eq!(oids.len(), paths.len());
+    for (path, oid) in paths.iter().zip(oids.iter()) {
+        assert_eq!(&db.hash_file(path)?, oid);
+    }
+    assert_eq!(db.list_objects()?.len(), 16);
+
+    fs::remove_file(&paths[40])?;
+    fs::remove_file(&paths[10])?;
+    let err = db.store_files(&paths).unwrap_err();
+    assert!(err.to_string().contains("file_10.txt"));
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 
 use anyhow::Result;
-use gitclone::{util, Index};
+use gitclone::{util, Index, StatChange};
 use ring::{
     digest,
     rand::{SecureRandom, SystemRandom},
@@ -142,3 +143,98 @@ fn replace_recursively_dir_with_file() -> Result<()> {
     assert_eq!(entries, vec![file_to_add_1, dir]);
     Ok(())
 }
+
+// An empty file made executable changed even though the size in the index is 0
+#[test]
+fn mode_changes_of_empty_files_are_detected() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let file = temp_dir.path().join("empty.txt");
+    fs::write(&file, "")?;
+    let mut index = Index::new(&temp_dir.path().join("index"));
+    index.add(file.to_path_buf(), vec![0; digest::SHA1_OUTPUT_LEN], util::stat_file(&file)?)?;
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o755))?;
+    let entries = index.each_entry()?;
+    assert_eq!(index.stat_change(&entries[0], &util::stat_file(&file)?), StatChange::Changed);
+    Ok(())
+}
+
+// A file modified in the same second the index is written cannot be trusted by its stat data
+#[test]
+fn entries_as_recent_as_the_index_are_racy() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let file = temp_dir.path().join("alice.txt");
+    let index_file = temp_dir.path().join("index");
+    fs::write(&file, "alice")?;
+    let mut index = Index::new(&index_file);
+    index.add(file.to_path_buf(), vec![1; digest::SHA1_OUTPUT_LEN], util::stat_file(&file)?)?;
+    index.write_updates()?;
+
+    let mut index = Index::new(&index_file);
+    index.load()?;
+    let entries = index.each_entry()?;
+    assert!(index.is_racy(&entries[0]));
+    assert_eq!(index.stat_change(&entries[0], &util::stat_file(&file)?), StatChange::Unknown);
+    Ok(())
+}
+
+// Should keep the skip-worktree bit, which needs version 3 of the index, across a write
+#[test]
+fn skip_worktree_entries_are_written_and_loaded() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let index_file = temp_dir.path().join("index");
+    let mut index = Index::new(&index_file);
+    index.add_without_stat("a.txt".into(), vec![1; 20], 0o100644)?;
+    index.add_without_stat("dir/b.txt".into(), vec![2; 20], 0o100644)?;
+    index.set_skip_worktree("dir/b.txt", true);
+    index.write_updates()?;
+    assert_eq!(&fs::read(&index_file)?[4..8], &[0, 0, 0, 3]);
+
+    let mut index = Index::new(&index_file);
+    index.load()?;
+    let entries = index
+        .each_entry()?
+        .iter()
+        .map(|e| (e.path.display().to_string(), e.oid.to_vec(), e.is_skip_worktree()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        entries,
+        vec![("a.txt".to_string(), vec![1; 20], false), ("dir/b.txt".to_string(), vec![2; 20], true)]
+    );
+
+    // without the bit the index is written in version 2 again
+    index.set_skip_worktree("dir/b.txt", false);
+    index.write_updates()?;
+    assert_eq!(&fs::read(&index_file)?[4..8], &[0, 0, 0, 2]);
+    Ok(())
+}
+
+// Should keep the stages of an unmerged path across a write, until the path is added again
+#[test]
+fn unmerged_stages_are_written_and_loaded() -> Result<()> {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let index_file = temp_dir.path().join("index");
+    let mut index = Index::new(&index_file);
+    index.add_without_stat("a.txt".into(), vec![1; 20], 0o100644)?;
+    index.add_without_stat("b.txt".into(), vec![2; 20], 0o100644)?;
+    index.add_unmerged("a.txt".into(), 1, vec![3; 20], 0o100644)?;
+    index.add_unmerged("a.txt".into(), 3, vec![4; 20], 0o100755)?;
+    index.write_updates()?;
+    // the entry count of the header includes every stage
+    assert_eq!(&fs::read(&index_file)?[8..12], &[0, 0, 0, 3]);
+
+    let mut index = Index::new(&index_file);
+    index.load()?;
+    assert_eq!(index.unmerged_paths().collect::<Vec<_>>(), vec!["a.txt"]);
+    assert!(index.is_tracked_file("a.txt"));
+    assert_eq!(index.unmerged_entry("a.txt", 1).map(|e| e.oid.to_vec()), Some(vec![3; 20]));
+    assert!(index.unmerged_entry("a.txt", 2).is_none());
+    assert_eq!(index.unmerged_entry("a.txt", 3).map(|e| e.get_mode().unwrap()), Some(0o100755));
+    let paths = index.each_entry()?.iter().map(|e| e.get_path()).collect::<Vec<_>>();
+    assert_eq!(paths, vec!["b.txt"]);
+
+    index.add_without_stat("a.txt".into(), vec![5; 20], 0o100644)?;
+    assert!(!index.is_unmerged("a.txt"));
+    index.write_updates()?;
+    assert_eq!(&fs::read(&index_file)?[8..12], &[0, 0, 0, 2]);
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use gitclone::lfs::{LfsStore, Pointer};
+use tempfile::TempDir;
+
+#[test]
+fn pointers_are_parsed_from_what_they_write() {
+    let pointer = Pointer::new(b"hello\n");
+    assert_eq!(pointer.oid, "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03");
+    assert_eq!(
+        pointer.to_bytes(),
+        b"version https://git-lfs.github.com/spec/v1\n\
+          oid sha256:5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03\n\
+          size 6\n"
+    );
+    assert_eq!(Pointer::parse(&pointer.to_bytes()), Some(pointer));
+    assert_eq!(Pointer::parse(b"hello\n"), None);
+    assert_eq!(Pointer::parse(b"version https://git-lfs.github.com/spec/v1\noid sha256:12\nsize 6\n"), None);
+}
+
+#[test]
+fn the_store_gives_back_the_content_of_its_pointers() {
+    let temp_dir = TempDir::new().expect("unable to create a temporary working directory");
+    let store = LfsStore::new(temp_dir.path());
+    let pointer = store.clean(b"large\n".to_vec()).unwrap();
+    assert_eq!(store.clean(pointer.clone()).unwrap(), pointer);
+    assert_eq!(store.smudge(pointer).unwrap(), b"large\n");
+    // a pointer to content which is not stored is left as it is
+    let missing = Pointer::new(b"missing\n").to_bytes();
+    assert_eq!(store.smudge(missing.clone()).unwrap(), missing);
+}
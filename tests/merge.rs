@@ -0,0 +1,63 @@
+use gitclone::diff::{self, EditKind};
+use gitclone::merge::{merge3, Chunk};
+
+#[test]
+fn diff_finds_the_shortest_edit_script() {
+    let a: Vec<char> = "ABCABBA".chars().collect();
+    let b: Vec<char> = "CBABAC".chars().collect();
+    let edits = diff::diff(&a, &b);
+    let changes = edits.iter().filter(|e| e.kind != EditKind::Eql).count();
+    assert_eq!(changes, 5);
+    let kept: String = edits
+        .iter()
+        .filter(|e| e.kind == EditKind::Eql)
+        .map(|e| a[e.a_index.unwrap()])
+        .collect();
+    assert_eq!(kept.len(), 4);
+}
+
+#[test]
+fn hunks_group_the_changes_with_their_context() {
+    let a: Vec<char> = "abcdefghijklmnop".chars().collect();
+    let mut b = a.clone();
+    b[1] = 'B';
+    b[3] = 'D';
+    b[14] = 'O';
+    let hunks = diff::hunks(&diff::diff(&a, &b), 3);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].header(), "@@ -1,7 +1,7 @@");
+    assert_eq!(hunks[1].header(), "@@ -12,5 +12,5 @@");
+}
+
+#[test]
+fn merge_takes_the_changes_of_both_sides() {
+    let base = b"celery\ngarlic\nonions\nsalmon\ntomatoes\nwine\n";
+    let ours = b"celery\nsalmon\ntomatoes\ngarlic\nonions\nwine\n";
+    let theirs = b"celery\ngarlic\nsalmon\ntomatoes\nonions\nwine\n";
+    let result = merge3(base, ours, theirs);
+    assert!(!result.is_clean());
+
+    let base = b"a\nb\nc\nd\ne\n";
+    let ours = b"A\nb\nc\nd\ne\n";
+    let theirs = b"a\nb\nc\nd\nE\n";
+    let result = merge3(base, ours, theirs);
+    assert!(result.is_clean());
+    assert_eq!(result.to_bytes("ours", "theirs"), b"A\nb\nc\nd\nE\n".to_vec());
+}
+
+#[test]
+fn merge_reports_conflicting_changes() {
+    let result = merge3(b"a\nb\nc\n", b"a\nours\nc\n", b"a\ntheirs\nc\n");
+    assert_eq!(
+        result.chunks[1],
+        Chunk::Conflict {
+            base: vec![&b"b\n"[..]],
+            ours: vec![&b"ours\n"[..]],
+            theirs: vec![&b"theirs\n"[..]],
+        }
+    );
+    assert_eq!(
+        result.to_bytes("HEAD", "other"),
+        b"a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> other\nc\n".to_vec()
+    );
+}
@@ -0,0 +1,33 @@
+use std::path::{Path, PathBuf};
+
+use gitclone::sparse::SparseCheckout;
+
+#[test]
+fn the_cone_includes_the_files_of_its_directories_and_their_parents() {
+    let sparse = SparseCheckout::new(vec![PathBuf::from("a/b"), PathBuf::from("c")]);
+    assert!(sparse.includes(Path::new("top.txt")));
+    assert!(sparse.includes(Path::new("a/x.txt")));
+    assert!(sparse.includes(Path::new("a/b/y.txt")));
+    assert!(sparse.includes(Path::new("a/b/deep/z.txt")));
+    assert!(sparse.includes(Path::new("c/d/w.txt")));
+    assert!(!sparse.includes(Path::new("a/other/z.txt")));
+    assert!(!sparse.includes(Path::new("d/w.txt")));
+}
+
+#[test]
+fn patterns_are_written_and_parsed_back() {
+    let sparse = SparseCheckout::new(vec![PathBuf::from("a/b"), PathBuf::from("c")]);
+    let patterns = sparse.to_patterns();
+    assert_eq!(patterns, "/*\n!/*/\n/a/\n!/a/*/\n/a/b/\n/c/\n");
+    assert_eq!(SparseCheckout::parse(&patterns).unwrap(), sparse);
+    assert_eq!(SparseCheckout::parse("/*\n!/*/\n").unwrap(), SparseCheckout::default());
+    assert!(SparseCheckout::parse("*.txt\n").is_err());
+}
+
+#[test]
+fn directories_under_the_cone_are_not_added_again() {
+    let mut sparse = SparseCheckout::new(vec![PathBuf::from("a/b")]);
+    sparse.add(vec![PathBuf::from("a")]);
+    sparse.add(vec![PathBuf::from("a/c")]);
+    assert_eq!(sparse.dirs().collect::<Vec<_>>(), vec![Path::new("a")]);
+}